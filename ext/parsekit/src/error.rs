@@ -1,4 +1,5 @@
-use magnus::{Error, RModule, Ruby, Module};
+use magnus::value::Lazy;
+use magnus::{Error, ExceptionClass, Module, RModule, Ruby};
 
 /// Custom error types for ParseKit
 #[derive(Debug)]
@@ -7,39 +8,64 @@ pub enum ParserError {
     ParseError(String),
     ConfigError(String),
     IoError(String),
+    ResourceLimit(String),
+    /// A failure raised as a plain RuntimeError, such as OCR errors
+    Runtime(String),
 }
 
+/// ParseKit::ParseError, looked up once after `init` has defined it
+static PARSE_ERROR: Lazy<ExceptionClass> = Lazy::new(|ruby| error_class(ruby, "ParseError"));
+
+/// ParseKit::ResourceLimitError, looked up once after `init` has defined it
+static RESOURCE_LIMIT_ERROR: Lazy<ExceptionClass> = Lazy::new(|ruby| error_class(ruby, "ResourceLimitError"));
+
 impl ParserError {
     /// Convert to Magnus Error
-    #[allow(dead_code)]
     pub fn to_error(&self) -> Error {
+        let ruby = Ruby::get().unwrap();
         match self {
-            ParserError::ParseError(msg) => {
-                Error::new(Ruby::get().unwrap().exception_runtime_error(), msg.clone())
-            }
-            ParserError::ConfigError(msg) => {
-                Error::new(Ruby::get().unwrap().exception_arg_error(), msg.clone())
-            }
-            ParserError::IoError(msg) => {
-                Error::new(Ruby::get().unwrap().exception_io_error(), msg.clone())
-            }
+            ParserError::ParseError(msg) => Error::new(ruby.get_inner(&PARSE_ERROR), msg.clone()),
+            ParserError::ConfigError(msg) => Error::new(ruby.exception_arg_error(), msg.clone()),
+            ParserError::IoError(msg) => Error::new(ruby.exception_io_error(), msg.clone()),
+            ParserError::ResourceLimit(msg) => Error::new(ruby.get_inner(&RESOURCE_LIMIT_ERROR), msg.clone()),
+            ParserError::Runtime(msg) => Error::new(ruby.exception_runtime_error(), msg.clone()),
         }
     }
+
+    /// Whether a Magnus Error is a ParseKit::ResourceLimitError
+    pub fn is_resource_limit(err: &Error) -> bool {
+        err.is_kind_of(Ruby::get().unwrap().get_inner(&RESOURCE_LIMIT_ERROR))
+    }
+}
+
+impl From<ParserError> for Error {
+    fn from(err: ParserError) -> Self {
+        err.to_error()
+    }
+}
+
+/// Look up one of the ParseKit exception classes registered by `init`
+fn error_class(ruby: &Ruby, name: &str) -> ExceptionClass {
+    ruby.define_module("ParseKit")
+        .and_then(|module| module.const_get::<_, ExceptionClass>(name))
+        .unwrap_or_else(|_| panic!("ParseKit::{} is defined by error::init", name))
 }
 
 /// Initialize error classes
-/// For simplicity, we'll just create Ruby classes that inherit from Object,
-/// and document that they should be treated as exceptions
-pub fn init(_ruby: &Ruby, module: RModule) -> Result<(), Error> {
-    // For now, just create placeholder classes
-    // In a real implementation, you'd want to properly set up exception classes
-    // but Magnus 0.7's API for this is complex
-    
-    // Define error classes as regular Ruby classes
-    // Users can still rescue them by name in Ruby code
-    let _error = module.define_class("Error", Ruby::get().unwrap().class_object())?;
-    let _parse_error = module.define_class("ParseError", Ruby::get().unwrap().class_object())?;
-    let _config_error = module.define_class("ConfigError", Ruby::get().unwrap().class_object())?;
+pub fn init(ruby: &Ruby, module: RModule) -> Result<(), Error> {
+    // Plain classes, not exceptions, kept so code referring to them still
+    // loads; nothing raises them
+    let _error = module.define_class("Error", ruby.class_object())?;
+    let _config_error = module.define_class("ConfigError", ruby.class_object())?;
+
+    // Raised when decompression or expansion would exceed the configured
+    // limits. A real exception class, subclassing RuntimeError so callers
+    // rescuing parse failures still catch it.
+    module.define_error("ResourceLimitError", ruby.exception_runtime_error())?;
+
+    // Raised when a document can't be read, such as malformed JSON in
+    // strict mode. Also a RuntimeError, like the errors it refines.
+    module.define_error("ParseError", ruby.exception_runtime_error())?;
     
     Ok(())
-}
\ No newline at end of file
+}
@@ -4,17 +4,48 @@ use std::path::Path;
 #[derive(Debug, Clone, PartialEq)]
 pub enum FileFormat {
     Pdf,
+    Djvu,
+    PostScript,
     Docx,
+    WordPerfect,
     Xlsx,
     Xls,
     Pptx,
+    Vsdx,
+    OneNote,
+    Parquet,
+    Arrow,
+    Dbf,
     Png,
     Jpeg,
     Tiff,
     Bmp,
+    Gif,
+    Avif,
+    Dicom,
+    Audio,
+    Video,
     Json,
+    MsgPack,
+    Cbor,
+    Bson,
     Xml,
+    Xbrl,
+    FlatOdf,
     Html,
+    Mhtml,
+    Email,
+    Chm,
+    Mobi,
+    Subtitles,
+    Log,
+    Tar,
+    Gzip,
+    Bzip2,
+    Xz,
+    SevenZip,
+    Rar,
+    Pst,
     Text,
     Unknown,
 }
@@ -24,17 +55,48 @@ impl FileFormat {
     pub fn to_symbol(&self) -> &'static str {
         match self {
             FileFormat::Pdf => "pdf",
+            FileFormat::Djvu => "djvu",
+            FileFormat::PostScript => "postscript",
             FileFormat::Docx => "docx",
+            FileFormat::WordPerfect => "wordperfect",
             FileFormat::Xlsx => "xlsx",
             FileFormat::Xls => "xls",
             FileFormat::Pptx => "pptx",
+            FileFormat::Vsdx => "vsdx",
+            FileFormat::OneNote => "onenote",
+            FileFormat::Parquet => "parquet",
+            FileFormat::Arrow => "arrow",
+            FileFormat::Dbf => "dbf",
             FileFormat::Png => "png",
             FileFormat::Jpeg => "jpeg",
             FileFormat::Tiff => "tiff",
             FileFormat::Bmp => "bmp",
+            FileFormat::Gif => "gif",
+            FileFormat::Avif => "avif",
+            FileFormat::Dicom => "dicom",
+            FileFormat::Audio => "audio",
+            FileFormat::Video => "video",
             FileFormat::Json => "json",
+            FileFormat::MsgPack => "msgpack",
+            FileFormat::Cbor => "cbor",
+            FileFormat::Bson => "bson",
             FileFormat::Xml => "xml",
+            FileFormat::Xbrl => "xbrl",
+            FileFormat::FlatOdf => "flat_odf",
             FileFormat::Html => "xml", // HTML is treated as XML in Ruby
+            FileFormat::Mhtml => "mhtml",
+            FileFormat::Email => "email",
+            FileFormat::Chm => "chm",
+            FileFormat::Mobi => "mobi",
+            FileFormat::Subtitles => "subtitles",
+            FileFormat::Log => "log",
+            FileFormat::Tar => "tar",
+            FileFormat::Gzip => "gzip",
+            FileFormat::Bzip2 => "bzip2",
+            FileFormat::Xz => "xz",
+            FileFormat::SevenZip => "sevenzip",
+            FileFormat::Rar => "rar",
+            FileFormat::Pst => "pst",
             FileFormat::Text => "text",
             FileFormat::Unknown => "unknown",
         }
@@ -86,17 +148,48 @@ impl FormatDetector {
         
         match ext.as_str() {
             "pdf" => FileFormat::Pdf,
+            "djvu" | "djv" => FileFormat::Djvu,
+            "ps" | "eps" | "epsf" => FileFormat::PostScript,
             "docx" => FileFormat::Docx,
+            "wpd" | "wp" | "wp5" | "wp6" => FileFormat::WordPerfect,
             "xlsx" => FileFormat::Xlsx,
             "xls" => FileFormat::Xls,
             "pptx" => FileFormat::Pptx,
+            "vsdx" | "vsdm" => FileFormat::Vsdx,
+            "one" => FileFormat::OneNote,
+            "parquet" => FileFormat::Parquet,
+            "arrow" | "arrows" | "feather" | "ipc" => FileFormat::Arrow,
+            "dbf" => FileFormat::Dbf,
             "png" => FileFormat::Png,
             "jpg" | "jpeg" => FileFormat::Jpeg,
             "tiff" | "tif" => FileFormat::Tiff,
             "bmp" => FileFormat::Bmp,
+            "gif" => FileFormat::Gif,
+            "avif" => FileFormat::Avif,
+            "dcm" | "dicom" => FileFormat::Dicom,
+            "wav" | "mp3" | "m4a" | "ogg" | "oga" => FileFormat::Audio,
+            "mp4" | "m4v" | "mov" | "mkv" | "webm" => FileFormat::Video,
             "json" => FileFormat::Json,
+            "msgpack" | "mpk" => FileFormat::MsgPack,
+            "cbor" => FileFormat::Cbor,
+            "bson" => FileFormat::Bson,
             "xml" => FileFormat::Xml,
+            "xbrl" => FileFormat::Xbrl,
+            "fodt" | "fods" | "fodp" => FileFormat::FlatOdf,
             "html" | "htm" => FileFormat::Html,
+            "mht" | "mhtml" => FileFormat::Mhtml,
+            "eml" => FileFormat::Email,
+            "chm" => FileFormat::Chm,
+            "mobi" | "azw" | "azw3" | "prc" => FileFormat::Mobi,
+            "srt" | "vtt" | "ass" | "ssa" => FileFormat::Subtitles,
+            "log" => FileFormat::Log,
+            "tar" => FileFormat::Tar,
+            "gz" | "tgz" => FileFormat::Gzip,
+            "bz2" | "tbz2" | "tbz" => FileFormat::Bzip2,
+            "xz" | "txz" => FileFormat::Xz,
+            "7z" => FileFormat::SevenZip,
+            "rar" => FileFormat::Rar,
+            "pst" | "ost" => FileFormat::Pst,
             "txt" | "text" | "md" | "markdown" | "csv" => FileFormat::Text,
             _ => FileFormat::Unknown,
         }
@@ -113,6 +206,84 @@ impl FormatDetector {
             return FileFormat::Pdf;
         }
         
+        // DjVu
+        if data.len() >= 8 && data.starts_with(b"AT&TFORM") {
+            return FileFormat::Djvu;
+        }
+        
+        // PostScript, plain or with a DOS EPS binary header
+        if data.len() >= 4 && (data.starts_with(b"%!PS") || data.starts_with(&[0xC5, 0xD0, 0xD3, 0xC6])) {
+            return FileFormat::PostScript;
+        }
+        
+        // DICOM (128-byte preamble followed by "DICM")
+        if data.len() >= 132 && &data[128..132] == b"DICM" {
+            return FileFormat::Dicom;
+        }
+        
+        // Compiled HTML Help
+        if data.len() >= 4 && data.starts_with(b"ITSF") {
+            return FileFormat::Chm;
+        }
+        
+        // MOBI/AZW and PalmDOC ebooks (PalmDB type/creator at offset 60)
+        if data.len() >= 68 && matches!(&data[60..68], b"BOOKMOBI" | b"TEXtREAd") {
+            return FileFormat::Mobi;
+        }
+        
+        // WordPerfect
+        if data.len() >= 4 && data.starts_with(&[0xFF, b'W', b'P', b'C']) {
+            return FileFormat::WordPerfect;
+        }
+        
+        // OneNote section (file type GUID {7B5C52E4-D88C-4DA7-AEB1-5378D02996D3})
+        if data.len() >= 16
+            && data.starts_with(&[
+                0xE4, 0x52, 0x5C, 0x7B, 0x8C, 0xD8, 0xA7, 0x4D, 0xAE, 0xB1, 0x53, 0x78, 0xD0, 0x29, 0x96, 0xD3,
+            ])
+        {
+            return FileFormat::OneNote;
+        }
+        
+        // Compressed streams: GZIP, BZIP2, XZ
+        if data.starts_with(&[0x1F, 0x8B]) {
+            return FileFormat::Gzip;
+        }
+        if data.len() >= 4 && data.starts_with(b"BZh") && data[3].is_ascii_digit() {
+            return FileFormat::Bzip2;
+        }
+        if data.starts_with(&[0xFD, b'7', b'z', b'X', b'Z', 0x00]) {
+            return FileFormat::Xz;
+        }
+        
+        // 7z and RAR (v4 `Rar!\x1A\x07\x00`, v5 `Rar!\x1A\x07\x01\x00`)
+        if data.starts_with(&[b'7', b'z', 0xBC, 0xAF, 0x27, 0x1C]) {
+            return FileFormat::SevenZip;
+        }
+        if data.starts_with(b"Rar!\x1A\x07") {
+            return FileFormat::Rar;
+        }
+        
+        // Outlook PST/OST (`!BDN`, client magic `SM` or `SO` at offset 8)
+        if data.len() >= 10 && data.starts_with(b"!BDN") && matches!(&data[8..10], b"SM" | b"SO") {
+            return FileFormat::Pst;
+        }
+        
+        // TAR (POSIX ustar or GNU magic at offset 257)
+        if data.len() >= 262 && &data[257..262] == b"ustar" {
+            return FileFormat::Tar;
+        }
+        
+        // Parquet
+        if data.len() >= 4 && data.starts_with(b"PAR1") {
+            return FileFormat::Parquet;
+        }
+        
+        // Arrow IPC file / Feather v2
+        if data.len() >= 6 && data.starts_with(b"ARROW1") {
+            return FileFormat::Arrow;
+        }
+        
         // PNG
         if data.len() >= 8 && data.starts_with(&[0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A]) {
             return FileFormat::Png;
@@ -123,6 +294,31 @@ impl FormatDetector {
             return FileFormat::Jpeg;
         }
         
+        // GIF
+        if data.len() >= 6 && (data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a")) {
+            return FileFormat::Gif;
+        }
+        
+        // AVIF (ISO-BMFF `ftyp` box with an AVIF brand)
+        if data.len() >= 12 && &data[4..8] == b"ftyp" && matches!(&data[8..12], b"avif" | b"avis") {
+            return FileFormat::Avif;
+        }
+        
+        // Audio: WAV, Ogg, M4A (`ftyp` audio brands), MP3 with an ID3 tag or frame sync
+        if (data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WAVE")
+            || data.starts_with(b"OggS")
+            || (data.len() >= 12 && &data[4..8] == b"ftyp" && matches!(&data[8..12], b"M4A " | b"M4B " | b"M4P "))
+            || data.starts_with(b"ID3")
+            || Self::is_mpeg_audio_frame(data)
+        {
+            return FileFormat::Audio;
+        }
+        
+        // Video: any other ISO-BMFF (MP4/MOV) file, or Matroska/WebM (EBML header)
+        if (data.len() >= 8 && &data[4..8] == b"ftyp") || data.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) {
+            return FileFormat::Video;
+        }
+        
         // BMP
         if data.len() >= 2 && data.starts_with(b"BM") {
             return FileFormat::Bmp;
@@ -135,6 +331,17 @@ impl FormatDetector {
             }
         }
         
+        // Self-described CBOR (tag 55799)
+        if data.len() >= 3 && data.starts_with(&[0xD9, 0xD9, 0xF7]) {
+            return FileFormat::Cbor;
+        }
+        
+        // dBase table: version byte, plausible YYMMDD date, and a field
+        // descriptor terminator (0x0D) at the end of the header
+        if Self::is_dbf(data) {
+            return FileFormat::Dbf;
+        }
+        
         // OLE Compound Document (old Excel/Word)
         if data.len() >= 4 && data.starts_with(&[0xD0, 0xCF, 0x11, 0xE0]) {
             return FileFormat::Xls; // Old Office format, usually Excel
@@ -145,15 +352,20 @@ impl FormatDetector {
             return Self::detect_office_format(data);
         }
         
-        // XML
-        if data.len() >= 5 {
-            let start = String::from_utf8_lossy(&data[0..5.min(data.len())]);
-            if start.starts_with("<?xml") || start.starts_with("<!") {
-                return FileFormat::Xml;
+        // Subtitles: WebVTT header, ASS/SSA script header, or a SubRip cue
+        // (index line followed by a `-->` timing line)
+        {
+            let head = String::from_utf8_lossy(&data[0..256.min(data.len())]);
+            let head = head.trim_start_matches('\u{feff}').trim_start();
+            let mut lines = head.lines();
+            let is_srt = lines.next().is_some_and(|l| !l.trim().is_empty() && l.trim().bytes().all(|b| b.is_ascii_digit()))
+                && lines.next().is_some_and(|l| l.contains("-->"));
+            if head.starts_with("WEBVTT") || head.starts_with("[Script Info]") || is_srt {
+                return FileFormat::Subtitles;
             }
         }
         
-        // HTML
+        // HTML (checked before XML so `<!DOCTYPE html>` isn't taken for XML)
         if data.len() >= 14 {
             let start = String::from_utf8_lossy(&data[0..14.min(data.len())]).to_lowercase();
             if start.contains("<!doctype") || start.contains("<html") {
@@ -161,6 +373,14 @@ impl FormatDetector {
             }
         }
         
+        // XML
+        if data.len() >= 5 {
+            let start = String::from_utf8_lossy(&data[0..5.min(data.len())]);
+            if start.starts_with("<?xml") || start.starts_with("<!") {
+                return Self::detect_xml_dialect(data);
+            }
+        }
+        
         // JSON
         if let Some(&first_non_ws) = data.iter().find(|&&b| !b" \t\n\r".contains(&b)) {
             if first_non_ws == b'{' || first_non_ws == b'[' {
@@ -185,6 +405,8 @@ impl FormatDetector {
             FileFormat::Xlsx
         } else if content.contains("ppt/") || content.contains("ppt/_rels") {
             FileFormat::Pptx
+        } else if content.contains("visio/") {
+            FileFormat::Vsdx
         } else {
             // Default to XLSX for generic ZIP (most common Office format)
             FileFormat::Xlsx
@@ -192,12 +414,83 @@ impl FormatDetector {
     }
     
     
+    /// Check for a dBase III-7 / FoxPro table header
+    fn is_dbf(data: &[u8]) -> bool {
+        if data.len() < 65 {
+            return false;
+        }
+        let version_ok = matches!(data[0], 0x02 | 0x03 | 0x04 | 0x05 | 0x30 | 0x31 | 0x32 | 0x43 | 0x63 | 0x83 | 0x8B | 0xCB | 0xF5 | 0xFB);
+        let date_ok = (1..=12).contains(&data[2]) && (1..=31).contains(&data[3]);
+        let header_len = u16::from_le_bytes([data[8], data[9]]) as usize;
+        let record_len = u16::from_le_bytes([data[10], data[11]]) as usize;
+        // Visual FoxPro appends a 263-byte backlink after the terminator
+        let terminated = header_len > 32
+            && (data.get(header_len - 1) == Some(&0x0D)
+                || (header_len > 264 && data.get(header_len - 264) == Some(&0x0D)));
+        version_ok && date_ok && record_len > 1 && terminated
+    }
+    
+    /// Check for an MPEG audio frame header: frame sync, then a version,
+    /// layer, bitrate and sample rate that aren't reserved. `FF FE` is the
+    /// UTF-16LE byte-order mark, not audio.
+    fn is_mpeg_audio_frame(data: &[u8]) -> bool {
+        if data.len() < 3 || data[0] != 0xFF || data[1] & 0xE0 != 0xE0 || data[1] == 0xFE {
+            return false;
+        }
+        let version = (data[1] >> 3) & 0x03;
+        let layer = (data[1] >> 1) & 0x03;
+        let bitrate = data[2] >> 4;
+        let sample_rate = (data[2] >> 2) & 0x03;
+        version != 0b01 && layer != 0b00 && bitrate != 0b0000 && bitrate != 0b1111 && sample_rate != 0b11
+    }
+    
+    /// Classify an XML document by its root element: XBRL instances
+    /// (`<xbrl>`, `<xbrli:xbrl>`) and Flat ODF (`<office:document>`) get
+    /// their own parsers, anything else is plain XML
+    pub fn detect_xml_dialect(data: &[u8]) -> FileFormat {
+        match Self::xml_root_element(data) {
+            Some(name) if name.rsplit(':').next() == Some("xbrl") => FileFormat::Xbrl,
+            Some(name) if name == "office:document" => FileFormat::FlatOdf,
+            _ => FileFormat::Xml,
+        }
+    }
+    
+    /// Qualified name of an XML document's root element
+    fn xml_root_element(data: &[u8]) -> Option<String> {
+        let check_len = 4096.min(data.len());
+        let content = String::from_utf8_lossy(&data[0..check_len]);
+        
+        // Skip the XML declaration, comments, processing instructions and DOCTYPE
+        let mut rest = content.as_ref();
+        while let Some(pos) = rest.find('<') {
+            rest = &rest[pos + 1..];
+            if rest.starts_with('?') || rest.starts_with('!') {
+                continue;
+            }
+            let name: String = rest
+                .chars()
+                .take_while(|c| !c.is_whitespace() && *c != '>' && *c != '/')
+                .collect();
+            return Some(name);
+        }
+        None
+    }
+    
     /// Get all supported extensions
     pub fn supported_extensions() -> Vec<&'static str> {
         vec![
-            "pdf", "docx", "xlsx", "xls", "pptx",
-            "png", "jpg", "jpeg", "tiff", "tif", "bmp",
-            "json", "xml", "html", "htm",
+            "pdf", "djvu", "djv", "ps", "eps", "epsf",
+            "docx", "xlsx", "xls", "pptx", "vsdx", "vsdm", "one",
+            "wpd", "wp", "wp5", "wp6",
+            "parquet", "arrow", "arrows", "feather", "ipc", "dbf",
+            "png", "jpg", "jpeg", "tiff", "tif", "bmp", "gif", "avif", "dcm", "dicom",
+            "wav", "mp3", "m4a", "ogg", "oga", "mp4", "m4v", "mov", "mkv", "webm",
+            "json", "msgpack", "mpk", "cbor", "bson",
+            "xml", "xbrl", "fodt", "fods", "fodp", "html", "htm", "mht", "mhtml", "eml", "chm",
+            "mobi", "azw", "azw3", "prc",
+            "srt", "vtt", "ass", "ssa", "log",
+            "tar", "gz", "tgz", "bz2", "tbz2", "tbz", "xz", "txz", "7z", "rar",
+            "pst", "ost",
             "txt", "text", "md", "markdown", "csv"
         ]
     }
@@ -219,11 +512,201 @@ mod tests {
         assert_eq!(FormatDetector::detect_from_content(png_data), FileFormat::Png);
     }
     
+    #[test]
+    fn test_detect_parquet() {
+        let parquet_data = b"PAR1\x15\x04\x15";
+        assert_eq!(FormatDetector::detect_from_content(parquet_data), FileFormat::Parquet);
+        assert_eq!(FormatDetector::detect_from_extension("events.parquet"), FileFormat::Parquet);
+    }
+    
+    #[test]
+    fn test_detect_arrow() {
+        let arrow_data = b"ARROW1\x00\x00";
+        assert_eq!(FormatDetector::detect_from_content(arrow_data), FileFormat::Arrow);
+        assert_eq!(FormatDetector::detect_from_extension("table.feather"), FileFormat::Arrow);
+        assert_eq!(FormatDetector::detect_from_extension("batches.arrows"), FileFormat::Arrow);
+    }
+    
+    #[test]
+    fn test_detect_dbf() {
+        let mut dbf_data = vec![0x03, 124, 5, 17, 2, 0, 0, 0, 65, 0, 11, 0];
+        dbf_data.resize(32, 0);
+        dbf_data.extend_from_slice(b"NAME\0\0\0\0\0\0\0C\0\0\0\0\x0A");
+        dbf_data.resize(64, 0);
+        dbf_data.push(0x0D);
+        assert_eq!(FormatDetector::detect_from_content(&dbf_data), FileFormat::Dbf);
+        assert_eq!(FormatDetector::detect_from_extension("parcels.dbf"), FileFormat::Dbf);
+    }
+    
+    #[test]
+    fn test_detect_cbor() {
+        let cbor_data = &[0xD9, 0xD9, 0xF7, 0xA1, 0x61, 0x61, 0x01];
+        assert_eq!(FormatDetector::detect_from_content(cbor_data), FileFormat::Cbor);
+        assert_eq!(FormatDetector::detect_from_extension("reading.cbor"), FileFormat::Cbor);
+    }
+    
+    #[test]
+    fn test_detect_html_doctype() {
+        let html_data = b"<!DOCTYPE html><html><body>text</body></html>";
+        assert_eq!(FormatDetector::detect_from_content(html_data), FileFormat::Html);
+        let xml_data = b"<?xml version=\"1.0\"?><root/>";
+        assert_eq!(FormatDetector::detect_from_content(xml_data), FileFormat::Xml);
+    }
+    
+    #[test]
+    fn test_detect_gif() {
+        assert_eq!(FormatDetector::detect_from_content(b"GIF89a\x01\x00"), FileFormat::Gif);
+        assert_eq!(FormatDetector::detect_from_content(b"GIF87a\x01\x00"), FileFormat::Gif);
+        assert_eq!(FormatDetector::detect_from_extension("scan.gif"), FileFormat::Gif);
+    }
+    
+    #[test]
+    fn test_detect_avif() {
+        let avif_data = b"\x00\x00\x00\x1cftypavif\x00\x00\x00\x00";
+        assert_eq!(FormatDetector::detect_from_content(avif_data), FileFormat::Avif);
+        assert_eq!(FormatDetector::detect_from_extension("photo.avif"), FileFormat::Avif);
+    }
+    
+    #[test]
+    fn test_detect_djvu() {
+        assert_eq!(FormatDetector::detect_from_content(b"AT&TFORM\x00\x00\x00\x10DJVU"), FileFormat::Djvu);
+        assert_eq!(FormatDetector::detect_from_extension("scan.djvu"), FileFormat::Djvu);
+    }
+    
+    #[test]
+    fn test_detect_postscript() {
+        assert_eq!(FormatDetector::detect_from_content(b"%!PS-Adobe-3.0\n"), FileFormat::PostScript);
+        assert_eq!(FormatDetector::detect_from_content(&[0xC5, 0xD0, 0xD3, 0xC6, 0x1E]), FileFormat::PostScript);
+        assert_eq!(FormatDetector::detect_from_extension("figure.eps"), FileFormat::PostScript);
+    }
+    
+    #[test]
+    fn test_detect_chm() {
+        assert_eq!(FormatDetector::detect_from_content(b"ITSF\x03\x00\x00\x00"), FileFormat::Chm);
+        assert_eq!(FormatDetector::detect_from_extension("manual.chm"), FileFormat::Chm);
+    }
+    
+    #[test]
+    fn test_detect_mobi() {
+        let mut mobi_data = vec![0u8; 78];
+        mobi_data[60..68].copy_from_slice(b"BOOKMOBI");
+        assert_eq!(FormatDetector::detect_from_content(&mobi_data), FileFormat::Mobi);
+        assert_eq!(FormatDetector::detect_from_extension("novel.azw3"), FileFormat::Mobi);
+    }
+    
+    #[test]
+    fn test_detect_wordperfect() {
+        assert_eq!(FormatDetector::detect_from_content(&[0xFF, b'W', b'P', b'C', 0x10, 0x00]), FileFormat::WordPerfect);
+        assert_eq!(FormatDetector::detect_from_extension("brief.wpd"), FileFormat::WordPerfect);
+    }
+    
+    #[test]
+    fn test_detect_vsdx() {
+        let vsdx_data = b"PK\x03\x04\x14\x00\x00\x00visio/document.xml";
+        assert_eq!(FormatDetector::detect_from_content(vsdx_data), FileFormat::Vsdx);
+        assert_eq!(FormatDetector::detect_from_extension("network.vsdx"), FileFormat::Vsdx);
+    }
+    
+    #[test]
+    fn test_detect_onenote() {
+        let onenote_data = &[
+            0xE4, 0x52, 0x5C, 0x7B, 0x8C, 0xD8, 0xA7, 0x4D, 0xAE, 0xB1, 0x53, 0x78, 0xD0, 0x29, 0x96, 0xD3, 0x00,
+        ];
+        assert_eq!(FormatDetector::detect_from_content(onenote_data), FileFormat::OneNote);
+        assert_eq!(FormatDetector::detect_from_extension("Meeting Notes.one"), FileFormat::OneNote);
+    }
+    
+    #[test]
+    fn test_detect_dicom() {
+        let mut dicom_data = vec![0u8; 128];
+        dicom_data.extend_from_slice(b"DICM\x02\x00\x00\x00");
+        assert_eq!(FormatDetector::detect_from_content(&dicom_data), FileFormat::Dicom);
+        assert_eq!(FormatDetector::detect_from_extension("IM00001.dcm"), FileFormat::Dicom);
+    }
+    
+    #[test]
+    fn test_detect_audio() {
+        assert_eq!(FormatDetector::detect_from_content(b"RIFF\x24\x08\x00\x00WAVEfmt "), FileFormat::Audio);
+        assert_eq!(FormatDetector::detect_from_content(b"OggS\x00\x02"), FileFormat::Audio);
+        assert_eq!(FormatDetector::detect_from_content(b"\x00\x00\x00\x20ftypM4A \x00"), FileFormat::Audio);
+        assert_eq!(FormatDetector::detect_from_content(b"ID3\x04\x00"), FileFormat::Audio);
+        assert_eq!(FormatDetector::detect_from_content(&[0xFF, 0xFB, 0x90, 0x64]), FileFormat::Audio);
+        assert_eq!(FormatDetector::detect_from_content(&[0xFF, 0xFE, 0x48, 0x00]), FileFormat::Text);
+        assert_eq!(FormatDetector::detect_from_content(&[0xFF, 0xFB, 0xF0, 0x64]), FileFormat::Text);
+        assert_eq!(FormatDetector::detect_from_content(&[0xFF, 0xD8, 0xFF, 0xE0]), FileFormat::Jpeg);
+        assert_eq!(FormatDetector::detect_from_extension("interview.m4a"), FileFormat::Audio);
+    }
+    
+    #[test]
+    fn test_detect_video() {
+        assert_eq!(FormatDetector::detect_from_content(b"\x00\x00\x00\x18ftypisom\x00\x00\x02\x00"), FileFormat::Video);
+        assert_eq!(FormatDetector::detect_from_content(b"\x00\x00\x00\x14ftypqt  \x00\x00\x00\x00"), FileFormat::Video);
+        assert_eq!(FormatDetector::detect_from_content(&[0x1A, 0x45, 0xDF, 0xA3, 0x9F, 0x42, 0x86]), FileFormat::Video);
+        assert_eq!(FormatDetector::detect_from_content(b"\x00\x00\x00\x1cftypavif\x00\x00\x00\x00"), FileFormat::Avif);
+        assert_eq!(FormatDetector::detect_from_extension("meeting.webm"), FileFormat::Video);
+        assert_eq!(FormatDetector::detect_from_extension("Recording.MOV"), FileFormat::Video);
+    }
+    
+    #[test]
+    fn test_detect_archives() {
+        let mut tar_data = vec![0u8; 512];
+        tar_data[257..263].copy_from_slice(b"ustar\0");
+        assert_eq!(FormatDetector::detect_from_content(&tar_data), FileFormat::Tar);
+        assert_eq!(FormatDetector::detect_from_content(&[0x1F, 0x8B, 0x08, 0x00]), FileFormat::Gzip);
+        assert_eq!(FormatDetector::detect_from_content(b"BZh91AY&SY"), FileFormat::Bzip2);
+        assert_eq!(FormatDetector::detect_from_content(&[0xFD, b'7', b'z', b'X', b'Z', 0x00, 0x00]), FileFormat::Xz);
+        assert_eq!(FormatDetector::detect_from_extension("backup.tar.gz"), FileFormat::Gzip);
+        assert_eq!(FormatDetector::detect_from_extension("backup.tgz"), FileFormat::Gzip);
+        assert_eq!(FormatDetector::detect_from_extension("logs.tar"), FileFormat::Tar);
+        assert_eq!(FormatDetector::detect_from_extension("dump.sql.bz2"), FileFormat::Bzip2);
+        assert_eq!(FormatDetector::detect_from_extension("release.tar.xz"), FileFormat::Xz);
+        assert_eq!(FormatDetector::detect_from_content(&[b'7', b'z', 0xBC, 0xAF, 0x27, 0x1C, 0x00, 0x04]), FileFormat::SevenZip);
+        assert_eq!(FormatDetector::detect_from_content(b"Rar!\x1A\x07\x01\x00"), FileFormat::Rar);
+        assert_eq!(FormatDetector::detect_from_extension("delivery.7z"), FileFormat::SevenZip);
+        assert_eq!(FormatDetector::detect_from_extension("delivery.rar"), FileFormat::Rar);
+    }
+
+    #[test]
+    fn test_detect_pst() {
+        assert_eq!(FormatDetector::detect_from_content(b"!BDN\x00\x00\x00\x00SM\x17\x00"), FileFormat::Pst);
+        assert_eq!(FormatDetector::detect_from_content(b"!BDN\x00\x00\x00\x00SO\x24\x00"), FileFormat::Pst);
+        assert_eq!(FormatDetector::detect_from_extension("archive.ost"), FileFormat::Pst);
+    }
+    
+    #[test]
+    fn test_detect_subtitles() {
+        assert_eq!(FormatDetector::detect_from_content(b"WEBVTT\n\n00:01.000 --> 00:02.000\nHi"), FileFormat::Subtitles);
+        assert_eq!(FormatDetector::detect_from_content(b"1\r\n00:00:01,000 --> 00:00:02,000\r\nHi"), FileFormat::Subtitles);
+        assert_eq!(FormatDetector::detect_from_content(b"[Script Info]\nTitle: Test"), FileFormat::Subtitles);
+        assert_eq!(FormatDetector::detect_from_content(b"2024\nwas a good year"), FileFormat::Text);
+        assert_eq!(FormatDetector::detect_from_extension("episode.srt"), FileFormat::Subtitles);
+        assert_eq!(FormatDetector::detect_from_extension("episode.ass"), FileFormat::Subtitles);
+    }
+    
+    #[test]
+    fn test_detect_xbrl() {
+        let xbrl_data = b"<?xml version=\"1.0\"?>\n<!-- filing -->\n<xbrli:xbrl xmlns:xbrli=\"http://www.xbrl.org/2003/instance\"></xbrli:xbrl>";
+        assert_eq!(FormatDetector::detect_from_content(xbrl_data), FileFormat::Xbrl);
+        assert_eq!(FormatDetector::detect_from_content(b"<?xml version=\"1.0\"?><xbrlx/>"), FileFormat::Xml);
+        assert_eq!(FormatDetector::detect_from_extension("10-K.xbrl"), FileFormat::Xbrl);
+    }
+
+    #[test]
+    fn test_detect_flat_odf() {
+        let fodt = b"<?xml version=\"1.0\"?>\n<office:document xmlns:office=\"urn:oasis:names:tc:opendocument:xmlns:office:1.0\" office:mimetype=\"application/vnd.oasis.opendocument.text\"/>";
+        assert_eq!(FormatDetector::detect_from_content(fodt), FileFormat::FlatOdf);
+        assert_eq!(FormatDetector::detect_from_extension("budget.fods"), FileFormat::FlatOdf);
+    }
+    
     #[test]
     fn test_detect_from_extension() {
         assert_eq!(FormatDetector::detect_from_extension("document.pdf"), FileFormat::Pdf);
         assert_eq!(FormatDetector::detect_from_extension("Document.PDF"), FileFormat::Pdf);
         assert_eq!(FormatDetector::detect_from_extension("data.xlsx"), FileFormat::Xlsx);
+        assert_eq!(FormatDetector::detect_from_extension("payload.msgpack"), FileFormat::MsgPack);
+        assert_eq!(FormatDetector::detect_from_extension("users.bson"), FileFormat::Bson);
+        assert_eq!(FormatDetector::detect_from_extension("saved_page.mht"), FileFormat::Mhtml);
+        assert_eq!(FormatDetector::detect_from_extension("forwarded.eml"), FileFormat::Email);
     }
     
     #[test]
@@ -1,12 +1,22 @@
 use magnus::{
-    function, method, prelude::*, scan_args, Error, Module, RHash, RModule, Ruby, Value,
+    function, method, prelude::*, scan_args, DataTypeFunctions, Error, Module, RArray, RHash, RModule,
+    RString, Ruby, TypedData, Value,
 };
+use crate::error::ParserError;
 use crate::format_detector::{FileFormat, FormatDetector};
 
-#[derive(Debug, Clone)]
-#[magnus::wrap(class = "ParseKit::Parser", free_immediately, size)]
+#[derive(Debug, Clone, TypedData)]
+#[magnus(class = "ParseKit::Parser", free_immediately, size, mark)]
 pub struct Parser {
     config: ParserConfig,
+    /// How many containers (archives, attachments) enclose the data being parsed
+    depth: usize,
+}
+
+impl DataTypeFunctions for Parser {
+    fn mark(&self, marker: &magnus::gc::Marker) {
+        self.config.ocr_backend.mark(marker);
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -15,6 +25,49 @@ struct ParserConfig {
     max_depth: usize,
     encoding: String,
     max_size: usize,
+    max_entry_size: usize,
+    max_compression_ratio: usize,
+    max_rows: usize,
+    ocr_pixel_data: bool,
+    transcribe: bool,
+    timestamps: bool,
+    whisper_model: Option<String>,
+    parse_attachments: bool,
+    resolve_entities: bool,
+    lenient: bool,
+    normalize_text: bool,
+    pdf_structure_order: bool,
+    min_confidence: Option<f32>,
+    ocr_output: OcrOutput,
+    ocr_preprocess: Vec<OcrPreprocess>,
+    ocr_dpi: u32,
+    ocr_language: String,
+    ocr_psm: Option<u8>,
+    ocr_oem: Option<u8>,
+    ocr_embedded_images: bool,
+    ocr_backend: std::sync::Arc<dyn OcrBackend>,
+    tessdata_path: Option<String>,
+    ocr_threads: usize,
+    docx_comments: bool,
+    revisions: DocxRevisions,
+    docx_links: bool,
+    docx_headings: bool,
+    pptx_alt_text: bool,
+    pptx_notes: PptxNotes,
+    pptx_links: bool,
+    include_formulas: bool,
+    include_hidden: bool,
+    xlsx_links: bool,
+    xlsx_cell_separator: String,
+    xlsx_trailing_separator: bool,
+    xlsx_row_separator: String,
+    xlsx_sheet_header: Option<String>,
+    xlsx_skip_empty_rows: bool,
+    json_output: JsonOutput,
+    xml_attributes: XmlAttributes,
+    xml_elements: Vec<String>,
+    xml_namespaces: Vec<String>,
+    main_content: bool,
 }
 
 impl Default for ParserConfig {
@@ -23,471 +76,11234 @@ impl Default for ParserConfig {
             strict_mode: false,
             max_depth: 100,
             encoding: "UTF-8".to_string(),
-            max_size: 100 * 1024 * 1024, // 100MB default limit
+            max_size: 100 * 1024 * 1024, // 100MB default limit, also the decompressed total per container
+            max_entry_size: 50 * 1024 * 1024, // Largest single decompressed archive entry or stream
+            max_compression_ratio: 100, // Decompressed-to-compressed size ceiling
+            max_rows: 10_000, // Row cap for columnar data formats
+            ocr_pixel_data: false, // OCR burned-in annotations in DICOM images
+            transcribe: false, // Transcribe the audio track of video files
+            timestamps: false, // Prefix subtitle cues and transcript segments with [hh:mm:ss]
+            whisper_model: None, // Path to a ggml Whisper model for audio transcription
+            parse_attachments: false, // Decode e-mail attachments and parse them by format
+            resolve_entities: false, // Expand entities declared in an XML document's internal DTD subset
+            lenient: false, // Salvage what can be read from damaged documents, noting what was lost
+            normalize_text: false, // Expand ligatures and rejoin hyphenated words in PDF text
+            pdf_structure_order: false, // Read tagged PDF pages in structure tree order
+            min_confidence: None, // Drop OCR words below this confidence (0-100)
+            ocr_output: OcrOutput::Text, // What ocr_image returns: text, hOCR, TSV or ALTO
+            ocr_preprocess: Vec::new(), // Image clean-up stages run before OCR, in order
+            ocr_dpi: 300, // Resolution images are enlarged to before OCR when they record less
+            ocr_language: "eng".to_string(), // Tesseract language(s), "+"-joined
+            ocr_psm: None, // Tesseract page segmentation mode (0-13), Tesseract's default when unset
+            ocr_oem: None, // Tesseract OCR engine mode (0-3), Tesseract's default when unset
+            ocr_embedded_images: false, // OCR pictures inside DOCX, PPTX and XLSX packages
+            ocr_backend: std::sync::Arc::new(TesseractOcr), // Engine every OCR call goes through
+            tessdata_path: None, // Directory holding the traineddata models, searched for when unset
+            ocr_threads: std::thread::available_parallelism().map_or(1, |n| n.get()), // Pages of a scan recognized at once
+            docx_comments: false, // Put DOCX reviewer comments after the paragraph they are anchored in
+            revisions: DocxRevisions::Accept, // Which side of DOCX tracked changes to extract
+            docx_links: false, // Follow DOCX hyperlink text with its target as "text (url)"
+            docx_headings: false, // Mark DOCX headings with Markdown `#` by level
+            pptx_alt_text: false, // Add the alt text of PPTX pictures as [Image: ...] lines
+            pptx_notes: PptxNotes::Include, // Whether PPTX speaker notes follow the slides, or replace them
+            pptx_links: false, // Follow PPTX hyperlink text with its target as "text (url)"
+            include_formulas: false, // Follow XLSX formula results with their formula as "value (=formula)"
+            include_hidden: true, // Keep hidden XLSX sheets, rows and columns
+            xlsx_links: false, // Follow XLSX hyperlink cells with their target as "text (url)"
+            xlsx_cell_separator: "\t".to_string(), // Between the cells of a row in spreadsheet text
+            xlsx_trailing_separator: true, // Also end each row's last cell with the cell separator
+            xlsx_row_separator: "\n".to_string(), // After each row, and after each sheet
+            xlsx_sheet_header: Some("Sheet: %{name}".to_string()), // Line starting each sheet, `%{name}` its name; none when unset
+            xlsx_skip_empty_rows: false, // Leave out rows whose cells are all empty
+            json_output: JsonOutput::Pretty, // How parse_json renders a document: pretty JSON or key: value lines
+            xml_attributes: XmlAttributes::None, // Attribute values parse_xml adds to the text
+            xml_elements: Vec::new(), // Only take XML text from within these elements; all when empty
+            xml_namespaces: Vec::new(), // Only take XML text from within elements in these namespaces; all when empty
+            main_content: false, // Keep only the main article of HTML pages, dropping navigation and boilerplate
         }
     }
 }
 
-// Error handling helpers
-impl Parser {
-    /// Create a RuntimeError with formatted message
-    fn runtime_error<E: std::fmt::Display>(context: &str, err: E) -> Error {
-        Error::new(
-            Ruby::get().unwrap().exception_runtime_error(),
-            format!("{}: {}", context, err),
-        )
+/// How `parse_docx` treats tracked changes, chosen with the `revisions` option
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DocxRevisions {
+    /// The document as if every change were accepted
+    Accept,
+    /// The document as if every change were rejected
+    Reject,
+    /// Both sides, marked up as `{+inserted+}` and `[-deleted-]`
+    ShowMarkup,
+}
+
+impl DocxRevisions {
+    fn name(self) -> &'static str {
+        match self {
+            DocxRevisions::Accept => "accept",
+            DocxRevisions::Reject => "reject",
+            DocxRevisions::ShowMarkup => "show_markup",
+        }
     }
-    
-    /// Create an ArgumentError with message
-    fn argument_error(msg: &str) -> Error {
-        Error::new(
-            Ruby::get().unwrap().exception_arg_error(),
-            msg.to_string(),
-        )
+}
+
+/// What `parse_pptx` does with speaker notes, chosen with the `pptx_notes` option
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PptxNotes {
+    /// After the slides, as `[Notes: ...]` paragraphs
+    Include,
+    /// Left out
+    Exclude,
+    /// The notes alone, without the slides
+    Only,
+}
+
+impl PptxNotes {
+    fn name(self) -> &'static str {
+        match self {
+            PptxNotes::Include => "include",
+            PptxNotes::Exclude => "exclude",
+            PptxNotes::Only => "only",
+        }
     }
-    
-    /// Create an IOError with formatted message
-    fn io_error<E: std::fmt::Display>(context: &str, err: E) -> Error {
-        Error::new(
-            Ruby::get().unwrap().exception_io_error(),
-            format!("{}: {}", context, err),
-        )
+}
+
+/// How `parse_json` renders a document, chosen with the `json_output` option
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum JsonOutput {
+    /// Pretty-printed JSON
+    Pretty,
+    /// A `dotted.key: value` line per scalar, without JSON punctuation
+    Flat,
+}
+
+impl JsonOutput {
+    fn name(self) -> &'static str {
+        match self {
+            JsonOutput::Pretty => "pretty",
+            JsonOutput::Flat => "flat",
+        }
     }
 }
 
-impl Parser {
-    /// Create a new Parser instance with optional configuration
-    fn new(ruby: &Ruby, args: &[Value]) -> Result<Self, Error> {
-        let args = scan_args::scan_args::<(), (Option<RHash>,), (), (), (), ()>(args)?;
-        let options = args.optional.0;
+/// Which attribute values `parse_xml` adds to the text, chosen with the
+/// `xml_attributes` option
+#[derive(Debug, Clone, PartialEq)]
+enum XmlAttributes {
+    /// None, only element text
+    None,
+    /// Every attribute's, except namespace declarations
+    All,
+    /// Those of the attributes named, by qualified or local name
+    Named(Vec<String>),
+}
 
-        let mut config = ParserConfig::default();
+/// Deserializes a JSON document only to check that no object repeats a
+/// key, which `serde_json::Value` would let through, the last one winning.
+/// Errors carry serde_json's line and column.
+struct UniqueKeys;
 
-        if let Some(opts) = options {
-            if let Some(strict) = opts.get(ruby.to_symbol("strict_mode")) {
-                config.strict_mode = bool::try_convert(strict)?;
-            }
-            if let Some(depth) = opts.get(ruby.to_symbol("max_depth")) {
-                config.max_depth = usize::try_convert(depth)?;
-            }
-            if let Some(encoding) = opts.get(ruby.to_symbol("encoding")) {
-                config.encoding = String::try_convert(encoding)?;
-            }
-            if let Some(max_size) = opts.get(ruby.to_symbol("max_size")) {
-                config.max_size = usize::try_convert(max_size)?;
+impl<'de> serde::Deserialize<'de> for UniqueKeys {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(UniqueKeys)
+    }
+}
+
+impl<'de> serde::de::Visitor<'de> for UniqueKeys {
+    type Value = UniqueKeys;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a JSON value")
+    }
+
+    fn visit_bool<E>(self, _: bool) -> Result<Self, E> {
+        Ok(self)
+    }
+
+    fn visit_i64<E>(self, _: i64) -> Result<Self, E> {
+        Ok(self)
+    }
+
+    fn visit_u64<E>(self, _: u64) -> Result<Self, E> {
+        Ok(self)
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<Self, E> {
+        Ok(self)
+    }
+
+    fn visit_str<E>(self, _: &str) -> Result<Self, E> {
+        Ok(self)
+    }
+
+    fn visit_unit<E>(self) -> Result<Self, E> {
+        Ok(self)
+    }
+
+    fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self, A::Error> {
+        while seq.next_element::<UniqueKeys>()?.is_some() {}
+        Ok(self)
+    }
+
+    fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<Self, A::Error> {
+        let mut keys = std::collections::HashSet::new();
+        while let Some(key) = map.next_key::<String>()? {
+            if keys.contains(&key) {
+                return Err(serde::de::Error::custom(format!("duplicate key `{}`", key)));
             }
+            map.next_value::<UniqueKeys>()?;
+            keys.insert(key);
         }
+        Ok(self)
+    }
+}
 
-        Ok(Self { config })
+/// What `ocr_image` returns, chosen with the `ocr_output` option
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OcrOutput {
+    Text,
+    Hocr,
+    Tsv,
+    Alto,
+}
+
+impl OcrOutput {
+    fn name(self) -> &'static str {
+        match self {
+            OcrOutput::Text => "text",
+            OcrOutput::Hocr => "hocr",
+            OcrOutput::Tsv => "tsv",
+            OcrOutput::Alto => "alto",
+        }
     }
+}
 
-    /// Parse input bytes based on file type (internal helper)
-    fn parse_bytes_internal(&self, data: Vec<u8>, filename: Option<&str>) -> Result<String, Error> {
-        // Check size limit
-        if data.len() > self.config.max_size {
-            return Err(Self::runtime_error(
-                "File size exceeds limit",
-                format!("{} bytes exceeds maximum allowed size of {} bytes", 
-                    data.len(), self.config.max_size)
-            ));
+/// A stage of the `ocr_preprocess` pipeline
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OcrPreprocess {
+    Grayscale,
+    Contrast,
+    Despeckle,
+    Deskew,
+    Binarize,
+}
+
+impl OcrPreprocess {
+    /// The stages `ocr_preprocess: true` runs
+    const DEFAULT: [OcrPreprocess; 5] = [
+        OcrPreprocess::Grayscale,
+        OcrPreprocess::Contrast,
+        OcrPreprocess::Despeckle,
+        OcrPreprocess::Deskew,
+        OcrPreprocess::Binarize,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            OcrPreprocess::Grayscale => "grayscale",
+            OcrPreprocess::Contrast => "contrast",
+            OcrPreprocess::Despeckle => "despeckle",
+            OcrPreprocess::Deskew => "deskew",
+            OcrPreprocess::Binarize => "binarize",
         }
+    }
+}
 
-        // Use centralized format detection
-        let format = FormatDetector::detect(filename, Some(&data));
-        
-        // Use centralized dispatch
-        self.dispatch_to_parser(format, data)
+/// An engine turning an image into plain text, chosen with the `ocr_backend`
+/// option. Every format that OCRs images goes through it, so another engine
+/// only needs an implementation here.
+trait OcrBackend: std::fmt::Debug + Send + Sync {
+    /// Recognize the text in an image, or in the `region` (x, y, w, h) of it
+    fn recognize(&self, parser: &Parser, data: &[u8], region: Option<(u32, u32, u32, u32)>) -> Result<String, Error>;
+
+    /// How `config` reports the backend
+    fn to_value(&self, ruby: &Ruby) -> Value;
+
+    /// Mark the Ruby objects the backend holds, so the GC keeps them alive
+    fn mark(&self, _marker: &magnus::gc::Marker) {}
+
+    /// Whether Tesseract's word boxes and layout output (`ocr_image_result`,
+    /// `ocr_output`) are available
+    fn is_tesseract(&self) -> bool {
+        false
     }
-    
-    /// Centralized dispatch logic - routes format to appropriate parser
-    fn dispatch_to_parser(&self, format: FileFormat, data: Vec<u8>) -> Result<String, Error> {
-        match format {
-            FileFormat::Pdf => self.parse_pdf(data),
-            FileFormat::Docx => self.parse_docx(data),
-            FileFormat::Pptx => self.parse_pptx(data),
-            FileFormat::Xlsx | FileFormat::Xls => self.parse_xlsx(data),
-            FileFormat::Json => self.parse_json(data),
-            FileFormat::Xml | FileFormat::Html => self.parse_xml(data),
-            FileFormat::Png | FileFormat::Jpeg | FileFormat::Tiff | FileFormat::Bmp => self.ocr_image(data),
-            FileFormat::Text | FileFormat::Unknown => self.parse_text(data),
+}
+
+/// The built-in Tesseract engine
+#[derive(Debug)]
+struct TesseractOcr;
+
+impl OcrBackend for TesseractOcr {
+    fn recognize(&self, parser: &Parser, data: &[u8], region: Option<(u32, u32, u32, u32)>) -> Result<String, Error> {
+        // Each page of a multi-page TIFF is recognized, a blank line apart
+        if region.is_none() {
+            if let Some(pages) = Parser::tiff_pages(data)? {
+                let source_dpi = Parser::image_dpi(data);
+                let texts = parser.ocr_pages(pages, |page| {
+                    let (tesseract, _) = parser.ocr_engine_for(&page, source_dpi)?;
+                    parser.tesseract_text(&tesseract)
+                })?;
+                return Ok(texts.into_iter().filter(|text| !text.is_empty()).collect::<Vec<_>>().join("\n\n"));
+            }
         }
+        let (tesseract, _) = parser.ocr_engine(data, region)?;
+        Ok(parser.tesseract_text(&tesseract)?)
     }
 
-    /// Ruby-accessible method to detect format from bytes
-    fn detect_format_from_bytes(&self, data: Vec<u8>) -> String {
-        let format = FormatDetector::detect_from_content(&data);
-        // For compatibility with Ruby tests, return "xlsx" for old Excel
-        match format {
-            FileFormat::Xls => "xlsx".to_string(),  // Compatibility with existing tests
-            _ => format.to_symbol().to_string(),
-        }
+    fn to_value(&self, ruby: &Ruby) -> Value {
+        ruby.to_symbol("tesseract").as_value()
     }
-    
-    /// Ruby-accessible method to detect format from filename
-    fn detect_format_from_filename(&self, filename: String) -> String {
-        let format = FormatDetector::detect_from_extension(&filename);
-        format.to_symbol().to_string()
+
+    fn is_tesseract(&self) -> bool {
+        true
     }
+}
 
-    /// Perform OCR on image data using Tesseract
-    fn ocr_image(&self, data: Vec<u8>) -> Result<String, Error> {
-        use tesseract_rs::TesseractAPI;
-        
-        // Create tesseract instance
-        let tesseract = TesseractAPI::new();
-        
-        // Try to initialize with appropriate tessdata path
-        // Even in bundled mode, we need to find tessdata files
-        #[cfg(feature = "bundled-tesseract")]
-        let init_result = {
-            // Build list of tessdata paths to try
-            let mut tessdata_paths = Vec::new();
-            
-            // Check TESSDATA_PREFIX environment variable first (for CI)
-            if let Ok(env_path) = std::env::var("TESSDATA_PREFIX") {
-                tessdata_paths.push(env_path);
-            }
-            
-            // Add common system paths
-            tessdata_paths.extend_from_slice(&[
-                "/usr/share/tessdata".to_string(),
-                "/usr/local/share/tessdata".to_string(), 
-                "/opt/homebrew/share/tessdata".to_string(),
-                "/opt/local/share/tessdata".to_string(),
-                "tessdata".to_string(),  // Local tessdata directory
-                ".".to_string(),  // Current directory as fallback
-            ]);
-            
-            let mut result = Err(tesseract_rs::TesseractError::InitError);
-            for path in &tessdata_paths {
-                // Check if path exists first to avoid noisy error messages
-                if std::path::Path::new(path).exists() {
-                    if tesseract.init(path.as_str(), "eng").is_ok() {
-                        result = Ok(());
-                        break;
-                    }
-                }
+/// A Ruby object whose `call(image_bytes)` returns the text, e.g. a lambda
+/// sending the image to a remote OCR service
+struct CallbackOcr(magnus::value::Opaque<Value>);
+
+impl std::fmt::Debug for CallbackOcr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CallbackOcr")
+    }
+}
+
+impl OcrBackend for CallbackOcr {
+    fn recognize(&self, _parser: &Parser, data: &[u8], region: Option<(u32, u32, u32, u32)>) -> Result<String, Error> {
+        let ruby = Ruby::get().unwrap();
+        // The callback only ever sees the part of the image to recognize
+        let image = match region {
+            Some(region) => {
+                let image = image::load_from_memory(data)
+                    .map_err(|e| Parser::runtime_error("Failed to load image", e))?;
+                let mut png = Vec::new();
+                Parser::crop_ocr_region(image, region)?
+                    .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+                    .map_err(|e| Parser::runtime_error("Failed to encode image", e))?;
+                png
             }
-            result
+            None => data.to_vec(),
         };
-        
-        #[cfg(not(feature = "bundled-tesseract"))]
-        let init_result = {
-            // Try common system tessdata paths
-            let tessdata_paths = vec![
-                "/usr/share/tessdata",
-                "/usr/local/share/tessdata", 
-                "/opt/homebrew/share/tessdata",
-                "/opt/local/share/tessdata",
-            ];
-            
-            let mut result = Err(tesseract_rs::TesseractError::InitError);
-            for path in &tessdata_paths {
-                if std::path::Path::new(path).exists() {
-                    if tesseract.init(path, "eng").is_ok() {
-                        result = Ok(());
-                        break;
-                    }
+        let text: String = ruby.get_inner(self.0).funcall("call", (ruby.str_from_slice(&image),))?;
+        Ok(text.trim().to_string())
+    }
+
+    fn to_value(&self, ruby: &Ruby) -> Value {
+        ruby.get_inner(self.0)
+    }
+
+    fn mark(&self, marker: &magnus::gc::Marker) {
+        marker.mark(self.0);
+    }
+}
+
+/// Longer side, in pixels, that images without a recorded resolution are
+/// enlarged to before OCR
+const OCR_SMALL_IMAGE: u32 = 1000;
+
+/// Most an image is enlarged for OCR, by factor and by resulting pixel count
+const OCR_MAX_UPSCALE: f32 = 4.0;
+const OCR_MAX_PIXELS: f32 = 40_000_000.0;
+
+/// How an image was prepared for OCR
+#[derive(Debug)]
+struct OcrScan {
+    /// Resolution recorded in the image file, if any
+    source_dpi: Option<f32>,
+    /// Factor the image was enlarged by
+    scale: f32,
+    /// The file records less than `ocr_dpi`, or no resolution and few pixels
+    low_resolution: bool,
+}
+
+/// What a Tesseract instance was set up with. Instances are only reused for
+/// the same languages, modes and model directory.
+#[derive(Debug, Clone, PartialEq)]
+struct TesseractKey {
+    language: String,
+    oem: Option<u8>,
+    psm: Option<u8>,
+    tessdata_path: Option<String>,
+}
+
+/// Fewest idle Tesseract instances the process keeps loaded; more are kept
+/// when `ocr_threads` workers each check one out
+const TESSERACT_POOL_SIZE: usize = 4;
+
+/// Idle Tesseract instances, since loading the traineddata costs more than
+/// recognizing a typical image. Shared by every thread, so instances outlive
+/// the OCR worker threads that loaded them.
+static TESSERACT_POOL: std::sync::Mutex<Vec<(TesseractKey, tesseract_rs::TesseractAPI)>> =
+    std::sync::Mutex::new(Vec::new());
+
+/// A Tesseract instance lent out by the pool, returned to it when dropped
+struct PooledTesseract {
+    key: TesseractKey,
+    api: Option<tesseract_rs::TesseractAPI>,
+    /// How many idle instances the pool may hold once this one is back
+    keep: usize,
+}
+
+impl PooledTesseract {
+    /// Take an idle instance set up for `key` out of the pool
+    fn take(key: &TesseractKey) -> Option<tesseract_rs::TesseractAPI> {
+        let mut pool = TESSERACT_POOL.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let index = pool.iter().position(|(idle, _)| idle == key)?;
+        Some(pool.swap_remove(index).1)
+    }
+}
+
+impl std::ops::Deref for PooledTesseract {
+    type Target = tesseract_rs::TesseractAPI;
+
+    fn deref(&self) -> &Self::Target {
+        self.api.as_ref().expect("Tesseract instance is only taken when dropped")
+    }
+}
+
+impl Drop for PooledTesseract {
+    fn drop(&mut self) {
+        let Some(api) = self.api.take() else {
+            return;
+        };
+        let mut pool = TESSERACT_POOL.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        while pool.len() >= self.keep {
+            pool.remove(0);
+        }
+        pool.push((self.key.clone(), api));
+    }
+}
+
+/// Column header Tesseract's command line puts on TSV output
+const OCR_TSV_HEADER: &str = "level\tpage_num\tblock_num\tpar_num\tline_num\tword_num\tleft\ttop\twidth\theight\tconf\ttext";
+
+/// Deepest chain of entities referencing entities that `resolve_entities` expands
+const MAX_ENTITY_DEPTH: usize = 8;
+
+/// Total text `resolve_entities` may produce from entity expansion in one document
+const MAX_ENTITY_EXPANSION: usize = 1024 * 1024;
+
+/// Decompressed size below which `max_compression_ratio` isn't enforced;
+/// small, highly repetitive files are harmless
+const COMPRESSION_RATIO_GRACE: u64 = 1024 * 1024;
+
+/// Deepest PDF name tree, form field hierarchy, /Next action chain or
+/// structure tree followed; guards against reference cycles in malformed files
+const MAX_PDF_NESTING: usize = 32;
+
+/// Deepest DICOM sequence nesting read; real files go a handful of levels deep
+const MAX_DICOM_NESTING: usize = 64;
+
+/// Deepest Matroska ChapterAtom nesting read; deeper sub-chapters are skipped
+const MAX_CHAPTER_NESTING: usize = 32;
+
+/// Deepest Office Math element nesting kept for `omml_latex`; elements below
+/// it are dropped, keeping the tree's recursion within the native stack
+const MAX_OMML_NESTING: usize = 64;
+
+/// Standard structure types that run inline; every other element of a
+/// tagged PDF ends its own line
+const PDF_INLINE_STRUCTURE: &[&str] = &[
+    "Span", "Link", "Quote", "Reference", "BibEntry", "Code", "Lbl", "Annot", "Ruby", "Warichu", "Em", "Strong", "Sub",
+];
+
+/// Pages picked by the `pages:` option of the PDF methods, or slides by
+/// `slides:` of `parse_pptx`, numbered from 1
+#[derive(Debug)]
+enum PageSelection {
+    All,
+    /// Inclusive bounds; an open end runs to the last page. Negative bounds
+    /// count back from the last page, which is -1, as in `Array#[]`.
+    Range(i64, Option<i64>),
+    List(Vec<usize>),
+    First(usize),
+}
+
+impl PageSelection {
+    /// Zero-based indices of the selected pages that exist in a document of `count` pages
+    fn indices(&self, count: usize) -> Vec<usize> {
+        match self {
+            PageSelection::All => (0..count).collect(),
+            PageSelection::Range(start, end) => {
+                let count = count as i64;
+                let resolve = |page: i64| if page < 0 { count + 1 + page } else { page };
+                let start = resolve(*start);
+                let end = end.map_or(count, |end| resolve(end).min(count));
+                // Like `Array#[]`, a start before the first page selects nothing
+                if start < 1 {
+                    return Vec::new();
                 }
+                (start..=end).map(|page| page as usize - 1).collect()
             }
-            result
-        };
-        
-        if let Err(e) = init_result {
-            return Err(Self::runtime_error("Failed to initialize Tesseract", e));
+            PageSelection::List(pages) => pages
+                .iter()
+                .filter(|&&page| page <= count)
+                .map(|page| page - 1)
+                .collect(),
+            PageSelection::First(n) => (0..(*n).min(count)).collect(),
         }
-        
-        // Load the image from bytes
-        let img = image::load_from_memory(&data)
-            .map_err(|e| Self::runtime_error("Failed to load image", e))?;
-        
-        // Convert to RGBA8 format
-        let rgba_img = img.to_rgba8();
-        let (width, height) = rgba_img.dimensions();
-        let raw_data = rgba_img.into_raw();
-        
-        // Set image data
-        tesseract.set_image(
-            &raw_data,
-            width as i32,
-            height as i32,
-            4,  // bytes per pixel (RGBA)
-            (width * 4) as i32,  // bytes per line
-        ).map_err(|e| Self::runtime_error("Failed to set image", e))?;
-        
-        // Extract text
-        tesseract.get_utf8_text()
-            .map(|text| text.trim().to_string())
-            .map_err(|e| Self::runtime_error("Failed to perform OCR", e))
     }
-    
+}
 
-    /// Parse PDF files using MuPDF (statically linked) - exposed to Ruby
-    fn parse_pdf(&self, data: Vec<u8>) -> Result<String, Error> {
-        use mupdf::Document;
+/// Worksheets picked by the `sheets:` option of `parse_xlsx`
+#[derive(Debug)]
+enum SheetSelection {
+    All,
+    /// Sheets matching any of these
+    Matching(Vec<SheetMatcher>),
+}
 
-        // Try to load the PDF from memory
-        // The magic parameter helps MuPDF identify the file type
-        let doc = Document::from_bytes(&data, "pdf")
-            .map_err(|e| Self::runtime_error("Failed to parse PDF", e))?;
-        
-        let mut all_text = String::new();
+/// One entry of a `sheets:` option
+#[derive(Debug)]
+enum SheetMatcher {
+    Name(String),
+    /// Position in the workbook, from 0
+    Index(usize),
+    Pattern(regex::Regex),
+}
 
-        // Get page count
-        let page_count = doc.page_count()
-            .map_err(|e| Self::runtime_error("Failed to get page count", e))?;
+impl SheetSelection {
+    /// The selected sheets among a workbook's, in workbook order
+    fn names(&self, sheets: &[String]) -> Vec<String> {
+        sheets
+            .iter()
+            .enumerate()
+            .filter(|(index, name)| match self {
+                SheetSelection::All => true,
+                SheetSelection::Matching(matchers) => matchers.iter().any(|matcher| match matcher {
+                    SheetMatcher::Name(wanted) => wanted == *name,
+                    SheetMatcher::Index(wanted) => wanted == index,
+                    SheetMatcher::Pattern(pattern) => pattern.is_match(name),
+                }),
+            })
+            .map(|(_, name)| name.clone())
+            .collect()
+    }
+}
+
+/// A `definedName` of an XLSX workbook, for `xlsx_defined_names`
+#[derive(Debug)]
+struct XlsxDefinedName {
+    name: String,
+    /// `localSheetId`, the position of the sheet a name is local to
+    sheet_index: Option<usize>,
+    /// The formula the name stands for, e.g. `Sheet1!$B$2:$B$3`
+    refers_to: String,
+    hidden: bool,
+}
+
+/// A reference to one range, such as `'Q1 Data'!$A$1:$C$9`, for
+/// `xlsx_reference_parts`: the quoted or plain sheet name, then a cell,
+/// an area, whole columns or whole rows
+static XLSX_RANGE_REFERENCE: std::sync::LazyLock<regex::Regex> = std::sync::LazyLock::new(|| {
+    regex::Regex::new(concat!(
+        r"^(?:'((?:[^']|'')+)'|([^'!:]+))!(",
+        r"\$?[A-Za-z]{1,3}\$?\d+(?::\$?[A-Za-z]{1,3}\$?\d+)?",
+        r"|\$?[A-Za-z]{1,3}:\$?[A-Za-z]{1,3}",
+        r"|\$?\d+:\$?\d+",
+        r")$",
+    ))
+    .unwrap()
+});
+
+/// What is read from an XLSX worksheet's own parts beside the cells
+/// calamine reads, see `read_xlsx_sheet_extras`
+#[derive(Debug, Default)]
+struct XlsxSheetExtras {
+    /// Hidden rows and columns, numbered from 0
+    hidden: (std::collections::HashSet<u32>, std::collections::HashSet<u32>),
+    /// Cell hyperlinks as the cells they cover and their target
+    links: Vec<(calamine::Dimensions, String)>,
+    /// `[Image: ...]` lines for the pictures in the sheet's drawings
+    images: Vec<String>,
+}
+
+/// Chars closer than this many ems stay in one table cell
+const PDF_CELL_GAP: f32 = 1.0;
+
+/// A soft hyphen with the line break after it, for `normalize_typography`
+static SOFT_HYPHEN_BREAK: std::sync::LazyLock<regex::Regex> =
+    std::sync::LazyLock::new(|| regex::Regex::new(r"\u{AD}(?:[ \t]*\r?\n[ \t]*)?").unwrap());
+
+/// A word hyphenated across a line break between lowercase letters, for
+/// `normalize_typography`
+static LINE_END_HYPHEN: std::sync::LazyLock<regex::Regex> =
+    std::sync::LazyLock::new(|| regex::Regex::new(r"(\p{Ll})[-\u{2010}][ \t]*\r?\n[ \t]*(\p{Ll})").unwrap());
+
+/// Three or more line breaks, collapsed to one blank line by `parse_wordperfect`
+static EXTRA_BLANK_LINES: std::sync::LazyLock<regex::Regex> =
+    std::sync::LazyLock::new(|| regex::Regex::new(r"\n{3,}").unwrap());
+
+/// WordPerfect 6 single-byte characters 0x01 to 0x1F, the default extended
+/// international set
+const WP6_INTERNATIONAL_CHARS: [char; 31] = [
+    'å', 'Å', 'æ', 'Æ', 'ä', 'Ä', 'á', 'à', 'â', 'ã', 'Ã', 'ç', 'Ç', 'ë', 'é', 'É', 'è', 'ê', 'í', 'ñ', 'Ñ',
+    'ø', 'Ø', 'õ', 'Õ', 'ö', 'Ö', 'ü', 'Ü', 'ú', 'ù',
+];
+
+/// WordPerfect character set 1 (Multinational) from 0x17, the Latin-1
+/// letters; the entries before it are combining accents
+const WP_MULTINATIONAL_CHARS: [char; 67] = [
+    'ß', 'ĸ', '\u{FFFD}', 'Á', 'á', 'Â', 'â', 'Ä', 'ä', 'À', 'à', 'Å', 'å', 'Æ', 'æ', 'Ç', 'ç', 'É', 'é', 'Ê',
+    'ê', 'Ë', 'ë', 'È', 'è', 'Í', 'í', 'Î', 'î', 'Ï', 'ï', 'Ì', 'ì', 'Ñ', 'ñ', 'Ó', 'ó', 'Ô', 'ô', 'Ö', 'ö',
+    'Ò', 'ò', 'Ú', 'ú', 'Û', 'û', 'Ü', 'ü', 'Ù', 'ù', 'Ÿ', 'ÿ', 'Ã', 'ã', 'Đ', 'đ', 'Ø', 'ø', 'Õ', 'õ', 'Ý',
+    'ý', 'Ð', 'ð', 'Þ', 'þ',
+];
+
+/// WordPerfect character set 4 (Typographic Symbols) from 0x00: bullets,
+/// Latin-1 signs, quotes and dashes
+const WP_TYPOGRAPHIC_CHARS: [char; 37] = [
+    '●', '○', '■', '•', '*', '¶', '§', '¡', '¿', '«', '»', '£', '¥', '₧', 'ƒ', 'ª', 'º', '½', '¼', '¢', '²',
+    'ⁿ', '®', '©', '¤', '¾', '³', '‛', '’', '‘', '‟', '”', '“', '–', '—', '‹', '›',
+];
+
+/// Class and id words of boilerplate elements, for `html_main_content`
+static HTML_UNLIKELY_CONTENT: std::sync::LazyLock<regex::Regex> = std::sync::LazyLock::new(|| {
+    regex::Regex::new(
+        r"(?i)\bads?\b|advert|banner|breadcrumb|comment|cookie|footer|masthead|menu|modal|nav|newsletter|pagination|popup|promo|related|share|sidebar|social|sponsor|subscribe|widget",
+    )
+    .unwrap()
+});
+
+/// Class and id words of content elements, for `html_main_content`
+static HTML_LIKELY_CONTENT: std::sync::LazyLock<regex::Regex> = std::sync::LazyLock::new(|| {
+    regex::Regex::new(r"(?i)article|body|content|entry|main|post|story|text").unwrap()
+});
+
+/// An RFC 5424 syslog line, for `normalize_log`
+static SYSLOG_RFC5424: std::sync::LazyLock<regex::Regex> = std::sync::LazyLock::new(|| {
+    regex::Regex::new(
+        r"^<(?P<pri>\d{1,3})>1 (?P<timestamp>\S+) \S+ (?P<app>\S+) \S+ \S+ (?:-|(?:\[(?:[^\]\\]|\\.)*\])+) ?(?P<message>.*)$",
+    )
+    .unwrap()
+});
+
+/// A BSD (RFC 3164) syslog line, with or without its PRI, for `normalize_log`
+static SYSLOG_RFC3164: std::sync::LazyLock<regex::Regex> = std::sync::LazyLock::new(|| {
+    regex::Regex::new(
+        r"^(?:<(?P<pri>\d{1,3})>)?(?P<timestamp>[A-Z][a-z]{2} +\d{1,2} \d{2}:\d{2}:\d{2}) \S+ (?P<message>.*)$",
+    )
+    .unwrap()
+});
+
+/// A Common or Combined Log Format access log line, for `normalize_log`
+static COMBINED_LOG_FORMAT: std::sync::LazyLock<regex::Regex> = std::sync::LazyLock::new(|| {
+    regex::Regex::new(
+        r#"^(?P<client>\S+) \S+ \S+ \[(?P<timestamp>[^\]]+)\] "(?P<request>[^"]*)" (?P<status>\d{3}) (?P<bytes>\S+)(?: "(?P<referer>[^"]*)" "(?P<agent>[^"]*)")?"#,
+    )
+    .unwrap()
+});
+
+/// HTML-style tags and ASS/SSA `{...}` override blocks in a subtitle cue,
+/// for `parse_subtitles`
+static SUBTITLE_MARKUP: std::sync::LazyLock<regex::Regex> =
+    std::sync::LazyLock::new(|| regex::Regex::new(r"<[^>]*>|\{[^}]*\}").unwrap());
+
+/// An internal general entity declaration with a quoted value, for
+/// `internal_xml_entities`
+static XML_ENTITY_DECLARATION: std::sync::LazyLock<regex::Regex> =
+    std::sync::LazyLock::new(|| regex::Regex::new(r#"<!ENTITY\s+([^\s%"'>]+)\s+(?:"([^"]*)"|'([^']*)')\s*>"#).unwrap());
+
+/// A run of text on one baseline, the unit `parse_pdf_tables` places in cells
+#[derive(Debug)]
+struct PdfTextChunk {
+    text: String,
+    x0: f32,
+    x1: f32,
+    y: f32,
+    size: f32,
+}
+
+/// A run of PDF text in one font, as returned by `parse_pdf_spans`
+#[derive(Debug)]
+struct PdfSpan {
+    text: String,
+    font: String,
+    size: f32,
+    bold: bool,
+    italic: bool,
+    /// x0, y0, x1, y1 in points from the page's top-left corner
+    bbox: [f32; 4],
+}
+
+/// A highlight, note or free-text annotation on a PDF page
+#[derive(Debug)]
+struct PdfAnnotation {
+    kind: &'static str,
+    contents: Option<String>,
+    author: Option<String>,
+    /// Highlighted areas in MuPDF page coordinates (x0, y0, x1, y1)
+    boxes: Vec<(f32, f32, f32, f32)>,
+}
+
+/// A file embedded in a PDF's /EmbeddedFiles name tree
+#[derive(Debug)]
+struct PdfAttachment {
+    name: String,
+    description: Option<String>,
+    mime_type: Option<String>,
+    data: Vec<u8>,
+}
+
+/// An action found in a PDF: where it fires, its /S type and, for
+/// JavaScript actions, the script source
+#[derive(Debug)]
+struct PdfAction {
+    trigger: String,
+    kind: String,
+    script: Option<String>,
+}
+
+/// A token of a PDF content stream (or CMap)
+#[derive(Debug, Clone, PartialEq)]
+enum PdfToken {
+    Number(f64),
+    Name(String),
+    String(Vec<u8>),
+    Array(Vec<PdfToken>),
+    Dict(Vec<(String, PdfToken)>),
+    Operator(String),
+}
+
+/// Text shown by a text operator, or a word gap from TJ kerning
+enum PdfDecoded<'a> {
+    Text(&'a [u8]),
+    Space,
+}
+
+/// Maps a font's character codes to Unicode, from its ToUnicode CMap
+#[derive(Debug)]
+struct PdfFontDecoder {
+    code_bytes: usize,
+    codes: std::collections::HashMap<u32, String>,
+}
+
+impl PdfFontDecoder {
+    /// Decode shown bytes; single-byte codes without a mapping are read as Latin-1
+    fn decode(&self, bytes: &[u8]) -> String {
+        bytes
+            .chunks(self.code_bytes)
+            .filter_map(|chunk| {
+                let code = chunk.iter().fold(0u32, |code, &b| code << 8 | b as u32);
+                match self.codes.get(&code) {
+                    Some(text) => Some(text.clone()),
+                    None if self.code_bytes == 1 => Some((chunk[0] as char).to_string()),
+                    None => None,
+                }
+            })
+            .collect()
+    }
+}
+
+/// A filled-in field of an XFA form
+#[derive(Debug)]
+struct PdfXfaField {
+    /// Path of data element names, e.g. `form1.Applicant.Name`
+    name: String,
+    /// The field's caption in the form template
+    label: Option<String>,
+    value: String,
+}
+
+/// A step of a tagged PDF's logical reading order
+#[derive(Debug)]
+enum PdfStructureItem {
+    /// Marked content `mcid` on the page whose object number is `page`
+    Content { page: i32, mcid: i64 },
+    /// An element's /ActualText, standing in for its content
+    Text { page: i32, text: String },
+    /// The end of a block-level element
+    Break,
+}
+
+/// A word recognized by Tesseract, read from its TSV output
+#[derive(Debug)]
+struct OcrWord {
+    text: String,
+    /// 0-100
+    confidence: f32,
+    /// left, top, width, height in pixels
+    bbox: [u32; 4],
+    /// Block, paragraph and line numbers, for laying the text out again
+    line: (u32, u32, u32),
+}
+
+/// An element read by `parse_xml_structured`, turned into a Hash once the
+/// whole document has been read
+#[derive(Debug)]
+struct XmlElement {
+    /// Qualified name, as written
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<XmlElement>,
+    text: String,
+}
+
+/// A reviewer comment from a DOCX, for `docx_comments`
+#[derive(Debug)]
+struct DocxComment {
+    id: String,
+    author: Option<String>,
+    date: Option<String>,
+    text: String,
+    /// The commented text
+    anchor: String,
+    /// Top-level body paragraph the comment's range ends in
+    paragraph: Option<usize>,
+}
+
+/// A content control (`w:sdt`) or legacy form field from a DOCX, for
+/// `docx_form_fields`
+#[derive(Debug)]
+struct DocxFormField {
+    /// `w:tag` of a content control, bookmark name of a legacy field
+    tag: Option<String>,
+    /// `w:alias`, the title shown in Word
+    title: Option<String>,
+    kind: &'static str,
+    /// `None` while a content control shows its placeholder
+    value: Option<String>,
+}
+
+/// An Office Math (OMML) element, read whole so `omml_latex` can look at
+/// its parts
+#[derive(Debug, Default)]
+struct OmmlNode {
+    /// Local name, e.g. `f` for `m:f`
+    name: String,
+    /// The `m:val` attribute, which properties such as `m:chr` keep their value in
+    val: Option<String>,
+    /// The text of an `m:t`
+    text: String,
+    children: Vec<OmmlNode>,
+}
+
+/// The text of a DOCX body, read by `DocxBodyReader`
+#[derive(Debug, Default)]
+struct DocxBody {
+    /// A line per paragraph or table row
+    lines: Vec<DocxLine>,
+    /// How many top-level body paragraphs there are
+    paragraphs: usize,
+    /// External hyperlinks as `(text, target)`
+    links: Vec<(String, String)>,
+}
+
+/// A paragraph or table row of a DOCX body
+#[derive(Debug)]
+struct DocxLine {
+    /// The index of the top-level `w:body` paragraph it is (see `ooxml_image_refs`)
+    paragraph: Option<usize>,
+    /// The heading level, see `docx_heading_level`
+    heading: Option<usize>,
+    text: String,
+}
+
+/// A part of a DOCX under one heading, for `docx_sections`
+#[derive(Debug, Default)]
+struct DocxSection {
+    /// `None` for the text before the first heading
+    heading: Option<String>,
+    level: usize,
+    text: Vec<String>,
+    sections: Vec<DocxSection>,
+}
+
+/// The paragraph properties (`w:pPr`) of a DOCX paragraph or style that
+/// list prefixes and heading levels depend on
+#[derive(Debug, Default, Clone)]
+struct DocxParagraphProperties {
+    /// `w:pStyle`
+    style: Option<String>,
+    /// `w:numPr` as `(w:numId, w:ilvl)`
+    numbering: Option<(Option<usize>, Option<usize>)>,
+    /// `w:outlineLvl`, counting from 0; 9 is body text
+    outline_level: Option<usize>,
+}
+
+/// A paragraph style from a DOCX `word/styles.xml`
+#[derive(Debug, Default)]
+struct DocxStyle {
+    /// `w:name`, e.g. "heading 1"
+    name: String,
+    /// `w:basedOn`, the Id of the style this one inherits from
+    based_on: Option<String>,
+    properties: DocxParagraphProperties,
+}
+
+/// A level (`w:lvl`) of a DOCX list
+#[derive(Debug, Default, Clone)]
+struct DocxListLevel {
+    start: usize,
+    /// `w:numFmt`, e.g. "decimal" or "bullet"
+    format: String,
+    /// `w:lvlText`, e.g. "%1.%2."
+    text: String,
+}
+
+/// A list instance (`w:num`) from a DOCX `word/numbering.xml`
+#[derive(Debug, Default)]
+struct DocxList {
+    abstract_id: usize,
+    /// `w:lvlOverride`s by level, as a new start and a level replacing the
+    /// abstract list's
+    overrides: std::collections::HashMap<usize, (Option<usize>, Option<DocxListLevel>)>,
+}
+
+/// List numbering while reading DOCX body paragraphs in order: resolves a
+/// paragraph's `w:numPr`, its own or its style's, against numbering.xml
+/// and counts the items of each list
+struct DocxLists<'a> {
+    styles: &'a std::collections::HashMap<String, DocxStyle>,
+    /// The levels of each `w:abstractNum`, by Id
+    abstract_lists: std::collections::HashMap<usize, std::collections::HashMap<usize, DocxListLevel>>,
+    /// `w:num`s by Id
+    lists: std::collections::HashMap<usize, DocxList>,
+    /// The current number at each level, by `w:num` Id; `None` for levels
+    /// with no item since their parent's last one
+    counters: std::collections::HashMap<usize, Vec<Option<usize>>>,
+}
+
+impl<'a> DocxLists<'a> {
+    /// Read the lists of a DOCX `word/numbering.xml` part
+    fn new(numbering: &str, styles: &'a std::collections::HashMap<String, DocxStyle>) -> Self {
+        use quick_xml::events::Event;
+        use quick_xml::Reader;
+
+        let number = |e: &quick_xml::events::BytesStart, name: &[u8]| Parser::xml_attribute(e, name).and_then(|v| v.trim().parse().ok());
+
+        let mut abstract_lists: std::collections::HashMap<usize, std::collections::HashMap<usize, DocxListLevel>> =
+            std::collections::HashMap::new();
+        let mut lists = std::collections::HashMap::new();
+        let mut abstract_id = None;
+        let mut list: Option<(usize, DocxList)> = None;
+        let mut override_level = None;
+        let mut level: Option<(usize, DocxListLevel)> = None;
+
+        let mut reader = Reader::from_str(numbering);
+        loop {
+            match reader.read_event() {
+                Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) => match e.name().local_name().as_ref() {
+                    b"abstractNum" => abstract_id = number(e, b"abstractNumId"),
+                    b"num" => list = number(e, b"numId").map(|id| (id, DocxList::default())),
+                    b"abstractNumId" => {
+                        if let (Some((_, list)), Some(id)) = (list.as_mut(), number(e, b"val")) {
+                            list.abstract_id = id;
+                        }
+                    }
+                    b"lvlOverride" => override_level = number(e, b"ilvl"),
+                    b"startOverride" => {
+                        if let (Some((_, list)), Some(ilvl)) = (list.as_mut(), override_level) {
+                            list.overrides.entry(ilvl).or_default().0 = number(e, b"val");
+                        }
+                    }
+                    b"lvl" => level = number(e, b"ilvl").map(|ilvl| (ilvl, DocxListLevel::default())),
+                    b"start" => {
+                        if let Some((_, level)) = level.as_mut() {
+                            level.start = number(e, b"val").unwrap_or(0);
+                        }
+                    }
+                    b"numFmt" => {
+                        if let Some((_, level)) = level.as_mut() {
+                            level.format = Parser::xml_attribute(e, b"val").unwrap_or_default();
+                        }
+                    }
+                    b"lvlText" => {
+                        if let Some((_, level)) = level.as_mut() {
+                            level.text = Parser::xml_attribute(e, b"val").unwrap_or_default();
+                        }
+                    }
+                    _ => {}
+                },
+                Ok(Event::End(ref e)) => match e.name().local_name().as_ref() {
+                    b"lvl" => {
+                        if let Some((ilvl, read)) = level.take() {
+                            match (list.as_mut(), override_level) {
+                                (Some((_, list)), Some(_)) => list.overrides.entry(ilvl).or_default().1 = Some(read),
+                                _ => {
+                                    if let Some(id) = abstract_id {
+                                        abstract_lists.entry(id).or_default().insert(ilvl, read);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    b"lvlOverride" => override_level = None,
+                    b"num" => lists.extend(list.take()),
+                    b"abstractNum" => abstract_id = None,
+                    _ => {}
+                },
+                Ok(Event::Eof) | Err(_) => break,
+                _ => {}
+            }
+        }
+
+        Self {
+            styles,
+            abstract_lists,
+            lists,
+            counters: std::collections::HashMap::new(),
+        }
+    }
+
+    /// Count a paragraph and return the prefix its list level shows, e.g.
+    /// `- ` for bullets or `1.2. `, indented two spaces per level. `None`
+    /// for paragraphs outside lists.
+    fn prefix(&mut self, properties: &DocxParagraphProperties) -> Option<String> {
+        let (id, ilvl) = properties
+            .numbering
+            .or_else(|| self.styles.get(properties.style.as_ref()?)?.properties.numbering)?;
+        // Id 0 takes a paragraph out of the list its style puts it in
+        let id = id?;
+        if id == 0 {
+            return None;
+        }
+        let ilvl = ilvl.unwrap_or(0).min(8);
+        let list = self.lists.get(&id)?;
+        let levels = self.abstract_lists.get(&list.abstract_id)?;
+
+        // A `w:lvlOverride` replaces a level or just its start
+        let level = |ilvl: usize| {
+            list.overrides
+                .get(&ilvl)
+                .and_then(|(_, level)| level.as_ref())
+                .or_else(|| levels.get(&ilvl))
+        };
+        let start = |ilvl: usize| {
+            list.overrides
+                .get(&ilvl)
+                .and_then(|(start, _)| *start)
+                .unwrap_or_else(|| level(ilvl).map_or(0, |l| l.start))
+        };
+
+        // An item restarts the levels below it
+        let counts = self.counters.entry(id).or_default();
+        counts.resize(ilvl + 1, None);
+        counts[ilvl] = Some(counts[ilvl].map_or_else(|| start(ilvl), |count| count + 1));
+
+        let current = level(ilvl)?;
+        let text = match current.format.as_str() {
+            "bullet" => "-".to_string(),
+            "none" => String::new(),
+            _ => {
+                let mut text = current.text.clone();
+                // `%1` to `%9` stand for the current number at each level
+                for at in (0..=ilvl).rev() {
+                    let placeholder = format!("%{}", at + 1);
+                    if text.contains(&placeholder) {
+                        let count = counts.get(at).copied().flatten().unwrap_or_else(|| start(at));
+                        let format = level(at).map_or("decimal", |l| l.format.as_str());
+                        text = text.replace(&placeholder, &Self::number(count, format));
+                    }
+                }
+                text
+            }
+        };
+
+        let mut prefix = "  ".repeat(ilvl);
+        if !text.is_empty() {
+            prefix.push_str(&text);
+            prefix.push(' ');
+        }
+        Some(prefix)
+    }
+
+    /// Write a list number in a `w:numFmt` style; formats without a
+    /// counterpart here fall back to decimal
+    fn number(n: usize, format: &str) -> String {
+        match format {
+            "decimalZero" => format!("{:02}", n),
+            "lowerLetter" | "upperLetter" if n > 0 => {
+                // After z come aa, bb, ...
+                let letter = (b'a' + ((n - 1) % 26) as u8) as char;
+                let letters = letter.to_string().repeat((n - 1) / 26 + 1);
+                if format == "upperLetter" {
+                    letters.to_uppercase()
+                } else {
+                    letters
+                }
+            }
+            "lowerRoman" | "upperRoman" if n > 0 => {
+                const NUMERALS: [(usize, &str); 13] = [
+                    (1000, "m"), (900, "cm"), (500, "d"), (400, "cd"), (100, "c"), (90, "xc"),
+                    (50, "l"), (40, "xl"), (10, "x"), (9, "ix"), (5, "v"), (4, "iv"), (1, "i"),
+                ];
+                let mut rest = n;
+                let mut roman = String::new();
+                for (value, numeral) in NUMERALS {
+                    while rest >= value {
+                        roman.push_str(numeral);
+                        rest -= value;
+                    }
+                }
+                if format == "upperRoman" {
+                    roman.to_uppercase()
+                } else {
+                    roman
+                }
+            }
+            _ => n.to_string(),
+        }
+    }
+}
+
+/// Where `walk_ooxml_part` is in an OOXML part, which its visitors place
+/// what they read by
+#[derive(Debug, Default)]
+struct OoxmlCursor {
+    /// The depth of the current element, the root being 1
+    depth: usize,
+    /// Whether the current element is empty, e.g. `<w:p/>`: its end follows
+    /// its start right away
+    empty: bool,
+    /// The depth of a DOCX `w:body`
+    body_depth: Option<usize>,
+    /// How many top-level `w:body` paragraphs have started
+    paragraphs: usize,
+    /// The top-level `w:body` paragraph being read, as (index, depth)
+    paragraph: Option<(usize, usize)>,
+}
+
+impl OoxmlCursor {
+    /// The index of the top-level `w:body` paragraph being read; always
+    /// `None` outside DOCX
+    fn paragraph(&self) -> Option<usize> {
+        self.paragraph.map(|(index, _)| index)
+    }
+
+    /// The index of the current element if it is a top-level `w:body` paragraph
+    fn body_paragraph(&self) -> Option<usize> {
+        self.paragraph.filter(|&(_, at)| at == self.depth).map(|(index, _)| index)
+    }
+
+    fn enter(&mut self, e: &quick_xml::events::BytesStart, empty: bool) {
+        self.depth += 1;
+        self.empty = empty;
+        match e.name().local_name().as_ref() {
+            b"body" if self.body_depth.is_none() => self.body_depth = Some(self.depth),
+            b"p" if self.body_depth == Some(self.depth - 1) => {
+                self.paragraph = Some((self.paragraphs, self.depth));
+                self.paragraphs += 1;
+            }
+            _ => {}
+        }
+    }
+
+    fn leave(&mut self) {
+        if self.paragraph.is_some_and(|(_, at)| at == self.depth) {
+            self.paragraph = None;
+        }
+        if self.body_depth == Some(self.depth) {
+            self.body_depth = None;
+        }
+        self.depth -= 1;
+        self.empty = false;
+    }
+}
+
+/// One of the readers `walk_ooxml_part` feeds an OOXML part to, so a DOCX
+/// `word/document.xml` is parsed once however much is read from it
+trait OoxmlVisitor {
+    /// An element starts; `at` already counts it
+    fn start(&mut self, e: &quick_xml::events::BytesStart, at: &OoxmlCursor);
+    /// An element ends, by local name; `at` still counts it
+    fn end(&mut self, name: &[u8], at: &OoxmlCursor);
+    /// Text, with references resolved
+    fn text(&mut self, text: &str);
+}
+
+/// Reads a DOCX body into a `DocxBody`. Each paragraph becomes a line, with
+/// its list prefix and heading level; each table row becomes a line of
+/// tab-separated cells. Tracked changes are resolved by the `revisions`
+/// option (see `push_docx_revision`). Text boxes and equations are left to
+/// `DocxTextBoxReader` and `DocxEquationReader`, and the VML copy Word
+/// keeps of a drawing (`mc:Fallback`) is skipped.
+struct DocxBodyReader<'a> {
+    parser: &'a Parser,
+    styles: &'a std::collections::HashMap<String, DocxStyle>,
+    lists: DocxLists<'a>,
+    /// The document's external relationship targets, for hyperlinks
+    links: &'a [(String, String)],
+    body: DocxBody,
+    /// An element whose content isn't read here, by depth
+    skip_depth: Option<usize>,
+    paragraph: Option<(usize, Option<usize>)>, // (depth, index)
+    properties: DocxParagraphProperties,
+    properties_depth: Option<usize>,
+    text: String,
+    run: Option<(usize, String)>, // (depth, text)
+    in_text: bool,
+    /// Open `w:ins`/`w:moveTo` (true) and `w:del`/`w:moveFrom` (false)
+    revisions: Vec<(usize, bool)>,
+    /// Open hyperlinks as (depth, where their text starts, relationship Id)
+    hyperlinks: Vec<(usize, usize, Option<String>)>,
+    tables: Vec<usize>,
+    row: Option<(usize, Vec<String>)>,
+    cell: Option<(usize, String)>,
+}
+
+impl<'a> DocxBodyReader<'a> {
+    fn new(
+        parser: &'a Parser,
+        styles: &'a std::collections::HashMap<String, DocxStyle>,
+        lists: DocxLists<'a>,
+        links: &'a [(String, String)],
+    ) -> Self {
+        Self {
+            parser,
+            styles,
+            lists,
+            links,
+            body: DocxBody::default(),
+            skip_depth: None,
+            paragraph: None,
+            properties: DocxParagraphProperties::default(),
+            properties_depth: None,
+            text: String::new(),
+            run: None,
+            in_text: false,
+            revisions: Vec::new(),
+            hyperlinks: Vec::new(),
+            tables: Vec::new(),
+            row: None,
+            cell: None,
+        }
+    }
+}
+
+impl OoxmlVisitor for DocxBodyReader<'_> {
+    fn start(&mut self, e: &quick_xml::events::BytesStart, at: &OoxmlCursor) {
+        let depth = at.depth;
+        let name = e.name();
+        match name.local_name().as_ref() {
+            _ if self.skip_depth.is_some() => {}
+            // Text boxes and equations are read on their own; control
+            // properties, run properties and earlier formatting hold no text
+            b"txbxContent" | b"oMath" | b"oMathPara" | b"Fallback" | b"sdtPr" | b"sdtEndPr" | b"rPr" | b"pPrChange" => {
+                self.skip_depth = Some(depth)
+            }
+            _ if self.properties_depth.is_some() => Parser::read_docx_paragraph_property(&mut self.properties, e),
+            b"p" => {
+                let index = at.body_paragraph();
+                if index.is_some() {
+                    self.body.paragraphs += 1;
+                }
+                self.paragraph = Some((depth, index));
+            }
+            b"pPr" if self.paragraph.is_some_and(|(at, _)| at == depth - 1) => self.properties_depth = Some(depth),
+            b"r" if self.paragraph.is_some() => self.run = Some((depth, String::new())),
+            b"t" | b"delText" if self.run.is_some() => self.in_text = true,
+            b"tab" => {
+                if let Some((_, text)) = self.run.as_mut() {
+                    text.push('\t');
+                }
+            }
+            // Page and column breaks don't break the line
+            b"br" | b"cr" if Parser::xml_attribute(e, b"type").is_none_or(|kind| kind == "textWrapping") => {
+                if let Some((_, text)) = self.run.as_mut() {
+                    text.push('\n');
+                }
+            }
+            b"ins" | b"moveTo" => self.revisions.push((depth, true)),
+            b"del" | b"moveFrom" => self.revisions.push((depth, false)),
+            b"hyperlink" => self.hyperlinks.push((depth, self.text.len(), Parser::xml_attribute(e, b"id"))),
+            b"tbl" => self.tables.push(depth),
+            b"tr" if self.tables.len() == 1 => self.row = Some((depth, Vec::new())),
+            b"tc" if self.tables.len() == 1 => self.cell = Some((depth, String::new())),
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, _name: &[u8], at: &OoxmlCursor) {
+        let depth = at.depth;
+        self.in_text = false;
+        if self.skip_depth.is_some() {
+            if self.skip_depth == Some(depth) {
+                self.skip_depth = None;
+            }
+            return;
+        }
+        if self.properties_depth == Some(depth) {
+            self.properties_depth = None;
+        }
+        if self.run.as_ref().is_some_and(|(at, _)| *at == depth) {
+            if let Some((_, revised)) = self.run.take() {
+                match self.revisions.last() {
+                    Some(&(_, inserted)) => self.parser.push_docx_revision(&mut self.text, inserted, &revised),
+                    None => self.text.push_str(&revised),
+                }
+            }
+        }
+        if self.revisions.last().is_some_and(|(at, _)| *at == depth) {
+            self.revisions.pop();
+        }
+        if self.hyperlinks.last().is_some_and(|(at, _, _)| *at == depth) {
+            if let Some((_, start, id)) = self.hyperlinks.pop() {
+                // Links to bookmarks inside the document have no target
+                let target = id.and_then(|id| self.links.iter().find(|(rel_id, _)| *rel_id == id));
+                if let Some((_, url)) = target {
+                    let link_text = self.text.get(start..).unwrap_or_default().trim().to_string();
+                    // A link showing its own address isn't repeated
+                    if self.parser.config.docx_links && link_text != *url {
+                        self.text.push_str(" (");
+                        self.text.push_str(url);
+                        self.text.push(')');
+                    }
+                    self.body.links.push((link_text, url.clone()));
+                }
+            }
+        }
+        if self.paragraph.is_some_and(|(at, _)| at == depth) {
+            if let Some((_, index)) = self.paragraph.take() {
+                let mut line = self.lists.prefix(&self.properties).unwrap_or_default();
+                line.push_str(&self.text);
+                match self.cell.as_mut() {
+                    Some((_, cell)) => {
+                        let line = line.trim();
+                        if !cell.is_empty() && !line.is_empty() {
+                            cell.push(' ');
+                        }
+                        cell.push_str(line);
+                    }
+                    None => self.body.lines.push(DocxLine {
+                        paragraph: index,
+                        heading: Parser::docx_heading_level(self.styles, &self.properties),
+                        text: line,
+                    }),
+                }
+                self.text.clear();
+                self.properties = DocxParagraphProperties::default();
+            }
+        }
+        if self.cell.as_ref().is_some_and(|(at, _)| *at == depth) {
+            if let (Some((_, value)), Some((_, row))) = (self.cell.take(), self.row.as_mut()) {
+                // Tabs and newlines inside a cell would break the row layout
+                row.push(value.replace(['\t', '\n'], " "));
+            }
+        }
+        if self.row.as_ref().is_some_and(|(at, _)| *at == depth) {
+            if let Some((_, mut row)) = self.row.take() {
+                while row.last().is_some_and(|value| value.is_empty()) {
+                    row.pop();
+                }
+                if !row.is_empty() {
+                    self.body.lines.push(DocxLine {
+                        paragraph: None,
+                        heading: None,
+                        text: row.join("\t"),
+                    });
+                }
+            }
+        }
+        if self.tables.last() == Some(&depth) {
+            self.tables.pop();
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if self.in_text {
+            if let Some((_, run)) = self.run.as_mut() {
+                run.push_str(text);
+            }
+        }
+    }
+}
+
+/// Reads the equations (`m:oMath`) of a DOCX `word/document.xml` as
+/// LaTeX-ish text (see `omml_latex`), with the top-level `w:body` paragraph
+/// each sits in. Display equations, in an `m:oMathPara`, are wrapped in
+/// `$$`, inline ones in `$`.
+#[derive(Default)]
+struct DocxEquationReader {
+    equations: Vec<(Option<usize>, String)>,
+    display_depth: Option<usize>,
+    /// The equation being read, from its `m:oMath` down
+    open: Vec<OmmlNode>,
+    /// Open elements past `MAX_OMML_NESTING`, which aren't kept
+    skipped: usize,
+}
+
+impl OoxmlVisitor for DocxEquationReader {
+    fn start(&mut self, e: &quick_xml::events::BytesStart, at: &OoxmlCursor) {
+        let name = e.name();
+        let local = name.local_name();
+        if self.open.len() >= MAX_OMML_NESTING {
+            self.skipped += 1;
+        } else if !self.open.is_empty() || local.as_ref() == b"oMath" {
+            self.open.push(OmmlNode {
+                name: String::from_utf8_lossy(local.as_ref()).into_owned(),
+                val: Parser::xml_attribute(e, b"val"),
+                ..Default::default()
+            });
+        } else if local.as_ref() == b"oMathPara" {
+            self.display_depth = Some(at.depth);
+        }
+    }
+
+    fn end(&mut self, _name: &[u8], at: &OoxmlCursor) {
+        if self.skipped > 0 {
+            self.skipped -= 1;
+        } else if let Some(node) = self.open.pop() {
+            match self.open.last_mut() {
+                Some(parent) => parent.children.push(node),
+                None => {
+                    let latex = Parser::omml_latex(&node);
+                    let latex = latex.trim();
+                    if !latex.is_empty() {
+                        let delimiter = if self.display_depth.is_some() { "$$" } else { "$" };
+                        let equation = format!("{}{}{}", delimiter, latex, delimiter);
+                        self.equations.push((at.paragraph(), equation));
+                    }
+                }
+            }
+        }
+        if self.display_depth == Some(at.depth) {
+            self.display_depth = None;
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if let Some(node) = self.open.last_mut().filter(|node| node.name == "t") {
+            node.text.push_str(text);
+        }
+    }
+}
+
+/// Reads the text boxes (`w:txbxContent`) of a DOCX `word/document.xml`,
+/// from DrawingML shapes and VML alike, with the top-level `w:body`
+/// paragraph each is anchored in. The VML copy Word writes as
+/// `mc:Fallback` for a DrawingML shape is skipped.
+#[derive(Default)]
+struct DocxTextBoxReader {
+    boxes: Vec<(Option<usize>, String)>,
+    fallback_depth: Option<usize>,
+    text_box: Option<(usize, String)>, // (depth, text)
+    in_text: bool,
+}
+
+impl OoxmlVisitor for DocxTextBoxReader {
+    fn start(&mut self, e: &quick_xml::events::BytesStart, at: &OoxmlCursor) {
+        let name = e.name();
+        match name.local_name().as_ref() {
+            _ if self.fallback_depth.is_some() => {}
+            b"Fallback" => self.fallback_depth = Some(at.depth),
+            b"txbxContent" if self.text_box.is_none() => self.text_box = Some((at.depth, String::new())),
+            b"t" if self.text_box.is_some() => self.in_text = true,
+            // Tab stops (`w:tabs`) carry a position; tabs in runs don't
+            b"tab" if Parser::xml_attribute(e, b"pos").is_none() => {
+                if let Some((_, text)) = self.text_box.as_mut() {
+                    text.push('\t');
+                }
+            }
+            b"br" | b"cr" => {
+                if let Some((_, text)) = self.text_box.as_mut() {
+                    text.push('\n');
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, name: &[u8], at: &OoxmlCursor) {
+        match name {
+            b"t" => self.in_text = false,
+            b"p" if !at.empty => {
+                if let Some((_, text)) = self.text_box.as_mut() {
+                    text.push('\n');
+                }
+            }
+            _ => {}
+        }
+        if self.text_box.as_ref().is_some_and(|(depth, _)| *depth == at.depth) {
+            if let Some((_, text)) = self.text_box.take() {
+                let text = text.trim();
+                if !text.is_empty() {
+                    self.boxes.push((at.paragraph(), text.to_string()));
+                }
+            }
+        }
+        if self.fallback_depth == Some(at.depth) {
+            self.fallback_depth = None;
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if self.in_text {
+            if let Some((_, box_text)) = self.text_box.as_mut() {
+                box_text.push_str(text);
+            }
+        }
+    }
+}
+
+/// Collects the image relationship Ids of an OOXML part, see `ooxml_image_refs`
+#[derive(Default)]
+struct OoxmlImageRefReader {
+    refs: Vec<(Option<usize>, String)>,
+}
+
+impl OoxmlVisitor for OoxmlImageRefReader {
+    fn start(&mut self, e: &quick_xml::events::BytesStart, at: &OoxmlCursor) {
+        let id = match e.name().local_name().as_ref() {
+            b"blip" => Parser::xml_attribute(e, b"embed"),
+            b"imagedata" => Parser::xml_attribute(e, b"id"),
+            _ => None,
+        };
+        self.refs.extend(id.map(|id| (at.paragraph(), id)));
+    }
+
+    fn end(&mut self, _name: &[u8], _at: &OoxmlCursor) {}
+
+    fn text(&mut self, _text: &str) {}
+}
+
+/// Fills in the anchors of DOCX comments, see `docx_comment_anchors`
+struct DocxCommentAnchorReader<'a> {
+    comments: &'a mut [DocxComment],
+    /// The comments whose range is open, by index
+    open: Vec<usize>,
+    in_text: bool,
+}
+
+impl<'a> DocxCommentAnchorReader<'a> {
+    fn new(comments: &'a mut [DocxComment]) -> Self {
+        Self {
+            comments,
+            open: Vec::new(),
+            in_text: false,
+        }
+    }
+
+    fn comment(&self, e: &quick_xml::events::BytesStart) -> Option<usize> {
+        let id = Parser::xml_attribute(e, b"id")?;
+        self.comments.iter().position(|comment| comment.id == id)
+    }
+}
+
+impl OoxmlVisitor for DocxCommentAnchorReader<'_> {
+    fn start(&mut self, e: &quick_xml::events::BytesStart, at: &OoxmlCursor) {
+        match e.name().local_name().as_ref() {
+            // Deleted text (w:delText) isn't part of the anchor
+            b"t" => self.in_text = true,
+            b"commentRangeStart" => self.open.extend(self.comment(e)),
+            b"commentRangeEnd" => {
+                if let Some(index) = self.comment(e) {
+                    self.open.retain(|&i| i != index);
+                    let comment = &mut self.comments[index];
+                    comment.paragraph = at.paragraph();
+                    comment.anchor = comment.anchor.trim().to_string();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, name: &[u8], at: &OoxmlCursor) {
+        match name {
+            b"t" => self.in_text = false,
+            b"p" if !at.empty => self.open.iter().for_each(|&i| self.comments[i].anchor.push('\n')),
+            _ => {}
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if self.in_text {
+            self.open.iter().for_each(|&i| self.comments[i].anchor.push_str(text));
+        }
+    }
+}
+
+/// A slide read from a PPTX, see `read_pptx_slides`
+#[derive(Debug, Default)]
+struct PptxSlide {
+    /// Position among the slides, from 1
+    number: usize,
+    /// The text of the title placeholder, when read apart from `text`
+    title: Option<String>,
+    text: String,
+    notes: Option<String>,
+}
+
+/// A page of `ocr_to_pdf` output: the scan, drawn over the whole page, and
+/// the words recognized on it
+struct SearchablePage {
+    image: image::DynamicImage,
+    /// Page size in points
+    width: f32,
+    height: f32,
+    words: Vec<OcrWord>,
+    /// Points per pixel of the word boxes
+    scale: f32,
+}
+
+/// Text attributes and image pixel module of a DICOM data set
+#[derive(Debug)]
+struct DicomDataSet {
+    explicit_vr: bool,
+    little_endian: bool,
+    specific_character_set: String,
+    fields: Vec<(&'static str, String)>,
+    rows: usize,
+    columns: usize,
+    samples_per_pixel: usize,
+    bits_allocated: usize,
+    pixel_representation: u16,
+    planar_configuration: u16,
+    photometric_interpretation: String,
+    pixel_data: Option<Vec<u8>>,
+    pixel_fragments: Vec<Vec<u8>>,
+}
+
+impl Default for DicomDataSet {
+    fn default() -> Self {
+        Self {
+            // File meta information is always explicit VR little endian
+            explicit_vr: true,
+            little_endian: true,
+            specific_character_set: String::new(),
+            fields: Vec::new(),
+            rows: 0,
+            columns: 0,
+            samples_per_pixel: 1,
+            bits_allocated: 8,
+            pixel_representation: 0,
+            planar_configuration: 0,
+            photometric_interpretation: String::new(),
+            pixel_data: None,
+            pixel_fragments: Vec::new(),
+        }
+    }
+}
+
+/// Container metadata reported by `parse_video`
+#[derive(Debug, Default)]
+struct VideoMetadata {
+    title: Option<String>,
+    duration: Option<f64>,
+    chapters: Vec<(f64, String)>,
+}
+
+/// Patient, study and series attributes emitted by `parse_dicom`, with their VRs
+/// (needed for implicit VR transfer syntaxes)
+const DICOM_TEXT_TAGS: &[((u16, u16), &str, [u8; 2])] = &[
+    ((0x0008, 0x0020), "Study Date", *b"DA"),
+    ((0x0008, 0x0030), "Study Time", *b"TM"),
+    ((0x0008, 0x0050), "Accession Number", *b"SH"),
+    ((0x0008, 0x0060), "Modality", *b"CS"),
+    ((0x0008, 0x0070), "Manufacturer", *b"LO"),
+    ((0x0008, 0x0080), "Institution Name", *b"LO"),
+    ((0x0008, 0x0090), "Referring Physician's Name", *b"PN"),
+    ((0x0008, 0x1030), "Study Description", *b"LO"),
+    ((0x0008, 0x103E), "Series Description", *b"LO"),
+    ((0x0010, 0x0010), "Patient's Name", *b"PN"),
+    ((0x0010, 0x0020), "Patient ID", *b"LO"),
+    ((0x0010, 0x0030), "Patient's Birth Date", *b"DA"),
+    ((0x0010, 0x0040), "Patient's Sex", *b"CS"),
+    ((0x0010, 0x1010), "Patient's Age", *b"AS"),
+    ((0x0010, 0x4000), "Patient Comments", *b"LT"),
+    ((0x0018, 0x0015), "Body Part Examined", *b"CS"),
+    ((0x0020, 0x000D), "Study Instance UID", *b"UI"),
+    ((0x0020, 0x0010), "Study ID", *b"SH"),
+    ((0x0020, 0x0011), "Series Number", *b"IS"),
+    ((0x0020, 0x0013), "Instance Number", *b"IS"),
+    ((0x0020, 0x4000), "Image Comments", *b"LT"),
+    ((0x0032, 0x4000), "Study Comments", *b"LT"),
+    ((0x0040, 0x0254), "Performed Procedure Step Description", *b"LO"),
+];
+
+// Error handling helpers
+impl Parser {
+    /// Create a RuntimeError with formatted message
+    fn runtime_error<E: std::fmt::Display>(context: &str, err: E) -> Error {
+        Error::new(
+            Ruby::get().unwrap().exception_runtime_error(),
+            format!("{}: {}", context, err),
+        )
+    }
+    
+    /// Create an ArgumentError with message
+    fn argument_error(msg: &str) -> Error {
+        Error::new(
+            Ruby::get().unwrap().exception_arg_error(),
+            msg.to_string(),
+        )
+    }
+    
+    /// Create an IOError with formatted message
+    fn io_error<E: std::fmt::Display>(context: &str, err: E) -> Error {
+        Error::new(
+            Ruby::get().unwrap().exception_io_error(),
+            format!("{}: {}", context, err),
+        )
+    }
+
+    /// Create a RuntimeError message without touching Ruby, for code that
+    /// also runs on OCR worker threads; `?` turns it into the exception
+    fn ocr_error<E: std::fmt::Display>(context: &str, err: E) -> ParserError {
+        ParserError::Runtime(format!("{}: {}", context, err))
+    }
+
+    /// Create a ParseKit::ResourceLimitError with formatted message
+    fn resource_limit_error<E: std::fmt::Display>(context: &str, err: E) -> Error {
+        ParserError::ResourceLimit(format!("{}: {}", context, err)).to_error()
+    }
+}
+
+impl Parser {
+    /// Create a new Parser instance with optional configuration
+    fn new(ruby: &Ruby, args: &[Value]) -> Result<Self, Error> {
+        let args = scan_args::scan_args::<(), (Option<RHash>,), (), (), (), ()>(args)?;
+        let options = args.optional.0;
+
+        let mut config = ParserConfig::default();
+
+        if let Some(opts) = options {
+            if let Some(strict) = opts.get(ruby.to_symbol("strict_mode")) {
+                config.strict_mode = bool::try_convert(strict)?;
+            }
+            if let Some(depth) = opts.get(ruby.to_symbol("max_depth")) {
+                config.max_depth = usize::try_convert(depth)?;
+            }
+            if let Some(encoding) = opts.get(ruby.to_symbol("encoding")) {
+                config.encoding = String::try_convert(encoding)?;
+            }
+            if let Some(max_size) = opts.get(ruby.to_symbol("max_size")) {
+                config.max_size = usize::try_convert(max_size)?;
+            }
+            if let Some(max_entry_size) = opts.get(ruby.to_symbol("max_entry_size")) {
+                config.max_entry_size = usize::try_convert(max_entry_size)?;
+            }
+            if let Some(max_compression_ratio) = opts.get(ruby.to_symbol("max_compression_ratio")) {
+                config.max_compression_ratio = usize::try_convert(max_compression_ratio)?;
+            }
+            if let Some(max_rows) = opts.get(ruby.to_symbol("max_rows")) {
+                config.max_rows = usize::try_convert(max_rows)?;
+            }
+            if let Some(ocr_pixel_data) = opts.get(ruby.to_symbol("ocr_pixel_data")) {
+                config.ocr_pixel_data = bool::try_convert(ocr_pixel_data)?;
+            }
+            if let Some(transcribe) = opts.get(ruby.to_symbol("transcribe")) {
+                config.transcribe = bool::try_convert(transcribe)?;
+            }
+            if let Some(timestamps) = opts.get(ruby.to_symbol("timestamps")) {
+                config.timestamps = bool::try_convert(timestamps)?;
+            }
+            if let Some(whisper_model) = opts.get(ruby.to_symbol("whisper_model")) {
+                config.whisper_model = Option::<String>::try_convert(whisper_model)?;
+            }
+            if let Some(parse_attachments) = opts.get(ruby.to_symbol("parse_attachments")) {
+                config.parse_attachments = bool::try_convert(parse_attachments)?;
+            }
+            if let Some(resolve_entities) = opts.get(ruby.to_symbol("resolve_entities")) {
+                config.resolve_entities = bool::try_convert(resolve_entities)?;
+            }
+            if let Some(lenient) = opts.get(ruby.to_symbol("lenient")) {
+                config.lenient = bool::try_convert(lenient)?;
+            }
+            if let Some(normalize_text) = opts.get(ruby.to_symbol("normalize_text")) {
+                config.normalize_text = bool::try_convert(normalize_text)?;
+            }
+            if let Some(pdf_structure_order) = opts.get(ruby.to_symbol("pdf_structure_order")) {
+                config.pdf_structure_order = bool::try_convert(pdf_structure_order)?;
+            }
+            if let Some(min_confidence) = opts.get(ruby.to_symbol("min_confidence")) {
+                config.min_confidence = Option::<f32>::try_convert(min_confidence)?;
+                if config.min_confidence.is_some_and(|min| !(0.0..=100.0).contains(&min)) {
+                    return Err(Self::argument_error("min_confidence must be between 0 and 100"));
+                }
+            }
+            if let Some(ocr_output) = opts.get(ruby.to_symbol("ocr_output")) {
+                config.ocr_output = match magnus::Symbol::try_convert(ocr_output)?.name()?.as_ref() {
+                    "text" => OcrOutput::Text,
+                    "hocr" => OcrOutput::Hocr,
+                    "tsv" => OcrOutput::Tsv,
+                    "alto" => OcrOutput::Alto,
+                    other => return Err(Self::argument_error(&format!("Unsupported OCR output: {}", other))),
+                };
+            }
+            if let Some(ocr_preprocess) = opts.get(ruby.to_symbol("ocr_preprocess")) {
+                config.ocr_preprocess = Self::ocr_preprocess(ocr_preprocess)?;
+            }
+            if let Some(ocr_dpi) = opts.get(ruby.to_symbol("ocr_dpi")) {
+                config.ocr_dpi = u32::try_convert(ocr_dpi)?;
+                if !(70..=1200).contains(&config.ocr_dpi) {
+                    return Err(Self::argument_error("ocr_dpi must be between 70 and 1200"));
+                }
+            }
+            if let Some(ocr_language) = opts.get(ruby.to_symbol("ocr_language")) {
+                config.ocr_language = Self::ocr_language(ocr_language)?;
+            }
+            if let Some(ocr_psm) = opts.get(ruby.to_symbol("ocr_psm")) {
+                config.ocr_psm = Option::<u8>::try_convert(ocr_psm)?;
+                if config.ocr_psm.is_some_and(|psm| psm > 13) {
+                    return Err(Self::argument_error("ocr_psm must be a Tesseract page segmentation mode from 0 to 13"));
+                }
+            }
+            if let Some(ocr_oem) = opts.get(ruby.to_symbol("ocr_oem")) {
+                config.ocr_oem = Option::<u8>::try_convert(ocr_oem)?;
+                if config.ocr_oem.is_some_and(|oem| oem > 3) {
+                    return Err(Self::argument_error("ocr_oem must be a Tesseract OCR engine mode from 0 to 3"));
+                }
+            }
+            if let Some(ocr_embedded_images) = opts.get(ruby.to_symbol("ocr_embedded_images")) {
+                config.ocr_embedded_images = bool::try_convert(ocr_embedded_images)?;
+            }
+            if let Some(ocr_backend) = opts.get(ruby.to_symbol("ocr_backend")) {
+                config.ocr_backend = Self::ocr_backend(ocr_backend)?;
+            }
+            if let Some(tessdata_path) = opts.get(ruby.to_symbol("tessdata_path")) {
+                config.tessdata_path = Option::<String>::try_convert(tessdata_path)?;
+                if let Some(path) = config.tessdata_path.as_deref().filter(|path| !std::path::Path::new(path).is_dir()) {
+                    return Err(Self::argument_error(&format!("tessdata_path is not a directory: {}", path)));
+                }
+            }
+            if let Some(ocr_threads) = opts.get(ruby.to_symbol("ocr_threads")) {
+                config.ocr_threads = usize::try_convert(ocr_threads)?;
+                if config.ocr_threads == 0 {
+                    return Err(Self::argument_error("ocr_threads must be at least 1"));
+                }
+            }
+            if let Some(docx_comments) = opts.get(ruby.to_symbol("docx_comments")) {
+                config.docx_comments = bool::try_convert(docx_comments)?;
+            }
+            if let Some(revisions) = opts.get(ruby.to_symbol("revisions")) {
+                config.revisions = match magnus::Symbol::try_convert(revisions)?.name()?.as_ref() {
+                    "accept" => DocxRevisions::Accept,
+                    "reject" => DocxRevisions::Reject,
+                    "show_markup" => DocxRevisions::ShowMarkup,
+                    other => return Err(Self::argument_error(&format!("Unsupported revisions mode: {}", other))),
+                };
+            }
+            if let Some(docx_links) = opts.get(ruby.to_symbol("docx_links")) {
+                config.docx_links = bool::try_convert(docx_links)?;
+            }
+            if let Some(docx_headings) = opts.get(ruby.to_symbol("docx_headings")) {
+                config.docx_headings = bool::try_convert(docx_headings)?;
+            }
+            if let Some(pptx_alt_text) = opts.get(ruby.to_symbol("pptx_alt_text")) {
+                config.pptx_alt_text = bool::try_convert(pptx_alt_text)?;
+            }
+            if let Some(pptx_notes) = opts.get(ruby.to_symbol("pptx_notes")) {
+                config.pptx_notes = match magnus::Symbol::try_convert(pptx_notes)?.name()?.as_ref() {
+                    "include" => PptxNotes::Include,
+                    "exclude" => PptxNotes::Exclude,
+                    "only" => PptxNotes::Only,
+                    other => return Err(Self::argument_error(&format!("Unsupported pptx_notes mode: {}", other))),
+                };
+            }
+            if let Some(pptx_links) = opts.get(ruby.to_symbol("pptx_links")) {
+                config.pptx_links = bool::try_convert(pptx_links)?;
+            }
+            if let Some(include_formulas) = opts.get(ruby.to_symbol("include_formulas")) {
+                config.include_formulas = bool::try_convert(include_formulas)?;
+            }
+            if let Some(include_hidden) = opts.get(ruby.to_symbol("include_hidden")) {
+                config.include_hidden = bool::try_convert(include_hidden)?;
+            }
+            if let Some(xlsx_links) = opts.get(ruby.to_symbol("xlsx_links")) {
+                config.xlsx_links = bool::try_convert(xlsx_links)?;
+            }
+            if let Some(separator) = opts.get(ruby.to_symbol("xlsx_cell_separator")) {
+                config.xlsx_cell_separator = String::try_convert(separator)?;
+            }
+            if let Some(trailing) = opts.get(ruby.to_symbol("xlsx_trailing_separator")) {
+                config.xlsx_trailing_separator = bool::try_convert(trailing)?;
+            }
+            if let Some(separator) = opts.get(ruby.to_symbol("xlsx_row_separator")) {
+                config.xlsx_row_separator = String::try_convert(separator)?;
+            }
+            if let Some(header) = opts.get(ruby.to_symbol("xlsx_sheet_header")) {
+                config.xlsx_sheet_header = Option::<String>::try_convert(header)?;
+            }
+            if let Some(skip) = opts.get(ruby.to_symbol("xlsx_skip_empty_rows")) {
+                config.xlsx_skip_empty_rows = bool::try_convert(skip)?;
+            }
+            if let Some(xml_attributes) = opts.get(ruby.to_symbol("xml_attributes")) {
+                config.xml_attributes = match RArray::from_value(xml_attributes) {
+                    Some(names) => XmlAttributes::Named(names.to_vec::<String>()?),
+                    None if bool::try_convert(xml_attributes)? => XmlAttributes::All,
+                    None => XmlAttributes::None,
+                };
+            }
+            if let Some(xml_elements) = opts.get(ruby.to_symbol("xml_elements")) {
+                config.xml_elements = Option::<Vec<String>>::try_convert(xml_elements)?.unwrap_or_default();
+            }
+            if let Some(xml_namespaces) = opts.get(ruby.to_symbol("xml_namespaces")) {
+                config.xml_namespaces = Option::<Vec<String>>::try_convert(xml_namespaces)?.unwrap_or_default();
+            }
+            if let Some(main_content) = opts.get(ruby.to_symbol("main_content")) {
+                config.main_content = bool::try_convert(main_content)?;
+            }
+            if let Some(json_output) = opts.get(ruby.to_symbol("json_output")) {
+                config.json_output = match magnus::Symbol::try_convert(json_output)?.name()?.as_ref() {
+                    "pretty" => JsonOutput::Pretty,
+                    "flat" => JsonOutput::Flat,
+                    other => return Err(Self::argument_error(&format!("Unsupported json_output mode: {}", other))),
+                };
+            }
+        }
+
+        Ok(Self { config, depth: 0 })
+    }
+
+    /// Read the `ocr_preprocess` option: `true` for the default pipeline,
+    /// or an Array of stage names run in the order given
+    fn ocr_preprocess(value: Value) -> Result<Vec<OcrPreprocess>, Error> {
+        let Some(list) = RArray::from_value(value) else {
+            return Ok(if bool::try_convert(value)? { OcrPreprocess::DEFAULT.to_vec() } else { Vec::new() });
+        };
+        list.to_vec::<magnus::Symbol>()?
+            .into_iter()
+            .map(|stage| {
+                let name = stage.name()?;
+                OcrPreprocess::DEFAULT
+                    .into_iter()
+                    .find(|known| known.name() == name)
+                    .ok_or_else(|| Self::argument_error(&format!("Unsupported OCR preprocessing stage: {}", name)))
+            })
+            .collect()
+    }
+
+    /// Read the `ocr_backend` option: `:tesseract`, or any object responding
+    /// to `call` that takes the image bytes and returns the text
+    fn ocr_backend(value: Value) -> Result<std::sync::Arc<dyn OcrBackend>, Error> {
+        if let Some(name) = magnus::Symbol::from_value(value) {
+            return match name.name()?.as_ref() {
+                "tesseract" => Ok(std::sync::Arc::new(TesseractOcr)),
+                other => Err(Self::argument_error(&format!("Unsupported OCR backend: {}", other))),
+            };
+        }
+        if !value.respond_to("call", false)? {
+            return Err(Self::argument_error("ocr_backend must be :tesseract or an object responding to call"));
+        }
+        Ok(std::sync::Arc::new(CallbackOcr(value.into())))
+    }
+
+    /// Read the `ocr_language` option: a Tesseract language code ("deu",
+    /// "chi_sim") or an Array of them, joined with "+" so Tesseract loads
+    /// every model for mixed-language scans
+    fn ocr_language(value: Value) -> Result<String, Error> {
+        let languages = match RArray::from_value(value) {
+            Some(list) => list.to_vec::<String>()?,
+            None => vec![String::try_convert(value)?],
+        };
+        let valid = |language: &String| {
+            !language.is_empty() && language.chars().all(|c| c.is_ascii_alphanumeric() || "_+/".contains(c))
+        };
+        if languages.is_empty() || !languages.iter().all(valid) {
+            return Err(Self::argument_error(
+                "ocr_language must be a Tesseract language code or an Array of them, e.g. [\"eng\", \"deu\"]",
+            ));
+        }
+        Ok(languages.join("+"))
+    }
+
+    /// Parse input bytes based on file type (internal helper)
+    fn parse_bytes_internal(&self, data: Vec<u8>, filename: Option<&str>) -> Result<String, Error> {
+        // Check size limit
+        if data.len() > self.config.max_size {
+            return Err(self.file_size_error(data.len() as u64));
+        }
+
+        // Use centralized format detection
+        let format = FormatDetector::detect(filename, Some(&data));
+        
+        // Use centralized dispatch
+        self.dispatch_to_parser(format, data)
+    }
+    
+    /// Centralized dispatch logic - routes format to appropriate parser
+    fn dispatch_to_parser(&self, format: FileFormat, data: Vec<u8>) -> Result<String, Error> {
+        match format {
+            FileFormat::Pdf => self.pdf_text(data, &PageSelection::All),
+            FileFormat::Djvu => self.parse_djvu(data),
+            FileFormat::PostScript => self.parse_postscript(data),
+            FileFormat::Docx => self.parse_docx(data),
+            FileFormat::WordPerfect => self.parse_wordperfect(data),
+            FileFormat::Pptx => self.pptx_text(std::io::Cursor::new(data), &PageSelection::All),
+            FileFormat::Vsdx => self.parse_vsdx(data),
+            FileFormat::OneNote => self.parse_onenote(data),
+            FileFormat::Xlsx | FileFormat::Xls => self.xlsx_text(data, &SheetSelection::All),
+            FileFormat::Parquet => self.parse_parquet(data),
+            FileFormat::Arrow => self.parse_arrow(data),
+            FileFormat::Dbf => self.parse_dbf(data),
+            FileFormat::Json => self.parse_json(data),
+            FileFormat::MsgPack => self.parse_msgpack(data),
+            FileFormat::Cbor => self.parse_cbor(data),
+            FileFormat::Bson => self.parse_bson(data),
+            FileFormat::Dicom => self.parse_dicom(data),
+            FileFormat::Audio => self.parse_audio(data),
+            FileFormat::Video => self.parse_video(data),
+            FileFormat::Xml => self.parse_xml(data),
+            FileFormat::Xbrl => self.parse_xbrl(data),
+            FileFormat::FlatOdf => self.parse_flat_odf(data),
+            FileFormat::Html => self.parse_html(data),
+            FileFormat::Mhtml => self.parse_mhtml(data),
+            FileFormat::Email => self.parse_email(data),
+            FileFormat::Chm => self.parse_chm(data),
+            FileFormat::Mobi => self.parse_mobi(data),
+            FileFormat::Subtitles => self.parse_subtitles(data),
+            FileFormat::Log => self.normalize_log(data, None),
+            FileFormat::Tar => self.parse_tar(data),
+            FileFormat::Gzip => self.parse_gzip(data),
+            FileFormat::Bzip2 => self.parse_bzip2(data),
+            FileFormat::Xz => self.parse_xz(data),
+            FileFormat::SevenZip => self.parse_sevenzip(data),
+            FileFormat::Rar => self.parse_rar(data),
+            FileFormat::Pst => self.pst_text(&data),
+            FileFormat::Png | FileFormat::Jpeg | FileFormat::Tiff | FileFormat::Bmp | FileFormat::Gif
+            | FileFormat::Avif => {
+                self.ocr_image(data)
+            }
+            FileFormat::Text | FileFormat::Unknown => self.parse_text(data),
+        }
+    }
+
+    /// Ruby-accessible method to detect format from bytes
+    fn detect_format_from_bytes(&self, data: Vec<u8>) -> String {
+        let format = FormatDetector::detect_from_content(&data);
+        // For compatibility with Ruby tests, return "xlsx" for old Excel
+        match format {
+            FileFormat::Xls => "xlsx".to_string(),  // Compatibility with existing tests
+            _ => format.to_symbol().to_string(),
+        }
+    }
+    
+    /// Ruby-accessible method to detect format from filename
+    fn detect_format_from_filename(&self, filename: String) -> String {
+        let format = FormatDetector::detect_from_extension(&filename);
+        format.to_symbol().to_string()
+    }
+
+    /// Perform OCR on image data using Tesseract
+    ///
+    /// Returns plain text, or with `ocr_output: :hocr`, `:tsv` or `:alto`
+    /// a complete hOCR (XHTML), TSV (with a header row) or ALTO XML
+    /// document for layout analysis tools. Their coordinates are those of
+    /// the image after any enlargement for `ocr_dpi`. The plain text of a
+    /// multi-page TIFF covers every page; the layout formats only the first.
+    fn ocr_image(&self, data: Vec<u8>) -> Result<String, Error> {
+        let page = match self.config.ocr_output {
+            OcrOutput::Text => return self.ocr_text(&data),
+            OcrOutput::Hocr => self.ocr_engine(&data, None)?.0.get_hocr_text(0).map(|page| Self::hocr_document(&page)),
+            OcrOutput::Tsv => self.ocr_engine(&data, None)?.0.get_tsv_text(0).map(|rows| format!("{}\n{}", OCR_TSV_HEADER, rows)),
+            OcrOutput::Alto => self.ocr_engine(&data, None)?.0.get_alto_text(0).map(|page| Self::alto_document(&page)),
+        };
+        page.map_err(|e| Self::runtime_error("Failed to perform OCR", e))
+    }
+
+    /// OCR part of an image - exposed to Ruby
+    ///
+    /// `x:`, `y:`, `w:` and `h:` give the rectangle in pixels from the
+    /// top-left corner, e.g. an invoice's total box. Returns plain text like
+    /// `ocr_image`, without recognizing the rest of the page.
+    fn ocr_image_region(&self, args: &[Value]) -> Result<String, Error> {
+        let args = scan_args::scan_args::<(Vec<u8>,), (), (), (), RHash, ()>(args)?;
+        let (data,) = args.required;
+        let kwargs = scan_args::get_kwargs::<_, (u32, u32, u32, u32), (), ()>(
+            args.keywords,
+            &["x", "y", "w", "h"],
+            &[],
+        )?;
+        self.ocr_text_in(&data, Some(kwargs.required))
+    }
+
+    /// OCR an image to plain text, leaving out words below `min_confidence`
+    fn ocr_text(&self, data: &[u8]) -> Result<String, Error> {
+        self.ocr_text_in(data, None)
+    }
+
+    /// OCR an image, or the `region` (x, y, w, h) of it, to plain text with
+    /// the configured `ocr_backend`
+    fn ocr_text_in(&self, data: &[u8], region: Option<(u32, u32, u32, u32)>) -> Result<String, Error> {
+        self.config.ocr_backend.recognize(self, data, region)
+    }
+
+    /// Perform OCR and report how sure Tesseract is - exposed to Ruby
+    ///
+    /// Returns `{text:, confidence:, needs_review:, words: [{text:, confidence:, bbox:}]}`.
+    /// Confidences run from 0 to 100; `confidence` is the mean over all
+    /// words and `bbox` is `[left, top, width, height]` in pixels of the
+    /// original image. With `min_confidence`, `text` leaves out words below
+    /// it, and `needs_review` is true when the mean falls below it. The
+    /// image's recorded resolution is reported as `source_dpi` (nil if the
+    /// file has none), with the `scale` it was enlarged by and a
+    /// `low_resolution` flag for inputs below `ocr_dpi`.
+    fn ocr_image_result(&self, data: Vec<u8>) -> Result<RHash, Error> {
+        let ruby = Ruby::get().unwrap();
+        let (tesseract, scan) = self.ocr_engine(&data, None)?;
+        let words = Self::ocr_words(&tesseract)?;
+
+        let confidence = if words.is_empty() {
+            0.0
+        } else {
+            words.iter().map(|word| word.confidence).sum::<f32>() / words.len() as f32
+        };
+        let min_confidence = self.config.min_confidence.unwrap_or(0.0);
+
+        let list = ruby.ary_new();
+        for word in &words {
+            let entry = ruby.hash_new();
+            entry.aset(ruby.to_symbol("text"), word.text.as_str())?;
+            entry.aset(ruby.to_symbol("confidence"), word.confidence)?;
+            let bbox: Vec<u32> = word.bbox.iter().map(|&value| (value as f32 / scan.scale).round() as u32).collect();
+            entry.aset(ruby.to_symbol("bbox"), bbox)?;
+            list.push(entry)?;
+        }
+
+        let result = ruby.hash_new();
+        result.aset(ruby.to_symbol("text"), Self::ocr_words_text(&words, min_confidence))?;
+        result.aset(ruby.to_symbol("confidence"), confidence)?;
+        result.aset(ruby.to_symbol("needs_review"), confidence < min_confidence)?;
+        result.aset(ruby.to_symbol("words"), list)?;
+        result.aset(ruby.to_symbol("source_dpi"), scan.source_dpi.map(f32::round))?;
+        result.aset(ruby.to_symbol("scale"), scan.scale)?;
+        result.aset(ruby.to_symbol("low_resolution"), scan.low_resolution)?;
+        Ok(result)
+    }
+
+    /// Make a searchable PDF from a scan - exposed to Ruby
+    ///
+    /// Takes image bytes, or a PDF whose pages are rendered at `ocr_dpi`,
+    /// and returns a PDF (binary String) showing each scan, or each page of
+    /// a multi-page TIFF, under an invisible text layer placed by the OCR
+    /// word boxes, so the text can be searched and selected. Pages are
+    /// recognized on up to `ocr_threads` threads. Words below
+    /// `min_confidence` are left out, and characters outside Latin-1 come
+    /// through as `?`. An image's page size follows the resolution it
+    /// records, or `ocr_dpi`.
+    fn ocr_to_pdf(&self, data: Vec<u8>) -> Result<RString, Error> {
+        let ruby = Ruby::get().unwrap();
+        let min_confidence = self.config.min_confidence.unwrap_or(0.0);
+
+        // Page images to recognize, with their resolution and, for PDF
+        // pages, the page size in points
+        let scans = if FormatDetector::detect_from_content(&data) == FileFormat::Pdf {
+            let doc = self.open_pdf(&data)?;
+            let page_count = doc.page_count()
+                .map_err(|e| Self::runtime_error("Failed to get page count", e))?;
+            let dpi = self.config.ocr_dpi as f32;
+            let mut scans = Vec::new();
+            for index in 0..page_count {
+                let page = doc.load_page(index)
+                    .map_err(|e| Self::runtime_error("Failed to load page", e))?;
+                let bounds = page.bounds()
+                    .map_err(|e| Self::runtime_error("Failed to get page size", e))?;
+                let image = self.render_page_image(&page, dpi, index as usize + 1)?;
+                scans.push((image::DynamicImage::ImageRgb8(image), Some(dpi), Some((bounds.x1 - bounds.x0, bounds.y1 - bounds.y0))));
+            }
+            scans
+        } else {
+            let source_dpi = Self::image_dpi(&data);
+            let images = match Self::tiff_pages(&data)? {
+                Some(pages) => pages,
+                None => vec![image::load_from_memory(&data).map_err(|e| Self::runtime_error("Failed to load image", e))?],
+            };
+            images.into_iter().map(|image| (image, source_dpi, None)).collect()
+        };
+
+        let pages = self.ocr_pages(scans, |(image, source_dpi, size)| {
+            let (tesseract, ocr) = self.ocr_engine_for(&image, source_dpi)?;
+            let mut words = Self::ocr_words(&tesseract)?;
+            words.retain(|word| word.confidence >= min_confidence);
+
+            let (width, height) = size.unwrap_or_else(|| {
+                let dpi = source_dpi.unwrap_or(self.config.ocr_dpi as f32);
+                (image.width() as f32 * 72.0 / dpi, image.height() as f32 * 72.0 / dpi)
+            });
+            // Word boxes are in pixels of the image Tesseract saw, enlarged by ocr.scale
+            let scale = width / (image.width() as f32 * ocr.scale);
+            Ok(SearchablePage { image, width, height, words, scale })
+        })?;
+
+        let pdf = Self::searchable_pdf(&pages).map_err(|e| Self::runtime_error("Failed to write PDF", e))?;
+        Ok(ruby.str_from_slice(&pdf))
+    }
+
+    /// Write `ocr_to_pdf` pages as a PDF: each page shows its scan, with the
+    /// recognized words over it as invisible Helvetica text (render mode 3),
+    /// stretched to fill their boxes so selection and search line up
+    fn searchable_pdf(pages: &[SearchablePage]) -> std::io::Result<Vec<u8>> {
+        use std::io::Write;
+
+        let deflate = |data: &[u8]| -> std::io::Result<Vec<u8>> {
+            let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data)?;
+            encoder.finish()
+        };
+
+        // Objects 1-3 are the catalog, page tree and font; each page then
+        // takes three: the page, its content stream and its image
+        let mut objects: Vec<Vec<u8>> = Vec::new();
+        let kids: Vec<String> = (0..pages.len()).map(|i| format!("{} 0 R", 4 + 3 * i)).collect();
+        objects.push(b"<< /Type /Catalog /Pages 2 0 R >>".to_vec());
+        objects.push(format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), pages.len()).into_bytes());
+        objects.push(b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>".to_vec());
+
+        for (i, page) in pages.iter().enumerate() {
+            let id = 4 + 3 * i;
+            objects.push(
+                format!(
+                    "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.2} {:.2}] /Resources << /Font << /F1 3 0 R >> /XObject << /Im1 {} 0 R >> >> /Contents {} 0 R >>",
+                    page.width, page.height, id + 2, id + 1
+                )
+                .into_bytes(),
+            );
+
+            let mut content = format!("q {:.2} 0 0 {:.2} 0 0 cm /Im1 Do Q\nBT\n3 Tr\n", page.width, page.height).into_bytes();
+            for word in &page.words {
+                let [left, top, width, height] = word.bbox.map(|value| value as f32 * page.scale);
+                let glyphs = word.text.chars().count();
+                if glyphs == 0 || width <= 0.0 || height <= 0.0 {
+                    continue;
+                }
+                // Helvetica averages about half an em per glyph
+                let stretch = 100.0 * width / (0.5 * height * glyphs as f32);
+                write!(
+                    content,
+                    "/F1 {:.2} Tf\n{:.2} Tz\n1 0 0 1 {:.2} {:.2} Tm\n(",
+                    height, stretch, left, page.height - top - height
+                )?;
+                content.extend(Self::pdf_win_ansi(&word.text));
+                content.extend_from_slice(b") Tj\n");
+            }
+            content.extend_from_slice(b"ET\n");
+            let content = deflate(&content)?;
+            let mut stream = format!("<< /Length {} /Filter /FlateDecode >>\nstream\n", content.len()).into_bytes();
+            stream.extend(content);
+            stream.extend_from_slice(b"\nendstream");
+            objects.push(stream);
+
+            let (color_space, pixels) = if page.image.color().has_color() {
+                ("DeviceRGB", page.image.to_rgb8().into_raw())
+            } else {
+                ("DeviceGray", page.image.to_luma8().into_raw())
+            };
+            let pixels = deflate(&pixels)?;
+            let mut image = format!(
+                "<< /Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /{} /BitsPerComponent 8 /Length {} /Filter /FlateDecode >>\nstream\n",
+                page.image.width(), page.image.height(), color_space, pixels.len()
+            )
+            .into_bytes();
+            image.extend(pixels);
+            image.extend_from_slice(b"\nendstream");
+            objects.push(image);
+        }
+
+        let mut pdf = b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n".to_vec();
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            writeln!(pdf, "{} 0 obj", i + 1)?;
+            pdf.extend_from_slice(object);
+            pdf.extend_from_slice(b"\nendobj\n");
+        }
+        let xref = pdf.len();
+        writeln!(pdf, "xref\n0 {}\n0000000000 65535 f ", objects.len() + 1)?;
+        for offset in offsets {
+            writeln!(pdf, "{:010} 00000 n ", offset)?;
+        }
+        writeln!(pdf, "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF", objects.len() + 1, xref)?;
+        Ok(pdf)
+    }
+
+    /// Encode text for a PDF literal string in WinAnsiEncoding, escaping
+    /// delimiters. Characters the encoding lacks become `?`.
+    fn pdf_win_ansi(text: &str) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(text.len());
+        for c in text.chars() {
+            let byte = match c {
+                '(' | ')' | '\\' => {
+                    bytes.push(b'\\');
+                    c as u8
+                }
+                ' '..='~' | '\u{A0}'..='\u{FF}' => c as u8,
+                '€' => 0x80,
+                '…' => 0x85,
+                '‘' => 0x91,
+                '’' => 0x92,
+                '“' => 0x93,
+                '”' => 0x94,
+                '•' => 0x95,
+                '–' => 0x96,
+                '—' => 0x97,
+                _ => b'?',
+            };
+            bytes.push(byte);
+        }
+        bytes
+    }
+
+    /// Run the `ocr_preprocess` stages over an image, in order. Every stage
+    /// but `grayscale` also leaves the image in grayscale.
+    fn preprocess_for_ocr(image: image::DynamicImage, stages: &[OcrPreprocess]) -> image::DynamicImage {
+        use image::{DynamicImage, Luma};
+        use imageproc::contrast::{adaptive_threshold, stretch_contrast};
+        use imageproc::filter::median_filter;
+        use imageproc::geometric_transformations::{rotate_about_center, Interpolation};
+
+        let mut image = image;
+        for stage in stages {
+            let gray = image.to_luma8();
+            let processed = match stage {
+                OcrPreprocess::Grayscale => gray,
+                OcrPreprocess::Contrast => {
+                    // Stretch between the 1st and 99th percentiles so a few
+                    // stray pixels don't pin the range
+                    let mut histogram = [0u64; 256];
+                    for pixel in gray.pixels() {
+                        histogram[pixel[0] as usize] += 1;
+                    }
+                    let total = gray.pixels().len() as u64;
+                    let percentile = |fraction: f64| {
+                        let target = (total as f64 * fraction) as u64;
+                        let mut seen = 0;
+                        histogram
+                            .iter()
+                            .position(|&count| {
+                                seen += count;
+                                seen > target
+                            })
+                            .unwrap_or(255) as u8
+                    };
+                    let (lower, upper) = (percentile(0.01), percentile(0.99));
+                    if upper > lower {
+                        stretch_contrast(&gray, lower, upper, 0, 255)
+                    } else {
+                        gray
+                    }
+                }
+                OcrPreprocess::Despeckle => median_filter(&gray, 1, 1),
+                OcrPreprocess::Binarize => {
+                    // Thresholds follow the local background, so shadows and
+                    // uneven lighting in phone photos don't swallow the text
+                    let radius = (gray.width().min(gray.height()) / 40).clamp(5, 50);
+                    adaptive_threshold(&gray, radius)
+                }
+                OcrPreprocess::Deskew => {
+                    let angle = Self::skew_angle(&gray);
+                    if angle.abs() < 0.1f32.to_radians() {
+                        gray
+                    } else {
+                        rotate_about_center(&gray, -angle, Interpolation::Bilinear, Luma([255]))
+                    }
+                }
+            };
+            image = DynamicImage::ImageLuma8(processed);
+        }
+        image
+    }
+
+    /// Estimate how far text lines are rotated from horizontal, in radians
+    /// (up to 5 degrees either way): the angle whose rotation gives the
+    /// sharpest profile of dark pixels per row
+    fn skew_angle(gray: &image::GrayImage) -> f32 {
+        use image::imageops::{resize, FilterType};
+        use imageproc::contrast::{otsu_level, threshold, ThresholdType};
+        use imageproc::geometric_transformations::{rotate_about_center, Interpolation};
+
+        let (width, height) = gray.dimensions();
+        let scale = (800.0 / width.max(height) as f32).min(1.0);
+        let small = resize(
+            gray,
+            ((width as f32 * scale) as u32).max(1),
+            ((height as f32 * scale) as u32).max(1),
+            FilterType::Triangle,
+        );
+        // Ink is white after the inverted threshold, so padding from the
+        // rotation (black) counts as background
+        let ink = threshold(&small, otsu_level(&small), ThresholdType::BinaryInverted);
+
+        let sharpness = |angle: f32| {
+            let rotated = rotate_about_center(&ink, angle, Interpolation::Nearest, image::Luma([0]));
+            let rows: Vec<f64> = rotated
+                .rows()
+                .map(|row| row.filter(|pixel| pixel[0] > 0).count() as f64)
+                .collect();
+            rows.windows(2).map(|pair| (pair[1] - pair[0]).powi(2)).sum::<f64>()
+        };
+
+        let mut best = (0.0f32, sharpness(0.0));
+        for step in -20..=20 {
+            let angle = (step as f32 * 0.25).to_radians();
+            let score = sharpness(-angle);
+            if score > best.1 * 1.0001 {
+                best = (angle, score);
+            }
+        }
+        best.0
+    }
+
+    /// The resolution an image file records, in dots per inch: PNG pHYs,
+    /// JPEG JFIF density, BMP pixels per metre or TIFF XResolution. None
+    /// when the file doesn't say or gives only an aspect ratio.
+    fn image_dpi(data: &[u8]) -> Option<f32> {
+        let u16_be = |at: usize| Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?));
+        let u32_be = |at: usize| Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?));
+
+        let dpi = if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            let mut at = 8;
+            let mut dpi = None;
+            while let (Some(len), Some(kind)) = (u32_be(at), data.get(at + 4..at + 8)) {
+                match kind {
+                    // Unit 1 is metres; 0 is an aspect ratio only
+                    b"pHYs" if data.get(at + 16) == Some(&1) => {
+                        dpi = u32_be(at + 8).map(|per_metre| per_metre as f32 * 0.0254);
+                        break;
+                    }
+                    b"IDAT" | b"IEND" => break,
+                    _ => at += 12 + len as usize,
+                }
+            }
+            dpi
+        } else if data.starts_with(&[0xFF, 0xD8]) {
+            let mut at = 2;
+            let mut dpi = None;
+            while data.get(at) == Some(&0xFF) {
+                let marker = *data.get(at + 1)?;
+                let len = u16_be(at + 2)? as usize;
+                if marker == 0xE0 && data.get(at + 4..at + 9) == Some(b"JFIF\0") {
+                    let density = u16_be(at + 12)? as f32;
+                    dpi = match data.get(at + 11) {
+                        Some(1) => Some(density),
+                        Some(2) => Some(density * 2.54),
+                        _ => None,
+                    };
+                    break;
+                }
+                // Image data starts at SOS
+                if marker == 0xDA {
+                    break;
+                }
+                at += 2 + len;
+            }
+            dpi
+        } else if data.starts_with(b"BM") {
+            let per_metre = i32::from_le_bytes(data.get(38..42)?.try_into().ok()?);
+            Some(per_metre as f32 * 0.0254)
+        } else if data.starts_with(b"II*\0") || data.starts_with(b"MM\0*") {
+            let little = data[0] == b'I';
+            let read = |at: usize, len: usize| -> Option<u32> {
+                let bytes = data.get(at..at + len)?;
+                Some(if little {
+                    bytes.iter().rev().fold(0, |value, &b| value << 8 | b as u32)
+                } else {
+                    bytes.iter().fold(0, |value, &b| value << 8 | b as u32)
+                })
+            };
+            let ifd = read(4, 4)? as usize;
+            let mut resolution = None;
+            let mut unit = 2; // inches unless ResolutionUnit says otherwise
+            for entry in 0..read(ifd, 2)? as usize {
+                let at = ifd + 2 + entry * 12;
+                match read(at, 2)? {
+                    282 => {
+                        let offset = read(at + 8, 4)? as usize;
+                        let (numerator, denominator) = (read(offset, 4)?, read(offset + 4, 4)?);
+                        if denominator > 0 {
+                            resolution = Some(numerator as f32 / denominator as f32);
+                        }
+                    }
+                    296 => unit = read(at + 8, 2)?,
+                    _ => {}
+                }
+            }
+            match unit {
+                2 => resolution,
+                3 => resolution.map(|per_cm| per_cm * 2.54),
+                _ => None,
+            }
+        } else {
+            None
+        };
+        dpi.filter(|dpi| (10.0..=10_000.0).contains(dpi))
+    }
+
+    /// Wrap the page Tesseract renders as hOCR in the XHTML document its
+    /// command line writes around it
+    fn hocr_document(page: &str) -> String {
+        format!(
+            concat!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+                "<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Transitional//EN\"\n",
+                "    \"http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd\">\n",
+                "<html xmlns=\"http://www.w3.org/1999/xhtml\" xml:lang=\"en\" lang=\"en\">\n",
+                " <head>\n",
+                "  <title></title>\n",
+                "  <meta http-equiv=\"Content-Type\" content=\"text/html;charset=utf-8\"/>\n",
+                "  <meta name='ocr-system' content='tesseract'/>\n",
+                "  <meta name='ocr-capabilities' content='ocr_page ocr_carea ocr_par ocr_line ocrx_word ocrp_wconf'/>\n",
+                " </head>\n",
+                " <body>\n{} </body>\n",
+                "</html>\n",
+            ),
+            page
+        )
+    }
+
+    /// Wrap the page Tesseract renders as ALTO in an ALTO v3 document
+    fn alto_document(page: &str) -> String {
+        format!(
+            concat!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+                "<alto xmlns=\"http://www.loc.gov/standards/alto/ns-v3#\" xmlns:xlink=\"http://www.w3.org/1999/xlink\" ",
+                "xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" ",
+                "xsi:schemaLocation=\"http://www.loc.gov/standards/alto/ns-v3# http://www.loc.gov/alto/v3/alto-3-0.xsd\">\n",
+                "\t<Description>\n",
+                "\t\t<MeasurementUnit>pixel</MeasurementUnit>\n",
+                "\t\t<OCRProcessing ID=\"OCR_0\">\n",
+                "\t\t\t<ocrProcessingStep>\n",
+                "\t\t\t\t<processingSoftware>\n",
+                "\t\t\t\t\t<softwareName>tesseract</softwareName>\n",
+                "\t\t\t\t</processingSoftware>\n",
+                "\t\t\t</ocrProcessingStep>\n",
+                "\t\t</OCRProcessing>\n",
+                "\t</Description>\n",
+                "\t<Layout>\n{}\t</Layout>\n",
+                "</alto>\n",
+            ),
+            page
+        )
+    }
+
+    /// The words Tesseract recognized, with their confidences and boxes
+    fn ocr_words(tesseract: &tesseract_rs::TesseractAPI) -> Result<Vec<OcrWord>, ParserError> {
+        let tsv = tesseract.get_tsv_text(0)
+            .map_err(|e| Self::ocr_error("Failed to perform OCR", e))?;
+        Ok(Self::ocr_words_from_tsv(&tsv))
+    }
+
+    /// The plain text Tesseract recognized, leaving out words below
+    /// `min_confidence`
+    fn tesseract_text(&self, tesseract: &tesseract_rs::TesseractAPI) -> Result<String, ParserError> {
+        match self.config.min_confidence {
+            Some(min_confidence) => Ok(Self::ocr_words_text(&Self::ocr_words(tesseract)?, min_confidence)),
+            None => tesseract.get_utf8_text()
+                .map(|text| text.trim().to_string())
+                .map_err(|e| Self::ocr_error("Failed to perform OCR", e)),
+        }
+    }
+
+    /// Read the words out of Tesseract's TSV output, skipping the header
+    /// and the rows for pages, blocks, paragraphs and lines
+    fn ocr_words_from_tsv(tsv: &str) -> Vec<OcrWord> {
+        let mut words = Vec::new();
+        for row in tsv.lines() {
+            let columns: Vec<&str> = row.splitn(12, '\t').collect();
+            if columns.len() < 12 || columns[0] != "5" {
+                continue;
+            }
+            let number = |i: usize| columns[i].trim().parse::<u32>().unwrap_or(0);
+            let text = columns[11].trim();
+            if text.is_empty() {
+                continue;
+            }
+            words.push(OcrWord {
+                text: text.to_string(),
+                confidence: columns[10].trim().parse().unwrap_or(0.0),
+                bbox: [number(6), number(7), number(8), number(9)],
+                line: (number(2), number(3), number(4)),
+            });
+        }
+        words
+    }
+
+    /// Lay recognized words out as text: words of a line joined by spaces,
+    /// lines by newlines and paragraphs by a blank line, leaving out words
+    /// below `min_confidence`
+    fn ocr_words_text(words: &[OcrWord], min_confidence: f32) -> String {
+        let mut text = String::new();
+        let mut previous: Option<(u32, u32, u32)> = None;
+        for word in words.iter().filter(|word| word.confidence >= min_confidence) {
+            match previous {
+                Some(line) if line == word.line => text.push(' '),
+                Some((block, paragraph, _)) if (block, paragraph) == (word.line.0, word.line.1) => text.push('\n'),
+                Some(_) => text.push_str("\n\n"),
+                None => {}
+            }
+            text.push_str(&word.text);
+            previous = Some(word.line);
+        }
+        text
+    }
+
+    /// How much to enlarge an image before OCR: up to `target_dpi` when the
+    /// file records a lower resolution, or to OCR_SMALL_IMAGE pixels on the
+    /// longer side when it records none. Never shrinks.
+    fn ocr_scale(source_dpi: Option<f32>, width: u32, height: u32, target_dpi: u32) -> f32 {
+        let scale = match source_dpi {
+            Some(dpi) => target_dpi as f32 / dpi,
+            None => OCR_SMALL_IMAGE as f32 / width.max(height).max(1) as f32,
+        };
+        let pixel_limit = (OCR_MAX_PIXELS / (width.max(1) as f32 * height.max(1) as f32)).sqrt();
+        scale.min(OCR_MAX_UPSCALE).min(pixel_limit).max(1.0)
+    }
+
+    /// Directories searched for traineddata when `tessdata_path` isn't set:
+    /// `TESSDATA_PREFIX`, the usual install locations, then the cache
+    /// `ParseKit::Tessdata.download` fills
+    fn tessdata_search_paths() -> Vec<String> {
+        let mut paths = Vec::new();
+
+        // Check TESSDATA_PREFIX environment variable first (for CI)
+        if let Ok(env_path) = std::env::var("TESSDATA_PREFIX") {
+            paths.push(env_path);
+        }
+
+        // Add common system paths
+        paths.extend(
+            ["/usr/share/tessdata", "/usr/local/share/tessdata", "/opt/homebrew/share/tessdata", "/opt/local/share/tessdata"]
+                .map(String::from),
+        );
+
+        // Even in bundled mode, the models have to come from somewhere
+        #[cfg(feature = "bundled-tesseract")]
+        paths.extend([
+            "tessdata".to_string(), // Local tessdata directory
+            ".".to_string(),        // Current directory as fallback
+        ]);
+
+        let cache = std::env::var("PARSEKIT_TESSDATA_CACHE").ok().or_else(|| {
+            let base = std::env::var("XDG_CACHE_HOME")
+                .ok()
+                .filter(|dir| !dir.is_empty())
+                .or_else(|| std::env::var("HOME").ok().map(|home| format!("{}/.cache", home)))?;
+            Some(format!("{}/parsekit/tessdata", base))
+        });
+        paths.extend(cache);
+        paths
+    }
+
+    /// Create a Tesseract instance and load the configured languages and
+    /// modes into it
+    fn new_tesseract(&self) -> Result<tesseract_rs::TesseractAPI, ParserError> {
+        use tesseract_rs::{TessOcrEngineMode, TesseractAPI};
+
+        let tesseract = TesseractAPI::new();
+
+        // The engine mode can only be chosen when Tesseract loads its models
+        let init = |path: &str| match self.config.ocr_oem {
+            Some(oem) => {
+                let mode = match oem {
+                    0 => TessOcrEngineMode::OEM_TESSERACT_ONLY,
+                    1 => TessOcrEngineMode::OEM_LSTM_ONLY,
+                    2 => TessOcrEngineMode::OEM_TESSERACT_LSTM_COMBINED,
+                    _ => TessOcrEngineMode::OEM_DEFAULT,
+                };
+                tesseract.init_2(path, &self.config.ocr_language, mode)
+            }
+            None => tesseract.init(path, &self.config.ocr_language),
+        };
+        
+        // An explicit tessdata_path is the only place looked in; otherwise
+        // the usual install locations are tried in turn
+        let tessdata_paths = match &self.config.tessdata_path {
+            Some(path) => vec![path.clone()],
+            None => Self::tessdata_search_paths(),
+        };
+        let mut init_result = Err(tesseract_rs::TesseractError::InitError);
+        for path in &tessdata_paths {
+            // Check if path exists first to avoid noisy error messages
+            if std::path::Path::new(path).exists() && init(path.as_str()).is_ok() {
+                init_result = Ok(());
+                break;
+            }
+        }
+        
+        if let Err(e) = init_result {
+            // Usually a missing traineddata file for one of the languages
+            let context = format!(
+                "Failed to initialize Tesseract for '{}' from {} (set tessdata_path, or fetch the models with ParseKit::Tessdata.download)",
+                self.config.ocr_language,
+                tessdata_paths.join(", ")
+            );
+            return Err(Self::ocr_error(&context, e));
+        }
+        if let Some(psm) = self.config.ocr_psm {
+            tesseract.set_variable("tessedit_pageseg_mode", &psm.to_string())
+                .map_err(|e| Self::ocr_error("Failed to set page segmentation mode", e))?;
+        }
+
+        Ok(tesseract)
+    }
+
+    /// Get a Tesseract instance with the configured languages and modes,
+    /// reusing one from the pool when it can, and give it the
+    /// decoded image, cropped to `region` (x, y, w, h) if given, ready
+    /// for recognition. Images below `ocr_dpi` are enlarged first, since
+    /// Tesseract does poorly under about 300 DPI.
+    fn ocr_engine(
+        &self,
+        data: &[u8],
+        region: Option<(u32, u32, u32, u32)>,
+    ) -> Result<(PooledTesseract, OcrScan), Error> {
+        // AVIF decoding needs dav1d, which is only linked with the `avif` feature
+        #[cfg(not(feature = "avif"))]
+        if FormatDetector::detect_from_content(data) == FileFormat::Avif {
+            return Err(Self::runtime_error(
+                "Failed to load image",
+                "AVIF decoding requires building with the `avif` feature",
+            ));
+        }
+        
+        // Load the image from bytes
+        let img = image::load_from_memory(data)
+            .map_err(|e| Self::runtime_error("Failed to load image", e))?;
+        let img = match region {
+            Some(region) => Self::crop_ocr_region(img, region)?,
+            None => img,
+        };
+        Ok(self.ocr_engine_for(&img, Self::image_dpi(data))?)
+    }
+
+    /// Like `ocr_engine`, for an image already decoded, recorded at
+    /// `source_dpi` if known. Doesn't touch Ruby, so OCR worker threads can
+    /// call it.
+    fn ocr_engine_for(
+        &self,
+        img: &image::DynamicImage,
+        source_dpi: Option<f32>,
+    ) -> Result<(PooledTesseract, OcrScan), ParserError> {
+        // Word boxes and layout output are only available from Tesseract
+        if !self.config.ocr_backend.is_tesseract() {
+            return Err(Self::ocr_error(
+                "Failed to perform OCR",
+                "word confidences and hOCR/TSV/ALTO output need the :tesseract ocr_backend",
+            ));
+        }
+
+        // Reuse an instance already loaded with the same models
+        let key = TesseractKey {
+            language: self.config.ocr_language.clone(),
+            oem: self.config.ocr_oem,
+            psm: self.config.ocr_psm,
+            tessdata_path: self.config.tessdata_path.clone(),
+        };
+        let api = match PooledTesseract::take(&key) {
+            Some(api) => api,
+            None => self.new_tesseract()?,
+        };
+        let tesseract = PooledTesseract {
+            key,
+            api: Some(api),
+            keep: TESSERACT_POOL_SIZE.max(self.config.ocr_threads),
+        };
+
+        let scale = Self::ocr_scale(source_dpi, img.width(), img.height(), self.config.ocr_dpi);
+        let scan = OcrScan {
+            source_dpi,
+            scale,
+            low_resolution: match source_dpi {
+                Some(dpi) => dpi < self.config.ocr_dpi as f32,
+                None => img.width().max(img.height()) < OCR_SMALL_IMAGE,
+            },
+        };
+        let img = if scale > 1.0 {
+            let width = (img.width() as f32 * scale).round() as u32;
+            let height = (img.height() as f32 * scale).round() as u32;
+            img.resize_exact(width, height, image::imageops::FilterType::CatmullRom)
+        } else {
+            img.clone()
+        };
+        let img = Self::preprocess_for_ocr(img, &self.config.ocr_preprocess);
+        
+        // Convert to RGBA8 format
+        let rgba_img = img.to_rgba8();
+        let (width, height) = rgba_img.dimensions();
+        let raw_data = rgba_img.into_raw();
+        
+        // Set image data
+        tesseract.set_image(
+            &raw_data,
+            width as i32,
+            height as i32,
+            4,  // bytes per pixel (RGBA)
+            (width * 4) as i32,  // bytes per line
+        ).map_err(|e| Self::ocr_error("Failed to set image", e))?;
+        if let Some(dpi) = source_dpi {
+            tesseract.set_source_resolution((dpi * scale).round() as i32)
+                .map_err(|e| Self::ocr_error("Failed to set image resolution", e))?;
+        }
+
+        Ok((tesseract, scan))
+    }
+
+    /// Recognize several page images on up to `ocr_threads` worker threads,
+    /// returning the results in page order. Workers check a Tesseract
+    /// instance out of the shared pool for each page and put it back after,
+    /// so instances loaded for one document serve the next. `recognize`
+    /// must not call into Ruby. Runs on the calling thread when
+    /// one thread is configured or there is only one page.
+    fn ocr_pages<T, R, F>(&self, pages: Vec<T>, recognize: F) -> Result<Vec<R>, Error>
+    where
+        T: Send,
+        R: Send,
+        F: Fn(T) -> Result<R, ParserError> + Sync,
+    {
+        let threads = self.config.ocr_threads.min(pages.len());
+        if threads <= 1 {
+            return Ok(pages.into_iter().map(&recognize).collect::<Result<_, _>>()?);
+        }
+
+        // Workers take the next page off a shared queue, so a slow page
+        // doesn't hold up the others
+        let queue = std::sync::Mutex::new(pages.into_iter().enumerate());
+        let mut results = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut done = Vec::new();
+                        loop {
+                            let next = queue.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).next();
+                            let Some((index, page)) = next else {
+                                break;
+                            };
+                            done.push((index, recognize(page)));
+                        }
+                        done
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+                .collect::<Vec<_>>()
+        });
+        results.sort_by_key(|(index, _)| *index);
+        Ok(results.into_iter().map(|(_, result)| result).collect::<Result<_, _>>()?)
+    }
+
+    /// The pages of a multi-page TIFF, such as a fax or a scanner's batch
+    /// output, or `None` for anything else, single-page TIFFs included.
+    /// Bilevel, grayscale and RGB(A) pages at 8 or 16 bits are read.
+    fn tiff_pages(data: &[u8]) -> Result<Option<Vec<image::DynamicImage>>, ParserError> {
+        use image::{DynamicImage, ImageBuffer};
+        use tiff::decoder::{Decoder, DecodingResult};
+        use tiff::ColorType;
+
+        if !(data.starts_with(b"II*\0") || data.starts_with(b"MM\0*")) {
+            return Ok(None);
+        }
+        let fail = |e: tiff::TiffError| Self::ocr_error("Failed to load image", e);
+        let mut decoder = Decoder::new(std::io::Cursor::new(data)).map_err(fail)?;
+        if !decoder.more_images() {
+            return Ok(None);
+        }
+
+        let mut pages = Vec::new();
+        loop {
+            let (width, height) = decoder.dimensions().map_err(fail)?;
+            let color = decoder.colortype().map_err(fail)?;
+            let page = match (color, decoder.read_image().map_err(fail)?) {
+                (ColorType::Gray(1), DecodingResult::U8(bits)) => {
+                    // Rows are packed 8 pixels to a byte, padded to whole bytes
+                    let row_bytes = width.div_ceil(8) as usize;
+                    let pixels = bits
+                        .chunks(row_bytes.max(1))
+                        .take(height as usize)
+                        .flat_map(|row| {
+                            (0..width as usize).map(move |x| if row.get(x / 8).is_some_and(|byte| byte >> (7 - x % 8) & 1 == 1) { 255 } else { 0 })
+                        })
+                        .collect();
+                    ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageLuma8)
+                }
+                (ColorType::Gray(8), DecodingResult::U8(pixels)) => ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageLuma8),
+                (ColorType::GrayA(8), DecodingResult::U8(pixels)) => ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageLumaA8),
+                (ColorType::RGB(8), DecodingResult::U8(pixels)) => ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageRgb8),
+                (ColorType::RGBA(8), DecodingResult::U8(pixels)) => ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageRgba8),
+                (ColorType::Gray(16), DecodingResult::U16(pixels)) => ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageLuma16),
+                (ColorType::RGB(16), DecodingResult::U16(pixels)) => ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageRgb16),
+                (ColorType::RGBA(16), DecodingResult::U16(pixels)) => ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageRgba16),
+                (color, _) => {
+                    return Err(Self::ocr_error(
+                        "Failed to load image",
+                        format!("TIFF page {} has unsupported color type {:?}", pages.len() + 1, color),
+                    ))
+                }
+            };
+            let page = page.ok_or_else(|| {
+                Self::ocr_error("Failed to load image", format!("TIFF page {} is truncated", pages.len() + 1))
+            })?;
+            pages.push(page);
+            if !decoder.more_images() {
+                break;
+            }
+            decoder.next_image().map_err(fail)?;
+        }
+        Ok(Some(pages))
+    }
+
+    /// Cut the `region` (x, y, w, h) to recognize out of an image
+    fn crop_ocr_region(img: image::DynamicImage, region: (u32, u32, u32, u32)) -> Result<image::DynamicImage, Error> {
+        let (x, y, w, h) = region;
+        let inside = x.checked_add(w).is_some_and(|right| right <= img.width())
+            && y.checked_add(h).is_some_and(|bottom| bottom <= img.height());
+        if w == 0 || h == 0 || !inside {
+            return Err(Self::argument_error(&format!(
+                "OCR region {}x{}+{}+{} is empty or outside the {}x{} image",
+                w, h, x, y, img.width(), img.height()
+            )));
+        }
+        Ok(img.crop_imm(x, y, w, h))
+    }
+    
+
+    /// Parse PDF files using MuPDF (statically linked) - exposed to Ruby
+    ///
+    /// `pages:` limits extraction to some pages, numbered from 1: a Range
+    /// (`1..10`, `5..`), an Array (`[1, 3, 5]`), an Integer or `{first: n}`.
+    /// Pages past the end of the document are ignored. Pages MuPDF can't
+    /// read are skipped, or marked with a `[Warning: ...]` line when the
+    /// parser is `lenient`. With `pdf_structure_order`, tagged pages are
+    /// read in the logical order of their structure tree, without artifacts.
+    /// The fields of an XFA form follow the page text under an
+    /// `=== XFA form data ===` heading, unless `pages:` is given.
+    fn parse_pdf(&self, args: &[Value]) -> Result<String, Error> {
+        let (data, pages) = Self::pdf_args(args)?;
+        self.pdf_text(data, &pages)
+    }
+
+    /// Parse PDF files page by page - exposed to Ruby
+    ///
+    /// Returns an Array of `{page:, text:, width:, height:}` Hashes, one per
+    /// page, with the page size in points. Takes the same `pages:` option as
+    /// `parse_pdf`. In lenient mode unreadable pages are kept with an empty
+    /// `text` and a `warning`.
+    fn parse_pdf_pages(&self, args: &[Value]) -> Result<RArray, Error> {
+        let ruby = Ruby::get().unwrap();
+        let (data, pages) = Self::pdf_args(args)?;
+        let doc = self.open_pdf(&data)?;
+        let page_count = doc.page_count()
+            .map_err(|e| Self::runtime_error("Failed to get page count", e))?;
+
+        let indices = pages.indices(page_count as usize);
+        let mut tagged = self.pdf_structure_text(&data, &indices);
+
+        let result = ruby.ary_new();
+        for index in indices {
+            let entry = ruby.hash_new();
+            entry.aset(ruby.to_symbol("page"), index + 1)?;
+
+            // Pages MuPDF can't read are left out, as in parse_pdf, or kept
+            // with a warning in lenient mode
+            let page = match doc.load_page(index as i32) {
+                Ok(page) => page,
+                Err(e) if self.config.lenient => {
+                    entry.aset(ruby.to_symbol("text"), "")?;
+                    entry.aset(ruby.to_symbol("warning"), format!("page could not be read: {}", e))?;
+                    result.push(entry)?;
+                    continue;
+                }
+                Err(_) => continue,
+            };
+            let bounds = page.bounds()
+                .map_err(|e| Self::runtime_error("Failed to get page size", e))?;
+            let mut text = match tagged.remove(&index) {
+                Some(text) => text.trim().to_string(),
+                None => page.to_text().unwrap_or_default().trim().to_string(),
+            };
+            if self.config.normalize_text {
+                text = Self::normalize_typography(&text);
+            }
+
+            entry.aset(ruby.to_symbol("text"), text)?;
+            entry.aset(ruby.to_symbol("width"), bounds.x1 - bounds.x0)?;
+            entry.aset(ruby.to_symbol("height"), bounds.y1 - bounds.y0)?;
+            result.push(entry)?;
+        }
+        Ok(result)
+    }
+
+    /// Extract tables from PDF pages - exposed to Ruby
+    ///
+    /// Tables are rebuilt from MuPDF's positioned text: consecutive lines that
+    /// split into several widely spaced runs become rows, and columns come
+    /// from where those runs line up. Returns `[{page:, rows: [[cell, ...]]}]`,
+    /// or `[{page:, markdown:}]` with `format: :markdown`. Takes `pages:` like
+    /// `parse_pdf`.
+    fn parse_pdf_tables(&self, args: &[Value]) -> Result<RArray, Error> {
+        use mupdf::TextPageOptions;
+
+        let ruby = Ruby::get().unwrap();
+        let args = scan_args::scan_args::<(Vec<u8>,), (), (), (), RHash, ()>(args)?;
+        let (data,) = args.required;
+        let kwargs = scan_args::get_kwargs::<_, (), (Option<Value>, Option<magnus::Symbol>), ()>(
+            args.keywords,
+            &[],
+            &["pages", "format"],
+        )?;
+        let (pages, format) = kwargs.optional;
+        let pages = Self::page_selection(pages, "pages")?;
+        let markdown = match format {
+            None => false,
+            Some(format) => match format.name()?.as_ref() {
+                "array" => false,
+                "markdown" => true,
+                other => return Err(Self::argument_error(&format!("Unsupported table format: {}", other))),
+            },
+        };
+
+        let doc = self.open_pdf(&data)?;
+        let page_count = doc.page_count()
+            .map_err(|e| Self::runtime_error("Failed to get page count", e))?;
+
+        let result = ruby.ary_new();
+        for index in pages.indices(page_count as usize) {
+            let Ok(page) = doc.load_page(index as i32) else {
+                continue;
+            };
+            let text_page = page
+                .to_text_page(TextPageOptions::empty())
+                .map_err(|e| Self::runtime_error("Failed to extract positioned text", e))?;
+
+            for table in Self::detect_pdf_tables(Self::pdf_text_chunks(&text_page)) {
+                let entry = ruby.hash_new();
+                entry.aset(ruby.to_symbol("page"), index + 1)?;
+                if markdown {
+                    entry.aset(ruby.to_symbol("markdown"), Self::markdown_table(&table))?;
+                } else {
+                    entry.aset(ruby.to_symbol("rows"), table)?;
+                }
+                result.push(entry)?;
+            }
+        }
+        Ok(result)
+    }
+
+    /// Split each line of a MuPDF text page into runs of text, breaking
+    /// wherever the gap between characters reaches `PDF_CELL_GAP` ems
+    fn pdf_text_chunks(text_page: &mupdf::TextPage) -> Vec<PdfTextChunk> {
+        let mut chunks = Vec::new();
+        for block in text_page.blocks() {
+            for line in block.lines() {
+                let mut current: Option<PdfTextChunk> = None;
+                let mut space = false;
+                for ch in line.chars() {
+                    let Some(c) = ch.char() else {
+                        continue;
+                    };
+                    if c.is_whitespace() {
+                        space = true;
+                        continue;
+                    }
+                    let quad = ch.quad();
+                    let (x0, x1, size) = (quad.ul.x, quad.ur.x, ch.size());
+                    match current.as_mut() {
+                        Some(chunk) if x0 - chunk.x1 < size * PDF_CELL_GAP => {
+                            if space {
+                                chunk.text.push(' ');
+                            }
+                            chunk.text.push(c);
+                            chunk.x1 = x1;
+                        }
+                        _ => {
+                            chunks.extend(current.take());
+                            current = Some(PdfTextChunk {
+                                text: c.to_string(),
+                                x0,
+                                x1,
+                                y: ch.origin().y,
+                                size,
+                            });
+                        }
+                    }
+                    space = false;
+                }
+                chunks.extend(current);
+            }
+        }
+        chunks
+    }
+
+    /// Find tables among a page's text chunks: runs of two or more
+    /// consecutive lines that each hold several chunks, with columns taken
+    /// from the merged horizontal extents of the chunks
+    fn detect_pdf_tables(mut chunks: Vec<PdfTextChunk>) -> Vec<Vec<Vec<String>>> {
+        chunks.sort_by(|a, b| a.y.total_cmp(&b.y).then(a.x0.total_cmp(&b.x0)));
+
+        let mut rows: Vec<Vec<PdfTextChunk>> = Vec::new();
+        for chunk in chunks {
+            match rows.last_mut() {
+                Some(row) if (chunk.y - row[0].y).abs() <= row[0].size.max(chunk.size) * 0.5 => row.push(chunk),
+                _ => rows.push(vec![chunk]),
+            }
+        }
+
+        let mut tables = Vec::new();
+        let mut region: Vec<Vec<PdfTextChunk>> = Vec::new();
+        for row in rows.into_iter().chain(std::iter::once(Vec::new())) {
+            if row.len() >= 2 {
+                region.push(row);
+                continue;
+            }
+            if region.len() >= 2 {
+                if let Some(table) = Self::pdf_table_cells(&region) {
+                    tables.push(table);
+                }
+            }
+            region.clear();
+        }
+        tables
+    }
+
+    /// Lay a region's rows out on shared columns, or None if they collapse
+    /// into a single column
+    fn pdf_table_cells(rows: &[Vec<PdfTextChunk>]) -> Option<Vec<Vec<String>>> {
+        let mut extents: Vec<(f32, f32)> = rows.iter().flatten().map(|chunk| (chunk.x0, chunk.x1)).collect();
+        extents.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let mut columns: Vec<(f32, f32)> = Vec::new();
+        for (x0, x1) in extents {
+            match columns.last_mut() {
+                Some(column) if x0 <= column.1 => column.1 = column.1.max(x1),
+                _ => columns.push((x0, x1)),
+            }
+        }
+        if columns.len() < 2 {
+            return None;
+        }
+
+        let table = rows
+            .iter()
+            .map(|row| {
+                let mut cells = vec![String::new(); columns.len()];
+                for chunk in row {
+                    let column = columns.iter().position(|&(x0, x1)| chunk.x0 >= x0 && chunk.x0 <= x1).unwrap_or(0);
+                    if !cells[column].is_empty() {
+                        cells[column].push(' ');
+                    }
+                    cells[column].push_str(&chunk.text);
+                }
+                cells
+            })
+            .collect();
+        Some(table)
+    }
+
+    /// Render table rows as a Markdown table, the first row as its header
+    fn markdown_table(rows: &[Vec<String>]) -> String {
+        let line = |cells: &[String]| {
+            let cells: Vec<String> = cells.iter().map(|cell| cell.replace('|', "\\|")).collect();
+            format!("| {} |", cells.join(" | "))
+        };
+        let mut lines = Vec::new();
+        if let Some((header, body)) = rows.split_first() {
+            lines.push(line(header));
+            lines.push(format!("|{}", " --- |".repeat(header.len())));
+            lines.extend(body.iter().map(|row| line(row)));
+        }
+        lines.join("\n")
+    }
+
+    /// Extract PDF text as styled spans - exposed to Ruby
+    ///
+    /// Returns `[{page:, text:, font:, size:, bold:, italic:, bbox: [x0, y0, x1, y1]}]`
+    /// from MuPDF's structured text, one span per run of a single font
+    /// within a line; boxes are in points from the page's top-left corner.
+    /// Bold and italic are read from the font name. Takes `pages:` like
+    /// `parse_pdf`.
+    fn parse_pdf_spans(&self, args: &[Value]) -> Result<RArray, Error> {
+        let ruby = Ruby::get().unwrap();
+        let (data, pages) = Self::pdf_args(args)?;
+        let doc = self.open_pdf(&data)?;
+        let page_count = doc.page_count()
+            .map_err(|e| Self::runtime_error("Failed to get page count", e))?;
+
+        let result = ruby.ary_new();
+        for index in pages.indices(page_count as usize) {
+            let Ok(page) = doc.load_page(index as i32) else {
+                continue;
+            };
+            let xml = page.to_xml()
+                .map_err(|e| Self::runtime_error("Failed to extract structured text", e))?;
+            let spans = Self::pdf_spans_from_stext(&xml)
+                .map_err(|e| Self::runtime_error("Failed to read structured text", e))?;
+
+            for span in spans {
+                let entry = ruby.hash_new();
+                entry.aset(ruby.to_symbol("page"), index + 1)?;
+                entry.aset(ruby.to_symbol("text"), span.text)?;
+                entry.aset(ruby.to_symbol("font"), span.font)?;
+                entry.aset(ruby.to_symbol("size"), span.size)?;
+                entry.aset(ruby.to_symbol("bold"), span.bold)?;
+                entry.aset(ruby.to_symbol("italic"), span.italic)?;
+                entry.aset(ruby.to_symbol("bbox"), span.bbox.to_vec())?;
+                result.push(entry)?;
+            }
+        }
+        Ok(result)
+    }
+
+    /// Collect font runs from MuPDF's structured-text XML: each `<font>`
+    /// element within a `<line>` becomes a span, its box the union of its
+    /// character quads and its text trimmed
+    fn pdf_spans_from_stext(xml: &str) -> Result<Vec<PdfSpan>, String> {
+        use quick_xml::events::Event;
+        use quick_xml::Reader;
+
+        let mut reader = Reader::from_str(xml);
+        let mut spans = Vec::new();
+        let mut current: Option<PdfSpan> = None;
+        loop {
+            match reader.read_event().map_err(|e| e.to_string())? {
+                Event::Start(ref e) if e.name().as_ref() == b"font" => {
+                    let font = Self::xml_attribute(e, b"name").unwrap_or_default();
+                    let lower = font.to_lowercase();
+                    current = Some(PdfSpan {
+                        bold: lower.contains("bold") || lower.contains("black") || lower.contains("heavy"),
+                        italic: lower.contains("italic") || lower.contains("oblique"),
+                        font,
+                        size: Self::xml_attribute(e, b"size").and_then(|size| size.parse().ok()).unwrap_or(0.0),
+                        text: String::new(),
+                        bbox: [f32::INFINITY, f32::INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY],
+                    });
+                }
+                Event::Empty(ref e) if e.name().as_ref() == b"char" => {
+                    let Some(span) = current.as_mut() else {
+                        continue;
+                    };
+                    let c = Self::xml_attribute(e, b"c").unwrap_or_default();
+                    span.text.push_str(&c);
+                    // Spaces MuPDF inserts between words don't widen the box
+                    if c.trim().is_empty() {
+                        continue;
+                    }
+                    let quad: Vec<f32> = Self::xml_attribute(e, b"quad")
+                        .unwrap_or_default()
+                        .split_whitespace()
+                        .filter_map(|value| value.parse().ok())
+                        .collect();
+                    for point in quad.chunks_exact(2) {
+                        span.bbox[0] = span.bbox[0].min(point[0]);
+                        span.bbox[1] = span.bbox[1].min(point[1]);
+                        span.bbox[2] = span.bbox[2].max(point[0]);
+                        span.bbox[3] = span.bbox[3].max(point[1]);
+                    }
+                }
+                Event::End(ref e) if e.name().as_ref() == b"font" => {
+                    if let Some(mut span) = current.take().filter(|span| !span.text.trim().is_empty()) {
+                        span.text = span.text.trim().to_string();
+                        spans.push(span);
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        Ok(spans)
+    }
+
+    /// Render a PDF page to PNG - exposed to Ruby
+    ///
+    /// `page` is numbered from 1; `dpi:` (default 150, up to 1200) sets the
+    /// resolution. Returns the PNG as a binary String. Renders whose RGB
+    /// pixels would exceed `max_size` raise a ResourceLimitError.
+    fn render_pdf_page(&self, args: &[Value]) -> Result<RString, Error> {
+        let ruby = Ruby::get().unwrap();
+        let args = scan_args::scan_args::<(Vec<u8>, usize), (), (), (), RHash, ()>(args)?;
+        let (data, page_number) = args.required;
+        let kwargs = scan_args::get_kwargs::<_, (), (Option<f32>,), ()>(
+            args.keywords,
+            &[],
+            &["dpi"],
+        )?;
+        let dpi = kwargs.optional.0.unwrap_or(150.0);
+        if !(1.0..=1200.0).contains(&dpi) {
+            return Err(Self::argument_error("dpi must be between 1 and 1200"));
+        }
+
+        let doc = self.open_pdf(&data)?;
+        let page_count = doc.page_count()
+            .map_err(|e| Self::runtime_error("Failed to get page count", e))?;
+        if page_number < 1 || page_number > page_count as usize {
+            return Err(Self::argument_error(&format!(
+                "Page {} is out of range, the PDF has {} pages",
+                page_number, page_count
+            )));
+        }
+        let page = doc.load_page(page_number as i32 - 1)
+            .map_err(|e| Self::runtime_error("Failed to load page", e))?;
+        let png = self.render_page_png(&page, dpi, page_number)?;
+        Ok(ruby.str_from_slice(&png))
+    }
+
+    /// Render a loaded PDF page (numbered from 1 for messages) to PNG bytes
+    /// at `dpi`, within `max_size`
+    fn render_page_png(&self, page: &mupdf::Page, dpi: f32, page_number: usize) -> Result<Vec<u8>, Error> {
+        let image = self.render_page_image(page, dpi, page_number)?;
+        let mut png = Vec::new();
+        image
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .map_err(|e| Self::runtime_error("Failed to encode PNG", e))?;
+        Ok(png)
+    }
+
+    /// Render a loaded PDF page to RGB pixels, like `render_page_png`
+    fn render_page_image(&self, page: &mupdf::Page, dpi: f32, page_number: usize) -> Result<image::RgbImage, Error> {
+        use mupdf::{Colorspace, Matrix};
+
+        // MuPDF page space is 72 units per inch
+        let scale = dpi / 72.0;
+        let bounds = page.bounds()
+            .map_err(|e| Self::runtime_error("Failed to get page size", e))?;
+        let pixels = ((bounds.x1 - bounds.x0) * scale).ceil() as u64 * ((bounds.y1 - bounds.y0) * scale).ceil() as u64;
+        if pixels * 3 > self.config.max_size as u64 {
+            return Err(Self::resource_limit_error(
+                "Rendered page exceeds limit",
+                format!("page {} at {} dpi needs more than {} bytes", page_number, dpi, self.config.max_size),
+            ));
+        }
+
+        let pixmap = page
+            .to_pixmap(&Matrix::new_scale(scale, scale), &Colorspace::device_rgb(), false, true)
+            .map_err(|e| Self::runtime_error("Failed to render page", e))?;
+        image::RgbImage::from_raw(pixmap.width(), pixmap.height(), pixmap.samples().to_vec())
+            .ok_or_else(|| Self::runtime_error("Failed to render page", "unexpected pixmap layout"))
+    }
+
+    /// Split the arguments of the PDF methods into the data and `pages:` selection
+    fn pdf_args(args: &[Value]) -> Result<(Vec<u8>, PageSelection), Error> {
+        let args = scan_args::scan_args::<(Vec<u8>,), (), (), (), RHash, ()>(args)?;
+        let (data,) = args.required;
+        let kwargs = scan_args::get_kwargs::<_, (), (Option<Value>,), ()>(
+            args.keywords,
+            &[],
+            &["pages"],
+        )?;
+        let (pages,) = kwargs.optional;
+
+        Ok((data, Self::page_selection(pages, "pages")?))
+    }
+
+    /// Read the `pages:` option of the PDF methods, or the `option` that
+    /// selects parts of another format the same way
+    fn page_selection(value: Option<Value>, option: &str) -> Result<PageSelection, Error> {
+        let Some(value) = value else {
+            return Ok(PageSelection::All);
+        };
+        let page_number = |page: i64| {
+            usize::try_from(page)
+                .ok()
+                .filter(|&page| page >= 1)
+                .ok_or_else(|| Self::argument_error(&format!("{} are numbered from 1", option)))
+        };
+
+        if let Some(range) = magnus::Range::from_value(value) {
+            // Negative bounds are resolved against the page count later
+            let start = range.beg::<Option<i64>>()?.unwrap_or(1);
+            if start == 0 {
+                return Err(Self::argument_error(&format!("{} are numbered from 1", option)));
+            }
+            let end = range
+                .end::<Option<i64>>()?
+                .map(|end| if range.excl() && end != 0 { end.saturating_sub(1) } else { end });
+            Ok(PageSelection::Range(start, end))
+        } else if let Some(list) = RArray::from_value(value) {
+            let pages = list
+                .to_vec::<i64>()?
+                .into_iter()
+                .map(page_number)
+                .collect::<Result<_, _>>()?;
+            Ok(PageSelection::List(pages))
+        } else if let Some(options) = RHash::from_value(value) {
+            let ruby = Ruby::get().unwrap();
+            match options.get(ruby.to_symbol("first")) {
+                Some(first) if options.len() == 1 => Ok(PageSelection::First(usize::try_convert(first)?)),
+                _ => Err(Self::argument_error(&format!("{}: Hash must be {{first: n}}", option))),
+            }
+        } else if value.is_nil() {
+            Ok(PageSelection::All)
+        } else {
+            Ok(PageSelection::List(vec![page_number(i64::try_convert(value)?)?]))
+        }
+    }
+
+    /// Extract the text of the selected pages of a PDF
+    fn pdf_text(&self, data: Vec<u8>, pages: &PageSelection) -> Result<String, Error> {
+        let doc = self.open_pdf(&data)?;
+        
+        let mut all_text = String::new();
+
+        // Get page count
+        let page_count = doc.page_count()
+            .map_err(|e| Self::runtime_error("Failed to get page count", e))?;
+
+        // With pdf_structure_order, tagged pages are read in the logical order
+        // of the structure tree, which keeps columns, sidebars and footnotes apart
+        let indices = pages.indices(page_count as usize);
+        let mut tagged = self.pdf_structure_text(&data, &indices);
+
+        // Iterate through the selected pages
+        for page_num in indices {
+            if let Some(text) = tagged.remove(&page_num) {
+                all_text.push_str(&text);
+                all_text.push('\n');
+                continue;
+            }
+
+            // Continue on page errors rather than failing entirely; lenient
+            // mode leaves a warning where the page would have been
+            match doc.load_page(page_num as i32).and_then(|page| page.to_text()) {
+                Ok(text) => {
+                    all_text.push_str(&text);
+                    all_text.push('\n');
+                }
+                Err(e) if self.config.lenient => {
+                    all_text.push_str(&format!("[Warning: page {} could not be read: {}]\n", page_num + 1, e));
+                }
+                Err(_) => {}
+            }
+        }
+
+        // XFA form data isn't on any page, so it's only added for whole documents
+        if matches!(pages, PageSelection::All) {
+            let fields = self.pdf_xfa_fields(&data).unwrap_or_default();
+            if !fields.is_empty() {
+                all_text.push_str("\n=== XFA form data ===\n");
+                for field in fields {
+                    let label = field.label.as_deref().unwrap_or(&field.name);
+                    all_text.push_str(&format!("{}: {}\n", label, field.value));
+                }
+            }
+        }
+
+        if all_text.is_empty() {
+            Ok("PDF contains no extractable text (might be scanned/image-based)".to_string())
+        } else if self.config.normalize_text {
+            Ok(Self::normalize_typography(all_text.trim()))
+        } else {
+            Ok(all_text.trim().to_string())
+        }
+    }
+
+    /// Undo typesetting artifacts that break search in extracted text:
+    /// ligature characters become their letters, soft hyphens are dropped
+    /// and lowercase words hyphenated across a line break are joined
+    fn normalize_typography(text: &str) -> String {
+        let ligatures = [
+            ('\u{FB00}', "ff"),
+            ('\u{FB01}', "fi"),
+            ('\u{FB02}', "fl"),
+            ('\u{FB03}', "ffi"),
+            ('\u{FB04}', "ffl"),
+            ('\u{FB05}', "st"),
+            ('\u{FB06}', "st"),
+        ];
+        let mut text = text.to_string();
+        for (ligature, letters) in ligatures {
+            text = text.replace(ligature, letters);
+        }
+
+        let text = SOFT_HYPHEN_BREAK.replace_all(&text, "");
+
+        // Only words lowercase on both sides are joined, so "Jean-\nPaul"
+        // and "UTF-\nencoded" keep their hyphen
+        LINE_END_HYPHEN.replace_all(&text, "$1$2").into_owned()
+    }
+
+    /// Open a PDF with MuPDF, which already rebuilds broken xref tables. If
+    /// that fails, retry from the `%PDF-` header to drop data prepended by
+    /// mail gateways and download wrappers; lenient mode also retries
+    /// headerless data with a header added, leaving MuPDF to rebuild the
+    /// document from whatever objects it finds.
+    fn open_pdf(&self, data: &[u8]) -> Result<mupdf::Document, Error> {
+        self.open_pdf_with(data, |data| mupdf::Document::from_bytes(data, "pdf"))
+    }
+
+    /// The repair steps of `open_pdf` for any way of opening the bytes, so
+    /// low-level `PdfDocument` readers see the same document
+    fn open_pdf_with<T>(
+        &self,
+        data: &[u8],
+        open: impl Fn(&[u8]) -> Result<T, mupdf::Error>,
+    ) -> Result<T, Error> {
+        let error = match open(data) {
+            Ok(doc) => return Ok(doc),
+            Err(e) => e,
+        };
+
+        let header = data.windows(5).position(|window| window == b"%PDF-");
+        if let Some(start) = header.filter(|&start| start > 0) {
+            if let Ok(doc) = open(&data[start..]) {
+                return Ok(doc);
+            }
+        }
+        if self.config.lenient && header.is_none() {
+            let mut patched = b"%PDF-1.7\n".to_vec();
+            patched.extend_from_slice(data);
+            if let Ok(doc) = open(&patched) {
+                return Ok(doc);
+            }
+        }
+
+        Err(Self::runtime_error("Failed to parse PDF", error))
+    }
+
+    /// List the JavaScript and open actions of a PDF - exposed to Ruby
+    ///
+    /// Covers the document's /OpenAction and additional actions, the
+    /// /JavaScript name tree, page and annotation actions, and form field
+    /// actions. Returns `{javascript: bool, actions: [{trigger:, type:, script:}]}`
+    /// where `script` is nil for open actions that aren't JavaScript
+    /// (/Launch, /URI, ...).
+    fn pdf_javascript(&self, data: Vec<u8>) -> Result<RHash, Error> {
+        let ruby = Ruby::get().unwrap();
+        let actions = Self::collect_pdf_actions(&data)
+            .map_err(|e| Self::runtime_error("Failed to read PDF actions", e))?;
+
+        let list = ruby.ary_new();
+        for action in &actions {
+            let entry = ruby.hash_new();
+            entry.aset(ruby.to_symbol("trigger"), action.trigger.as_str())?;
+            entry.aset(ruby.to_symbol("type"), action.kind.as_str())?;
+            entry.aset(ruby.to_symbol("script"), action.script.clone())?;
+            list.push(entry)?;
+        }
+
+        let result = ruby.hash_new();
+        result.aset(ruby.to_symbol("javascript"), actions.iter().any(|action| action.script.is_some()))?;
+        result.aset(ruby.to_symbol("actions"), list)?;
+        Ok(result)
+    }
+
+    /// Read a PDF's bookmarks - exposed to Ruby
+    ///
+    /// Returns the outline as nested `{title:, page:, children:}` Hashes;
+    /// `page` is numbered from 1, or nil for entries that link outside the
+    /// document. An empty Array means the PDF has no outline.
+    fn pdf_outline(&self, data: Vec<u8>) -> Result<RArray, Error> {
+        let doc = self.open_pdf(&data)?;
+        let outlines = doc.outlines()
+            .map_err(|e| Self::runtime_error("Failed to read PDF outline", e))?;
+        Self::outline_entries(&Ruby::get().unwrap(), &outlines)
+    }
+
+    /// Convert MuPDF outline entries, and their children, to Ruby Hashes
+    fn outline_entries(ruby: &Ruby, outlines: &[mupdf::Outline]) -> Result<RArray, Error> {
+        let entries = ruby.ary_new();
+        for outline in outlines {
+            let entry = ruby.hash_new();
+            entry.aset(ruby.to_symbol("title"), outline.title.as_str())?;
+            entry.aset(ruby.to_symbol("page"), outline.page.map(|page| page + 1))?;
+            entry.aset(ruby.to_symbol("children"), Self::outline_entries(ruby, &outline.down)?)?;
+            entries.push(entry)?;
+        }
+        Ok(entries)
+    }
+
+    /// Extract highlight, note and free-text annotations - exposed to Ruby
+    ///
+    /// Returns `[{page:, type:, contents:, author:}]` with `type` one of
+    /// `highlight`, `note` or `free_text`; highlights also carry the page
+    /// `text` under them. Takes `pages:` like `parse_pdf`. Rotated pages are
+    /// read unrotated.
+    fn pdf_annotations(&self, args: &[Value]) -> Result<RArray, Error> {
+        use mupdf::pdf::PdfDocument;
+        use mupdf::TextPageOptions;
+
+        let ruby = Ruby::get().unwrap();
+        let (data, pages) = Self::pdf_args(args)?;
+        let pdf = PdfDocument::from_bytes(&data)
+            .map_err(|e| Self::runtime_error("Failed to parse PDF", e))?;
+        let page_count = pdf.page_count()
+            .map_err(|e| Self::runtime_error("Failed to get page count", e))?;
+
+        let result = ruby.ary_new();
+        for index in pages.indices(page_count as usize) {
+            let annotations = pdf
+                .find_page(index as i32)
+                .and_then(|page| Self::pdf_page_annotations(&page))
+                .map_err(|e| Self::runtime_error("Failed to read PDF annotations", e))?;
+            if annotations.is_empty() {
+                continue;
+            }
+
+            let text_page = if annotations.iter().any(|annotation| annotation.kind == "highlight") {
+                let text_page = pdf
+                    .load_page(index as i32)
+                    .and_then(|page| page.to_text_page(TextPageOptions::empty()))
+                    .map_err(|e| Self::runtime_error("Failed to extract positioned text", e))?;
+                Some(text_page)
+            } else {
+                None
+            };
+
+            for annotation in annotations {
+                let entry = ruby.hash_new();
+                entry.aset(ruby.to_symbol("page"), index + 1)?;
+                entry.aset(ruby.to_symbol("type"), annotation.kind)?;
+                entry.aset(ruby.to_symbol("contents"), annotation.contents)?;
+                entry.aset(ruby.to_symbol("author"), annotation.author)?;
+                if let Some(text_page) = text_page.as_ref().filter(|_| annotation.kind == "highlight") {
+                    entry.aset(ruby.to_symbol("text"), Self::pdf_text_in_boxes(text_page, &annotation.boxes))?;
+                }
+                result.push(entry)?;
+            }
+        }
+        Ok(result)
+    }
+
+    /// Read the highlight, note and free-text annotations of a page object,
+    /// with highlight areas converted to MuPDF's top-left page coordinates
+    fn pdf_page_annotations(page: &mupdf::pdf::PdfObject) -> Result<Vec<PdfAnnotation>, mupdf::Error> {
+        let Some(annots) = page.get_dict("Annots")? else {
+            return Ok(Vec::new());
+        };
+        let page_box = match Self::pdf_inherited(page, "CropBox", 0)? {
+            Some(page_box) => Some(page_box),
+            None => Self::pdf_inherited(page, "MediaBox", 0)?,
+        };
+        let (left, top) = match page_box {
+            Some(page_box) if page_box.len()? == 4 => {
+                let coordinate = |i: i32| -> Result<f32, mupdf::Error> {
+                    Ok(page_box.get_array(i)?.map(|value| value.as_float()).transpose()?.unwrap_or(0.0))
+                };
+                (coordinate(0)?.min(coordinate(2)?), coordinate(1)?.max(coordinate(3)?))
+            }
+            _ => (0.0, 0.0),
+        };
+
+        let mut annotations = Vec::new();
+        for i in 0..annots.len()? {
+            let Some(annot) = annots.get_array(i as i32)? else {
+                continue;
+            };
+            let kind = match annot.get_dict("Subtype")? {
+                Some(subtype) => match subtype.as_name()? {
+                    b"Highlight" => "highlight",
+                    b"Text" => "note",
+                    b"FreeText" => "free_text",
+                    _ => continue,
+                },
+                None => continue,
+            };
+            let text = |key: &str| -> Result<Option<String>, mupdf::Error> {
+                match annot.get_dict(key)? {
+                    Some(value) => Ok(Some(value.as_string()?.to_string())),
+                    None => Ok(None),
+                }
+            };
+
+            // QuadPoints holds four x/y corners per highlighted area
+            let mut boxes = Vec::new();
+            if let Some(quads) = annot.get_dict("QuadPoints")? {
+                let mut numbers = Vec::new();
+                for j in 0..quads.len()? {
+                    if let Some(number) = quads.get_array(j as i32)? {
+                        numbers.push(number.as_float()?);
+                    }
+                }
+                for quad in numbers.chunks_exact(8) {
+                    let xs = [quad[0], quad[2], quad[4], quad[6]];
+                    let ys = [quad[1], quad[3], quad[5], quad[7]];
+                    let min = |values: [f32; 4]| values.into_iter().fold(f32::INFINITY, f32::min);
+                    let max = |values: [f32; 4]| values.into_iter().fold(f32::NEG_INFINITY, f32::max);
+                    boxes.push((min(xs) - left, top - max(ys), max(xs) - left, top - min(ys)));
+                }
+            }
+
+            annotations.push(PdfAnnotation {
+                kind,
+                contents: text("Contents")?,
+                author: text("T")?,
+                boxes,
+            });
+        }
+        Ok(annotations)
+    }
+
+    /// Look up a page attribute, following /Parent for inherited values
+    fn pdf_inherited(
+        object: &mupdf::pdf::PdfObject,
+        key: &str,
+        depth: usize,
+    ) -> Result<Option<mupdf::pdf::PdfObject>, mupdf::Error> {
+        if let Some(value) = object.get_dict(key)? {
+            return Ok(Some(value));
+        }
+        match object.get_dict("Parent")? {
+            Some(parent) if depth < MAX_PDF_NESTING => Self::pdf_inherited(&parent, key, depth + 1),
+            _ => Ok(None),
+        }
+    }
+
+    /// Text of the selected pages of a tagged PDF in the logical order of its
+    /// structure tree, keyed by page index, when `pdf_structure_order` is set.
+    /// Content marked as an artifact (running headers, page numbers) is never
+    /// part of the tree and so is dropped. Any page missing from the result
+    /// is read in layout order: pages the tree doesn't reach, every page of
+    /// an untagged PDF, and pages the content-stream decoder gets no text
+    /// from. The decoder only knows ToUnicode CMaps and single-byte fonts read
+    /// as Latin-1, and doesn't follow Form XObjects, so opting in suits
+    /// well-tagged documents such as exported papers.
+    fn pdf_structure_text(&self, data: &[u8], indices: &[usize]) -> std::collections::HashMap<usize, String> {
+        if !self.config.pdf_structure_order {
+            return std::collections::HashMap::new();
+        }
+        let Ok(pdf) = self.open_pdf_with(data, mupdf::pdf::PdfDocument::from_bytes) else {
+            return std::collections::HashMap::new();
+        };
+        Self::pdf_structure_pages(&pdf, indices).unwrap_or_default()
+    }
+
+    /// Structure-order text of the given pages of an open PDF
+    fn pdf_structure_pages(
+        pdf: &mupdf::pdf::PdfDocument,
+        indices: &[usize],
+    ) -> Result<std::collections::HashMap<usize, String>, mupdf::Error> {
+        use std::collections::HashMap;
+
+        let catalog = pdf.catalog()?;
+        let Some(root) = catalog.get_dict("StructTreeRoot")? else {
+            return Ok(HashMap::new());
+        };
+        let mut items = Vec::new();
+        if let Some(kids) = root.get_dict("K")? {
+            let role_map = root.get_dict("RoleMap")?;
+            Self::collect_pdf_structure(&kids, None, role_map.as_ref(), 0, &mut items)?;
+        }
+
+        let mut marked: HashMap<i32, (usize, HashMap<i64, String>)> = HashMap::new();
+        for &index in indices {
+            let page = pdf.find_page(index as i32)?;
+            marked.insert(page.as_indirect()?, (index, Self::pdf_page_marked_content(&page)?));
+        }
+
+        let mut texts: HashMap<usize, String> = HashMap::new();
+        let mut last_page = None;
+        for item in items {
+            let (page, text) = match item {
+                PdfStructureItem::Content { page, mcid } => match marked.get(&page) {
+                    Some((index, content)) => (*index, content.get(&mcid).cloned().unwrap_or_default()),
+                    None => continue,
+                },
+                PdfStructureItem::Text { page, text } => match marked.get(&page) {
+                    Some((index, _)) => (*index, text),
+                    None => continue,
+                },
+                PdfStructureItem::Break => {
+                    if let Some(text) = last_page.and_then(|page| texts.get_mut(&page)) {
+                        let trimmed = text.trim_end_matches(' ').len();
+                        text.truncate(trimmed);
+                        if !text.is_empty() && !text.ends_with('\n') {
+                            text.push('\n');
+                        }
+                    }
+                    continue;
+                }
+            };
+            let page_text = texts.entry(page).or_default();
+            let text = text.trim();
+            if !text.is_empty() {
+                if !page_text.is_empty() && !page_text.ends_with(char::is_whitespace) {
+                    page_text.push(' ');
+                }
+                page_text.push_str(text);
+            }
+            last_page = Some(page);
+        }
+        texts.retain(|_, text| !text.trim().is_empty());
+        Ok(texts)
+    }
+
+    /// Walk a structure tree node depth-first, recording marked content in
+    /// logical order. `page` is the object number of the nearest /Pg.
+    fn collect_pdf_structure(
+        node: &mupdf::pdf::PdfObject,
+        page: Option<i32>,
+        role_map: Option<&mupdf::pdf::PdfObject>,
+        depth: usize,
+        items: &mut Vec<PdfStructureItem>,
+    ) -> Result<(), mupdf::Error> {
+        if depth > MAX_PDF_NESTING {
+            return Ok(());
+        }
+        if node.is_array()? {
+            for i in 0..node.len()? {
+                if let Some(kid) = node.get_array(i as i32)? {
+                    Self::collect_pdf_structure(&kid, page, role_map, depth, items)?;
+                }
+            }
+            return Ok(());
+        }
+        if node.is_int()? {
+            if let Some(page) = page {
+                items.push(PdfStructureItem::Content { page, mcid: node.as_int()? as i64 });
+            }
+            return Ok(());
+        }
+        if !node.is_dict()? {
+            return Ok(());
+        }
+
+        let page = match node.get_dict("Pg")? {
+            Some(pg) => Some(pg.as_indirect()?),
+            None => page,
+        };
+        // A marked-content reference; object references (/OBJR) carry no text
+        if let Some(mcid) = node.get_dict("MCID")? {
+            if let Some(page) = page {
+                items.push(PdfStructureItem::Content { page, mcid: mcid.as_int()? as i64 });
+            }
+            return Ok(());
+        }
+        let Some(kind) = node.get_dict("S")? else {
+            return Ok(());
+        };
+        let mut kind = String::from_utf8_lossy(kind.as_name()?).to_string();
+        if let Some(standard) = role_map.map(|map| map.get_dict(&kind)).transpose()?.flatten() {
+            kind = String::from_utf8_lossy(standard.as_name()?).to_string();
+        }
+        if kind == "Artifact" {
+            return Ok(());
+        }
+
+        match (node.get_dict("ActualText")?, page) {
+            (Some(text), Some(page)) => {
+                items.push(PdfStructureItem::Text { page, text: text.as_string()?.to_string() });
+            }
+            _ => {
+                if let Some(kids) = node.get_dict("K")? {
+                    Self::collect_pdf_structure(&kids, page, role_map, depth + 1, items)?;
+                }
+            }
+        }
+        if !PDF_INLINE_STRUCTURE.contains(&kind.as_str()) {
+            items.push(PdfStructureItem::Break);
+        }
+        Ok(())
+    }
+
+    /// The text of each marked-content sequence on a page, by MCID. Fonts and
+    /// property lists named in the content stream are looked up in the
+    /// page's resources.
+    fn pdf_page_marked_content(
+        page: &mupdf::pdf::PdfObject,
+    ) -> Result<std::collections::HashMap<i64, String>, mupdf::Error> {
+        use std::collections::HashMap;
+
+        let mut content = Vec::new();
+        if let Some(contents) = page.get_dict("Contents")? {
+            if contents.is_array()? {
+                for i in 0..contents.len()? {
+                    if let Some(stream) = contents.get_array(i as i32)? {
+                        content.extend(stream.read_stream()?);
+                        content.push(b'\n');
+                    }
+                }
+            } else {
+                content = contents.read_stream()?;
+            }
+        }
+        let tokens = Self::pdf_content_tokens(&content);
+
+        let mut fonts = HashMap::new();
+        let mut properties = HashMap::new();
+        if let Some(resources) = Self::pdf_inherited(page, "Resources", 0)? {
+            for (i, token) in tokens.iter().enumerate() {
+                match (token, i.checked_sub(2).map(|i| &tokens[i]), i.checked_sub(1).map(|i| &tokens[i])) {
+                    (PdfToken::Operator(op), Some(PdfToken::Name(name)), _) if op == "Tf" => {
+                        if fonts.contains_key(name) {
+                            continue;
+                        }
+                        let font = resources.get_dict("Font")?.map(|dict| dict.get_dict(name)).transpose()?.flatten();
+                        let Some(font) = font else {
+                            continue;
+                        };
+                        let decoder = match font.get_dict("ToUnicode")? {
+                            Some(cmap) if cmap.is_stream()? => Self::pdf_to_unicode(&cmap.read_stream()?),
+                            // Composite fonts without a CMap can't be decoded
+                            _ if font.get_dict("Subtype")?.is_some_and(|s| s.as_name().is_ok_and(|n| n == b"Type0")) => {
+                                PdfFontDecoder { code_bytes: 2, codes: HashMap::new() }
+                            }
+                            _ => PdfFontDecoder { code_bytes: 1, codes: Self::pdf_differences(&font)? },
+                        };
+                        fonts.insert(name.clone(), decoder);
+                    }
+                    (PdfToken::Operator(op), _, Some(PdfToken::Name(name))) if op == "BDC" => {
+                        let mcid = resources
+                            .get_dict("Properties")?
+                            .map(|list| list.get_dict(name))
+                            .transpose()?
+                            .flatten()
+                            .map(|list| list.get_dict("MCID"))
+                            .transpose()?
+                            .flatten();
+                        if let Some(mcid) = mcid {
+                            properties.insert(name.clone(), mcid.as_int()? as i64);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(Self::pdf_marked_content(&tokens, &fonts, &properties))
+    }
+
+    /// Tokenize a PDF content stream or CMap. Inline image data is skipped.
+    fn pdf_content_tokens(data: &[u8]) -> Vec<PdfToken> {
+        let is_delimiter = |b: u8| b"()<>[]{}/%".contains(&b);
+        let mut stack: Vec<Vec<PdfToken>> = vec![Vec::new()];
+        let mut i = 0;
+        while i < data.len() {
+            let b = data[i];
+            let token = match b {
+                b if b.is_ascii_whitespace() || b == 0 => {
+                    i += 1;
+                    continue;
+                }
+                b'%' => {
+                    while i < data.len() && data[i] != b'\n' && data[i] != b'\r' {
+                        i += 1;
+                    }
+                    continue;
+                }
+                b'[' => {
+                    stack.push(Vec::new());
+                    i += 1;
+                    continue;
+                }
+                b'<' if data.get(i + 1) == Some(&b'<') => {
+                    stack.push(Vec::new());
+                    i += 2;
+                    continue;
+                }
+                b']' | b'>' if stack.len() > 1 => {
+                    let items = stack.pop().unwrap_or_default();
+                    if b == b']' {
+                        i += 1;
+                        PdfToken::Array(items)
+                    } else {
+                        i += 2;
+                        let mut entries = Vec::new();
+                        let mut items = items.into_iter();
+                        while let (Some(PdfToken::Name(key)), Some(value)) = (items.next(), items.next()) {
+                            entries.push((key, value));
+                        }
+                        PdfToken::Dict(entries)
+                    }
+                }
+                b'(' => {
+                    let mut bytes = Vec::new();
+                    let mut depth = 0;
+                    i += 1;
+                    while i < data.len() {
+                        match data[i] {
+                            b'\\' => {
+                                i += 1;
+                                match data.get(i) {
+                                    Some(b'n') => bytes.push(b'\n'),
+                                    Some(b'r') => bytes.push(b'\r'),
+                                    Some(b't') => bytes.push(b'\t'),
+                                    Some(b'b') => bytes.push(8),
+                                    Some(b'f') => bytes.push(12),
+                                    Some(b'\r') if data.get(i + 1) == Some(&b'\n') => i += 1,
+                                    Some(b'\r') | Some(b'\n') => {}
+                                    Some(d) if d.is_ascii_digit() => {
+                                        let mut value = 0u32;
+                                        let mut digits = 0;
+                                        while digits < 3 && i < data.len() && (b'0'..=b'7').contains(&data[i]) {
+                                            value = value * 8 + (data[i] - b'0') as u32;
+                                            i += 1;
+                                            digits += 1;
+                                        }
+                                        bytes.push(value as u8);
+                                        continue;
+                                    }
+                                    Some(&c) => bytes.push(c),
+                                    None => {}
+                                }
+                            }
+                            b'(' => {
+                                depth += 1;
+                                bytes.push(b'(');
+                            }
+                            b')' if depth == 0 => break,
+                            b')' => {
+                                depth -= 1;
+                                bytes.push(b')');
+                            }
+                            c => bytes.push(c),
+                        }
+                        i += 1;
+                    }
+                    i += 1;
+                    PdfToken::String(bytes)
+                }
+                b'<' => {
+                    let end = data[i..].iter().position(|&c| c == b'>').map_or(data.len(), |p| i + p);
+                    let mut hex: Vec<u8> = data[i + 1..end].iter().copied().filter(u8::is_ascii_hexdigit).collect();
+                    if hex.len() % 2 == 1 {
+                        hex.push(b'0');
+                    }
+                    i = end + 1;
+                    let bytes = hex
+                        .chunks(2)
+                        .filter_map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+                        .collect();
+                    PdfToken::String(bytes)
+                }
+                b'/' => {
+                    let start = i + 1;
+                    i = start;
+                    while i < data.len() && !data[i].is_ascii_whitespace() && !is_delimiter(data[i]) {
+                        i += 1;
+                    }
+                    PdfToken::Name(String::from_utf8_lossy(&data[start..i]).to_string())
+                }
+                _ => {
+                    let start = i;
+                    i += 1;
+                    while i < data.len() && !data[i].is_ascii_whitespace() && !is_delimiter(data[i]) {
+                        i += 1;
+                    }
+                    let word = String::from_utf8_lossy(&data[start..i]).to_string();
+                    match word.parse::<f64>() {
+                        Ok(number) => PdfToken::Number(number),
+                        Err(_) => {
+                            if word == "ID" {
+                                // Inline image data runs to a whitespace-delimited EI
+                                i += 1;
+                                while i + 2 < data.len()
+                                    && !(data[i].is_ascii_whitespace()
+                                        && &data[i + 1..i + 3] == b"EI"
+                                        && data.get(i + 3).is_none_or(|c| c.is_ascii_whitespace()))
+                                {
+                                    i += 1;
+                                }
+                                i += 3;
+                                PdfToken::Operator("EI".to_string())
+                            } else {
+                                PdfToken::Operator(word)
+                            }
+                        }
+                    }
+                }
+            };
+            if let Some(current) = stack.last_mut() {
+                current.push(token);
+            }
+        }
+        while stack.len() > 1 {
+            let items = stack.pop().unwrap_or_default();
+            if let Some(parent) = stack.last_mut() {
+                parent.extend(items);
+            }
+        }
+        stack.pop().unwrap_or_default()
+    }
+
+    /// Codes a simple font's /Encoding /Differences array remaps. Glyphs
+    /// without a known Unicode name map to nothing rather than to the
+    /// Latin-1 character their code would otherwise stand for.
+    fn pdf_differences(font: &mupdf::pdf::PdfObject) -> Result<std::collections::HashMap<u32, String>, mupdf::Error> {
+        let mut codes = std::collections::HashMap::new();
+        let Some(differences) = font.get_dict("Encoding")?.map(|e| e.get_dict("Differences")).transpose()?.flatten() else {
+            return Ok(codes);
+        };
+        let mut code = 0u32;
+        for i in 0..differences.len()? {
+            let Some(item) = differences.get_array(i as i32)? else {
+                continue;
+            };
+            if item.is_int()? {
+                code = item.as_int()?.max(0) as u32;
+            } else if item.is_name()? {
+                let name = String::from_utf8_lossy(item.as_name()?).into_owned();
+                codes.insert(code, Self::pdf_glyph_text(&name).unwrap_or_default());
+                code = code.saturating_add(1);
+            }
+        }
+        Ok(codes)
+    }
+
+    /// Unicode text of a glyph name: single characters, `uniXXXX` names and
+    /// the common Adobe names for punctuation, digits and ligatures
+    fn pdf_glyph_text(name: &str) -> Option<String> {
+        let mut chars = name.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return Some(c.to_string());
+        }
+        if let Some(hex) = name.strip_prefix("uni").filter(|hex| hex.len() == 4) {
+            return u32::from_str_radix(hex, 16).ok().and_then(char::from_u32).map(String::from);
+        }
+        let digits = ["zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine"];
+        if let Some(digit) = digits.iter().position(|&d| d == name) {
+            return Some(digit.to_string());
+        }
+        let text = match name {
+            "space" | "nbspace" => " ",
+            "period" => ".",
+            "comma" => ",",
+            "colon" => ":",
+            "semicolon" => ";",
+            "hyphen" | "minus" => "-",
+            "endash" => "\u{2013}",
+            "emdash" => "\u{2014}",
+            "exclam" => "!",
+            "question" => "?",
+            "quoteright" => "\u{2019}",
+            "quoteleft" => "\u{2018}",
+            "quotedblleft" => "\u{201C}",
+            "quotedblright" => "\u{201D}",
+            "quotesingle" => "'",
+            "quotedbl" => "\"",
+            "parenleft" => "(",
+            "parenright" => ")",
+            "slash" => "/",
+            "ampersand" => "&",
+            "percent" => "%",
+            "bullet" => "\u{2022}",
+            "fi" => "fi",
+            "fl" => "fl",
+            "ff" => "ff",
+            "ffi" => "ffi",
+            "ffl" => "ffl",
+            _ => return None,
+        };
+        Some(text.to_string())
+    }
+
+    /// Read a ToUnicode CMap: its code width and bfchar/bfrange mappings
+    fn pdf_to_unicode(cmap: &[u8]) -> PdfFontDecoder {
+        let utf16 = |bytes: &[u8]| {
+            let units: Vec<u16> = bytes.chunks(2).map(|pair| u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)])).collect();
+            String::from_utf16_lossy(&units)
+        };
+        let code = |bytes: &[u8]| bytes.iter().fold(0u32, |code, &b| code << 8 | b as u32);
+
+        let tokens = Self::pdf_content_tokens(cmap);
+        let mut decoder = PdfFontDecoder { code_bytes: 1, codes: std::collections::HashMap::new() };
+        let mut section = "";
+        let mut operands: Vec<&PdfToken> = Vec::new();
+        for token in &tokens {
+            let PdfToken::Operator(op) = token else {
+                operands.push(token);
+                continue;
+            };
+            match op.as_str() {
+                "begincodespacerange" | "beginbfchar" | "beginbfrange" => section = op.as_str(),
+                "endcodespacerange" => {
+                    if let Some(PdfToken::String(low)) = operands.first() {
+                        decoder.code_bytes = low.len().max(1);
+                    }
+                }
+                "endbfchar" => {
+                    for pair in operands.chunks_exact(2) {
+                        if let (PdfToken::String(src), PdfToken::String(dst)) = (pair[0], pair[1]) {
+                            decoder.codes.insert(code(src), utf16(dst));
+                        }
+                    }
+                }
+                "endbfrange" => {
+                    for range in operands.chunks_exact(3) {
+                        let (PdfToken::String(low), PdfToken::String(high)) = (range[0], range[1]) else {
+                            continue;
+                        };
+                        let (low, high) = (code(low), code(high));
+                        for (offset, src) in (low..=high.min(low.saturating_add(0xFFFF))).enumerate() {
+                            let text = match range[2] {
+                                PdfToken::String(dst) if dst.len() >= 2 => {
+                                    let mut dst = dst.clone();
+                                    let last = dst.len() - 2;
+                                    let unit = u16::from_be_bytes([dst[last], dst[last + 1]]).wrapping_add(offset as u16);
+                                    dst[last..].copy_from_slice(&unit.to_be_bytes());
+                                    utf16(&dst)
+                                }
+                                PdfToken::Array(items) => match items.get(offset) {
+                                    Some(PdfToken::String(dst)) => utf16(dst),
+                                    _ => continue,
+                                },
+                                _ => continue,
+                            };
+                            decoder.codes.insert(src, text);
+                        }
+                    }
+                }
+                _ => {}
+            }
+            if !section.is_empty() && op.starts_with("end") {
+                section = "";
+            }
+            if section.is_empty() || op.starts_with("begin") {
+                operands.clear();
+            }
+        }
+        decoder
+    }
+
+    /// Collect the text of each marked-content sequence (by MCID) in a page's
+    /// content stream. `properties` resolves named BDC property lists to MCIDs.
+    fn pdf_marked_content(
+        tokens: &[PdfToken],
+        fonts: &std::collections::HashMap<String, PdfFontDecoder>,
+        properties: &std::collections::HashMap<String, i64>,
+    ) -> std::collections::HashMap<i64, String> {
+        let mut texts: std::collections::HashMap<i64, String> = std::collections::HashMap::new();
+        let mut marks: Vec<Option<i64>> = Vec::new();
+        let mut font: Option<&PdfFontDecoder> = None;
+        let mut new_line = false;
+        let mut operands: Vec<&PdfToken> = Vec::new();
+
+        for token in tokens {
+            let PdfToken::Operator(op) = token else {
+                operands.push(token);
+                continue;
+            };
+            let mut shown = Vec::new();
+            match op.as_str() {
+                "BDC" => {
+                    let mcid = match operands.get(1) {
+                        Some(PdfToken::Dict(entries)) => entries.iter().find_map(|(key, value)| match value {
+                            PdfToken::Number(mcid) if key == "MCID" => Some(*mcid as i64),
+                            _ => None,
+                        }),
+                        Some(PdfToken::Name(name)) => properties.get(name).copied(),
+                        _ => None,
+                    };
+                    marks.push(mcid);
+                }
+                "BMC" => marks.push(None),
+                "EMC" => {
+                    marks.pop();
+                }
+                "Tf" => {
+                    if let Some(PdfToken::Name(name)) = operands.first() {
+                        font = fonts.get(name);
+                    }
+                }
+                "Td" | "TD" => new_line |= matches!(operands.get(1), Some(PdfToken::Number(ty)) if *ty != 0.0),
+                "T*" | "Tm" => new_line = true,
+                "Tj" | "'" | "\"" => {
+                    new_line |= op != "Tj";
+                    if let Some(PdfToken::String(bytes)) = operands.last() {
+                        shown.push(PdfDecoded::Text(bytes));
+                    }
+                }
+                "TJ" => {
+                    if let Some(PdfToken::Array(items)) = operands.first() {
+                        for item in items {
+                            match item {
+                                PdfToken::String(bytes) => shown.push(PdfDecoded::Text(bytes)),
+                                // Kerning wider than a quarter em reads as a word gap
+                                PdfToken::Number(adjust) if *adjust < -250.0 => shown.push(PdfDecoded::Space),
+                                _ => {}
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+            operands.clear();
+
+            let Some(mcid) = marks.iter().rev().find_map(|mark| *mark) else {
+                continue;
+            };
+            for piece in shown {
+                let text = texts.entry(mcid).or_default();
+                if (new_line || matches!(piece, PdfDecoded::Space)) && !text.is_empty() && !text.ends_with(' ') {
+                    text.push(' ');
+                }
+                new_line = false;
+                if let PdfDecoded::Text(bytes) = piece {
+                    match font {
+                        Some(font) => text.push_str(&font.decode(bytes)),
+                        None => text.extend(bytes.iter().map(|&b| b as char)),
+                    }
+                }
+            }
+        }
+        texts
+    }
+
+    /// The text of a MuPDF text page whose characters sit inside any of
+    /// `boxes` (x0, y0, x1, y1), one space between lines
+    fn pdf_text_in_boxes(text_page: &mupdf::TextPage, boxes: &[(f32, f32, f32, f32)]) -> String {
+        let mut lines = Vec::new();
+        for block in text_page.blocks() {
+            for line in block.lines() {
+                let mut text = String::new();
+                for ch in line.chars() {
+                    let Some(c) = ch.char() else {
+                        continue;
+                    };
+                    let quad = ch.quad();
+                    let x = (quad.ul.x + quad.lr.x) / 2.0;
+                    let y = (quad.ul.y + quad.lr.y) / 2.0;
+                    if boxes.iter().any(|&(x0, y0, x1, y1)| x >= x0 && x <= x1 && y >= y0 && y <= y1) {
+                        text.push(c);
+                    }
+                }
+                let text = text.trim();
+                if !text.is_empty() {
+                    lines.push(text.to_string());
+                }
+            }
+        }
+        lines.join(" ")
+    }
+
+    /// List the files embedded in a PDF - exposed to Ruby
+    ///
+    /// Reads the /EmbeddedFiles name tree, where ZUGFeRD/Factur-X invoices
+    /// keep their XML. Returns `[{name:, description:, mime_type:, data:}]`
+    /// with `data` as a binary String; `parse: true` adds each file's parsed
+    /// `text`, detected by name and content like archive members.
+    fn pdf_attachments(&self, args: &[Value]) -> Result<RArray, Error> {
+        let ruby = Ruby::get().unwrap();
+        let args = scan_args::scan_args::<(Vec<u8>,), (), (), (), RHash, ()>(args)?;
+        let (data,) = args.required;
+        let kwargs = scan_args::get_kwargs::<_, (), (Option<bool>,), ()>(
+            args.keywords,
+            &[],
+            &["parse"],
+        )?;
+        let parse = kwargs.optional.0.unwrap_or(false);
+
+        let attachments = Self::collect_pdf_attachments(&data)
+            .map_err(|e| Self::runtime_error("Failed to read PDF attachments", e))?;
+
+        let result = ruby.ary_new();
+        let mut total = 0;
+        for attachment in attachments {
+            if attachment.data.len() > self.config.max_entry_size {
+                return Err(self.entry_size_error("PDF attachment"));
+            }
+            total += attachment.data.len();
+            if total > self.config.max_size {
+                return Err(self.decompressed_size_error("PDF attachment"));
+            }
+
+            let entry = ruby.hash_new();
+            entry.aset(ruby.to_symbol("name"), attachment.name.as_str())?;
+            entry.aset(ruby.to_symbol("description"), attachment.description)?;
+            entry.aset(ruby.to_symbol("mime_type"), attachment.mime_type)?;
+            entry.aset(ruby.to_symbol("data"), ruby.str_from_slice(&attachment.data))?;
+            if parse {
+                let text = self.parse_embedded_file(&attachment.name, attachment.data)?;
+                entry.aset(ruby.to_symbol("text"), text)?;
+            }
+            result.push(entry)?;
+        }
+        Ok(result)
+    }
+
+    /// Read the filled-in fields of an XFA form - exposed to Ruby
+    ///
+    /// XFA forms keep their data as XML in the AcroForm dictionary, where
+    /// MuPDF's page text doesn't reach. Returns `[{name:, label:, value:}]`:
+    /// `name` is the data path (`form1.Applicant.Name`) and `label` the
+    /// field's caption in the form template, or nil. An empty Array means
+    /// the PDF has no XFA form.
+    fn pdf_xfa(&self, data: Vec<u8>) -> Result<RArray, Error> {
+        let ruby = Ruby::get().unwrap();
+        let result = ruby.ary_new();
+        for field in self.pdf_xfa_fields(&data)? {
+            let entry = ruby.hash_new();
+            entry.aset(ruby.to_symbol("name"), field.name)?;
+            entry.aset(ruby.to_symbol("label"), field.label)?;
+            entry.aset(ruby.to_symbol("value"), field.value)?;
+            result.push(entry)?;
+        }
+        Ok(result)
+    }
+
+    /// The fields of a PDF's XFA form, if it has one
+    fn pdf_xfa_fields(&self, data: &[u8]) -> Result<Vec<PdfXfaField>, Error> {
+        let packets = Self::collect_pdf_xfa(data)
+            .map_err(|e| Self::runtime_error("Failed to read PDF XFA data", e))?;
+        let Some((datasets, template)) = packets else {
+            return Ok(Vec::new());
+        };
+        Self::xfa_fields(&datasets, template.as_deref())
+            .map_err(|e| Self::runtime_error("Failed to parse XFA data", e))
+    }
+
+    /// Read the datasets and template packets of a PDF's XFA form. /XFA is
+    /// either a single XDP stream holding every packet, or an Array of
+    /// packet names and streams.
+    fn collect_pdf_xfa(data: &[u8]) -> Result<Option<(String, Option<String>)>, mupdf::Error> {
+        use mupdf::pdf::PdfDocument;
+
+        let pdf = PdfDocument::from_bytes(data)?;
+        let catalog = pdf.catalog()?;
+        let Some(xfa) = catalog
+            .get_dict("AcroForm")?
+            .map(|form| form.get_dict("XFA"))
+            .transpose()?
+            .flatten()
+        else {
+            return Ok(None);
+        };
+
+        if xfa.is_stream()? {
+            let xdp = String::from_utf8_lossy(&xfa.read_stream()?).to_string();
+            return Ok(Some((xdp.clone(), Some(xdp))));
+        }
+        if !xfa.is_array()? {
+            return Ok(None);
+        }
+
+        let mut datasets = None;
+        let mut template = None;
+        let len = xfa.len()?;
+        for i in (0..len.saturating_sub(1)).step_by(2) {
+            let (Some(name), Some(stream)) = (xfa.get_array(i as i32)?, xfa.get_array(i as i32 + 1)?) else {
+                continue;
+            };
+            let packet = match name.as_string()? {
+                "datasets" => &mut datasets,
+                "template" => &mut template,
+                _ => continue,
+            };
+            *packet = Some(String::from_utf8_lossy(&stream.read_stream()?).to_string());
+        }
+        Ok(datasets.map(|datasets| (datasets, template)))
+    }
+
+    /// Pair the values in an XFA datasets packet with the captions of their
+    /// fields in the template. Fields are named by their path of element
+    /// names below `<xfa:data>` (e.g. `form1.Applicant.Name`), which is how
+    /// XFA binds data to template fields; empty values are left out.
+    fn xfa_fields(datasets: &str, template: Option<&str>) -> Result<Vec<PdfXfaField>, String> {
+        use quick_xml::events::Event;
+        use quick_xml::Reader;
+
+        let mut captions = std::collections::HashMap::new();
+        if let Some(template) = template {
+            let mut reader = Reader::from_str(template);
+            // The name of each open element, if it's a named container or field
+            let mut names: Vec<Option<String>> = Vec::new();
+            // Captions read so far, with the depth of the field they belong to
+            let mut pending: Vec<(usize, String)> = Vec::new();
+            let mut caption_depth = 0;
+            loop {
+                match reader.read_event().map_err(|e| e.to_string())? {
+                    Event::Start(ref e) => {
+                        let local = e.local_name();
+                        let name = match local.as_ref() {
+                            b"subform" | b"field" | b"exclGroup" => e
+                                .attributes()
+                                .flatten()
+                                .find(|a| a.key.as_ref() == b"name")
+                                .and_then(|a| a.unescape_value().ok().map(|v| v.to_string())),
+                            _ => None,
+                        };
+                        if local.as_ref() == b"caption" && caption_depth == 0 {
+                            caption_depth = names.len() + 1;
+                            pending.push((names.len(), String::new()));
+                        }
+                        names.push(name);
+                    }
+                    Event::Text(ref e) if caption_depth > 0 => {
+                        if let Some((_, caption)) = pending.last_mut() {
+                            caption.push_str(&e.decode().unwrap_or_default());
+                        }
+                    }
+                    Event::GeneralRef(ref e) if caption_depth > 0 => {
+                        if let Some((_, caption)) = pending.last_mut() {
+                            caption.push_str(&Self::resolve_xml_reference(e));
+                        }
+                    }
+                    Event::End(ref e) => {
+                        if names.len() == caption_depth {
+                            caption_depth = 0;
+                        }
+                        let caption = match pending.last() {
+                            Some((owner, _)) if *owner == names.len() => pending.pop().map(|(_, caption)| caption),
+                            _ => None,
+                        };
+                        let is_field = matches!(e.local_name().as_ref(), b"field" | b"exclGroup");
+                        if let (true, Some(Some(_)), Some(caption)) = (is_field, names.last(), caption) {
+                            let path: Vec<&str> = names.iter().flatten().map(String::as_str).collect();
+                            let label = caption.split_whitespace().collect::<Vec<_>>().join(" ");
+                            if !label.is_empty() {
+                                captions.insert(path.join("."), label);
+                            }
+                        }
+                        names.pop();
+                    }
+                    Event::Eof => break,
+                    _ => {}
+                }
+            }
+        }
+
+        let mut reader = Reader::from_str(datasets);
+        let mut fields = Vec::new();
+        // Element names from <xfa:data> down, and whether each has child elements
+        let mut path: Vec<(String, bool)> = Vec::new();
+        let mut in_data = false;
+        // Elements enclosing <xfa:data>; a full XDP also has a <data> in its config
+        let mut outer: Vec<Vec<u8>> = Vec::new();
+        let mut value = String::new();
+        loop {
+            match reader.read_event().map_err(|e| e.to_string())? {
+                Event::Start(ref e) if !in_data => {
+                    let local = e.local_name().as_ref().to_vec();
+                    in_data = local == b"data" && outer.last().is_some_and(|parent| parent == b"datasets");
+                    if !in_data {
+                        outer.push(local);
+                    }
+                }
+                Event::End(_) if !in_data => {
+                    outer.pop();
+                }
+                Event::Start(ref e) => {
+                    if let Some(parent) = path.last_mut() {
+                        parent.1 = true;
+                    }
+                    path.push((String::from_utf8_lossy(e.local_name().as_ref()).to_string(), false));
+                    value.clear();
+                }
+                Event::Text(ref e) if in_data => value.push_str(&e.decode().unwrap_or_default()),
+                Event::CData(ref e) if in_data => value.push_str(&String::from_utf8_lossy(e)),
+                Event::GeneralRef(ref e) if in_data => value.push_str(&Self::resolve_xml_reference(e)),
+                Event::End(_) if in_data => {
+                    let name = path.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>().join(".");
+                    match path.pop() {
+                        Some((_, false)) if !value.trim().is_empty() => {
+                            fields.push(PdfXfaField {
+                                label: captions.get(&name).cloned(),
+                                name,
+                                value: value.trim().to_string(),
+                            });
+                        }
+                        Some(_) => {}
+                        None => in_data = false,
+                    }
+                    value.clear();
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        Ok(fields)
+    }
+
+    /// Read the file specifications of a PDF's /EmbeddedFiles name tree
+    fn collect_pdf_attachments(data: &[u8]) -> Result<Vec<PdfAttachment>, mupdf::Error> {
+        use mupdf::pdf::PdfDocument;
+
+        let pdf = PdfDocument::from_bytes(data)?;
+        let catalog = pdf.catalog()?;
+        let Some(tree) = catalog
+            .get_dict("Names")?
+            .map(|names| names.get_dict("EmbeddedFiles"))
+            .transpose()?
+            .flatten()
+        else {
+            return Ok(Vec::new());
+        };
+
+        let mut attachments = Vec::new();
+        for (key, spec) in Self::pdf_name_tree(&tree)? {
+            let Some(files) = spec.get_dict("EF")? else {
+                continue;
+            };
+            let Some(file) = files.get_dict("UF")?.or(files.get_dict("F")?) else {
+                continue;
+            };
+
+            let name = match spec.get_dict("UF")?.or(spec.get_dict("F")?) {
+                Some(name) => name.as_string()?.to_string(),
+                None => key,
+            };
+            let description = match spec.get_dict("Desc")? {
+                Some(description) => Some(description.as_string()?.to_string()),
+                None => None,
+            };
+            // The stream's /Subtype is the MIME type, e.g. /text#2Fxml for text/xml
+            let mime_type = match file.get_dict("Subtype")? {
+                Some(subtype) => Some(String::from_utf8_lossy(subtype.as_name()?).to_string()),
+                None => None,
+            };
+            attachments.push(PdfAttachment {
+                name,
+                description,
+                mime_type,
+                data: file.read_stream()?,
+            });
+        }
+        Ok(attachments)
+    }
+
+    /// Walk every place a PDF can attach an action that runs on its own
+    fn collect_pdf_actions(data: &[u8]) -> Result<Vec<PdfAction>, mupdf::Error> {
+        use mupdf::pdf::PdfDocument;
+
+        let pdf = PdfDocument::from_bytes(data)?;
+        let catalog = pdf.catalog()?;
+        let mut actions = Vec::new();
+
+        if let Some(action) = catalog.get_dict("OpenAction")? {
+            Self::collect_pdf_action(&action, "OpenAction", true, 0, &mut actions)?;
+        }
+        Self::collect_pdf_additional_actions(&catalog, "Document", &mut actions)?;
+
+        if let Some(names) = catalog.get_dict("Names")? {
+            if let Some(tree) = names.get_dict("JavaScript")? {
+                for (name, action) in Self::pdf_name_tree(&tree)? {
+                    Self::collect_pdf_action(&action, &format!("Names /JavaScript {}", name), false, 0, &mut actions)?;
+                }
+            }
+        }
+
+        for index in 0..pdf.page_count()? {
+            let page = pdf.find_page(index)?;
+            let owner = format!("Page {}", index + 1);
+            Self::collect_pdf_additional_actions(&page, &owner, &mut actions)?;
+
+            if let Some(annots) = page.get_dict("Annots")? {
+                for i in 0..annots.len()? {
+                    if let Some(annot) = annots.get_array(i as i32)? {
+                        let owner = format!("{} annotation", owner);
+                        if let Some(action) = annot.get_dict("A")? {
+                            Self::collect_pdf_action(&action, &owner, false, 0, &mut actions)?;
+                        }
+                        Self::collect_pdf_additional_actions(&annot, &owner, &mut actions)?;
+                    }
+                }
+            }
+        }
+
+        if let Some(form) = catalog.get_dict("AcroForm")? {
+            if let Some(fields) = form.get_dict("Fields")? {
+                Self::collect_pdf_field_actions(&fields, 0, &mut actions)?;
+            }
+        }
+
+        Ok(actions)
+    }
+
+    /// Record an action dictionary and the actions chained after it via /Next.
+    /// Non-JavaScript actions are only kept when `keep_all` is set.
+    fn collect_pdf_action(
+        action: &mupdf::pdf::PdfObject,
+        trigger: &str,
+        keep_all: bool,
+        depth: usize,
+        actions: &mut Vec<PdfAction>,
+    ) -> Result<(), mupdf::Error> {
+        if depth > MAX_PDF_NESTING || !action.is_dict()? {
+            return Ok(());
+        }
+
+        let kind = match action.get_dict("S")? {
+            Some(kind) => String::from_utf8_lossy(kind.as_name()?).to_string(),
+            None => String::new(),
+        };
+        let script = if kind == "JavaScript" {
+            match action.get_dict("JS")? {
+                Some(js) if js.is_stream()? => Some(String::from_utf8_lossy(&js.read_stream()?).to_string()),
+                Some(js) => Some(js.as_string()?.to_string()),
+                None => Some(String::new()),
+            }
+        } else {
+            None
+        };
+        if keep_all || script.is_some() {
+            actions.push(PdfAction {
+                trigger: trigger.to_string(),
+                kind,
+                script,
+            });
+        }
+
+        if let Some(next) = action.get_dict("Next")? {
+            if next.is_array()? {
+                for i in 0..next.len()? {
+                    if let Some(next) = next.get_array(i as i32)? {
+                        Self::collect_pdf_action(&next, trigger, keep_all, depth + 1, actions)?;
+                    }
+                }
+            } else {
+                Self::collect_pdf_action(&next, trigger, keep_all, depth + 1, actions)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Record the JavaScript in an object's /AA (additional actions) dictionary
+    fn collect_pdf_additional_actions(
+        object: &mupdf::pdf::PdfObject,
+        owner: &str,
+        actions: &mut Vec<PdfAction>,
+    ) -> Result<(), mupdf::Error> {
+        const TRIGGERS: [&str; 20] = [
+            "O", "C", "WC", "WS", "DS", "WP", "DP", "K", "F", "V", "Fo", "Bl", "D", "U", "E", "X",
+            "PO", "PC", "PV", "PI",
+        ];
+
+        if let Some(additional) = object.get_dict("AA")? {
+            for trigger in TRIGGERS {
+                if let Some(action) = additional.get_dict(trigger)? {
+                    let label = format!("{} /AA /{}", owner, trigger);
+                    Self::collect_pdf_action(&action, &label, false, 0, actions)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Record the actions of AcroForm fields and their /Kids
+    fn collect_pdf_field_actions(
+        fields: &mupdf::pdf::PdfObject,
+        depth: usize,
+        actions: &mut Vec<PdfAction>,
+    ) -> Result<(), mupdf::Error> {
+        if depth > MAX_PDF_NESTING || !fields.is_array()? {
+            return Ok(());
+        }
+
+        for i in 0..fields.len()? {
+            let Some(field) = fields.get_array(i as i32)? else {
+                continue;
+            };
+            let owner = match field.get_dict("T")? {
+                Some(name) => format!("Field {}", name.as_string()?),
+                None => "Field".to_string(),
+            };
+            if let Some(action) = field.get_dict("A")? {
+                Self::collect_pdf_action(&action, &owner, false, 0, actions)?;
+            }
+            Self::collect_pdf_additional_actions(&field, &owner, actions)?;
+            if let Some(kids) = field.get_dict("Kids")? {
+                Self::collect_pdf_field_actions(&kids, depth + 1, actions)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Flatten a PDF name tree into (name, value) pairs in key order
+    fn pdf_name_tree(
+        root: &mupdf::pdf::PdfObject,
+    ) -> Result<Vec<(String, mupdf::pdf::PdfObject)>, mupdf::Error> {
+        fn walk(
+            node: &mupdf::pdf::PdfObject,
+            depth: usize,
+            entries: &mut Vec<(String, mupdf::pdf::PdfObject)>,
+        ) -> Result<(), mupdf::Error> {
+            if depth > MAX_PDF_NESTING {
+                return Ok(());
+            }
+            if let Some(names) = node.get_dict("Names")? {
+                let len = names.len()?;
+                for i in (0..len.saturating_sub(1)).step_by(2) {
+                    if let (Some(key), Some(value)) = (names.get_array(i as i32)?, names.get_array(i as i32 + 1)?) {
+                        entries.push((key.as_string()?.to_string(), value));
+                    }
+                }
+            }
+            if let Some(kids) = node.get_dict("Kids")? {
+                for i in 0..kids.len()? {
+                    if let Some(kid) = kids.get_array(i as i32)? {
+                        walk(&kid, depth + 1, entries)?;
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        let mut entries = Vec::new();
+        walk(root, 0, &mut entries)?;
+        Ok(entries)
+    }
+
+    /// Parse DjVu documents - exposed to Ruby
+    ///
+    /// Uncompressed text layers (TXTa) are read directly. Compressed layers
+    /// (TXTz) and pages without any text layer are handled by the djvulibre
+    /// command line tools: `djvutxt` for text, then `ddjvu` rendering + OCR.
+    fn parse_djvu(&self, data: Vec<u8>) -> Result<String, Error> {
+        if !data.starts_with(b"AT&TFORM") {
+            return Err(Self::runtime_error("Failed to parse DjVu file", "missing AT&T FORM header"));
+        }
+
+        let mut pages = Vec::new();
+        Self::collect_djvu_pages(&data[4..], &mut pages, true);
+
+        if !pages.is_empty() && pages.iter().all(|page| page.is_some()) {
+            let text: Vec<String> = pages.into_iter().flatten().collect();
+            return Ok(text.join("\n\n").trim().to_string());
+        }
+
+        self.parse_djvu_with_djvulibre(&data, pages.len().max(1))
+    }
+
+    /// Walk IFF chunks, recording each page's TXTa text (None if the page has
+    /// no text layer or only a compressed one). A DJVM bundle is only opened
+    /// at the top level, where real documents put it; nested ones are skipped.
+    fn collect_djvu_pages(mut data: &[u8], pages: &mut Vec<Option<String>>, top_level: bool) {
+        while data.len() >= 8 {
+            let id = &data[0..4];
+            let size = u32::from_be_bytes([data[4], data[5], data[6], data[7]]) as usize;
+            let body = &data[8..data.len().min(8 + size)];
+
+            if id == b"FORM" && body.len() >= 4 {
+                match &body[0..4] {
+                    b"DJVU" => {
+                        let mut page_text = None;
+                        Self::find_djvu_text(&body[4..], &mut page_text);
+                        pages.push(page_text);
+                    }
+                    b"DJVM" if top_level => Self::collect_djvu_pages(&body[4..], pages, false),
+                    _ => {}
+                }
+            }
+
+            // Chunks are padded to an even length
+            let next = 8 + size + (size & 1);
+            if next > data.len() {
+                break;
+            }
+            data = &data[next..];
+        }
+    }
+
+    /// Find the TXTa chunk of a single page
+    fn find_djvu_text(mut data: &[u8], text: &mut Option<String>) {
+        while data.len() >= 8 {
+            let size = u32::from_be_bytes([data[4], data[5], data[6], data[7]]) as usize;
+            let body = &data[8..data.len().min(8 + size)];
+
+            // TXTa starts with a 24-bit text length followed by UTF-8 text
+            if &data[0..4] == b"TXTa" && body.len() >= 3 {
+                let len = ((body[0] as usize) << 16) | ((body[1] as usize) << 8) | body[2] as usize;
+                let end = body.len().min(3 + len);
+                *text = Some(String::from_utf8_lossy(&body[3..end]).trim().to_string());
+            }
+
+            let next = 8 + size + (size & 1);
+            if next > data.len() {
+                break;
+            }
+            data = &data[next..];
+        }
+    }
+
+    /// Extract DjVu text with djvulibre, falling back to OCR of rendered pages
+    fn parse_djvu_with_djvulibre(&self, data: &[u8], page_count: usize) -> Result<String, Error> {
+        use std::process::Command;
+
+        Self::with_temp_file(data, "djvu", |path| {
+            let output = Command::new("djvutxt").arg(path).output().map_err(|e| {
+                Self::runtime_error("Failed to run djvutxt (is djvulibre installed?)", e)
+            })?;
+            let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            if output.status.success() && !text.is_empty() {
+                return Ok(text);
+            }
+
+            // No text layer at all: render each page and OCR it
+            let mut pages = Vec::new();
+            for page in 1..=page_count {
+                let output = Command::new("ddjvu")
+                    .args(["-format=pnm", &format!("-page={}", page)])
+                    .arg(path)
+                    .output()
+                    .map_err(|e| Self::runtime_error("Failed to run ddjvu (is djvulibre installed?)", e))?;
+                if !output.status.success() {
+                    return Err(Self::runtime_error(
+                        "Failed to render DjVu page",
+                        String::from_utf8_lossy(&output.stderr).trim(),
+                    ));
+                }
+                let text = self.ocr_text(&output.stdout)?;
+                if !text.is_empty() {
+                    pages.push(text);
+                }
+            }
+            Ok(pages.join("\n\n"))
+        })
+    }
+
+    /// Parse PostScript and EPS files via Ghostscript's text device - exposed to Ruby
+    fn parse_postscript(&self, data: Vec<u8>) -> Result<String, Error> {
+        use std::process::Command;
+
+        Self::with_temp_file(&data, "ps", |path| {
+            let output = Command::new("gs")
+                .args(["-q", "-dNOPAUSE", "-dBATCH", "-dSAFER", "-sDEVICE=txtwrite", "-sOutputFile=-"])
+                .arg(path)
+                .output()
+                .map_err(|e| Self::runtime_error("Failed to run Ghostscript (is gs installed?)", e))?;
+
+            if !output.status.success() {
+                return Err(Self::runtime_error(
+                    "Failed to parse PostScript file",
+                    String::from_utf8_lossy(&output.stderr).trim(),
+                ));
+            }
+
+            Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        })
+    }
+
+    /// Write data to a uniquely named temporary file for external tools,
+    /// removing it once `f` returns
+    fn with_temp_file<T>(
+        data: &[u8],
+        extension: &str,
+        f: impl FnOnce(&std::path::Path) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        use std::io::Write;
+
+        // A random name opened with O_EXCL and mode 0600, removed on drop
+        let mut file = tempfile::Builder::new()
+            .prefix("parsekit-")
+            .suffix(&format!(".{}", extension))
+            .tempfile()
+            .map_err(|e| Self::io_error("Failed to create temporary file", e))?;
+        file.write_all(data)
+            .and_then(|_| file.flush())
+            .map_err(|e| Self::io_error("Failed to write temporary file", e))?;
+
+        f(file.path())
+    }
+
+    /// Parse DOCX (Word) files - exposed to Ruby
+    ///
+    /// Reads `word/document.xml` in one pass of XML events (see `read_docx_lines`)
+    /// rather than building a document model, so large files stay cheap.
+    /// List items keep their bullet or number, indented by level, and table
+    /// rows become a line of tab-separated cells. Equations, as LaTeX-ish
+    /// `$...$` lines, and text boxes follow the paragraph they are anchored
+    /// in. Tracked changes are resolved by the `revisions` option, by default
+    /// reading as if all of them were accepted. With `docx_comments`,
+    /// reviewer comments follow the paragraph they are anchored in, with
+    /// `docx_links` hyperlinks are followed by their target, and with
+    /// `docx_headings` headings start with a Markdown `#` per level.
+    fn parse_docx(&self, data: Vec<u8>) -> Result<String, Error> {
+        let mut result = String::new();
+        for (heading, line) in self.read_docx_lines(&data)? {
+            if let Some(level) = heading.filter(|_| self.config.docx_headings) {
+                result.push_str(&"#".repeat(level));
+                result.push(' ');
+            }
+            result.push_str(&line);
+            result.push('\n');
+        }
+        Ok(result.trim().to_string())
+    }
+
+    /// Read the lines of a DOCX as `parse_docx` writes them, each with its
+    /// heading level. Equations, text boxes, pictures and comments have none.
+    /// The body, equations, text boxes, picture references and comment
+    /// anchors are all read in one pass over `word/document.xml`.
+    fn read_docx_lines(&self, data: &[u8]) -> Result<Vec<(Option<usize>, String)>, Error> {
+        self.check_zip_limits(data, "DOCX")?;
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(data))
+            .map_err(|e| Self::runtime_error("Failed to open DOCX as ZIP", e))?;
+        let mut budget = self.config.max_size as u64;
+        let part = "word/document.xml";
+        let Some(document) = self.read_zip_part(&mut archive, part, &mut budget, "DOCX")? else {
+            return Err(Self::runtime_error("Failed to parse DOCX file", "no word/document.xml part"));
+        };
+        let document = String::from_utf8_lossy(&document);
+
+        let mut comments = if self.config.docx_comments {
+            self.read_docx_comment_part(&mut archive, &mut budget)?
+        } else {
+            Vec::new()
+        };
+        let mut equations = DocxEquationReader::default();
+        let mut text_boxes = DocxTextBoxReader::default();
+        let mut image_refs = OoxmlImageRefReader::default();
+        let mut anchors = DocxCommentAnchorReader::new(&mut comments);
+        let body = self.read_docx_body(
+            &mut archive,
+            &document,
+            &mut budget,
+            &mut [&mut equations, &mut text_boxes, &mut image_refs, &mut anchors],
+        )?;
+        let (equations, text_boxes) = (equations.equations, text_boxes.boxes);
+        let images = if self.config.ocr_embedded_images {
+            self.ocr_ooxml_image_refs(&mut archive, part, image_refs.refs, &mut budget, "DOCX")?
+        } else {
+            Vec::new()
+        };
+
+        let mut lines = Vec::new();
+        for line in body.lines {
+            let paragraph = line.paragraph;
+            lines.push((line.heading, line.text));
+            let Some(paragraph) = paragraph else {
+                continue;
+            };
+            // Equations, text boxes and pictures follow the text of the paragraph they sit in
+            for (_, text) in equations.iter().chain(&text_boxes).chain(&images).filter(|(at, _)| *at == Some(paragraph)) {
+                lines.push((None, text.clone()));
+            }
+            // So do comments whose range ends in it
+            for comment in comments.iter().filter(|comment| comment.paragraph == Some(paragraph)) {
+                lines.push((None, Self::docx_comment_line(comment)));
+            }
+        }
+
+        // Equations, text boxes and pictures outside body paragraphs, e.g. in tables, go last
+        let unplaced = |at: &Option<usize>| at.is_none_or(|at| at >= body.paragraphs);
+        for (_, text) in equations.iter().chain(&text_boxes).chain(&images).filter(|(at, _)| unplaced(at)) {
+            lines.push((None, text.clone()));
+        }
+        for comment in comments.iter().filter(|comment| unplaced(&comment.paragraph)) {
+            lines.push((None, Self::docx_comment_line(comment)));
+        }
+        Ok(lines)
+    }
+
+    /// Read the text of a DOCX body (see `DocxBodyReader`) with the styles,
+    /// numbering and hyperlink targets it refers to. `visitors` read what
+    /// else they need from `document` in the same pass.
+    fn read_docx_body<R: std::io::Read + std::io::Seek>(
+        &self,
+        archive: &mut zip::ZipArchive<R>,
+        document: &str,
+        budget: &mut u64,
+        visitors: &mut [&mut dyn OoxmlVisitor],
+    ) -> Result<DocxBody, Error> {
+        let part = "word/document.xml";
+        let rels = self
+            .read_zip_part(archive, &Self::ooxml_rels_path(part), budget, "DOCX")?
+            .map(|rels| String::from_utf8_lossy(&rels).into_owned())
+            .unwrap_or_default();
+        let relationships = Self::ooxml_relationships(&rels, part);
+        let mut read_related = |kind: &str, fallback: &str| -> Result<String, Error> {
+            let name = relationships
+                .iter()
+                .find(|(_, rel_kind, _)| rel_kind.ends_with(kind))
+                .map_or(fallback, |(_, _, target)| target.as_str());
+            let xml = self.read_zip_part(archive, name, budget, "DOCX")?;
+            Ok(xml.map(|xml| String::from_utf8_lossy(&xml).into_owned()).unwrap_or_default())
+        };
+        let styles = Self::docx_styles(&read_related("/styles", "word/styles.xml")?);
+        let numbering = read_related("/numbering", "word/numbering.xml")?;
+        let links = Self::ooxml_external_targets(&rels);
+        let mut body = DocxBodyReader::new(self, &styles, DocxLists::new(&numbering, &styles), &links);
+        let mut all: Vec<&mut dyn OoxmlVisitor> = vec![&mut body];
+        all.extend(visitors.iter_mut().map(|visitor| &mut **visitor));
+        Self::walk_ooxml_part(document, &mut all);
+        Ok(body.body)
+    }
+
+
+    /// Read a `w:pPr` child that `DocxParagraphProperties` keeps
+    fn read_docx_paragraph_property(properties: &mut DocxParagraphProperties, e: &quick_xml::events::BytesStart) {
+        let val = Self::xml_attribute(e, b"val").map(|v| v.trim().to_string());
+        match e.name().local_name().as_ref() {
+            b"pStyle" => properties.style = val,
+            b"numPr" => {
+                properties.numbering.get_or_insert_default();
+            }
+            b"numId" => properties.numbering.get_or_insert_default().0 = val.and_then(|v| v.parse().ok()),
+            b"ilvl" => properties.numbering.get_or_insert_default().1 = val.and_then(|v| v.parse().ok()),
+            b"outlineLvl" => properties.outline_level = val.and_then(|v| v.parse().ok()),
+            _ => {}
+        }
+    }
+
+    /// Read the paragraph styles of a DOCX `word/styles.xml` part, by Id
+    fn docx_styles(xml: &str) -> std::collections::HashMap<String, DocxStyle> {
+        use quick_xml::events::Event;
+        use quick_xml::Reader;
+
+        let mut reader = Reader::from_str(xml);
+        let mut styles = std::collections::HashMap::new();
+        let mut style: Option<(String, DocxStyle)> = None;
+        let mut in_properties = false;
+        loop {
+            match reader.read_event() {
+                Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) => {
+                    let name = e.name();
+                    if name.local_name().as_ref() == b"style" {
+                        style = Self::xml_attribute(e, b"styleId").map(|id| (id, DocxStyle::default()));
+                        in_properties = false;
+                    } else if let Some((_, current)) = style.as_mut() {
+                        match name.local_name().as_ref() {
+                            b"name" => current.name = Self::xml_attribute(e, b"val").unwrap_or_default(),
+                            b"basedOn" => current.based_on = Self::xml_attribute(e, b"val"),
+                            b"pPr" => in_properties = true,
+                            _ if in_properties => Self::read_docx_paragraph_property(&mut current.properties, e),
+                            _ => {}
+                        }
+                    }
+                }
+                Ok(Event::End(ref e)) => match e.name().local_name().as_ref() {
+                    b"pPr" => in_properties = false,
+                    b"style" => styles.extend(style.take()),
+                    _ => {}
+                },
+                Ok(Event::Eof) | Err(_) => break,
+                _ => {}
+            }
+        }
+        styles
+    }
+
+    /// The heading level, 1 to 6, of a DOCX paragraph: its outline level,
+    /// or that of a "heading N" style, following `w:basedOn` to the styles
+    /// a custom heading style inherits from
+    fn docx_heading_level(
+        styles: &std::collections::HashMap<String, DocxStyle>,
+        properties: &DocxParagraphProperties,
+    ) -> Option<usize> {
+        // Outline level 9 is body text
+        let heading = |level: usize| (1..=6).contains(&level).then_some(level);
+
+        if let Some(level) = properties.outline_level {
+            return heading(level + 1);
+        }
+        let mut id = properties.style.as_ref()?;
+        // Bounded, in case a broken file has styles based on each other
+        for _ in 0..16 {
+            let style = styles.get(id)?;
+            let named = style
+                .name
+                .to_lowercase()
+                .strip_prefix("heading ")
+                .and_then(|number| number.trim().parse().ok());
+            if let Some(level) = named.or(style.properties.outline_level.map(|level| level + 1)) {
+                return heading(level);
+            }
+            id = style.based_on.as_ref()?;
+        }
+        None
+    }
+
+    /// List the external hyperlinks of a DOCX - exposed to Ruby
+    ///
+    /// Returns `[{text:, url:}]` in document order, for links in paragraphs
+    /// and tables of the body. Links to bookmarks inside the document are
+    /// left out.
+    fn docx_links(&self, data: Vec<u8>) -> Result<RArray, Error> {
+        let ruby = Ruby::get().unwrap();
+        self.check_zip_limits(&data, "DOCX")?;
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(&data))
+            .map_err(|e| Self::runtime_error("Failed to open DOCX as ZIP", e))?;
+        let mut budget = self.config.max_size as u64;
+        let Some(document) = self.read_zip_part(&mut archive, "word/document.xml", &mut budget, "DOCX")? else {
+            return Err(Self::runtime_error("Failed to parse DOCX file", "no word/document.xml part"));
+        };
+        let body = self.read_docx_body(&mut archive, &String::from_utf8_lossy(&document), &mut budget, &mut [])?;
+
+        let result = ruby.ary_new();
+        for (text, url) in body.links {
+            let entry = ruby.hash_new();
+            entry.aset(ruby.to_symbol("text"), text)?;
+            entry.aset(ruby.to_symbol("url"), url)?;
+            result.push(entry)?;
+        }
+        Ok(result)
+    }
+
+    /// Split a DOCX into sections by its headings - exposed to Ruby
+    ///
+    /// Returns nested `{heading:, level:, text:, children:}` Hashes, for
+    /// indexing a document in chunks that follow its outline. `text` is
+    /// what `parse_docx` reads between a heading and the next one, and
+    /// `children` are the sections under headings of a lower level. Text
+    /// before the first heading is a section with a nil heading at level 0.
+    fn docx_sections(&self, data: Vec<u8>) -> Result<RArray, Error> {
+        let sections = Self::docx_section_tree(self.read_docx_lines(&data)?);
+        Self::docx_section_entries(&Ruby::get().unwrap(), &sections)
+    }
+
+    /// Group `(heading level, text)` lines under their headings, see
+    /// `docx_sections`. Empty heading paragraphs are read as text.
+    fn docx_section_tree(lines: Vec<(Option<usize>, String)>) -> Vec<DocxSection> {
+        // A section is added to its parent once closed
+        let close = |open: &mut Vec<DocxSection>| {
+            if let Some(closed) = open.pop() {
+                if let Some(parent) = open.last_mut() {
+                    parent.sections.push(closed);
+                }
+            }
+        };
+
+        let mut open = vec![DocxSection::default()];
+        for (heading, line) in lines {
+            let Some(level) = heading.filter(|_| !line.trim().is_empty()) else {
+                if let Some(section) = open.last_mut() {
+                    section.text.push(line);
+                }
+                continue;
+            };
+            // A heading closes the sections at its level and below
+            while open.len() > 1 && open.last().is_some_and(|section| section.level >= level) {
+                close(&mut open);
+            }
+            open.push(DocxSection {
+                heading: Some(line.trim().to_string()),
+                level,
+                ..Default::default()
+            });
+        }
+        while open.len() > 1 {
+            close(&mut open);
+        }
+
+        let mut root = open.pop().unwrap_or_default();
+        let mut sections = std::mem::take(&mut root.sections);
+        if root.text.iter().any(|line| !line.trim().is_empty()) {
+            sections.insert(0, root);
+        }
+        sections
+    }
+
+    /// Convert DOCX sections, and their children, to Ruby Hashes
+    fn docx_section_entries(ruby: &Ruby, sections: &[DocxSection]) -> Result<RArray, Error> {
+        let entries = ruby.ary_new();
+        for section in sections {
+            let entry = ruby.hash_new();
+            entry.aset(ruby.to_symbol("heading"), section.heading.as_deref())?;
+            entry.aset(ruby.to_symbol("level"), section.level)?;
+            entry.aset(ruby.to_symbol("text"), section.text.join("\n").trim())?;
+            entry.aset(ruby.to_symbol("children"), Self::docx_section_entries(ruby, &section.sections)?)?;
+            entries.push(entry)?;
+        }
+        Ok(entries)
+    }
+
+    /// List the pictures stored in a DOCX - exposed to Ruby
+    ///
+    /// Returns `[{name:, content_type:, paragraph:, data:}]` for the parts
+    /// under `word/media/`, with `data` as a binary String. `paragraph` is
+    /// the top-level body paragraph a picture first appears in; pictures
+    /// used only elsewhere, e.g. in tables or headers, have none and come
+    /// last. `ocr: true` adds the recognized `text` of raster pictures.
+    fn docx_images(&self, args: &[Value]) -> Result<RArray, Error> {
+        let ruby = Ruby::get().unwrap();
+        let args = scan_args::scan_args::<(Vec<u8>,), (), (), (), RHash, ()>(args)?;
+        let (data,) = args.required;
+        let kwargs = scan_args::get_kwargs::<_, (), (Option<bool>,), ()>(
+            args.keywords,
+            &[],
+            &["ocr"],
+        )?;
+        let ocr = kwargs.optional.0.unwrap_or(false);
+
+        self.check_zip_limits(&data, "DOCX")?;
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(&data))
+            .map_err(|e| Self::runtime_error("Failed to open DOCX as ZIP", e))?;
+        let mut budget = self.config.max_size as u64;
+        let part = "word/document.xml";
+
+        // Pictures placed in the body, in order of first use
+        let mut media: Vec<(String, Option<usize>)> = Vec::new();
+        if let Some(xml) = self.read_zip_part(&mut archive, part, &mut budget, "DOCX")? {
+            let relationships = self.read_ooxml_relationships(&mut archive, part, &mut budget, "DOCX")?;
+            for (paragraph, id) in Self::ooxml_image_refs(&String::from_utf8_lossy(&xml)) {
+                let Some((_, _, target)) = relationships
+                    .iter()
+                    .find(|(rel_id, kind, _)| *rel_id == id && kind.ends_with("/image"))
+                else {
+                    continue;
+                };
+                match media.iter_mut().find(|(name, _)| name == target) {
+                    Some((_, at)) => *at = at.or(paragraph),
+                    None => media.push((target.clone(), paragraph)),
+                }
+            }
+        }
+        let mut unplaced: Vec<String> = archive
+            .file_names()
+            .filter(|name| name.starts_with("word/media/") && !name.ends_with('/'))
+            .filter(|name| !media.iter().any(|(placed, _)| placed == name))
+            .map(str::to_string)
+            .collect();
+        unplaced.sort();
+        media.extend(unplaced.into_iter().map(|name| (name, None)));
+
+        let types = self
+            .read_zip_part(&mut archive, "[Content_Types].xml", &mut budget, "DOCX")?
+            .map(|types| String::from_utf8_lossy(&types).into_owned())
+            .unwrap_or_default();
+
+        let result = ruby.ary_new();
+        for (name, paragraph) in media {
+            let Some(contents) = self.read_zip_part(&mut archive, &name, &mut budget, "DOCX")? else {
+                continue;
+            };
+            let entry = ruby.hash_new();
+            entry.aset(ruby.to_symbol("name"), name.as_str())?;
+            entry.aset(ruby.to_symbol("content_type"), Self::ooxml_content_type(&types, &name))?;
+            entry.aset(ruby.to_symbol("paragraph"), paragraph)?;
+            entry.aset(ruby.to_symbol("data"), ruby.str_from_slice(&contents))?;
+            if ocr {
+                let text = if Self::is_raster_image(&name, &contents) {
+                    Some(self.ocr_text(&contents)?)
+                } else {
+                    None
+                };
+                entry.aset(ruby.to_symbol("text"), text)?;
+            }
+            result.push(entry)?;
+        }
+        Ok(result)
+    }
+
+    /// Add inserted or deleted text as the `revisions` option says: only
+    /// insertions for `:accept`, only deletions for `:reject`, and both for
+    /// `:show_markup`, as `{+inserted+}` and `[-deleted-]`
+    fn push_docx_revision(&self, text: &mut String, inserted: bool, revised: &str) {
+        if revised.is_empty() {
+            return;
+        }
+        match (self.config.revisions, inserted) {
+            (DocxRevisions::Accept, true) | (DocxRevisions::Reject, false) => text.push_str(revised),
+            (DocxRevisions::ShowMarkup, true) => {
+                text.push_str("{+");
+                text.push_str(revised);
+                text.push_str("+}");
+            }
+            (DocxRevisions::ShowMarkup, false) => {
+                text.push_str("[-");
+                text.push_str(revised);
+                text.push_str("-]");
+            }
+            _ => {}
+        }
+    }
+
+    /// Parse WordPerfect (.wpd) documents - exposed to Ruby
+    ///
+    /// Walks the document area, keeping printable text, extended characters
+    /// and line breaks while skipping the fixed- and variable-length
+    /// function codes around them.
+    fn parse_wordperfect(&self, data: Vec<u8>) -> Result<String, Error> {
+        if data.len() < 16 || !data.starts_with(&[0xFF, b'W', b'P', b'C']) {
+            return Err(Self::runtime_error("Failed to parse WordPerfect file", "missing WPC header"));
+        }
+
+        let doc_start = u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize;
+        // Major version 0 is WordPerfect 5.x, 2 is WordPerfect 6 and later
+        let wp6 = data[10] >= 2;
+        let body = data.get(doc_start..).unwrap_or_default();
+
+        let mut text = String::new();
+        let mut i = 0;
+        while i < body.len() {
+            let byte = body[i];
+            match byte {
+                0x20..=0x7E => text.push(byte as char),
+                0x01..=0x1F if wp6 => text.push(WP6_INTERNATIONAL_CHARS[byte as usize - 1]),
+                0x09 => text.push('\t'),
+                // WP5: hard return, soft/hard page; soft returns are line wraps
+                0x0A | 0x0B | 0x0C if !wp6 => text.push('\n'),
+                0x0D if !wp6 => text.push(' '),
+                // WP6: soft/hard space, hard and soft end of line
+                0x80 | 0x81 if wp6 => text.push(' '),
+                0xCC if wp6 => text.push('\n'),
+                0xCF if wp6 => text.push(' '),
+                // WP6 variable-length groups carry their total size after the subgroup
+                0xD0..=0xEF if wp6 => {
+                    if byte == 0xD0 {
+                        text.push('\n'); // End-of-line group
+                    }
+                    let size = body
+                        .get(i + 2..i + 4)
+                        .map(|b| u16::from_le_bytes([b[0], b[1]]) as usize)
+                        .unwrap_or(1);
+                    i += size.max(1);
+                    continue;
+                }
+                // WP5 variable-length functions: code, subcode, length, data
+                0xD0..=0xFF if !wp6 => {
+                    let size = body
+                        .get(i + 2..i + 4)
+                        .map(|b| u16::from_le_bytes([b[0], b[1]]) as usize)
+                        .unwrap_or(0);
+                    i += 4 + size;
+                    continue;
+                }
+                // Extended character: code, character, character set, code
+                0xC0 if !wp6 && body.get(i + 3) == Some(&0xC0) => {
+                    text.push(Self::wordperfect_char(body[i + 2], body[i + 1]));
+                    i += 4;
+                    continue;
+                }
+                0xF0 if wp6 && body.get(i + 3) == Some(&0xF0) => {
+                    text.push(Self::wordperfect_char(body[i + 2], body[i + 1]));
+                    i += 4;
+                    continue;
+                }
+                // Fixed-length functions are closed by a repeat of their code
+                0xC0..=0xCF | 0xF0..=0xFF => {
+                    let limit = body.len().min(i + 16);
+                    if let Some(end) = body[i + 1..limit].iter().position(|&b| b == byte) {
+                        i += end + 2;
+                        continue;
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+
+        let lines: Vec<String> = text
+            .lines()
+            .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+            .collect();
+        let text = lines.join("\n");
+        let collapsed = EXTRA_BLANK_LINES.replace_all(&text, "\n\n");
+
+        Ok(collapsed.trim().to_string())
+    }
+
+    /// Map a WordPerfect extended character to Unicode. Set 0 is ASCII and
+    /// sets 1 and 4 hold the Latin-1 letters and signs; anything else comes
+    /// out as U+FFFD so the text still shows a character was there.
+    fn wordperfect_char(charset: u8, code: u8) -> char {
+        let found = match charset {
+            0 if (0x20..=0x7E).contains(&code) => Some(code as char),
+            1 => code.checked_sub(0x17).and_then(|n| WP_MULTINATIONAL_CHARS.get(n as usize)).copied(),
+            4 => WP_TYPOGRAPHIC_CHARS.get(code as usize).copied(),
+            _ => None,
+        };
+        found.unwrap_or('\u{FFFD}')
+    }
+
+    /// Parse PPTX (PowerPoint) files - exposed to Ruby
+    ///
+    /// Takes the deck's data, or `path:` to read it from the file part by
+    /// part like `parse_file` does instead of loading it whole.
+    /// `slides:` limits extraction to some slides, numbered from 1, and
+    /// takes the same forms as `pages:` of `parse_pdf`. Only the notes of
+    /// the selected slides are kept.
+    fn parse_pptx(&self, args: &[Value]) -> Result<String, Error> {
+        let (data, path, slides) = Self::pptx_args(args)?;
+        match path {
+            Some(path) => self.pptx_text(self.open_within_size(&path)?, &slides),
+            None => self.pptx_text(std::io::Cursor::new(data), &slides),
+        }
+    }
+
+    /// Split the arguments of the PPTX methods into the data or `path:`,
+    /// exactly one of which is given, and the `slides:` selection
+    fn pptx_args(args: &[Value]) -> Result<(Vec<u8>, Option<String>, PageSelection), Error> {
+        let args = scan_args::scan_args::<(), (Option<Vec<u8>>,), (), (), RHash, ()>(args)?;
+        let (data,) = args.optional;
+        let kwargs = scan_args::get_kwargs::<_, (), (Option<String>, Option<Value>), ()>(
+            args.keywords,
+            &[],
+            &["path", "slides"],
+        )?;
+        let (path, slides) = kwargs.optional;
+
+        let data = match (data, &path) {
+            (Some(data), None) => data,
+            (None, Some(_)) => Vec::new(),
+            (Some(_), Some(_)) => return Err(Self::argument_error("Pass either data or path:, not both")),
+            (None, None) => return Err(Self::argument_error("Pass the PPTX data or path:")),
+        };
+        Ok((data, path, Self::page_selection(slides, "slides")?))
+    }
+
+    /// Open a file to be read where it lies rather than loaded whole,
+    /// checking its size against `max_size` first
+    fn open_within_size(&self, path: &str) -> Result<std::io::BufReader<std::fs::File>, Error> {
+        let file = std::fs::File::open(path)
+            .map_err(|e| Self::io_error("Failed to read file", e))?;
+        let size = file.metadata()
+            .map_err(|e| Self::io_error("Failed to read file", e))?
+            .len();
+        if size > self.config.max_size as u64 {
+            return Err(self.file_size_error(size));
+        }
+        Ok(std::io::BufReader::new(file))
+    }
+
+    /// Extract the text of the selected slides of a PPTX, followed by their
+    /// notes (see `pptx_notes`)
+    fn pptx_text<R: std::io::Read + std::io::Seek>(&self, reader: R, slides: &PageSelection) -> Result<String, Error> {
+        let mut all_text = Vec::new();
+        let mut notes = Vec::new();
+        for slide in self.read_pptx_slides(reader, slides, false)? {
+            if !slide.text.is_empty() {
+                all_text.push(slide.text);
+            }
+            notes.extend(slide.notes);
+        }
+
+        // Notes on their own need no label
+        if self.config.pptx_notes == PptxNotes::Only {
+            all_text.extend(notes);
+        } else {
+            all_text.extend(notes.into_iter().map(|text| format!("[Notes: {}]", text)));
+        }
+        
+        if all_text.is_empty() {
+            Ok("".to_string())
+        } else {
+            Ok(all_text.join("\n\n"))
+        }
+    }
+
+    /// Parse PPTX files slide by slide - exposed to Ruby
+    ///
+    /// Returns an Array of `{slide:, title:, body:, notes:}` Hashes, one per
+    /// slide. `title` is the text of the slide's title placeholder, and
+    /// `body` the rest of what `parse_pptx` reads from the slide; `notes` is
+    /// nil for slides without notes, or with `pptx_notes: :exclude`. Takes
+    /// `path:` and `slides:` like `parse_pptx`.
+    fn parse_pptx_slides(&self, args: &[Value]) -> Result<RArray, Error> {
+        let ruby = Ruby::get().unwrap();
+        let (data, path, slides) = Self::pptx_args(args)?;
+        let slides = match path {
+            Some(path) => self.read_pptx_slides(self.open_within_size(&path)?, &slides, true)?,
+            None => self.read_pptx_slides(std::io::Cursor::new(data), &slides, true)?,
+        };
+
+        let result = ruby.ary_new();
+        for slide in slides {
+            let entry = ruby.hash_new();
+            entry.aset(ruby.to_symbol("slide"), slide.number)?;
+            entry.aset(ruby.to_symbol("title"), slide.title)?;
+            entry.aset(ruby.to_symbol("body"), slide.text)?;
+            entry.aset(ruby.to_symbol("notes"), slide.notes)?;
+            result.push(entry)?;
+        }
+        Ok(result)
+    }
+
+    /// Read the selected slides of a PPTX in order, with their notes unless
+    /// `pptx_notes` is `:exclude`. With `titled` the title placeholder is
+    /// read apart from the rest of the slide; without it the slide text is
+    /// left out when `pptx_notes` is `:only`.
+    ///
+    /// Parts are decompressed one at a time into a reused buffer, so memory
+    /// follows the largest slide rather than the deck; media is only read
+    /// for `ocr_embedded_images`.
+    fn read_pptx_slides<R: std::io::Read + std::io::Seek>(
+        &self,
+        reader: R,
+        slides: &PageSelection,
+        titled: bool,
+    ) -> Result<Vec<PptxSlide>, Error> {
+        use zip::ZipArchive;
+        
+        let mut archive = ZipArchive::new(reader)
+            .map_err(|e| Self::runtime_error("Failed to open PPTX as ZIP", e))?;
+        
+        let mut slide_numbers = Vec::new();
+        
+        // First, collect slide numbers and sort them
+        for name in archive.file_names() {
+            // Match slide XML files (e.g., ppt/slides/slide1.xml)
+            if name.starts_with("ppt/slides/slide") && name.ends_with(".xml") && !name.contains("_rels") {
+                // Extract slide number from filename
+                if let Some(num_str) = name
+                    .strip_prefix("ppt/slides/slide")
+                    .and_then(|s| s.strip_suffix(".xml"))
+                {
+                    if let Ok(num) = num_str.parse::<usize>() {
+                        slide_numbers.push((num, name.to_string()));
+                    }
+                }
+            }
+        }
+        
+        // Sort by slide number to maintain order
+        slide_numbers.sort_by_key(|&(num, _)| num);
+        
+        // Slides and notes share one decompression budget
+        let mut budget = self.config.max_size as u64;
+        let mut contents = Vec::new();
+        let mut result = Vec::new();
+
+        // Now process the selected slides in order
+        for index in slides.indices(slide_numbers.len()) {
+            let name = &slide_numbers[index].1;
+            let mut slide = PptxSlide {
+                number: index + 1,
+                ..Default::default()
+            };
+            if titled || self.config.pptx_notes != PptxNotes::Only {
+                if !self.read_zip_part_into(&mut archive, name, &mut budget, "PPTX", &mut contents)? {
+                    continue;
+                }
+                // Extract text from slide XML
+                let xml = String::from_utf8_lossy(&contents);
+                if titled {
+                    slide.title = Self::pptx_slide_title(&xml);
+                }
+                let links = self.read_pptx_links(&mut archive, name, &mut budget)?;
+                let mut text = self.extract_text_from_slide_xml(&xml, titled, &links);
+                if self.config.pptx_alt_text {
+                    for description in Self::pptx_alt_texts(&xml) {
+                        if !text.is_empty() {
+                            text.push('\n');
+                        }
+                        text.push_str(&format!("[Image: {}]", description));
+                    }
+                }
+                if self.config.ocr_embedded_images {
+                    for (_, image) in self.ocr_ooxml_images(&mut archive, name, &xml, &mut budget, "PPTX")? {
+                        if !text.is_empty() {
+                            text.push('\n');
+                        }
+                        text.push_str(&image);
+                    }
+                }
+                slide.text = text;
+            }
+
+            // The slide's notes, if present, are linked from its relationships
+            if self.config.pptx_notes != PptxNotes::Exclude {
+                let relationships = self.read_ooxml_relationships(&mut archive, name, &mut budget, "PPTX")?;
+                if let Some((_, _, target)) = relationships.iter().find(|(_, kind, _)| kind.ends_with("/notesSlide")) {
+                    if self.read_zip_part_into(&mut archive, target, &mut budget, "PPTX", &mut contents)? {
+                        let links = self.read_pptx_links(&mut archive, target, &mut budget)?;
+                        let text = self.extract_text_from_slide_xml(&String::from_utf8_lossy(&contents), false, &links);
+                        slide.notes = Some(text).filter(|text| !text.is_empty());
+                    }
+                }
+            }
+            result.push(slide);
+        }
+        Ok(result)
+    }
+
+    /// The hyperlink targets of a slide or notes part for `pptx_links`, as
+    /// `(Id, target)`; empty when the option is off
+    fn read_pptx_links<R: std::io::Read + std::io::Seek>(
+        &self,
+        archive: &mut zip::ZipArchive<R>,
+        part: &str,
+        budget: &mut u64,
+    ) -> Result<Vec<(String, String)>, Error> {
+        if !self.config.pptx_links {
+            return Ok(Vec::new());
+        }
+        let rels = self.read_zip_part(archive, &Self::ooxml_rels_path(part), budget, "PPTX")?;
+        Ok(rels
+            .map(|rels| Self::ooxml_external_targets(&String::from_utf8_lossy(&rels)))
+            .unwrap_or_default())
+    }
+
+    /// Whether a PresentationML placeholder (`p:ph`) is a slide title
+    fn pptx_is_title_placeholder(e: &quick_xml::events::BytesStart) -> bool {
+        e.attributes()
+            .flatten()
+            .find(|a| a.key.local_name().as_ref() == b"type")
+            .is_some_and(|a| matches!(a.value.as_ref(), b"title" | b"ctrTitle"))
+    }
+
+    /// The text of the first non-empty title placeholder on a slide, its runs joined
+    /// by spaces like `extract_text_from_slide_xml`
+    fn pptx_slide_title(xml: &str) -> Option<String> {
+        use quick_xml::events::Event;
+        use quick_xml::Reader;
+
+        let mut reader = Reader::from_str(xml);
+        let mut in_title = false;
+        let mut in_text_element = false;
+        let mut parts = Vec::new();
+        loop {
+            match reader.read_event() {
+                Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e))
+                    if e.name().local_name().as_ref() == b"ph" && Self::pptx_is_title_placeholder(e) =>
+                {
+                    in_title = true;
+                }
+                Ok(Event::Start(ref e)) if in_title && e.name().local_name().as_ref() == b"t" => in_text_element = true,
+                Ok(Event::Text(e)) if in_text_element => {
+                    if let Ok(text) = e.decode() {
+                        let text = text.trim();
+                        if !text.is_empty() {
+                            parts.push(text.to_string());
+                        }
+                    }
+                }
+                Ok(Event::End(ref e)) => match e.name().local_name().as_ref() {
+                    b"t" => in_text_element = false,
+                    // The title shape ends; the first non-empty one counts
+                    b"sp" if in_title => {
+                        if !parts.is_empty() {
+                            break;
+                        }
+                        in_title = false;
+                    }
+                    _ => {}
+                },
+                Ok(Event::Eof) | Err(_) => break,
+                _ => {}
+            }
+        }
+        Some(parts.join(" ")).filter(|title| !title.is_empty())
+    }
+    
+    /// Helper method to extract text from slide XML
+    ///
+    /// Text runs are joined by spaces. Tables (`a:tbl`) interrupt that
+    /// with a line per row, its cells separated by tabs; merged cells stay
+    /// as empty cells so columns line up. `skip_title` leaves out the
+    /// shapes that are title placeholders. Hyperlinked runs are followed by
+    /// their target as "text (url)" when it is among `links`, the slide's
+    /// external relationships as `(Id, target)`.
+    fn extract_text_from_slide_xml(&self, xml_content: &str, skip_title: bool, links: &[(String, String)]) -> String {
+        use quick_xml::events::Event;
+        use quick_xml::Reader;
+        
+        let mut reader = Reader::from_str(xml_content);
+        
+        let mut lines = Vec::new();
+        let mut text_parts = Vec::new();
+        let mut buf = Vec::new();
+        let mut in_text_element = false;
+        let mut in_title = false;
+        let mut in_table = false;
+        let mut row: Vec<String> = Vec::new();
+        let mut cell: Option<String> = None;
+        let mut in_run_properties = false;
+        // The link of the current run as (Id, target), and the link whose
+        // text is being written, with that text
+        let mut run_link: Option<(String, String)> = None;
+        let mut link: Option<(String, String, String)> = None;
+        
+        loop {
+            match reader.read_event_into(&mut buf) {
+                Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e))
+                    if skip_title && e.name().local_name().as_ref() == b"ph" && Self::pptx_is_title_placeholder(e) =>
+                {
+                    in_title = true;
+                }
+                Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e))
+                    if in_run_properties && e.name().local_name().as_ref() == b"hlinkClick" =>
+                {
+                    let id = Self::xml_attribute(e, b"id");
+                    run_link = id.and_then(|id| {
+                        links.iter().find(|(rel_id, _)| *rel_id == id).map(|(_, url)| (id, url.clone()))
+                    });
+                }
+                Ok(Event::Start(ref e)) => {
+                    // Look for text elements (a:t or t)
+                    let name = e.name();
+                    let local_name_bytes = name.local_name();
+                    let local_name = std::str::from_utf8(local_name_bytes.as_ref()).unwrap_or("");
+                    match local_name {
+                        "t" => in_text_element = true,
+                        "rPr" => in_run_properties = true,
+                        "tbl" => {
+                            if !text_parts.is_empty() {
+                                lines.push(text_parts.join(" "));
+                                text_parts.clear();
+                            }
+                            in_table = true;
+                        }
+                        "tr" if in_table => row.clear(),
+                        "tc" if in_table => cell = Some(String::new()),
+                        _ => {}
+                    }
+                }
+                Ok(Event::Empty(ref e)) if in_table && e.name().local_name().as_ref() == b"tc" => {
+                    row.push(String::new());
+                }
+                Ok(Event::Text(e)) if in_text_element && !in_title => {
+                    if let Ok(text) = e.decode() {
+                        let text_str = text.trim();
+                        if !text_str.is_empty() {
+                            // Runs of one link share its target
+                            if link.as_ref().map(|(id, _, _)| id) != run_link.as_ref().map(|(id, _)| id) {
+                                Self::close_pptx_link(&mut link, &mut cell, &mut text_parts);
+                                link = run_link.clone().map(|(id, url)| (id, url, String::new()));
+                            }
+                            if let Some((_, _, link_text)) = link.as_mut() {
+                                if !link_text.is_empty() {
+                                    link_text.push(' ');
+                                }
+                                link_text.push_str(text_str);
+                            }
+                            match cell.as_mut() {
+                                Some(cell) => {
+                                    if !cell.is_empty() {
+                                        cell.push(' ');
+                                    }
+                                    cell.push_str(text_str);
+                                }
+                                None => text_parts.push(text_str.to_string()),
+                            }
+                        }
+                    }
+                }
+                Ok(Event::End(ref e)) => {
+                    let name = e.name();
+                    let local_name_bytes = name.local_name();
+                    let local_name = std::str::from_utf8(local_name_bytes.as_ref()).unwrap_or("");
+                    match local_name {
+                        "t" => in_text_element = false,
+                        "rPr" => in_run_properties = false,
+                        "r" => run_link = None,
+                        "p" => Self::close_pptx_link(&mut link, &mut cell, &mut text_parts),
+                        "sp" => in_title = false,
+                        "tc" if in_table => row.extend(cell.take()),
+                        "tr" if in_table => {
+                            while row.last().is_some_and(|value| value.is_empty()) {
+                                row.pop();
+                            }
+                            if !row.is_empty() {
+                                lines.push(row.join("\t"));
+                            }
+                        }
+                        "tbl" => in_table = false,
+                        _ => {}
+                    }
+                }
+                Ok(Event::Eof) => break,
+                _ => {}
+            }
+            buf.clear();
+        }
+        
+        Self::close_pptx_link(&mut link, &mut cell, &mut text_parts);
+        if !text_parts.is_empty() {
+            lines.push(text_parts.join(" "));
+        }
+        lines.join("\n")
+    }
+
+    /// Follow the text of a finished slide link with its target, in the
+    /// table cell or text it was written to, unless the text is the target
+    fn close_pptx_link(
+        link: &mut Option<(String, String, String)>,
+        cell: &mut Option<String>,
+        text_parts: &mut [String],
+    ) {
+        let Some((_, url, text)) = link.take() else {
+            return;
+        };
+        if text == url {
+            return;
+        }
+        let target = format!(" ({})", url);
+        match cell.as_mut() {
+            Some(cell) => cell.push_str(&target),
+            None => {
+                if let Some(last) = text_parts.last_mut() {
+                    last.push_str(&target);
+                }
+            }
+        }
+    }
+
+    /// The alt text (`descr`) of the pictures on a slide, in slide order.
+    /// Pictures without one, such as those marked decorative, are left out.
+    fn pptx_alt_texts(xml: &str) -> Vec<String> {
+        use quick_xml::events::Event;
+        use quick_xml::Reader;
+
+        let mut reader = Reader::from_str(xml);
+        let mut descriptions = Vec::new();
+        let mut in_picture = false;
+        loop {
+            match reader.read_event() {
+                Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) => match e.name().local_name().as_ref() {
+                    b"pic" => in_picture = true,
+                    // The picture's own properties come first
+                    b"cNvPr" if in_picture => {
+                        in_picture = false;
+                        let description = Self::xml_attribute(e, b"descr")
+                            .map(|v| v.split_whitespace().collect::<Vec<_>>().join(" "));
+                        descriptions.extend(description.filter(|description| !description.is_empty()));
+                    }
+                    _ => {}
+                },
+                Ok(Event::Eof) | Err(_) => break,
+                _ => {}
+            }
+        }
+        descriptions
+    }
+
+    /// The part holding the relationships of `part`, e.g.
+    /// `word/_rels/document.xml.rels` for `word/document.xml`
+    fn ooxml_rels_path(part: &str) -> String {
+        match part.rsplit_once('/') {
+            Some((folder, name)) => format!("{}/_rels/{}.rels", folder, name),
+            None => format!("_rels/{}.rels", part),
+        }
+    }
+
+    /// Resolve a relationship target against the folder of the part it
+    /// belongs to, e.g. `../media/image1.png` from `ppt/slides/slide1.xml`
+    fn ooxml_resolve_target(part: &str, target: &str) -> String {
+        if let Some(absolute) = target.strip_prefix('/') {
+            return absolute.to_string();
+        }
+        let mut segments: Vec<&str> = part.split('/').collect();
+        segments.pop();
+        for segment in target.split('/') {
+            match segment {
+                "" | "." => {}
+                ".." => {
+                    segments.pop();
+                }
+                _ => segments.push(segment),
+            }
+        }
+        segments.join("/")
+    }
+
+    /// Read a `.rels` part into `(Id, Type, target)` triples in document
+    /// order, with targets resolved against `part`. External links are left
+    /// out, as they aren't inside the package.
+    fn ooxml_relationships(rels: &str, part: &str) -> Vec<(String, String, String)> {
+        use quick_xml::events::Event;
+        use quick_xml::Reader;
+
+        let mut reader = Reader::from_str(rels);
+        let mut relationships = Vec::new();
+        loop {
+            match reader.read_event() {
+                Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e))
+                    if e.name().local_name().as_ref() == b"Relationship" =>
+                {
+                    if Self::xml_attribute(e, b"TargetMode").is_some_and(|mode| mode == "External") {
+                        continue;
+                    }
+                    if let (Some(id), Some(kind), Some(target)) =
+                        (Self::xml_attribute(e, b"Id"), Self::xml_attribute(e, b"Type"), Self::xml_attribute(e, b"Target"))
+                    {
+                        relationships.push((id, kind, Self::ooxml_resolve_target(part, &target)));
+                    }
+                }
+                Ok(Event::Eof) | Err(_) => break,
+                _ => {}
+            }
+        }
+        relationships
+    }
+
+    /// Read a `.rels` part into `(Id, target)` pairs for the relationships
+    /// that point outside the package, such as hyperlinks
+    fn ooxml_external_targets(rels: &str) -> Vec<(String, String)> {
+        use quick_xml::events::Event;
+        use quick_xml::Reader;
+
+        let mut reader = Reader::from_str(rels);
+        let mut targets = Vec::new();
+        loop {
+            match reader.read_event() {
+                Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e))
+                    if e.name().local_name().as_ref() == b"Relationship"
+                        && Self::xml_attribute(e, b"TargetMode").is_some_and(|mode| mode == "External") =>
+                {
+                    if let (Some(id), Some(target)) = (Self::xml_attribute(e, b"Id"), Self::xml_attribute(e, b"Target")) {
+                        targets.push((id, target));
+                    }
+                }
+                Ok(Event::Eof) | Err(_) => break,
+                _ => {}
+            }
+        }
+        targets
+    }
+
+    /// Walk an OOXML part once, event by event, feeding each element and
+    /// text to every visitor in turn. An empty element is handled as a
+    /// start and an end.
+    fn walk_ooxml_part(xml: &str, visitors: &mut [&mut dyn OoxmlVisitor]) {
+        use quick_xml::events::Event;
+        use quick_xml::Reader;
+
+        let mut reader = Reader::from_str(xml);
+        let mut at = OoxmlCursor::default();
+        loop {
+            match reader.read_event() {
+                Ok(Event::Start(e)) => {
+                    at.enter(&e, false);
+                    visitors.iter_mut().for_each(|visitor| visitor.start(&e, &at));
+                }
+                Ok(Event::Empty(e)) => {
+                    at.enter(&e, true);
+                    visitors.iter_mut().for_each(|visitor| visitor.start(&e, &at));
+                    let name = e.name();
+                    visitors.iter_mut().for_each(|visitor| visitor.end(name.local_name().as_ref(), &at));
+                    at.leave();
+                }
+                Ok(Event::End(e)) => {
+                    let name = e.name();
+                    visitors.iter_mut().for_each(|visitor| visitor.end(name.local_name().as_ref(), &at));
+                    at.leave();
+                }
+                Ok(Event::Text(e)) => {
+                    let text = e.decode().unwrap_or_default();
+                    visitors.iter_mut().for_each(|visitor| visitor.text(&text));
+                }
+                Ok(Event::GeneralRef(e)) => {
+                    let text = Self::resolve_xml_reference(&e);
+                    visitors.iter_mut().for_each(|visitor| visitor.text(&text));
+                }
+                Ok(Event::Eof) | Err(_) => break,
+                _ => {}
+            }
+        }
+    }
+
+    /// The unescaped value of an XML element's attribute, by local name
+    fn xml_attribute(e: &quick_xml::events::BytesStart, name: &[u8]) -> Option<String> {
+        e.attributes()
+            .flatten()
+            .find(|a| a.key.local_name().as_ref() == name)
+            .and_then(|a| a.unescape_value().ok().map(|v| v.to_string()))
+    }
+
+    /// The image relationship Ids a part refers to, in document order:
+    /// DrawingML `a:blip r:embed` and VML `v:imagedata r:id`. Each comes with
+    /// the index of the top-level `w:body` paragraph holding it, so DOCX
+    /// pictures can be placed among the paragraphs; it is `None` elsewhere.
+    fn ooxml_image_refs(xml: &str) -> Vec<(Option<usize>, String)> {
+        let mut images = OoxmlImageRefReader::default();
+        Self::walk_ooxml_part(xml, &mut [&mut images]);
+        images.refs
+    }
+
+
+    /// The worksheets of an XLSX workbook part as `(name, relationship Id)`
+    fn xlsx_sheet_refs(workbook: &str) -> Vec<(String, String)> {
+        use quick_xml::events::Event;
+        use quick_xml::Reader;
+
+        let mut reader = Reader::from_str(workbook);
+        let mut sheets = Vec::new();
+        loop {
+            match reader.read_event() {
+                Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e))
+                    if e.name().local_name().as_ref() == b"sheet" =>
+                {
+                    // `sheetId` is the sheet's number; `r:id` names its part
+                    if let (Some(name), Some(id)) = (Self::xml_attribute(e, b"name"), Self::xml_attribute(e, b"id")) {
+                        sheets.push((name, id));
+                    }
+                }
+                Ok(Event::Eof) | Err(_) => break,
+                _ => {}
+            }
+        }
+        sheets
+    }
+
+    /// The hidden rows and columns of an XLSX worksheet part, numbered from
+    /// 0 like calamine's cell positions
+    fn xlsx_hidden_lines(sheet: &str) -> (std::collections::HashSet<u32>, std::collections::HashSet<u32>) {
+        use quick_xml::events::Event;
+        use quick_xml::Reader;
+
+        let number = |e: &quick_xml::events::BytesStart, name: &[u8]| -> Option<u32> {
+            Self::xml_attribute(e, name).and_then(|value| value.parse::<u32>().ok()).filter(|&n| n > 0)
+        };
+
+        let mut reader = Reader::from_str(sheet);
+        let mut rows = std::collections::HashSet::new();
+        let mut columns = std::collections::HashSet::new();
+        loop {
+            match reader.read_event() {
+                Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) => {
+                    let hidden = matches!(Self::xml_attribute(e, b"hidden").as_deref(), Some("1") | Some("true"));
+                    match e.name().local_name().as_ref() {
+                        b"row" if hidden => rows.extend(number(e, b"r").map(|r| r - 1)),
+                        // A `col` element covers columns `min` to `max`
+                        b"col" if hidden => {
+                            if let (Some(min), Some(max)) = (number(e, b"min"), number(e, b"max")) {
+                                columns.extend(min - 1..max);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Ok(Event::Eof) | Err(_) => break,
+                _ => {}
+            }
+        }
+        (rows, columns)
+    }
+
+    /// The `hyperlink` elements of an XLSX worksheet part with a
+    /// relationship, as `(cell reference, relationship Id, location)`
+    fn xlsx_hyperlinks(sheet: &str) -> Vec<(String, String, Option<String>)> {
+        use quick_xml::events::Event;
+        use quick_xml::Reader;
+
+        let mut reader = Reader::from_str(sheet);
+        let mut links = Vec::new();
+        loop {
+            match reader.read_event() {
+                Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e))
+                    if e.name().local_name().as_ref() == b"hyperlink" =>
+                {
+                    if let (Some(reference), Some(id)) = (Self::xml_attribute(e, b"ref"), Self::xml_attribute(e, b"id")) {
+                        links.push((reference, id, Self::xml_attribute(e, b"location")));
+                    }
+                }
+                Ok(Event::Eof) | Err(_) => break,
+                _ => {}
+            }
+        }
+        links
+    }
+
+    /// The `(row, column)` of an A1-style cell reference, numbered from 0
+    fn xlsx_cell_position(reference: &str) -> Option<(u32, u32)> {
+        let reference = reference.replace('$', "");
+        let (letters, digits) = reference.split_at(reference.find(|c: char| c.is_ascii_digit())?);
+        if letters.is_empty() || !letters.bytes().all(|b| b.is_ascii_alphabetic()) {
+            return None;
+        }
+        let column = letters.bytes().try_fold(0u32, |column, b| {
+            column.checked_mul(26)?.checked_add(u32::from(b.to_ascii_uppercase() - b'A') + 1)
+        })?;
+        let row = digits.parse::<u32>().ok().filter(|&row| row > 0)?;
+        Some((row - 1, column - 1))
+    }
+
+    /// The target of a `HYPERLINK("url", ...)` formula, when given as a
+    /// string literal
+    fn xlsx_hyperlink_formula_target(formula: &str) -> Option<String> {
+        let formula = formula.trim_start().trim_start_matches('=');
+        if !formula.get(..9)?.eq_ignore_ascii_case("HYPERLINK") {
+            return None;
+        }
+        let rest = formula[9..].trim_start().strip_prefix('(')?.trim_start().strip_prefix('"')?;
+
+        // A doubled quote stands for one quote
+        let mut target = String::new();
+        let mut chars = rest.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '"' {
+                target.push(c);
+            } else if chars.next_if_eq(&'"').is_some() {
+                target.push('"');
+            } else {
+                return Some(target);
+            }
+        }
+        None
+    }
+
+    /// The content type of a package part from `[Content_Types].xml`: its
+    /// `Override`, or else the `Default` for its extension
+    fn ooxml_content_type(types: &str, part: &str) -> Option<String> {
+        use quick_xml::events::Event;
+        use quick_xml::Reader;
+
+        // Part names and extensions compare case-insensitively
+        let extension = part.rsplit_once('.').map(|(_, extension)| extension);
+        let mut reader = Reader::from_str(types);
+        let mut default = None;
+        loop {
+            match reader.read_event() {
+                Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) => match e.name().local_name().as_ref() {
+                    b"Override" => {
+                        let name = Self::xml_attribute(e, b"PartName").unwrap_or_default();
+                        if name.trim_start_matches('/').eq_ignore_ascii_case(part) {
+                            return Self::xml_attribute(e, b"ContentType");
+                        }
+                    }
+                    b"Default" if default.is_none() => {
+                        if Self::xml_attribute(e, b"Extension").is_some_and(|ext| extension.is_some_and(|x| ext.eq_ignore_ascii_case(x))) {
+                            default = Self::xml_attribute(e, b"ContentType");
+                        }
+                    }
+                    _ => {}
+                },
+                Ok(Event::Eof) | Err(_) => break,
+                _ => {}
+            }
+        }
+        default
+    }
+
+    /// Read the comments of a DOCX `word/comments.xml` part, in file order,
+    /// with their paragraphs joined by newlines. Anchors are filled in by
+    /// `docx_comment_anchors`.
+    fn docx_comments_xml(xml: &str) -> Vec<DocxComment> {
+        use quick_xml::events::Event;
+        use quick_xml::Reader;
+
+        let mut reader = Reader::from_str(xml);
+        let mut comments = Vec::new();
+        let mut current: Option<DocxComment> = None;
+        let mut in_text = false;
+        loop {
+            match reader.read_event() {
+                Ok(Event::Start(ref e)) => match e.name().local_name().as_ref() {
+                    b"comment" => {
+                        current = Some(DocxComment {
+                            id: Self::xml_attribute(e, b"id").unwrap_or_default(),
+                            author: Self::xml_attribute(e, b"author").filter(|author| !author.is_empty()),
+                            date: Self::xml_attribute(e, b"date").filter(|date| !date.is_empty()),
+                            text: String::new(),
+                            anchor: String::new(),
+                            paragraph: None,
+                        })
+                    }
+                    b"t" => in_text = true,
+                    b"p" => {
+                        if let Some(comment) = current.as_mut().filter(|comment| !comment.text.is_empty()) {
+                            comment.text.push('\n');
+                        }
+                    }
+                    _ => {}
+                },
+                Ok(Event::Empty(ref e)) => match e.name().local_name().as_ref() {
+                    b"tab" => current.iter_mut().for_each(|comment| comment.text.push('\t')),
+                    b"br" | b"cr" => current.iter_mut().for_each(|comment| comment.text.push('\n')),
+                    _ => {}
+                },
+                Ok(Event::Text(ref e)) if in_text => {
+                    if let Some(comment) = current.as_mut() {
+                        comment.text.push_str(&e.decode().unwrap_or_default());
+                    }
+                }
+                Ok(Event::GeneralRef(ref e)) if in_text => {
+                    if let Some(comment) = current.as_mut() {
+                        comment.text.push_str(&Self::resolve_xml_reference(e));
+                    }
+                }
+                Ok(Event::End(ref e)) => match e.name().local_name().as_ref() {
+                    b"comment" => comments.extend(current.take().map(|mut comment| {
+                        comment.text = comment.text.trim().to_string();
+                        comment
+                    })),
+                    b"t" => in_text = false,
+                    _ => {}
+                },
+                Ok(Event::Eof) | Err(_) => break,
+                _ => {}
+            }
+        }
+        comments
+    }
+
+    /// Find where each comment is anchored in a DOCX `word/document.xml`:
+    /// the text between its `w:commentRangeStart` and `w:commentRangeEnd`,
+    /// and the index of the top-level `w:body` paragraph the range ends in
+    /// (`None` inside tables and other containers)
+    fn docx_comment_anchors(xml: &str, comments: &mut [DocxComment]) {
+        Self::walk_ooxml_part(xml, &mut [&mut DocxCommentAnchorReader::new(comments)]);
+    }
+
+
+
+    /// Read the content controls (`w:sdt`) and legacy form fields (`w:ffData`)
+    /// of a DOCX `word/document.xml` in document order. Building-block
+    /// controls such as cover pages and tables of contents aren't fields
+    /// and are left out. Check boxes have the value "true" or "false", and
+    /// a legacy drop-down the entry it has selected.
+    fn docx_form_fields_xml(xml: &str) -> Vec<DocxFormField> {
+        use quick_xml::events::Event;
+        use quick_xml::Reader;
+
+        // On/off properties are on when present without a value
+        let on = |e: &quick_xml::events::BytesStart| {
+            Self::xml_attribute(e, b"val").is_none_or(|v| !matches!(v.as_str(), "0" | "false" | "off"))
+        };
+
+        let mut reader = Reader::from_str(xml);
+        let mut fields: Vec<DocxFormField> = Vec::new();
+        // Open content controls as (depth, field index)
+        let mut controls: Vec<(usize, usize)> = Vec::new();
+        let mut properties_depth = None;
+        let mut content: Vec<usize> = Vec::new();
+        let mut placeholders: Vec<usize> = Vec::new();
+        let mut building_blocks: Vec<usize> = Vec::new();
+        // Open complex fields, with the index of a legacy text field and
+        // whether its result has started
+        let mut complex: Vec<(Option<usize>, bool)> = Vec::new();
+        let mut form_data: Option<usize> = None;
+        let mut list: (usize, Vec<String>) = (0, Vec::new());
+        let mut depth = 0;
+        let mut in_text = false;
+        loop {
+            let (e, empty) = match reader.read_event() {
+                Ok(Event::Start(e)) => (e, false),
+                Ok(Event::Empty(e)) => (e, true),
+                Ok(Event::Text(e)) if in_text => {
+                    let text = e.decode().unwrap_or_default();
+                    Self::push_docx_field_text(&mut fields, &content, &complex, &text);
+                    continue;
+                }
+                Ok(Event::GeneralRef(e)) if in_text => {
+                    let text = Self::resolve_xml_reference(&e);
+                    Self::push_docx_field_text(&mut fields, &content, &complex, &text);
+                    continue;
+                }
+                Ok(Event::End(e)) => {
+                    match e.name().local_name().as_ref() {
+                        b"t" => in_text = false,
+                        b"p" => Self::push_docx_field_text(&mut fields, &content, &[], "\n"),
+                        b"sdtPr" => properties_depth = None,
+                        b"sdtContent" => {
+                            content.pop();
+                        }
+                        b"ffData" => {
+                            if let Some(i) = form_data.take() {
+                                if fields[i].kind == "drop_down" {
+                                    let (selected, entries) = std::mem::take(&mut list);
+                                    fields[i].value = entries.into_iter().nth(selected);
+                                }
+                            }
+                        }
+                        b"sdt" if controls.last().is_some_and(|(at, _)| *at == depth) => {
+                            if let Some((_, i)) = controls.pop() {
+                                if placeholders.contains(&i) {
+                                    fields[i].value = None;
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                    depth -= 1;
+                    continue;
+                }
+                Ok(Event::Eof) | Err(_) => break,
+                _ => continue,
+            };
+
+            let level = depth + 1;
+            let name = e.name();
+            let control = controls.last().map(|&(_, i)| i);
+            match name.local_name().as_ref() {
+                b"sdt" if !empty => {
+                    controls.push((level, fields.len()));
+                    fields.push(DocxFormField { tag: None, title: None, kind: "rich_text", value: None });
+                }
+                b"sdtPr" if !empty => properties_depth = Some(level),
+                b"sdtContent" if !empty => content.extend(control),
+                // Properties of the innermost open control
+                local if properties_depth.is_some() => {
+                    if let Some(i) = control {
+                        match local {
+                            b"tag" => fields[i].tag = Self::xml_attribute(&e, b"val"),
+                            b"alias" => fields[i].title = Self::xml_attribute(&e, b"val"),
+                            b"showingPlcHdr" if on(&e) => placeholders.push(i),
+                            b"text" => fields[i].kind = "text",
+                            b"date" => fields[i].kind = "date",
+                            b"dropDownList" => fields[i].kind = "drop_down",
+                            b"comboBox" => fields[i].kind = "combo_box",
+                            b"picture" => fields[i].kind = "picture",
+                            b"checkbox" => {
+                                fields[i].kind = "checkbox";
+                                fields[i].value = Some("false".to_string());
+                            }
+                            b"checked" => fields[i].value = Some(on(&e).to_string()),
+                            b"docPartObj" | b"docPartList" => building_blocks.push(i),
+                            _ => {}
+                        }
+                    }
+                }
+                b"t" => in_text = !empty,
+                b"fldChar" => match Self::xml_attribute(&e, b"fldCharType").as_deref() {
+                    Some("begin") => complex.push((None, false)),
+                    Some("separate") => {
+                        if let Some(open) = complex.last_mut() {
+                            open.1 = true;
+                        }
+                    }
+                    Some("end") => {
+                        complex.pop();
+                    }
+                    _ => {}
+                },
+                b"ffData" if !empty => {
+                    form_data = Some(fields.len());
+                    fields.push(DocxFormField { tag: None, title: None, kind: "text", value: None });
+                }
+                local if form_data.is_some() => {
+                    if let Some(i) = form_data {
+                        match local {
+                            b"name" => fields[i].tag = Self::xml_attribute(&e, b"val"),
+                            b"textInput" => {
+                                fields[i].value = Some(String::new());
+                                if let Some(open) = complex.last_mut() {
+                                    open.0 = Some(i);
+                                }
+                            }
+                            b"checkBox" => {
+                                fields[i].kind = "checkbox";
+                                fields[i].value = Some("false".to_string());
+                            }
+                            // A later `w:checked` overrides the default state
+                            b"default" if fields[i].kind == "checkbox" => fields[i].value = Some(on(&e).to_string()),
+                            b"checked" => fields[i].value = Some(on(&e).to_string()),
+                            b"ddList" => fields[i].kind = "drop_down",
+                            b"result" => list.0 = Self::xml_attribute(&e, b"val").and_then(|v| v.parse().ok()).unwrap_or(0),
+                            b"listEntry" => list.1.extend(Self::xml_attribute(&e, b"val")),
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+            if !empty {
+                depth = level;
+            }
+        }
+
+        fields
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| !building_blocks.contains(i))
+            .map(|(_, mut field)| {
+                field.value = field.value.map(|value| value.trim().to_string());
+                field
+            })
+            .collect()
+    }
+
+    /// Add text to the values of the open content controls and, once its
+    /// result has started, the innermost legacy text field. A check box's
+    /// value is its state, not the box character it shows.
+    fn push_docx_field_text(fields: &mut [DocxFormField], controls: &[usize], complex: &[(Option<usize>, bool)], text: &str) {
+        let field = match complex.last() {
+            Some(&(Some(i), true)) => Some(i),
+            _ => None,
+        };
+        for &i in controls.iter().chain(field.iter()) {
+            if fields[i].kind != "checkbox" {
+                fields[i].value.get_or_insert_default().push_str(text);
+            }
+        }
+    }
+
+
+    /// Write an Office Math element as LaTeX-ish text: fractions, scripts,
+    /// radicals, n-ary operators, accents and matrices become their LaTeX
+    /// commands, while symbols are kept as the Unicode characters they are
+    fn omml_latex(node: &OmmlNode) -> String {
+        let child = |name: &str| node.children.iter().find(|child| child.name == name);
+        let part = |name: &str| child(name).map(Self::omml_latex).unwrap_or_default();
+        let parts = |name: &str| -> Vec<String> {
+            node.children.iter().filter(|child| child.name == name).map(Self::omml_latex).collect()
+        };
+        // A property from the element's `*Pr` child, e.g. `m:chr` in `m:naryPr`
+        let property = |name: &str| -> Option<Option<&str>> {
+            let properties = node.children.iter().find(|child| child.name.ends_with("Pr"))?;
+            let property = properties.children.iter().find(|child| child.name == name)?;
+            Some(property.val.as_deref())
+        };
+        let hidden = |name: &str| property(name).is_some_and(|val| val.is_none_or(|val| val == "1" || val == "on"));
+        let script = |mark: char, text: String| if text.is_empty() { text } else { format!("{}{{{}}}", mark, text) };
+
+        match node.name.as_str() {
+            "t" => node.text.clone(),
+            "f" => match property("type").flatten() {
+                Some("lin") => format!("{}/{}", part("num"), part("den")),
+                Some("noBar") => format!("\\binom{{{}}}{{{}}}", part("num"), part("den")),
+                _ => format!("\\frac{{{}}}{{{}}}", part("num"), part("den")),
+            },
+            "sSup" => format!("{}{}", part("e"), script('^', part("sup"))),
+            "sSub" => format!("{}{}", part("e"), script('_', part("sub"))),
+            "sSubSup" => format!("{}{}{}", part("e"), script('_', part("sub")), script('^', part("sup"))),
+            "sPre" => format!("{{}}{}{}{}", script('_', part("sub")), script('^', part("sup")), part("e")),
+            "rad" => match part("deg") {
+                degree if degree.is_empty() || hidden("degHide") => format!("\\sqrt{{{}}}", part("e")),
+                degree => format!("\\sqrt[{}]{{{}}}", degree, part("e")),
+            },
+            "d" => {
+                // Delimiters default to parentheses, separators to a bar
+                let begin = property("begChr").map_or(Some("("), |val| val).unwrap_or_default();
+                let end = property("endChr").map_or(Some(")"), |val| val).unwrap_or_default();
+                let separator = property("sepChr").map_or(Some("|"), |val| val).unwrap_or_default();
+                format!("{}{}{}", begin, parts("e").join(separator), end)
+            }
+            "nary" => {
+                let operator = match property("chr").flatten().unwrap_or("∫") {
+                    "∑" => "\\sum",
+                    "∏" => "\\prod",
+                    "∐" => "\\coprod",
+                    "∫" => "\\int",
+                    "∬" => "\\iint",
+                    "∭" => "\\iiint",
+                    "∮" => "\\oint",
+                    "⋃" => "\\bigcup",
+                    "⋂" => "\\bigcap",
+                    other => other,
+                };
+                let sub = if hidden("subHide") { String::new() } else { script('_', part("sub")) };
+                let sup = if hidden("supHide") { String::new() } else { script('^', part("sup")) };
+                format!("{}{}{} {}", operator, sub, sup, part("e"))
+            }
+            "func" => {
+                let name = part("fName");
+                const FUNCTIONS: [&str; 14] =
+                    ["sin", "cos", "tan", "cot", "sec", "csc", "log", "ln", "exp", "lim", "max", "min", "sup", "inf"];
+                match FUNCTIONS.iter().find(|function| name.starts_with(*function)) {
+                    Some(_) => format!("\\{} {}", name, part("e")),
+                    None => format!("{} {}", name, part("e")),
+                }
+            }
+            "limLow" => format!("{}{}", part("e"), script('_', part("lim"))),
+            "limUpp" => format!("{}{}", part("e"), script('^', part("lim"))),
+            "acc" => {
+                let command = match property("chr").flatten().unwrap_or("\u{302}") {
+                    "\u{302}" => "hat",
+                    "\u{303}" => "tilde",
+                    "\u{304}" | "\u{305}" => "bar",
+                    "\u{307}" => "dot",
+                    "\u{308}" => "ddot",
+                    "\u{20D7}" => "vec",
+                    _ => return part("e"),
+                };
+                format!("\\{}{{{}}}", command, part("e"))
+            }
+            "bar" => match property("pos").flatten() {
+                Some("top") => format!("\\overline{{{}}}", part("e")),
+                _ => format!("\\underline{{{}}}", part("e")),
+            },
+            "groupChr" => match property("chr").flatten().unwrap_or("\u{23DF}") {
+                "\u{23DF}" => format!("\\underbrace{{{}}}", part("e")),
+                "\u{23DE}" => format!("\\overbrace{{{}}}", part("e")),
+                _ => part("e"),
+            },
+            "m" => {
+                let rows: Vec<String> = node
+                    .children
+                    .iter()
+                    .filter(|row| row.name == "mr")
+                    .map(|row| {
+                        let cells: Vec<String> = row.children.iter().map(Self::omml_latex).collect();
+                        cells.join(" & ")
+                    })
+                    .collect();
+                format!("\\begin{{matrix}} {} \\end{{matrix}}", rows.join(" \\\\ "))
+            }
+            "eqArr" => parts("e").join(" \\\\ "),
+            // Properties only style what they belong to
+            name if name.ends_with("Pr") => String::new(),
+            _ => node.children.iter().map(Self::omml_latex).collect(),
+        }
+    }
+
+    /// Read a part of a ZIP package under the resource limits, or `None`
+    /// when the package has no such part
+    fn read_zip_part<R: std::io::Read + std::io::Seek>(
+        &self,
+        archive: &mut zip::ZipArchive<R>,
+        name: &str,
+        budget: &mut u64,
+        format: &str,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let mut contents = Vec::new();
+        Ok(self
+            .read_zip_part_into(archive, name, budget, format, &mut contents)?
+            .then_some(contents))
+    }
+
+    /// `read_zip_part` into a buffer the caller reuses; false when the
+    /// package has no such part
+    fn read_zip_part_into<R: std::io::Read + std::io::Seek>(
+        &self,
+        archive: &mut zip::ZipArchive<R>,
+        name: &str,
+        budget: &mut u64,
+        format: &str,
+        contents: &mut Vec<u8>,
+    ) -> Result<bool, Error> {
+        let Ok(mut file) = archive.by_name(name) else {
+            return Ok(false);
+        };
+        let compressed_size = file.compressed_size();
+        self.read_decompressed_into(&mut file, Some(compressed_size), budget, format, contents)?;
+        Ok(true)
+    }
+
+    /// The relationships of an OOXML part (see `ooxml_relationships`), empty
+    /// when it has none
+    fn read_ooxml_relationships<R: std::io::Read + std::io::Seek>(
+        &self,
+        archive: &mut zip::ZipArchive<R>,
+        part: &str,
+        budget: &mut u64,
+        format: &str,
+    ) -> Result<Vec<(String, String, String)>, Error> {
+        let rels = self.read_zip_part(archive, &Self::ooxml_rels_path(part), budget, format)?;
+        Ok(rels
+            .map(|rels| Self::ooxml_relationships(&String::from_utf8_lossy(&rels), part))
+            .unwrap_or_default())
+    }
+
+    /// OCR the pictures an OOXML part refers to, for `ocr_embedded_images`.
+    /// Returns an `[Image: text]` line per picture in document order, with
+    /// the body paragraph it sits in (see `ooxml_image_refs`). Pictures
+    /// that aren't raster images or hold no text are left out.
+    fn ocr_ooxml_images<R: std::io::Read + std::io::Seek>(
+        &self,
+        archive: &mut zip::ZipArchive<R>,
+        part: &str,
+        xml: &str,
+        budget: &mut u64,
+        format: &str,
+    ) -> Result<Vec<(Option<usize>, String)>, Error> {
+        self.ocr_ooxml_image_refs(archive, part, Self::ooxml_image_refs(xml), budget, format)
+    }
+
+    /// OCR the pictures of an OOXML part as `ocr_ooxml_images` does, given
+    /// the references already read from it
+    fn ocr_ooxml_image_refs<R: std::io::Read + std::io::Seek>(
+        &self,
+        archive: &mut zip::ZipArchive<R>,
+        part: &str,
+        refs: Vec<(Option<usize>, String)>,
+        budget: &mut u64,
+        format: &str,
+    ) -> Result<Vec<(Option<usize>, String)>, Error> {
+        if refs.is_empty() {
+            return Ok(Vec::new());
+        }
+        let relationships = self.read_ooxml_relationships(archive, part, budget, format)?;
+
+        // A picture used several times is only recognized once
+        let mut recognized: std::collections::HashMap<String, Option<String>> = std::collections::HashMap::new();
+        let mut images = Vec::new();
+        for (paragraph, id) in refs {
+            let Some((_, _, target)) = relationships
+                .iter()
+                .find(|(rel_id, kind, _)| *rel_id == id && kind.ends_with("/image"))
+            else {
+                continue;
+            };
+            if !recognized.contains_key(target) {
+                let text = match self.read_zip_part(archive, target, budget, format)? {
+                    Some(contents) => self.ocr_embedded_image(target, &contents)?,
+                    None => None,
+                };
+                recognized.insert(target.clone(), text);
+            }
+            if let Some(Some(text)) = recognized.get(target) {
+                images.push((paragraph, text.clone()));
+            }
+        }
+        Ok(images)
+    }
+
+    /// OCR one picture from a document package to an `[Image: text]` line,
+    /// its text run together on one line. A picture that can't be recognized
+    /// is noted in place of its text rather than failing the document.
+    fn ocr_embedded_image(&self, name: &str, contents: &[u8]) -> Result<Option<String>, Error> {
+        if !Self::is_raster_image(name, contents) {
+            return Ok(None);
+        }
+        match self.ocr_text(contents) {
+            Ok(text) if text.is_empty() => Ok(None),
+            Ok(text) => Ok(Some(format!("[Image: {}]", text.split_whitespace().collect::<Vec<_>>().join(" ")))),
+            Err(e) if ParserError::is_resource_limit(&e) => Err(e),
+            Err(e) => Ok(Some(format!("[Image: (failed to OCR: {})]", e))),
+        }
+    }
+
+    /// Whether a picture from a document package is a raster image OCR can
+    /// read, rather than e.g. an EMF or SVG drawing
+    fn is_raster_image(name: &str, contents: &[u8]) -> bool {
+        matches!(
+            FormatDetector::detect(Some(name), Some(contents)),
+            FileFormat::Png | FileFormat::Jpeg | FileFormat::Tiff | FileFormat::Bmp | FileFormat::Gif | FileFormat::Avif
+        )
+    }
+
+    /// Read a DOCX's reviewer comments with where they are anchored in
+    /// `document`, its `word/document.xml`. A document without a comments
+    /// part has none.
+    fn read_docx_comments<R: std::io::Read + std::io::Seek>(
+        &self,
+        archive: &mut zip::ZipArchive<R>,
+        document: &str,
+        budget: &mut u64,
+    ) -> Result<Vec<DocxComment>, Error> {
+        let mut comments = self.read_docx_comment_part(archive, budget)?;
+        if !comments.is_empty() {
+            Self::docx_comment_anchors(document, &mut comments);
+        }
+        Ok(comments)
+    }
+
+    /// Read a DOCX's reviewer comments without their anchors (see
+    /// `docx_comments_xml`). A document without a comments part has none.
+    fn read_docx_comment_part<R: std::io::Read + std::io::Seek>(
+        &self,
+        archive: &mut zip::ZipArchive<R>,
+        budget: &mut u64,
+    ) -> Result<Vec<DocxComment>, Error> {
+        let part_name = self
+            .read_ooxml_relationships(archive, "word/document.xml", budget, "DOCX")?
+            .into_iter()
+            .find(|(_, kind, _)| kind.ends_with("/comments"))
+            .map_or_else(|| "word/comments.xml".to_string(), |(_, _, target)| target);
+        let Some(xml) = self.read_zip_part(archive, &part_name, budget, "DOCX")? else {
+            return Ok(Vec::new());
+        };
+        Ok(Self::docx_comments_xml(&String::from_utf8_lossy(&xml)))
+    }
+
+    /// A comment as an inline `[Comment by author, date: text]` line
+    fn docx_comment_line(comment: &DocxComment) -> String {
+        let date = comment.date.as_deref().map(|date| date.split('T').next().unwrap_or(date));
+        let by = match (comment.author.as_deref(), date) {
+            (Some(author), Some(date)) => format!(" by {}, {}", author, date),
+            (Some(author), None) => format!(" by {}", author),
+            (None, Some(date)) => format!(", {}", date),
+            (None, None) => String::new(),
+        };
+        format!("[Comment{}: {}]", by, comment.text.split_whitespace().collect::<Vec<_>>().join(" "))
+    }
+
+    /// List the reviewer comments of a DOCX - exposed to Ruby
+    ///
+    /// Returns `[{id:, author:, date:, text:, anchor:}]` in the order of
+    /// `word/comments.xml`, where `anchor` is the commented text (empty if
+    /// the comment marks a point) and `date` is as recorded, e.g.
+    /// `2024-01-05T10:00:00Z`. Missing authors and dates are nil.
+    fn docx_comments(&self, data: Vec<u8>) -> Result<RArray, Error> {
+        let ruby = Ruby::get().unwrap();
+        self.check_zip_limits(&data, "DOCX")?;
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(&data))
+            .map_err(|e| Self::runtime_error("Failed to open DOCX as ZIP", e))?;
+        let mut budget = self.config.max_size as u64;
+        let document = self
+            .read_zip_part(&mut archive, "word/document.xml", &mut budget, "DOCX")?
+            .map(|xml| String::from_utf8_lossy(&xml).into_owned())
+            .unwrap_or_default();
+        let result = ruby.ary_new();
+        for comment in self.read_docx_comments(&mut archive, &document, &mut budget)? {
+            let entry = ruby.hash_new();
+            entry.aset(ruby.to_symbol("id"), comment.id)?;
+            entry.aset(ruby.to_symbol("author"), comment.author)?;
+            entry.aset(ruby.to_symbol("date"), comment.date)?;
+            entry.aset(ruby.to_symbol("text"), comment.text)?;
+            entry.aset(ruby.to_symbol("anchor"), comment.anchor)?;
+            result.push(entry)?;
+        }
+        Ok(result)
+    }
+
+    /// List the content controls and legacy form fields of a DOCX - exposed to Ruby
+    ///
+    /// Returns `[{tag:, title:, type:, value:}]` in document order, for
+    /// pulling the filled-in values out of templated forms. See
+    /// `docx_form_fields_xml` for what is listed.
+    fn docx_form_fields(&self, data: Vec<u8>) -> Result<RArray, Error> {
+        let ruby = Ruby::get().unwrap();
+        self.check_zip_limits(&data, "DOCX")?;
+        let result = ruby.ary_new();
+        let Some(xml) = self.read_docx_document(&data)? else {
+            return Ok(result);
+        };
+        for field in Self::docx_form_fields_xml(&xml) {
+            let entry = ruby.hash_new();
+            entry.aset(ruby.to_symbol("tag"), field.tag)?;
+            entry.aset(ruby.to_symbol("title"), field.title)?;
+            entry.aset(ruby.to_symbol("type"), field.kind)?;
+            entry.aset(ruby.to_symbol("value"), field.value)?;
+            result.push(entry)?;
+        }
+        Ok(result)
+    }
+
+    /// Read the `word/document.xml` part of a DOCX under the resource
+    /// limits, or `None` when the package has none
+    fn read_docx_document(&self, data: &[u8]) -> Result<Option<String>, Error> {
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(data))
+            .map_err(|e| Self::runtime_error("Failed to open DOCX as ZIP", e))?;
+        let mut budget = self.config.max_size as u64;
+        let xml = self.read_zip_part(&mut archive, "word/document.xml", &mut budget, "DOCX")?;
+        Ok(xml.map(|xml| String::from_utf8_lossy(&xml).into_owned()))
+    }
+
+    /// Read what the XLSX methods take from each worksheet's own parts,
+    /// by sheet name, opening the package once: its hidden rows and columns
+    /// unless `include_hidden` is on (see `xlsx_hidden_lines`), with `links`
+    /// its cell hyperlinks, and with `images` the OCR text of the pictures
+    /// in its drawings (see `ocr_ooxml_images`). Links to a place in the
+    /// workbook alone are left out.
+    fn read_xlsx_sheet_extras(
+        &self,
+        data: &[u8],
+        links: bool,
+        images: bool,
+    ) -> Result<std::collections::HashMap<String, XlsxSheetExtras>, Error> {
+        let mut extras = std::collections::HashMap::new();
+        let hidden = !self.config.include_hidden;
+        if !hidden && !links && !images {
+            return Ok(extras);
+        }
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(data))
+            .map_err(|e| Self::runtime_error("Failed to open XLSX as ZIP", e))?;
+        let mut budget = self.config.max_size as u64;
+
+        let mut contents = Vec::new();
+        for (name, sheet) in self.read_xlsx_sheet_parts(&mut archive, &mut budget)? {
+            let mut sheet_extras = XlsxSheetExtras::default();
+            let mut hyperlinks = Vec::new();
+            if (hidden || links) && self.read_zip_part_into(&mut archive, &sheet, &mut budget, "XLSX", &mut contents)? {
+                let xml = String::from_utf8_lossy(&contents);
+                if hidden {
+                    sheet_extras.hidden = Self::xlsx_hidden_lines(&xml);
+                }
+                if links {
+                    hyperlinks = Self::xlsx_hyperlinks(&xml);
+                }
+            }
+            // Hyperlink targets and drawings are both found through the sheet's relationships
+            let rels = if images || !hyperlinks.is_empty() {
+                self.read_zip_part(&mut archive, &Self::ooxml_rels_path(&sheet), &mut budget, "XLSX")?
+                    .map(|rels| String::from_utf8_lossy(&rels).into_owned())
+                    .unwrap_or_default()
+            } else {
+                String::new()
+            };
+
+            let targets = Self::ooxml_external_targets(&rels);
+            for (reference, id, location) in hyperlinks {
+                let Some((_, target)) = targets.iter().find(|(rel_id, _)| *rel_id == id) else {
+                    continue;
+                };
+                let (first, last) = reference.split_once(':').unwrap_or((&reference, &reference));
+                let (Some(start), Some(end)) = (Self::xlsx_cell_position(first), Self::xlsx_cell_position(last)) else {
+                    continue;
+                };
+                let target = match location {
+                    Some(location) => format!("{}#{}", target, location),
+                    None => target.clone(),
+                };
+                sheet_extras.links.push((calamine::Dimensions::new(start, end), target));
+            }
+
+            if images {
+                for (_, kind, drawing) in Self::ooxml_relationships(&rels, &sheet) {
+                    if !kind.ends_with("/drawing") {
+                        continue;
+                    }
+                    let Some(xml) = self.read_zip_part(&mut archive, &drawing, &mut budget, "XLSX")? else {
+                        continue;
+                    };
+                    let found = self.ocr_ooxml_images(&mut archive, &drawing, &String::from_utf8_lossy(&xml), &mut budget, "XLSX")?;
+                    sheet_extras.images.extend(found.into_iter().map(|(_, text)| text));
+                }
+            }
+            extras.insert(name, sheet_extras);
+        }
+        Ok(extras)
+    }
+
+    /// The hidden rows and columns of each worksheet of an XLSX by sheet
+    /// name (see `xlsx_hidden_lines`), read when `include_hidden` is off
+    fn read_xlsx_hidden_lines(
+        &self,
+        data: &[u8],
+    ) -> Result<std::collections::HashMap<String, (std::collections::HashSet<u32>, std::collections::HashSet<u32>)>, Error> {
+        Ok(self
+            .read_xlsx_sheet_extras(data, false, false)?
+            .into_iter()
+            .map(|(name, extras)| (name, extras.hidden))
+            .collect())
+    }
+
+    /// The worksheets of an XLSX as `(name, part)`, in workbook order
+    fn read_xlsx_sheet_parts<R: std::io::Read + std::io::Seek>(
+        &self,
+        archive: &mut zip::ZipArchive<R>,
+        budget: &mut u64,
+    ) -> Result<Vec<(String, String)>, Error> {
+        let workbook_part = "xl/workbook.xml";
+        let Some(workbook) = self.read_zip_part(archive, workbook_part, budget, "XLSX")? else {
+            return Ok(Vec::new());
+        };
+        let sheets = self.read_ooxml_relationships(archive, workbook_part, budget, "XLSX")?;
+
+        Ok(Self::xlsx_sheet_refs(&String::from_utf8_lossy(&workbook))
+            .into_iter()
+            .filter_map(|(name, id)| {
+                sheets
+                    .iter()
+                    .find(|(rel_id, _, _)| *rel_id == id)
+                    .map(|(_, _, sheet)| (name, sheet.clone()))
+            })
+            .collect())
+    }
+
+    /// The sheets of an XLSX picked by `sheets`, in workbook order, leaving
+    /// out hidden and very hidden ones when `include_hidden` is off
+    fn xlsx_sheet_names<RS: std::io::Read + std::io::Seek>(
+        &self,
+        workbook: &calamine::Xlsx<RS>,
+        sheets: &SheetSelection,
+    ) -> Vec<String> {
+        use calamine::{Reader, SheetVisible};
+
+        let names = sheets.names(&workbook.sheet_names());
+        if self.config.include_hidden {
+            return names;
+        }
+        names
+            .into_iter()
+            .filter(|name| {
+                workbook
+                    .sheets_metadata()
+                    .iter()
+                    .any(|sheet| sheet.name == *name && sheet.visible == SheetVisible::Visible)
+            })
+            .collect()
+    }
+
+    /// Parse Visio (.vsdx) diagrams - exposed to Ruby
+    fn parse_vsdx(&self, data: Vec<u8>) -> Result<String, Error> {
+        use std::io::Cursor;
+        use zip::ZipArchive;
+
+        let cursor = Cursor::new(data);
+        let mut archive = ZipArchive::new(cursor)
+            .map_err(|e| Self::runtime_error("Failed to open VSDX as ZIP", e))?;
+
+        // Collect page parts (visio/pages/page1.xml, ...) in page order
+        let mut page_numbers = Vec::new();
+        for i in 0..archive.len() {
+            let file = match archive.by_index(i) {
+                Ok(file) => file,
+                Err(_) => continue,
+            };
+            if let Some(num_str) = file
+                .name()
+                .strip_prefix("visio/pages/page")
+                .and_then(|s| s.strip_suffix(".xml"))
+            {
+                if let Ok(num) = num_str.parse::<usize>() {
+                    page_numbers.push((num, i));
+                }
+            }
+        }
+        page_numbers.sort_by_key(|&(num, _)| num);
+
+        let mut pages = Vec::new();
+        let mut budget = self.config.max_size as u64;
+        for (_, index) in page_numbers {
+            let mut file = match archive.by_index(index) {
+                Ok(file) => file,
+                Err(_) => continue,
+            };
+            let compressed_size = file.compressed_size();
+            let contents = self.read_decompressed(&mut file, Some(compressed_size), &mut budget, "VSDX")?;
+            let text = self.extract_text_from_visio_page(&String::from_utf8_lossy(&contents));
+            if !text.is_empty() {
+                pages.push(text);
+            }
+        }
+
+        Ok(pages.join("\n\n"))
+    }
+
+    /// Extract the text of each shape's `<Text>` element, one shape per line
+    fn extract_text_from_visio_page(&self, xml_content: &str) -> String {
+        use quick_xml::events::Event;
+        use quick_xml::Reader;
+
+        let mut reader = Reader::from_str(xml_content);
+        let mut buf = Vec::new();
+        let mut shapes = Vec::new();
+        let mut current = String::new();
+        let mut text_depth = 0;
+
+        loop {
+            match reader.read_event_into(&mut buf) {
+                Ok(Event::Start(ref e)) => {
+                    if text_depth > 0 || e.name().local_name().as_ref() == b"Text" {
+                        text_depth += 1;
+                    }
+                }
+                Ok(Event::Text(e)) => {
+                    if text_depth > 0 {
+                        if let Ok(text) = e.decode() {
+                            current.push_str(&text);
+                        }
+                    }
+                }
+                Ok(Event::End(_)) => {
+                    if text_depth > 0 {
+                        text_depth -= 1;
+                        if text_depth == 0 {
+                            let text = current.split_whitespace().collect::<Vec<_>>().join(" ");
+                            if !text.is_empty() {
+                                shapes.push(text);
+                            }
+                            current.clear();
+                        }
+                    }
+                }
+                Ok(Event::Eof) | Err(_) => break,
+                _ => {}
+            }
+            buf.clear();
+        }
+
+        shapes.join("\n")
+    }
+
+    /// Parse OneNote section (.one) files - exposed to Ruby
+    fn parse_onenote(&self, data: Vec<u8>) -> Result<String, Error> {
+        use onenote_parser::page::PageContent;
+
+        let section = Self::with_temp_file(&data, "one", |path| {
+            onenote_parser::Parser::new()
+                .parse_section(path)
+                .map_err(|e| Self::runtime_error("Failed to parse OneNote section", e))
+        })?;
+
+        let mut pages = Vec::new();
+        for series in section.page_series() {
+            for page in series.pages() {
+                let mut lines = Vec::new();
+                if let Some(title) = page.title_text() {
+                    lines.push(title.trim().to_string());
+                }
+                for content in page.contents() {
+                    if let PageContent::Outline(outline) = content {
+                        Self::collect_onenote_outline(outline.items(), &mut lines);
+                    }
+                }
+                lines.retain(|line| !line.is_empty());
+                if !lines.is_empty() {
+                    pages.push(lines.join("\n"));
+                }
+            }
+        }
+
+        Ok(pages.join("\n\n"))
+    }
+
+    /// Collect rich-text content from a OneNote outline, including nested items
+    fn collect_onenote_outline(
+        items: &[onenote_parser::contents::OutlineItem],
+        lines: &mut Vec<String>,
+    ) {
+        use onenote_parser::contents::{Content, OutlineItem};
+
+        for item in items {
+            match item {
+                OutlineItem::Element(element) => {
+                    for content in element.contents() {
+                        if let Content::RichText(text) = content {
+                            lines.push(text.text().trim().to_string());
+                        }
+                    }
+                    Self::collect_onenote_outline(element.children(), lines);
+                }
+                OutlineItem::Group(group) => Self::collect_onenote_outline(group.outlines(), lines),
+            }
+        }
+    }
+
+    /// Extract VBA macro source from Office documents - exposed to Ruby
+    ///
+    /// Takes the document's data, or `path:` to read it from a file. The VBA
+    /// project is read from `vbaProject.bin` in OOXML packages (.docm, .xlsm,
+    /// .pptm) or from the compound file itself for legacy .doc and .xls.
+    /// Returns a Hash of module name to decompressed source code, empty when
+    /// the document has no macros.
+    fn extract_macros(&self, args: &[Value]) -> Result<RHash, Error> {
+        use std::io::Read;
+
+        let ruby = Ruby::get().unwrap();
+        let args = scan_args::scan_args::<(), (Option<Vec<u8>>,), (), (), RHash, ()>(args)?;
+        let (data,) = args.optional;
+        let kwargs = scan_args::get_kwargs::<_, (), (Option<String>,), ()>(args.keywords, &[], &["path"])?;
+        let (path,) = kwargs.optional;
+
+        let data = match (data, path) {
+            (Some(data), None) => data,
+            (None, Some(path)) => {
+                let mut data = Vec::new();
+                self.open_within_size(&path)?
+                    .read_to_end(&mut data)
+                    .map_err(|e| Self::io_error("Failed to read file", e))?;
+                data
+            }
+            (Some(_), Some(_)) => return Err(Self::argument_error("Pass either data or path:, not both")),
+            (None, None) => return Err(Self::argument_error("Pass the document data or path:")),
+        };
+        if data.is_empty() {
+            return Err(Self::argument_error("Data cannot be empty"));
+        }
+
+        let macros = ruby.hash_new();
+        if let Some(project) = self.read_vba_project(&data)? {
+            let mut names = project.get_module_names();
+            names.sort_unstable();
+            for name in names {
+                let source = project
+                    .get_module(name)
+                    .map_err(|e| Self::runtime_error(&format!("Failed to decompress VBA module {}", name), e))?;
+                macros.aset(name, source)?;
+            }
+        }
+        Ok(macros)
+    }
+
+    /// Open the VBA project of an OOXML package or OLE compound file, or None
+    /// when the document carries no macros
+    fn read_vba_project(&self, data: &[u8]) -> Result<Option<calamine::vba::VbaProject>, Error> {
+        use calamine::vba::VbaProject;
+        use std::io::Cursor;
+
+        if data.starts_with(b"PK\x03\x04") {
+            let mut archive = zip::ZipArchive::new(Cursor::new(data))
+                .map_err(|e| Self::runtime_error("Failed to open document as ZIP", e))?;
+            let index = (0..archive.len()).find(|&i| {
+                archive
+                    .name_for_index(i)
+                    .is_some_and(|name| name.to_ascii_lowercase().ends_with("vbaproject.bin"))
+            });
+            let Some(index) = index else {
+                return Ok(None);
+            };
+            let mut file = archive
+                .by_index(index)
+                .map_err(|e| Self::runtime_error("Failed to read vbaProject.bin", e))?;
+            let compressed_size = file.compressed_size();
+            let mut budget = self.config.max_size as u64;
+            let project = self.read_decompressed(&mut file, Some(compressed_size), &mut budget, "vbaProject.bin")?;
+            VbaProject::new(&mut Cursor::new(&project), project.len())
+                .map(Some)
+                .map_err(|e| Self::runtime_error("Failed to read VBA project", e))
+        } else if data.starts_with(&[0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1]) {
+            // Word keeps the project under Macros/VBA and Excel under
+            // _VBA_PROJECT_CUR/VBA; both hold a UTF-16 named _VBA_PROJECT stream
+            let marker: Vec<u8> = "_VBA_PROJECT"
+                .encode_utf16()
+                .flat_map(|unit| unit.to_le_bytes())
+                .collect();
+            if !data.windows(marker.len()).any(|window| window == marker.as_slice()) {
+                return Ok(None);
+            }
+            VbaProject::new(&mut Cursor::new(data), data.len())
+                .map(Some)
+                .map_err(|e| Self::runtime_error("Failed to read VBA project", e))
+        } else {
+            Err(Self::argument_error("Macros can only be extracted from OOXML or OLE Office documents"))
+        }
+    }
+
+    /// Parse Excel files - exposed to Ruby
+    ///
+    /// `sheets:` limits extraction to some worksheets: a name, an index
+    /// from 0, a Regexp matched against the names, or an Array of these.
+    /// Sheets are kept in workbook order; entries matching none are ignored.
+    fn parse_xlsx(&self, args: &[Value]) -> Result<String, Error> {
+        let args = scan_args::scan_args::<(Vec<u8>,), (), (), (), RHash, ()>(args)?;
+        let (data,) = args.required;
+        let kwargs = scan_args::get_kwargs::<_, (), (Option<Value>,), ()>(
+            args.keywords,
+            &[],
+            &["sheets"],
+        )?;
+        let (sheets,) = kwargs.optional;
+
+        self.xlsx_text(data, &Self::sheet_selection(sheets)?)
+    }
+
+    /// Read the `sheets:` option of `parse_xlsx`. A Regexp is rebuilt from
+    /// its source for the regex crate, keeping the `i`, `x` and `m` flags.
+    fn sheet_selection(value: Option<Value>) -> Result<SheetSelection, Error> {
+        let Some(value) = value.filter(|value| !value.is_nil()) else {
+            return Ok(SheetSelection::All);
+        };
+        let values = match RArray::from_value(value) {
+            Some(list) => list.to_vec::<Value>()?,
+            None => vec![value],
+        };
+
+        let matchers = values
+            .into_iter()
+            .map(|value| {
+                if let Some(regexp) = magnus::RRegexp::from_value(value) {
+                    let source: String = value.funcall("source", ())?;
+                    let options = regexp.options();
+                    regex::RegexBuilder::new(&source)
+                        .case_insensitive(options.is_ignorecase())
+                        .ignore_whitespace(options.is_extend())
+                        .dot_matches_new_line(options.is_multiline())
+                        .build()
+                        .map(SheetMatcher::Pattern)
+                        .map_err(|e| Self::argument_error(&format!("Unsupported sheets: pattern: {}", e)))
+                } else if let Some(name) = RString::from_value(value) {
+                    Ok(SheetMatcher::Name(name.to_string()?))
+                } else {
+                    usize::try_from(i64::try_convert(value)?)
+                        .map(SheetMatcher::Index)
+                        .map_err(|_| Self::argument_error("XLSX sheet indexes start at 0"))
+                }
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok(SheetSelection::Matching(matchers))
+    }
+
+    /// Extract the text of the selected worksheets of an XLSX, one line of
+    /// tab-separated cells per row (see `xlsx_sheet_text`). Hidden sheets
+    /// are left out when `include_hidden` is off. The `xlsx_cell_separator`,
+    /// `xlsx_trailing_separator`, `xlsx_row_separator`, `xlsx_sheet_header`
+    /// and `xlsx_skip_empty_rows` options change the layout.
+    fn xlsx_text(&self, data: Vec<u8>, sheets: &SheetSelection) -> Result<String, Error> {
+        use calamine::{Reader, Xlsx};
+        use std::io::Cursor;
+
+        self.check_zip_limits(&data, "XLSX")?;
+        let extras = self.read_xlsx_sheet_extras(&data, self.config.xlsx_links, self.config.ocr_embedded_images)?;
+        let no_extras = XlsxSheetExtras::default();
+        let cursor = Cursor::new(data);
+        match Xlsx::new(cursor) {
+            Ok(mut workbook) => {
+                let mut result = String::new();
+
+                let cell_separator = self.config.xlsx_cell_separator.as_str();
+                let row_separator = self.config.xlsx_row_separator.as_str();
+                for sheet_name in self.xlsx_sheet_names(&workbook, sheets) {
+                    if let Some(header) = &self.config.xlsx_sheet_header {
+                        result.push_str(&header.replace("%{name}", &sheet_name));
+                        result.push_str(row_separator);
+                    }
+                    let sheet = extras.get(&sheet_name).unwrap_or(&no_extras);
+                    for row in self.xlsx_sheet_text(&mut workbook, &sheet_name, &sheet.hidden, &sheet.links) {
+                        if self.config.xlsx_skip_empty_rows && row.iter().all(String::is_empty) {
+                            continue;
+                        }
+                        result.push_str(&row.join(cell_separator));
+                        if self.config.xlsx_trailing_separator && !row.is_empty() {
+                            result.push_str(cell_separator);
+                        }
+                        result.push_str(row_separator);
+                    }
+                    for text in &sheet.images {
+                        result.push_str(text);
+                        result.push_str(row_separator);
+                    }
+                    result.push_str(row_separator);
+                }
+
+                Ok(result)
+            }
+            Err(e) => Err(Self::runtime_error("Failed to parse Excel file", e)),
+        }
+    }
+
+    /// The cells of a worksheet as text, row by row, with date cells as
+    /// ISO-8601 (see `xlsx_cell_text`). With `include_formulas` a formula
+    /// cell reads "value (=formula)", or just "=formula" when the workbook
+    /// holds no cached result. Cells covered by a merged range repeat the
+    /// value of its top-left cell. `hidden` rows and columns are left out.
+    /// With `xlsx_links` a hyperlink cell reads "text (url)", from the
+    /// worksheet's hyperlinks or a `HYPERLINK` formula.
+    fn xlsx_sheet_text<RS: std::io::Read + std::io::Seek>(
+        &self,
+        workbook: &mut calamine::Xlsx<RS>,
+        sheet_name: &str,
+        hidden: &(std::collections::HashSet<u32>, std::collections::HashSet<u32>),
+        links: &[(calamine::Dimensions, String)],
+    ) -> Vec<Vec<String>> {
+        use calamine::Reader;
+
+        let (hidden_rows, hidden_columns) = hidden;
+        // HYPERLINK formulas carry link targets too
+        let formulas = if self.config.include_formulas || self.config.xlsx_links {
+            workbook.worksheet_formula(sheet_name).ok()
+        } else {
+            None
+        };
+        let merges = workbook
+            .worksheet_merge_cells(sheet_name)
+            .and_then(|merges| merges.ok())
+            .unwrap_or_default();
+        let Ok(range) = workbook.worksheet_range(sheet_name) else {
+            return Vec::new();
+        };
+
+        // The formula range has its own origin, so cells are matched by
+        // absolute position
+        let (top, left) = range.start().unwrap_or((0, 0));
+        let mut rows = Vec::new();
+        for (r, row) in range.rows().enumerate() {
+            if hidden_rows.contains(&(top + r as u32)) {
+                continue;
+            }
+            let mut texts = Vec::new();
+            for (c, cell) in row.iter().enumerate() {
+                let position = (top + r as u32, left + c as u32);
+                if hidden_columns.contains(&position.1) {
+                    continue;
+                }
+                let cell = Self::xlsx_merge_anchor(&merges, position)
+                    .and_then(|anchor| range.get_value(anchor))
+                    .unwrap_or(cell);
+                let formula = formulas
+                    .as_ref()
+                    .and_then(|formulas| formulas.get_value(position))
+                    .filter(|formula| !formula.is_empty());
+
+                let mut text = Self::xlsx_cell_text(cell);
+                if self.config.xlsx_links {
+                    let target = links
+                        .iter()
+                        .find(|(cells, _)| cells.contains(position.0, position.1))
+                        .map(|(_, target)| target.clone())
+                        .or_else(|| formula.and_then(|formula| Self::xlsx_hyperlink_formula_target(formula)));
+                    match target {
+                        Some(target) if text.is_empty() => text = target,
+                        Some(target) if target != text => text = format!("{} ({})", text, target),
+                        _ => {}
+                    }
+                }
+
+                texts.push(match formula.filter(|_| self.config.include_formulas) {
+                    Some(formula) if matches!(cell, calamine::Data::Empty) => format!("={}", formula),
+                    Some(formula) => format!("{} (={})", text, formula),
+                    None => text,
+                });
+            }
+            rows.push(texts);
+        }
+        rows
+    }
+
+    /// Export one worksheet of an XLSX as CSV - exposed to Ruby
+    ///
+    /// `sheet:` picks the sheet as in `parse_xlsx`'s `sheets:` (the first
+    /// match is taken), defaulting to the first sheet. Cells read as in
+    /// `parse_xlsx`, and fields holding the separator, a quote or a line
+    /// break are quoted, with quotes doubled (RFC 4180). Rows end in CRLF.
+    /// `col_sep: "\t"` gives TSV.
+    fn xlsx_to_csv(&self, args: &[Value]) -> Result<String, Error> {
+        use calamine::{Reader, Xlsx};
+
+        let args = scan_args::scan_args::<(Vec<u8>,), (), (), (), RHash, ()>(args)?;
+        let (data,) = args.required;
+        let kwargs = scan_args::get_kwargs::<_, (), (Option<Value>, Option<String>), ()>(
+            args.keywords,
+            &[],
+            &["sheet", "col_sep"],
+        )?;
+        let (sheet, col_sep) = kwargs.optional;
+        let separator = match col_sep.as_deref().map(|sep| {
+            let mut chars = sep.chars();
+            (chars.next(), chars.next())
+        }) {
+            None => ',',
+            Some((Some(separator), None)) if !matches!(separator, '"' | '\r' | '\n') => separator,
+            _ => return Err(Self::argument_error("col_sep: must be one character other than a quote or line break")),
+        };
+        let selection = Self::sheet_selection(sheet)?;
+
+        self.check_zip_limits(&data, "XLSX")?;
+        let mut extras = self.read_xlsx_sheet_extras(&data, self.config.xlsx_links, false)?;
+        let mut workbook = Xlsx::new(std::io::Cursor::new(data))
+            .map_err(|e| Self::runtime_error("Failed to parse Excel file", e))?;
+        let Some(sheet_name) = self.xlsx_sheet_names(&workbook, &selection).into_iter().next() else {
+            return Err(Self::argument_error("No such sheet in the workbook"));
+        };
+
+        let sheet = extras.remove(&sheet_name).unwrap_or_default();
+        let mut csv = String::new();
+        for row in self.xlsx_sheet_text(&mut workbook, &sheet_name, &sheet.hidden, &sheet.links) {
+            let fields: Vec<String> = row.iter().map(|text| Self::csv_field(text, separator)).collect();
+            csv.push_str(&fields.join(&separator.to_string()));
+            csv.push_str("\r\n");
+        }
+        Ok(csv)
+    }
+
+    /// A CSV field, quoted when it holds the separator, a quote or a line
+    /// break
+    fn csv_field(text: &str, separator: char) -> String {
+        if text.contains(|c: char| c == separator || matches!(c, '"' | '\r' | '\n')) {
+            format!("\"{}\"", text.replace('"', "\"\""))
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// The top-left cell of the merged range covering `position`, unless
+    /// `position` is that cell or lies outside every merge
+    fn xlsx_merge_anchor(merges: &[calamine::Dimensions], position: (u32, u32)) -> Option<(u32, u32)> {
+        merges
+            .iter()
+            .find(|merge| merge.contains(position.0, position.1))
+            .map(|merge| merge.start)
+            .filter(|&start| start != position)
+    }
+
+    /// Where lines `first..=last` of a worksheet land in the arrays of
+    /// `parse_xlsx_structured`, which start at line `origin` and drop hidden
+    /// lines: the index of the first one kept and how many are kept
+    fn xlsx_kept_span(
+        hidden: &std::collections::HashSet<u32>,
+        origin: u32,
+        first: u32,
+        last: u32,
+    ) -> Option<(usize, usize)> {
+        let first = first.max(origin);
+        let start = (first..=last).find(|line| !hidden.contains(line))?;
+        let index = (origin..start).filter(|line| !hidden.contains(line)).count();
+        let count = (start..=last).filter(|line| !hidden.contains(line)).count();
+        Some((index, count))
+    }
+
+    /// Render a worksheet cell as text. Date cells, which hold serial day
+    /// numbers, come out as ISO-8601 dates, times or date-times, counted
+    /// from the workbook's 1900 or 1904 epoch; durations as ISO-8601
+    /// durations such as "PT5400S".
+    fn xlsx_cell_text(cell: &calamine::Data) -> String {
+        use calamine::Data;
+
+        match cell {
+            Data::DateTime(value) if value.is_duration() => match value.as_duration() {
+                Some(duration) => duration.to_string(),
+                None => value.to_string(),
+            },
+            Data::DateTime(value) => match value.as_datetime() {
+                // Serials below 1 carry no day, only a time of day
+                Some(datetime) if value.as_f64() < 1.0 => datetime.time().to_string(),
+                Some(datetime) if value.as_f64().fract() == 0.0 => datetime.date().to_string(),
+                Some(datetime) => format!("{}T{}", datetime.date(), datetime.time()),
+                None => value.to_string(),
+            },
+            _ => cell.to_string(),
+        }
+    }
+
+    /// Parse Excel files into typed rows - exposed to Ruby
+    ///
+    /// Returns `[{name:, rows: [[cell, ...], ...], merged: [...]}, ...]` for
+    /// the sheets picked by `sheets:` (as in `parse_xlsx`). Cells keep their
+    /// types: Integer, Float, String, true/false, a UTC Time for dates, Float
+    /// seconds for durations, a ParseKit::CellError for error values and
+    /// nil for empty cells. Hidden sheets, rows and columns are left out
+    /// when `include_hidden` is off.
+    ///
+    /// Cells covered by a merged range stay nil; `merged` lists each range
+    /// as `{row:, column:, rows:, columns:}`, indexes into `rows` and its
+    /// arrays of the top-left cell and the span.
+    ///
+    /// With `headers: true` each sheet is `{name:, headers:, records:}`
+    /// instead: the first non-empty row gives the headers (see
+    /// `xlsx_header_names`) and each later non-empty row is a Hash from
+    /// header to cell.
+    fn parse_xlsx_structured(&self, args: &[Value]) -> Result<RArray, Error> {
+        use calamine::{Reader, Xlsx};
+
+        let ruby = Ruby::get().unwrap();
+        let args = scan_args::scan_args::<(Vec<u8>,), (), (), (), RHash, ()>(args)?;
+        let (data,) = args.required;
+        let kwargs = scan_args::get_kwargs::<_, (), (Option<Value>, Option<bool>), ()>(
+            args.keywords,
+            &[],
+            &["sheets", "headers"],
+        )?;
+        let (sheets, headers) = kwargs.optional;
+        let sheets = Self::sheet_selection(sheets)?;
+
+        self.check_zip_limits(&data, "XLSX")?;
+        let hidden = self.read_xlsx_hidden_lines(&data)?;
+        let no_lines = Default::default();
+        let mut workbook = Xlsx::new(std::io::Cursor::new(data))
+            .map_err(|e| Self::runtime_error("Failed to parse Excel file", e))?;
+
+        let result = ruby.ary_new();
+        for sheet_name in self.xlsx_sheet_names(&workbook, &sheets) {
+            let (hidden_rows, hidden_columns) = hidden.get(&sheet_name).unwrap_or(&no_lines);
+            let merges = workbook
+                .worksheet_merge_cells(&sheet_name)
+                .and_then(|merges| merges.ok())
+                .unwrap_or_default();
+            let range = workbook
+                .worksheet_range(&sheet_name)
+                .map_err(|e| Self::runtime_error("Failed to read worksheet", e))?;
+            let (top, left) = range.start().unwrap_or((0, 0));
+            // Cells with their column, hidden lines dropped
+            let kept_rows: Vec<Vec<(u32, &calamine::Data)>> = range
+                .rows()
+                .enumerate()
+                .filter(|(r, _)| !hidden_rows.contains(&(top + *r as u32)))
+                .map(|(_, row)| {
+                    row.iter()
+                        .enumerate()
+                        .map(|(c, cell)| (left + c as u32, cell))
+                        .filter(|(column, _)| !hidden_columns.contains(column))
+                        .collect()
+                })
+                .collect();
+
+            let entry = ruby.hash_new();
+            entry.aset(ruby.to_symbol("name"), sheet_name)?;
+            if headers.unwrap_or(false) {
+                let is_blank = |row: &&Vec<(u32, &calamine::Data)>| {
+                    row.iter().all(|(_, cell)| matches!(cell, calamine::Data::Empty))
+                };
+                let mut rows = kept_rows.iter().filter(|row| !is_blank(row));
+                let names = rows.next().map(|row| Self::xlsx_header_names(row)).unwrap_or_default();
+
+                let records = ruby.ary_new();
+                for row in rows {
+                    let record = ruby.hash_new();
+                    for (name, (_, cell)) in names.iter().zip(row) {
+                        record.aset(name.as_str(), Self::xlsx_cell_value(&ruby, cell)?)?;
+                    }
+                    records.push(record)?;
+                }
+                entry.aset(ruby.to_symbol("headers"), names)?;
+                entry.aset(ruby.to_symbol("records"), records)?;
+                result.push(entry)?;
+                continue;
+            }
+
+            let rows = ruby.ary_new();
+            for row in &kept_rows {
+                let cells = ruby.ary_new();
+                for (_, cell) in row {
+                    cells.push(Self::xlsx_cell_value(&ruby, cell)?)?;
+                }
+                rows.push(cells)?;
+            }
+
+            let (bottom, right) = range.end().unwrap_or((0, 0));
+            let merged = ruby.ary_new();
+            for merge in &merges {
+                let spans = (
+                    Self::xlsx_kept_span(hidden_rows, top, merge.start.0, merge.end.0.min(bottom)),
+                    Self::xlsx_kept_span(hidden_columns, left, merge.start.1, merge.end.1.min(right)),
+                );
+                let (Some((row, row_count)), Some((column, column_count))) = spans else {
+                    continue;
+                };
+                let span = ruby.hash_new();
+                span.aset(ruby.to_symbol("row"), row)?;
+                span.aset(ruby.to_symbol("column"), column)?;
+                span.aset(ruby.to_symbol("rows"), row_count)?;
+                span.aset(ruby.to_symbol("columns"), column_count)?;
+                merged.push(span)?;
+            }
+
+            entry.aset(ruby.to_symbol("rows"), rows)?;
+            entry.aset(ruby.to_symbol("merged"), merged)?;
+            result.push(entry)?;
+        }
+        Ok(result)
+    }
+
+    /// Column names from a header row as text. A blank header is named
+    /// after its column letter ("C"), and a repeated one gets a suffix
+    /// ("Amount_2") so no column is lost to another of the same name.
+    fn xlsx_header_names(row: &[(u32, &calamine::Data)]) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for (column, cell) in row {
+            let text = Self::xlsx_cell_text(cell).trim().to_string();
+            let base = if text.is_empty() { Self::xlsx_column_name(*column) } else { text };
+            let mut name = base.clone();
+            let mut count = 1;
+            while names.contains(&name) {
+                count += 1;
+                name = format!("{}_{}", base, count);
+            }
+            names.push(name);
+        }
+        names
+    }
+
+    /// The letters of a worksheet column numbered from 0: "A", ..., "Z", "AA"
+    fn xlsx_column_name(column: u32) -> String {
+        let mut letters = Vec::new();
+        let mut n = column + 1;
+        while n > 0 {
+            n -= 1;
+            letters.push(b'A' + (n % 26) as u8);
+            n /= 26;
+        }
+        letters.iter().rev().map(|&b| b as char).collect()
+    }
+
+    /// Convert a worksheet cell to the matching Ruby object. ISO-8601 date
+    /// and duration strings (written by some tools instead of serials) are
+    /// kept as Strings.
+    fn xlsx_cell_value(ruby: &Ruby, cell: &calamine::Data) -> Result<Value, Error> {
+        use calamine::Data;
+
+        Ok(match cell {
+            Data::Int(value) => ruby.into_value(*value),
+            Data::Float(value) => ruby.into_value(*value),
+            Data::String(value) | Data::DateTimeIso(value) | Data::DurationIso(value) => {
+                ruby.into_value(value.as_str())
+            }
+            Data::Bool(value) => ruby.into_value(*value),
+            Data::DateTime(value) if value.is_duration() => {
+                let duration = value.as_duration().unwrap_or_default();
+                ruby.into_value(duration.num_milliseconds() as f64 / 1000.0)
+            }
+            Data::DateTime(value) => match value.as_datetime() {
+                Some(datetime) => {
+                    let datetime = datetime.and_utc();
+                    let timespec = magnus::time::Timespec {
+                        tv_sec: datetime.timestamp(),
+                        tv_nsec: i64::from(datetime.timestamp_subsec_nanos()),
+                    };
+                    ruby.time_timespec_new(timespec, magnus::time::Offset::utc())?.as_value()
+                }
+                // Out of chrono's range; keep the serial number
+                None => ruby.into_value(value.as_f64()),
+            },
+            Data::Error(code) => {
+                let class = ruby
+                    .define_module("ParseKit")?
+                    .const_get::<_, magnus::RClass>("CellError")?;
+                class.new_instance((code.to_string(),))?
+            }
+            Data::Empty => ruby.qnil().as_value(),
+        })
+    }
+
+    /// List the defined names of an XLSX workbook - exposed to Ruby
+    ///
+    /// Returns `[{name:, scope:, refers_to:, sheet:, range:, hidden:}]` in
+    /// workbook order. `scope` is the name of the sheet a name is local to,
+    /// or nil for workbook-wide names. When `refers_to` is a single range,
+    /// `sheet` and `range` (without `$` signs) give it apart; both are nil
+    /// for constants and formulas.
+    fn xlsx_defined_names(&self, data: Vec<u8>) -> Result<RArray, Error> {
+        let ruby = Ruby::get().unwrap();
+        self.check_zip_limits(&data, "XLSX")?;
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(&data))
+            .map_err(|e| Self::runtime_error("Failed to open XLSX as ZIP", e))?;
+        let mut budget = self.config.max_size as u64;
+        let Some(workbook) = self.read_zip_part(&mut archive, "xl/workbook.xml", &mut budget, "XLSX")? else {
+            return Err(Self::runtime_error("Failed to parse Excel file", "no xl/workbook.xml part"));
+        };
+        let workbook = String::from_utf8_lossy(&workbook);
+        let sheets = Self::xlsx_sheet_refs(&workbook);
+
+        let result = ruby.ary_new();
+        for defined in Self::xlsx_defined_names_xml(&workbook) {
+            let scope = defined
+                .sheet_index
+                .and_then(|index| sheets.get(index))
+                .map(|(name, _)| name.clone());
+            let (sheet, range) = Self::xlsx_reference_parts(&defined.refers_to).unzip();
+
+            let entry = ruby.hash_new();
+            entry.aset(ruby.to_symbol("name"), defined.name)?;
+            entry.aset(ruby.to_symbol("scope"), scope)?;
+            entry.aset(ruby.to_symbol("refers_to"), defined.refers_to)?;
+            entry.aset(ruby.to_symbol("sheet"), sheet)?;
+            entry.aset(ruby.to_symbol("range"), range)?;
+            entry.aset(ruby.to_symbol("hidden"), defined.hidden)?;
+            result.push(entry)?;
+        }
+        Ok(result)
+    }
+
+    /// The `definedName` elements of an XLSX workbook part, in order
+    fn xlsx_defined_names_xml(workbook: &str) -> Vec<XlsxDefinedName> {
+        use quick_xml::events::Event;
+        use quick_xml::Reader;
+
+        let mut reader = Reader::from_str(workbook);
+        let mut names = Vec::new();
+        let mut current: Option<XlsxDefinedName> = None;
+        loop {
+            match reader.read_event() {
+                Ok(Event::Start(ref e)) if e.name().local_name().as_ref() == b"definedName" => {
+                    current = Self::xml_attribute(e, b"name").map(|name| XlsxDefinedName {
+                        name,
+                        sheet_index: Self::xml_attribute(e, b"localSheetId").and_then(|id| id.parse().ok()),
+                        refers_to: String::new(),
+                        hidden: matches!(Self::xml_attribute(e, b"hidden").as_deref(), Some("1") | Some("true")),
+                    });
+                }
+                Ok(Event::Text(ref e)) => {
+                    if let Some(defined) = current.as_mut() {
+                        defined.refers_to.push_str(&e.decode().unwrap_or_default());
+                    }
+                }
+                Ok(Event::GeneralRef(ref e)) => {
+                    if let Some(defined) = current.as_mut() {
+                        defined.refers_to.push_str(&Self::resolve_xml_reference(e));
+                    }
+                }
+                Ok(Event::End(ref e)) if e.name().local_name().as_ref() == b"definedName" => {
+                    names.extend(current.take());
+                }
+                Ok(Event::Eof) | Err(_) => break,
+                _ => {}
+            }
+        }
+        names
+    }
+
+    /// The sheet and range of a reference to one range, such as
+    /// `'Q1 Data'!$A$1:$C$9`, with `$` signs dropped. Cells, areas, whole
+    /// columns (`$A:$C`) and whole rows (`$1:$3`) count as ranges.
+    fn xlsx_reference_parts(reference: &str) -> Option<(String, String)> {
+        let captures = XLSX_RANGE_REFERENCE.captures(reference.trim())?;
+        let sheet = match captures.get(1) {
+            Some(quoted) => quoted.as_str().replace("''", "'"),
+            None => captures.get(2)?.as_str().to_string(),
+        };
+        Some((sheet, captures[3].replace('$', "")))
+    }
+
+    /// Parse Parquet files - exposed to Ruby
+    fn parse_parquet(&self, data: Vec<u8>) -> Result<String, Error> {
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+        let builder = ParquetRecordBatchReaderBuilder::try_new(bytes::Bytes::from(data))
+            .map_err(|e| Self::runtime_error("Failed to parse Parquet file", e))?;
+
+        let schema = builder.schema().clone();
+        let total_rows = usize::try_from(builder.metadata().file_metadata().num_rows()).unwrap_or(0);
+
+        let reader = builder
+            .with_limit(self.config.max_rows)
+            .build()
+            .map_err(|e| Self::runtime_error("Failed to read Parquet row groups", e))?;
+
+        self.format_record_batches(&schema, reader, Some(total_rows))
+    }
+
+    /// Render an Arrow schema and its record batches as tab-delimited text,
+    /// stopping once `max_rows` rows have been written. When `total_rows` is
+    /// unknown, remaining batches are still read to count the rows skipped.
+    fn format_record_batches<I>(
+        &self,
+        schema: &arrow::datatypes::Schema,
+        batches: I,
+        total_rows: Option<usize>,
+    ) -> Result<String, Error>
+    where
+        I: IntoIterator<Item = Result<arrow::record_batch::RecordBatch, arrow::error::ArrowError>>,
+    {
+        use arrow::util::display::{ArrayFormatter, FormatOptions};
+
+        let mut result = String::from("Schema:\n");
+        for field in schema.fields() {
+            let nullable = if field.is_nullable() { " (nullable)" } else { "" };
+            result.push_str(&format!("  {}: {}{}\n", field.name(), field.data_type(), nullable));
+        }
+        result.push('\n');
+
+        let header: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        result.push_str(&header.join("\t"));
+        result.push('\n');
+
+        let options = FormatOptions::default().with_null("");
+        let mut rows_written = 0;
+        let mut rows_seen = 0;
+
+        for batch in batches {
+            if rows_written >= self.config.max_rows && total_rows.is_some() {
+                break;
+            }
+
+            let batch = batch.map_err(|e| Self::runtime_error("Failed to read record batch", e))?;
+            rows_seen += batch.num_rows();
+            if rows_written >= self.config.max_rows {
+                continue;
+            }
+
+            let formatters = batch
+                .columns()
+                .iter()
+                .map(|column| ArrayFormatter::try_new(column.as_ref(), &options))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| Self::runtime_error("Failed to format record batch", e))?;
+
+            for row in 0..batch.num_rows() {
+                if rows_written >= self.config.max_rows {
+                    break;
+                }
+                let cells: Vec<String> = formatters.iter().map(|f| f.value(row).to_string()).collect();
+                result.push_str(&cells.join("\t"));
+                result.push('\n');
+                rows_written += 1;
+            }
+        }
+
+        let total = total_rows.unwrap_or(rows_seen);
+        if total > rows_written {
+            result.push_str(&format!("\n[Truncated: showing {} of {} rows]\n", rows_written, total));
+        }
+
+        Ok(result.trim_end().to_string())
+    }
+
+    /// Parse Arrow IPC files and streams (including Feather v2) - exposed to Ruby
+    fn parse_arrow(&self, data: Vec<u8>) -> Result<String, Error> {
+        use arrow::ipc::reader::{FileReader, StreamReader};
+        use std::io::Cursor;
+
+        // The file format is framed by the ARROW1 magic; anything else is
+        // treated as the streaming format
+        if data.starts_with(b"ARROW1") {
+            let reader = FileReader::try_new(Cursor::new(data), None)
+                .map_err(|e| Self::runtime_error("Failed to parse Arrow IPC file", e))?;
+            let schema = reader.schema();
+            self.format_record_batches(&schema, reader, None)
+        } else {
+            let reader = StreamReader::try_new(Cursor::new(data), None)
+                .map_err(|e| Self::runtime_error("Failed to parse Arrow IPC stream", e))?;
+            let schema = reader.schema();
+            self.format_record_batches(&schema, reader, None)
+        }
+    }
+
+    /// Parse dBase (.dbf) tables - exposed to Ruby
+    ///
+    /// Output mirrors the columnar formats: the field list, then a header row
+    /// and tab-delimited records, capped by `max_rows`. Deleted records are
+    /// skipped; memo fields only hold a block number, as the .dbt/.fpt file
+    /// isn't available.
+    fn parse_dbf(&self, data: Vec<u8>) -> Result<String, Error> {
+        let invalid = |msg: &str| Self::runtime_error("Failed to parse dBase file", msg);
+        if data.len() < 32 {
+            return Err(invalid("truncated header"));
+        }
+
+        let record_count = u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize;
+        let header_len = u16::from_le_bytes([data[8], data[9]]) as usize;
+        let record_len = u16::from_le_bytes([data[10], data[11]]) as usize;
+        // Every record holds at least its deletion flag
+        if record_len == 0 {
+            let message = "Failed to parse dBase file: zero record length".to_string();
+            return Err(ParserError::ParseError(message).to_error());
+        }
+
+        // Field descriptors: 32 bytes each, terminated by 0x0D
+        let mut fields = Vec::new();
+        let mut pos = 32;
+        while pos + 32 <= header_len.min(data.len()) && data[pos] != 0x0D {
+            let descriptor = &data[pos..pos + 32];
+            let name_end = descriptor[..11].iter().position(|&b| b == 0).unwrap_or(11);
+            let name = String::from_utf8_lossy(&descriptor[..name_end]).to_string();
+            fields.push((name, descriptor[11] as char, descriptor[16] as usize, descriptor[17]));
+            pos += 32;
+        }
+        if fields.is_empty() {
+            return Err(invalid("no field descriptors"));
+        }
+
+        let mut result = String::from("Schema:\n");
+        for (name, kind, length, decimals) in &fields {
+            match (kind, decimals) {
+                ('N' | 'F', 1..) => result.push_str(&format!("  {}: {}({},{})\n", name, kind, length, decimals)),
+                _ => result.push_str(&format!("  {}: {}({})\n", name, kind, length)),
+            }
+        }
+        result.push('\n');
+        let header: Vec<&str> = fields.iter().map(|(name, ..)| name.as_str()).collect();
+        result.push_str(&header.join("\t"));
+        result.push('\n');
+
+        let mut rows_written = 0;
+        let mut live_records = 0;
+        for index in 0..record_count {
+            let start = header_len + index * record_len;
+            let Some(record) = data.get(start..start + record_len) else { break };
+            // First byte is the deletion flag: '*' marks a deleted record
+            if record[0] == b'*' {
+                continue;
+            }
+            live_records += 1;
+            if rows_written >= self.config.max_rows {
+                continue;
+            }
+
+            let mut offset = 1;
+            let mut cells = Vec::with_capacity(fields.len());
+            for (_, kind, length, _) in &fields {
+                let raw = record.get(offset..offset + length).unwrap_or_default();
+                offset += length;
+                cells.push(Self::dbf_value(*kind, raw));
+            }
+            result.push_str(&cells.join("\t"));
+            result.push('\n');
+            rows_written += 1;
+        }
+
+        if live_records > rows_written {
+            result.push_str(&format!("\n[Truncated: showing {} of {} rows]\n", rows_written, live_records));
+        }
+
+        Ok(result.trim_end().to_string())
+    }
+
+    /// Render one dBase field value as text
+    fn dbf_value(kind: char, raw: &[u8]) -> String {
+        match kind {
+            // Dates are stored as YYYYMMDD
+            'D' if raw.len() == 8 && raw.iter().all(u8::is_ascii_digit) => {
+                let s = String::from_utf8_lossy(raw);
+                format!("{}-{}-{}", &s[0..4], &s[4..6], &s[6..8])
+            }
+            'L' => match raw.first() {
+                Some(b'T' | b't' | b'Y' | b'y') => "true".to_string(),
+                Some(b'F' | b'f' | b'N' | b'n') => "false".to_string(),
+                _ => String::new(),
+            },
+            // Visual FoxPro binary integer and double
+            'I' if raw.len() == 4 => i32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]).to_string(),
+            'B' if raw.len() == 8 => f64::from_le_bytes(raw.try_into().unwrap()).to_string(),
+            _ => {
+                let text = match std::str::from_utf8(raw) {
+                    Ok(text) => std::borrow::Cow::Borrowed(text),
+                    Err(_) => encoding_rs::WINDOWS_1252.decode(raw).0,
+                };
+                text.trim_matches(|c: char| c == ' ' || c == '\0').to_string()
+            }
+        }
+    }
+
+    /// Parse JSON files - exposed to Ruby
+    ///
+    /// Pretty-printed, or with `json_output: :flat` a `dotted.key: value`
+    /// line per scalar (see `json_flat_lines`). Relaxed JSON is read as in
+    /// `read_json`; text that still isn't JSON comes back as it is, or
+    /// raises `ParseKit::ParseError` in strict mode.
+    fn parse_json(&self, data: Vec<u8>) -> Result<String, Error> {
+        self.check_json_utf8(&data)?;
+        let text = String::from_utf8_lossy(&data);
+        match self.read_json(&text) {
+            Ok(json) if self.config.json_output == JsonOutput::Flat => {
+                let mut lines = Vec::new();
+                Self::json_flat_lines(&mut String::new(), &json, &mut lines);
+                Ok(lines.join("\n"))
+            }
+            Ok(json) => {
+                Ok(serde_json::to_string_pretty(&json).unwrap_or_else(|_| text.to_string()))
+            }
+            Err(e) if self.config.strict_mode => Err(Self::json_error(e)),
+            Err(_) => Ok(text.to_string()),
+        }
+    }
+
+    /// Parse a JSON document. Outside strict mode, text serde_json rejects
+    /// is tried again as relaxed JSON (see `relaxed_json`), so commented
+    /// config files with trailing commas and bare keys parse too; the
+    /// error, if that fails as well, is the one for the original text. In
+    /// strict mode objects may not repeat a key (see `UniqueKeys`).
+    fn read_json(&self, text: &str) -> Result<serde_json::Value, serde_json::Error> {
+        if self.config.strict_mode {
+            let json = serde_json::from_str(text)?;
+            serde_json::from_str::<UniqueKeys>(text)?;
+            return Ok(json);
+        }
+        serde_json::from_str(text)
+            .or_else(|e| serde_json::from_str(&Self::relaxed_json(text)).map_err(|_| e))
+    }
+
+    /// In strict mode, reject JSON that isn't valid UTF-8, which is
+    /// otherwise read with U+FFFD in place of the bad bytes
+    fn check_json_utf8(&self, data: &[u8]) -> Result<(), Error> {
+        if !self.config.strict_mode {
+            return Ok(());
+        }
+        match std::str::from_utf8(data) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                let valid = &data[..e.valid_up_to()];
+                let line = valid.iter().filter(|&&b| b == b'\n').count() + 1;
+                let line_start = valid.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+                Err(Self::json_error(format!(
+                    "invalid UTF-8 at line {} column {}",
+                    line,
+                    valid.len() - line_start + 1
+                )))
+            }
+        }
+    }
+
+    /// Create the ParseKit::ParseError for JSON that can't be read
+    fn json_error<E: std::fmt::Display>(err: E) -> Error {
+        ParserError::ParseError(format!("Failed to parse JSON: {}", err)).to_error()
+    }
+
+    /// Rewrite JSONC/JSON5-style text as standard JSON: `//` and `/* */`
+    /// comments become spaces, commas before `}` or `]` are dropped, and
+    /// identifier keys such as `{name: 1}` are quoted. Line breaks are
+    /// kept, so line numbers still point into the original.
+    fn relaxed_json(text: &str) -> String {
+        let chars: Vec<char> = text.chars().collect();
+        let mut out = String::with_capacity(text.len());
+        let mut i = 0;
+        while i < chars.len() {
+            if let Some(end) = Self::json_comment_end(&chars, i) {
+                out.extend(chars[i..end].iter().map(|&c| if c == '\n' { c } else { ' ' }));
+                i = end;
+                continue;
+            }
+            match chars[i] {
+                '"' => {
+                    // Copy the string through, escapes and all
+                    let start = i;
+                    i += 1;
+                    while i < chars.len() && chars[i] != '"' {
+                        i += if chars[i] == '\\' { 2 } else { 1 };
+                    }
+                    i = (i + 1).min(chars.len());
+                    out.extend(&chars[start..i]);
+                }
+                ',' => {
+                    let next = Self::json_skip_trivia(&chars, i + 1);
+                    out.push(if matches!(chars.get(next), Some('}' | ']')) { ' ' } else { ',' });
+                    i += 1;
+                }
+                c if c.is_alphabetic() || c == '_' || c == '$' => {
+                    let start = i;
+                    while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$') {
+                        i += 1;
+                    }
+                    let word: String = chars[start..i].iter().collect();
+                    if chars.get(Self::json_skip_trivia(&chars, i)) == Some(&':') {
+                        out.push('"');
+                        out.push_str(&word);
+                        out.push('"');
+                    } else {
+                        out.push_str(&word);
+                    }
+                }
+                c => {
+                    out.push(c);
+                    i += 1;
+                }
+            }
+        }
+        out
+    }
+
+    /// Where the comment starting at `chars[i]` ends, if one does: before
+    /// the line break of a `//` comment, after the `*/` of a block comment
+    fn json_comment_end(chars: &[char], i: usize) -> Option<usize> {
+        if chars.get(i) != Some(&'/') {
+            return None;
+        }
+        match chars.get(i + 1) {
+            Some('/') => Some(
+                (i + 2..chars.len()).find(|&j| chars[j] == '\n').unwrap_or(chars.len()),
+            ),
+            Some('*') => Some(
+                (i + 2..chars.len().saturating_sub(1))
+                    .find(|&j| chars[j] == '*' && chars[j + 1] == '/')
+                    .map_or(chars.len(), |j| j + 2),
+            ),
+            _ => None,
+        }
+    }
+
+    /// The index of the next character at or after `i` that is neither
+    /// whitespace nor part of a comment
+    fn json_skip_trivia(chars: &[char], mut i: usize) -> usize {
+        loop {
+            while chars.get(i).is_some_and(|c| c.is_whitespace()) {
+                i += 1;
+            }
+            match Self::json_comment_end(chars, i) {
+                Some(end) => i = end,
+                None => return i,
+            }
+        }
+    }
+
+    /// Collect a `path: value` line for each string, number and boolean
+    /// under `json`, in document order. Object keys are joined with dots
+    /// and array items add `[index]`, as in `order.items[0].sku: A-1`; a
+    /// top-level scalar is its value alone. Nulls, empty strings and empty
+    /// containers have nothing to show and are skipped.
+    fn json_flat_lines(path: &mut String, json: &serde_json::Value, lines: &mut Vec<String>) {
+        use serde_json::Value as Json;
+
+        let value = match json {
+            Json::Null => return,
+            Json::String(text) if text.is_empty() => return,
+            Json::String(text) => text.clone(),
+            Json::Bool(value) => value.to_string(),
+            Json::Number(number) => number.to_string(),
+            Json::Array(items) => {
+                for (index, item) in items.iter().enumerate() {
+                    let len = path.len();
+                    path.push_str(&format!("[{}]", index));
+                    Self::json_flat_lines(path, item, lines);
+                    path.truncate(len);
+                }
+                return;
+            }
+            Json::Object(map) => {
+                for (key, item) in map {
+                    let len = path.len();
+                    if !path.is_empty() {
+                        path.push('.');
+                    }
+                    path.push_str(key);
+                    Self::json_flat_lines(path, item, lines);
+                    path.truncate(len);
+                }
+                return;
+            }
+        };
+        if path.is_empty() {
+            lines.push(value);
+        } else {
+            lines.push(format!("{}: {}", path, value));
+        }
+    }
+
+    /// Parse JSON into Ruby objects - exposed to Ruby
+    ///
+    /// Objects become Hashes with String keys in document order, arrays
+    /// Arrays, numbers Integer (of any size) or BigDecimal, and null nil, as
+    /// `JSON.parse(json, decimal_class: BigDecimal)` would give. Relaxed JSON is read as in `read_json`. Unlike `parse_json`,
+    /// invalid JSON raises `ParseKit::ParseError` instead of coming back as
+    /// text.
+    fn parse_json_object(&self, data: Vec<u8>) -> Result<Value, Error> {
+        let ruby = Ruby::get().unwrap();
+        self.check_json_utf8(&data)?;
+        let text = String::from_utf8_lossy(&data);
+        let json = self.read_json(&text).map_err(Self::json_error)?;
+        Self::json_to_ruby(&ruby, json)
+    }
+
+    /// Convert a JSON value into the matching Ruby object, recursively
+    fn json_to_ruby(ruby: &Ruby, json: serde_json::Value) -> Result<Value, Error> {
+        use serde_json::Value as Json;
+
+        Ok(match json {
+            Json::Null => ruby.qnil().as_value(),
+            Json::Bool(value) => ruby.into_value(value),
+            Json::Number(number) => {
+                let text = number.as_str();
+                if let Some(value) = number.as_i64() {
+                    ruby.into_value(value)
+                } else if let Some(value) = number.as_u64() {
+                    ruby.into_value(value)
+                } else if !text.contains(['.', 'e', 'E']) {
+                    // Past 64 bits; Ruby's Integer() keeps every digit
+                    ruby.module_kernel().funcall("Integer", (text,))?
+                } else {
+                    // Decimals become BigDecimal, which keeps every digit
+                    // that a Float would round away
+                    ruby.require("bigdecimal")?;
+                    ruby.module_kernel().funcall("BigDecimal", (text,))?
+                }
+            }
+            Json::String(value) => ruby.into_value(value),
+            Json::Array(items) => {
+                let array = ruby.ary_new_capa(items.len());
+                for item in items {
+                    array.push(Self::json_to_ruby(ruby, item)?)?;
+                }
+                array.as_value()
+            }
+            Json::Object(map) => {
+                let hash = ruby.hash_new();
+                for (key, value) in map {
+                    hash.aset(key, Self::json_to_ruby(ruby, value)?)?;
+                }
+                hash.as_value()
+            }
+        })
+    }
+
+    /// Parse MessagePack payloads into pretty JSON - exposed to Ruby
+    fn parse_msgpack(&self, data: Vec<u8>) -> Result<String, Error> {
+        let mut cursor = &data[..];
+        let mut values = Vec::new();
+
+        // A payload may hold several concatenated values (e.g. a log stream)
+        while !cursor.is_empty() {
+            let value = rmpv::decode::read_value(&mut cursor)
+                .map_err(|e| Self::runtime_error("Failed to parse MessagePack data", e))?;
+            values.push(Self::msgpack_to_json(value));
+        }
+
+        let json = if values.len() == 1 {
+            values.remove(0)
+        } else {
+            serde_json::Value::Array(values)
+        };
+
+        serde_json::to_string_pretty(&json)
+            .map_err(|e| Self::runtime_error("Failed to render MessagePack data", e))
+    }
+
+    /// Convert a decoded MessagePack value into its JSON equivalent
+    fn msgpack_to_json(value: rmpv::Value) -> serde_json::Value {
+        use rmpv::Value as Mp;
+        use serde_json::Value as Json;
+
+        match value {
+            Mp::Nil => Json::Null,
+            Mp::Boolean(b) => Json::Bool(b),
+            Mp::Integer(i) => match (i.as_i64(), i.as_u64()) {
+                (Some(n), _) => Json::from(n),
+                (None, Some(n)) => Json::from(n),
+                _ => Json::Null,
+            },
+            Mp::F32(f) => Self::float_to_json(f as f64),
+            Mp::F64(f) => Self::float_to_json(f),
+            Mp::String(s) => Json::String(String::from_utf8_lossy(s.as_bytes()).into_owned()),
+            Mp::Binary(bytes) => Self::binary_to_json(&bytes),
+            Mp::Array(items) => Json::Array(items.into_iter().map(Self::msgpack_to_json).collect()),
+            Mp::Map(entries) => {
+                let mut map = serde_json::Map::new();
+                for (key, value) in entries {
+                    // JSON keys must be strings; render other key types as text
+                    let key = match key {
+                        Mp::String(s) => String::from_utf8_lossy(s.as_bytes()).into_owned(),
+                        other => Self::msgpack_to_json(other).to_string(),
+                    };
+                    map.insert(key, Self::msgpack_to_json(value));
+                }
+                Json::Object(map)
+            }
+            Mp::Ext(ext_type, bytes) => serde_json::json!({
+                "$ext": { "type": ext_type, "data": Self::base64_encode(&bytes) }
+            }),
+        }
+    }
+
+    /// Parse CBOR payloads into pretty JSON - exposed to Ruby
+    fn parse_cbor(&self, data: Vec<u8>) -> Result<String, Error> {
+        let mut cursor = &data[..];
+        let mut values = Vec::new();
+
+        // Like MessagePack, a CBOR sequence may hold several concatenated items
+        while !cursor.is_empty() {
+            let value: ciborium::value::Value = ciborium::de::from_reader(&mut cursor)
+                .map_err(|e| Self::runtime_error("Failed to parse CBOR data", e))?;
+            values.push(Self::cbor_to_json(value));
+        }
+
+        let json = if values.len() == 1 {
+            values.remove(0)
+        } else {
+            serde_json::Value::Array(values)
+        };
+
+        serde_json::to_string_pretty(&json)
+            .map_err(|e| Self::runtime_error("Failed to render CBOR data", e))
+    }
+
+    /// Convert a decoded CBOR value into its JSON equivalent
+    fn cbor_to_json(value: ciborium::value::Value) -> serde_json::Value {
+        use ciborium::value::Value as Cbor;
+        use serde_json::Value as Json;
+
+        match value {
+            Cbor::Null => Json::Null,
+            Cbor::Bool(b) => Json::Bool(b),
+            Cbor::Integer(i) => {
+                let n = i128::from(i);
+                if let Ok(n) = i64::try_from(n) {
+                    Json::from(n)
+                } else if let Ok(n) = u64::try_from(n) {
+                    Json::from(n)
+                } else {
+                    Json::String(n.to_string())
+                }
+            }
+            Cbor::Float(f) => Self::float_to_json(f),
+            Cbor::Text(s) => Json::String(s),
+            Cbor::Bytes(bytes) => Self::binary_to_json(&bytes),
+            Cbor::Array(items) => Json::Array(items.into_iter().map(Self::cbor_to_json).collect()),
+            Cbor::Map(entries) => {
+                let mut map = serde_json::Map::new();
+                for (key, value) in entries {
+                    let key = match key {
+                        Cbor::Text(s) => s,
+                        other => Self::cbor_to_json(other).to_string(),
+                    };
+                    map.insert(key, Self::cbor_to_json(value));
+                }
+                Json::Object(map)
+            }
+            // The self-describe tag only marks the payload as CBOR
+            Cbor::Tag(55799, inner) => Self::cbor_to_json(*inner),
+            Cbor::Tag(tag, inner) => serde_json::json!({
+                "$tag": tag,
+                "value": Self::cbor_to_json(*inner)
+            }),
+            _ => Json::Null,
+        }
+    }
+
+    /// Parse BSON documents (e.g. mongodump output) into pretty JSON - exposed to Ruby
+    fn parse_bson(&self, data: Vec<u8>) -> Result<String, Error> {
+        let mut cursor = &data[..];
+        let mut documents = Vec::new();
+
+        // Dump files are a plain concatenation of documents
+        while !cursor.is_empty() {
+            let document = bson::Document::from_reader(&mut cursor)
+                .map_err(|e| Self::runtime_error("Failed to parse BSON document", e))?;
+            // Relaxed Extended JSON keeps ObjectIds, dates and binary subtypes
+            // distinguishable ($oid, $date, $binary) while staying readable
+            documents.push(bson::Bson::Document(document).into_relaxed_extjson());
+        }
+
+        let json = if documents.len() == 1 {
+            documents.remove(0)
+        } else {
+            serde_json::Value::Array(documents)
+        };
+
+        serde_json::to_string_pretty(&json)
+            .map_err(|e| Self::runtime_error("Failed to render BSON data", e))
+    }
+
+    /// Decode binary protobuf into pretty JSON - exposed to Ruby
+    ///
+    /// With `descriptor_set:` (a serialized FileDescriptorSet, as produced by
+    /// `protoc --descriptor_set_out`) and `message_type:` the payload is decoded
+    /// with full field names. Without a descriptor, fields are decoded from the
+    /// wire format alone and keyed by field number.
+    fn parse_protobuf(&self, args: &[Value]) -> Result<String, Error> {
+        let args = scan_args::scan_args::<(Vec<u8>,), (), (), (), RHash, ()>(args)?;
+        let (data,) = args.required;
+        let kwargs = scan_args::get_kwargs::<_, (), (Option<Vec<u8>>, Option<String>), ()>(
+            args.keywords,
+            &[],
+            &["descriptor_set", "message_type"],
+        )?;
+        let (descriptor_set, message_type) = kwargs.optional;
+
+        let json = match descriptor_set {
+            Some(descriptor_set) => {
+                let message_type = message_type.ok_or_else(|| {
+                    Self::argument_error("message_type is required when descriptor_set is given")
+                })?;
+                self.decode_protobuf_with_descriptor(&data, &descriptor_set, &message_type)?
+            }
+            None => Self::decode_protobuf_raw(&data, self.config.max_depth).ok_or_else(|| {
+                Self::runtime_error("Failed to decode protobuf", "data is not a valid protobuf message")
+            })?,
+        };
+
+        serde_json::to_string_pretty(&json)
+            .map_err(|e| Self::runtime_error("Failed to render protobuf message", e))
+    }
+
+    /// Decode a protobuf message using a compiled FileDescriptorSet
+    fn decode_protobuf_with_descriptor(
+        &self,
+        data: &[u8],
+        descriptor_set: &[u8],
+        message_type: &str,
+    ) -> Result<serde_json::Value, Error> {
+        use prost_reflect::{DescriptorPool, DynamicMessage};
+
+        let pool = DescriptorPool::decode(descriptor_set)
+            .map_err(|e| Self::runtime_error("Failed to load protobuf descriptor set", e))?;
+        let descriptor = pool.get_message_by_name(message_type).ok_or_else(|| {
+            Self::argument_error(&format!("Unknown protobuf message type: {}", message_type))
+        })?;
+        let message = DynamicMessage::decode(descriptor, data)
+            .map_err(|e| Self::runtime_error("Failed to decode protobuf", e))?;
+
+        serde_json::to_value(&message)
+            .map_err(|e| Self::runtime_error("Failed to render protobuf message", e))
+    }
+
+    /// Decode protobuf wire format without a schema, keyed by field number.
+    /// Returns None if the data is not a well-formed message.
+    fn decode_protobuf_raw(data: &[u8], depth: usize) -> Option<serde_json::Value> {
+        use std::collections::BTreeMap;
+
+        let mut fields: BTreeMap<u64, Vec<serde_json::Value>> = BTreeMap::new();
+        let mut pos = 0;
+
+        while pos < data.len() {
+            let key = Self::read_varint(data, &mut pos)?;
+            let field_number = key >> 3;
+            if field_number == 0 {
+                return None;
+            }
+
+            let value = match key & 0x7 {
+                0 => serde_json::Value::from(Self::read_varint(data, &mut pos)?),
+                1 => {
+                    let bytes = data.get(pos..pos + 8)?;
+                    pos += 8;
+                    serde_json::Value::from(u64::from_le_bytes(bytes.try_into().ok()?))
+                }
+                2 => {
+                    let len = usize::try_from(Self::read_varint(data, &mut pos)?).ok()?;
+                    let end = pos.checked_add(len)?;
+                    let bytes = data.get(pos..end)?;
+                    pos = end;
+                    Self::protobuf_bytes_to_json(bytes, depth)
+                }
+                5 => {
+                    let bytes = data.get(pos..pos + 4)?;
+                    pos += 4;
+                    serde_json::Value::from(u32::from_le_bytes(bytes.try_into().ok()?))
+                }
+                // Groups (3/4) are deprecated; other wire types are invalid
+                _ => return None,
+            };
+
+            fields.entry(field_number).or_default().push(value);
+        }
+
+        let object = fields
+            .into_iter()
+            .map(|(number, mut values)| {
+                let value = if values.len() == 1 {
+                    values.remove(0)
+                } else {
+                    serde_json::Value::Array(values)
+                };
+                (number.to_string(), value)
+            })
+            .collect();
+
+        Some(serde_json::Value::Object(object))
+    }
+
+    /// Interpret a length-delimited protobuf field as text, a nested message,
+    /// or raw bytes, in that order of preference
+    fn protobuf_bytes_to_json(bytes: &[u8], depth: usize) -> serde_json::Value {
+        if let Ok(text) = std::str::from_utf8(bytes) {
+            if !text.chars().any(|c| c.is_control() && !c.is_whitespace()) {
+                return serde_json::Value::String(text.to_string());
+            }
+        }
+
+        if depth > 0 && !bytes.is_empty() {
+            if let Some(nested) = Self::decode_protobuf_raw(bytes, depth - 1) {
+                return nested;
+            }
+        }
+
+        Self::binary_to_json(bytes)
+    }
+
+    /// Read a base-128 varint, advancing `pos` past it
+    fn read_varint(data: &[u8], pos: &mut usize) -> Option<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = *data.get(*pos)?;
+            *pos += 1;
+            value |= u64::from(byte & 0x7F) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    /// Represent a float as JSON, falling back to a string for NaN/Infinity
+    fn float_to_json(f: f64) -> serde_json::Value {
+        serde_json::Number::from_f64(f)
+            .map(serde_json::Value::Number)
+            .unwrap_or_else(|| serde_json::Value::String(f.to_string()))
+    }
+
+    /// Represent raw binary data as a tagged base64 JSON value
+    fn binary_to_json(bytes: &[u8]) -> serde_json::Value {
+        serde_json::json!({ "$binary": Self::base64_encode(bytes) })
+    }
+
+    /// Base64-encode bytes using the standard alphabet
+    fn base64_encode(bytes: &[u8]) -> String {
+        use base64::Engine;
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    }
+
+    /// Parse XML/HTML files - exposed to Ruby
+    fn parse_xml(&self, data: Vec<u8>) -> Result<String, Error> {
+        // XBRL instances and Flat ODF documents have dedicated parsers that
+        // keep their structure
+        match FormatDetector::detect_xml_dialect(&data) {
+            FileFormat::Xbrl => return self.parse_xbrl(data),
+            FileFormat::FlatOdf => return self.parse_flat_odf(data),
+            _ => {}
+        }
+
+        self.xml_text(&data[..])
+    }
+
+    /// Extract the text of an XML document, read event by event from
+    /// `source`; `parse_file` streams plain XML files through here, so
+    /// only the text is held in memory. With `xml_elements` or
+    /// `xml_namespaces` set, only text within the elements they select is
+    /// kept; `xml_attributes` adds attribute values before an element's text.
+    fn xml_text<R: std::io::BufRead>(&self, source: R) -> Result<String, Error> {
+        use quick_xml::events::Event;
+        use quick_xml::NsReader;
+
+        let mut reader = NsReader::from_reader(source);
+        let mut txt = String::new();
+        let mut buf = Vec::new();
+        let mut skip_depth = 0usize;
+        let mut entities = std::collections::HashMap::new();
+        let mut entity_budget = MAX_ENTITY_EXPANSION;
+        // Depth within the outermost element the filters select, if any
+        let filtered = !self.config.xml_elements.is_empty() || !self.config.xml_namespaces.is_empty();
+        let mut selected_depth = 0usize;
+
+        loop {
+            let keep = skip_depth == 0 && (!filtered || selected_depth > 0);
+            match reader.read_resolved_event_into(&mut buf) {
+                Ok((namespace, Event::Start(e))) => {
+                    if skip_depth > 0 {
+                        skip_depth += 1;
+                    } else if Self::is_non_content_element(e.local_name().as_ref()) {
+                        skip_depth = 1;
+                    }
+                    if selected_depth > 0 {
+                        selected_depth += 1;
+                    } else if filtered && self.xml_element_selected(&namespace, &e) {
+                        selected_depth = 1;
+                    }
+                    Self::separate_words(&mut txt);
+                    if skip_depth == 0 && (!filtered || selected_depth > 0) {
+                        self.push_xml_attributes(&e, &mut txt);
+                    }
+                }
+                Ok((_, Event::End(_))) => {
+                    skip_depth = skip_depth.saturating_sub(1);
+                    selected_depth = selected_depth.saturating_sub(1);
+                    Self::separate_words(&mut txt);
+                }
+                Ok((namespace, Event::Empty(e))) => {
+                    Self::separate_words(&mut txt);
+                    if keep || (skip_depth == 0 && filtered && self.xml_element_selected(&namespace, &e)) {
+                        self.push_xml_attributes(&e, &mut txt);
+                    }
+                }
+                Ok((_, Event::Text(e))) if keep => {
+                    txt.push_str(&e.decode().unwrap_or_default());
+                }
+                Ok((_, Event::CData(e))) if keep => {
+                    txt.push_str(&String::from_utf8_lossy(&e));
+                }
+                Ok((_, Event::DocType(e))) if self.config.resolve_entities => {
+                    entities = Self::internal_xml_entities(&String::from_utf8_lossy(&e));
+                }
+                Ok((_, Event::GeneralRef(e))) if keep => {
+                    let name = e.decode().unwrap_or_default();
+                    if entities.contains_key(name.as_ref()) {
+                        txt.push_str(&Self::expand_xml_entity(&name, &entities, 0, &mut entity_budget)?);
+                    } else {
+                        txt.push_str(&Self::resolve_xml_reference(&e));
+                    }
+                }
+                Ok((_, Event::Eof)) => break,
+                Err(e) => {
+                    return Err(Self::runtime_error("XML parse error", e))
+                }
+                _ => {}
+            }
+            buf.clear();
+        }
+
+        Ok(txt.trim().to_string())
+    }
+
+    /// Whether the `xml_elements` and `xml_namespaces` options select an
+    /// element. Names match the qualified or the local name; a namespace
+    /// of "" matches elements in no namespace.
+    fn xml_element_selected(
+        &self,
+        namespace: &quick_xml::name::ResolveResult,
+        e: &quick_xml::events::BytesStart,
+    ) -> bool {
+        use quick_xml::name::ResolveResult;
+
+        let names = &self.config.xml_elements;
+        let name_selected = names.is_empty()
+            || names.iter().any(|name| {
+                name.as_bytes() == e.name().as_ref() || name.as_bytes() == e.local_name().as_ref()
+            });
+        let uri: &[u8] = match namespace {
+            ResolveResult::Bound(namespace) => namespace.as_ref(),
+            _ => b"",
+        };
+        let namespaces = &self.config.xml_namespaces;
+        name_selected && (namespaces.is_empty() || namespaces.iter().any(|ns| ns.as_bytes() == uri))
+    }
+
+    /// Add the values of an element's attributes that `xml_attributes`
+    /// asks for to the text, as words of their own
+    fn push_xml_attributes(&self, e: &quick_xml::events::BytesStart, txt: &mut String) {
+        if self.config.xml_attributes == XmlAttributes::None {
+            return;
+        }
+        for attribute in e.attributes().flatten() {
+            let key = attribute.key;
+            let wanted = match &self.config.xml_attributes {
+                _ if key.as_namespace_binding().is_some() => false,
+                XmlAttributes::Named(names) => names.iter().any(|name| {
+                    name.as_bytes() == key.as_ref() || name.as_bytes() == key.local_name().as_ref()
+                }),
+                other => *other == XmlAttributes::All,
+            };
+            let value = attribute.unescape_value().map(|v| v.trim().to_string()).unwrap_or_default();
+            if wanted && !value.is_empty() {
+                Self::separate_words(txt);
+                txt.push_str(&value);
+                Self::separate_words(txt);
+            }
+        }
+    }
+
+    /// Parse XML into a tree of Hashes - exposed to Ruby
+    ///
+    /// Returns the root element as `{name:, attributes:, children:, text:}`,
+    /// with names and attribute keys as written (prefixes included),
+    /// `children` the child elements in order and `text` the element's own
+    /// text, trimmed, without that of its children. Entities are handled as
+    /// in `parse_xml`.
+    fn parse_xml_structured(&self, data: Vec<u8>) -> Result<RHash, Error> {
+        use quick_xml::events::Event;
+        use quick_xml::Reader;
+
+        let ruby = Ruby::get().unwrap();
+        let mut reader = Reader::from_reader(&data[..]);
+        let mut buf = Vec::new();
+        let mut entities = std::collections::HashMap::new();
+        let mut entity_budget = MAX_ENTITY_EXPANSION;
+        // Open elements, innermost last, with their text so far
+        let mut stack: Vec<XmlElement> = Vec::new();
+        let mut root = None;
+
+        loop {
+            let closed = match reader.read_event_into(&mut buf) {
+                Ok(Event::Start(e)) => {
+                    stack.push(Self::xml_element(&e));
+                    None
+                }
+                Ok(Event::Empty(e)) => Some(Self::xml_element(&e)),
+                Ok(Event::End(_)) => stack.pop().map(|mut element| {
+                    element.text = element.text.trim().to_string();
+                    element
+                }),
+                Ok(Event::Text(e)) => {
+                    if let Some(element) = stack.last_mut() {
+                        element.text.push_str(&e.decode().unwrap_or_default());
+                    }
+                    None
+                }
+                Ok(Event::CData(e)) => {
+                    if let Some(element) = stack.last_mut() {
+                        element.text.push_str(&String::from_utf8_lossy(&e));
+                    }
+                    None
+                }
+                Ok(Event::DocType(e)) if self.config.resolve_entities => {
+                    entities = Self::internal_xml_entities(&String::from_utf8_lossy(&e));
+                    None
+                }
+                Ok(Event::GeneralRef(e)) => {
+                    let name = e.decode().unwrap_or_default();
+                    let expansion = if entities.contains_key(name.as_ref()) {
+                        Self::expand_xml_entity(&name, &entities, 0, &mut entity_budget)?
+                    } else {
+                        Self::resolve_xml_reference(&e)
+                    };
+                    if let Some(element) = stack.last_mut() {
+                        element.text.push_str(&expansion);
+                    }
+                    None
+                }
+                Ok(Event::Eof) => break,
+                Err(e) => return Err(Self::runtime_error("XML parse error", e)),
+                _ => None,
+            };
+            if let Some(element) = closed {
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => {
+                        root.get_or_insert(element);
+                    }
+                }
+            }
+            buf.clear();
+        }
+
+        if let Some(element) = stack.first() {
+            return Err(Self::runtime_error("XML parse error", format!("unclosed element <{}>", element.name)));
+        }
+        let root = root.ok_or_else(|| Self::runtime_error("XML parse error", "no root element"))?;
+
+        // Each Hash is added to its parent's children as soon as it's made,
+        // so everything on the work stack stays reachable from the root
+        let (hash, children, rest) = Self::xml_element_hash(&ruby, root)?;
+        let mut pending = vec![(children, rest)];
+        while let Some((children, rest)) = pending.last_mut() {
+            let children = *children;
+            match rest.next() {
+                Some(child) => {
+                    let (child, grandchildren, rest) = Self::xml_element_hash(&ruby, child)?;
+                    children.push(child)?;
+                    pending.push((grandchildren, rest));
+                }
+                None => {
+                    pending.pop();
+                }
+            }
+        }
+        Ok(hash)
+    }
+
+    /// Start an element for `parse_xml_structured` with its name and attributes
+    fn xml_element(e: &quick_xml::events::BytesStart) -> XmlElement {
+        let attributes = e
+            .attributes()
+            .flatten()
+            .map(|attribute| {
+                let value = attribute.unescape_value().map(|v| v.to_string()).unwrap_or_default();
+                (String::from_utf8_lossy(attribute.key.as_ref()).to_string(), value)
+            })
+            .collect();
+        XmlElement {
+            name: String::from_utf8_lossy(e.name().as_ref()).to_string(),
+            attributes,
+            children: Vec::new(),
+            text: String::new(),
+        }
+    }
+
+    /// The Hash of an element read by `parse_xml_structured`, returned with
+    /// its (still empty) children Array and the children left to add to it
+    fn xml_element_hash(
+        ruby: &Ruby,
+        element: XmlElement,
+    ) -> Result<(RHash, RArray, std::vec::IntoIter<XmlElement>), Error> {
+        let attributes = ruby.hash_new();
+        for (key, value) in element.attributes {
+            attributes.aset(key, value)?;
+        }
+        let children = ruby.ary_new();
+        let hash = ruby.hash_new();
+        hash.aset(ruby.to_symbol("name"), element.name)?;
+        hash.aset(ruby.to_symbol("attributes"), attributes)?;
+        hash.aset(ruby.to_symbol("children"), children)?;
+        hash.aset(ruby.to_symbol("text"), element.text)?;
+        Ok((hash, children, element.children.into_iter()))
+    }
+
+    /// Evaluate an XPath 1.0 expression against an XML document - exposed to Ruby
+    ///
+    /// Node-sets come back in document order as `[{name:, text:, attributes:}]`,
+    /// with the local name (nil for text nodes), the XPath string value and,
+    /// for elements, their attributes by local name. Expressions such as
+    /// `count(//item)` or `string(/feed/@version)` return a Float, String or
+    /// true/false. `namespaces:` maps the prefixes used in the expression to
+    /// namespace URIs.
+    fn xml_query(&self, args: &[Value]) -> Result<Value, Error> {
+        use sxd_xpath::nodeset::Node;
+        use sxd_xpath::{Context, Factory};
+
+        let ruby = Ruby::get().unwrap();
+        let args = scan_args::scan_args::<(Vec<u8>, String), (), (), (), RHash, ()>(args)?;
+        let (data, expression) = args.required;
+        let kwargs = scan_args::get_kwargs::<_, (), (Option<std::collections::HashMap<String, String>>,), ()>(
+            args.keywords,
+            &[],
+            &["namespaces"],
+        )?;
+        let (namespaces,) = kwargs.optional;
+
+        let text = String::from_utf8_lossy(&data);
+        let package = sxd_document::parser::parse(text.trim_start_matches('\u{feff}'))
+            .map_err(|e| Self::runtime_error("Failed to parse XML", e))?;
+        let document = package.as_document();
+        let xpath = Factory::new()
+            .build(&expression)
+            .map_err(|e| Self::argument_error(&format!("Invalid XPath {:?}: {}", expression, e)))?;
+        let mut context = Context::new();
+        for (prefix, uri) in namespaces.unwrap_or_default() {
+            context.set_namespace(&prefix, &uri);
+        }
+        let value = xpath
+            .evaluate(&context, document.root())
+            .map_err(|e| Self::runtime_error("XPath evaluation failed", e))?;
+
+        Ok(match value {
+            sxd_xpath::Value::Boolean(value) => ruby.into_value(value),
+            sxd_xpath::Value::Number(value) => ruby.into_value(value),
+            sxd_xpath::Value::String(value) => ruby.into_value(value),
+            sxd_xpath::Value::Nodeset(nodes) => {
+                let result = ruby.ary_new();
+                for node in nodes.document_order() {
+                    let name = match &node {
+                        Node::Element(element) => Some(element.name().local_part().to_string()),
+                        Node::Attribute(attribute) => Some(attribute.name().local_part().to_string()),
+                        Node::ProcessingInstruction(pi) => Some(pi.target().to_string()),
+                        Node::Namespace(namespace) => Some(namespace.prefix().to_string()),
+                        Node::Root(_) | Node::Text(_) | Node::Comment(_) => None,
+                    };
+                    let attributes = ruby.hash_new();
+                    if let Node::Element(element) = &node {
+                        for attribute in element.attributes() {
+                            attributes.aset(attribute.name().local_part(), attribute.value())?;
+                        }
+                    }
+                    let hash = ruby.hash_new();
+                    hash.aset(ruby.to_symbol("name"), name)?;
+                    hash.aset(ruby.to_symbol("text"), node.string_value())?;
+                    hash.aset(ruby.to_symbol("attributes"), attributes)?;
+                    result.push(hash)?;
+                }
+                result.as_value()
+            }
+        })
+    }
+
+    /// Internal general entities declared in a DOCTYPE (`<!ENTITY name "value">`).
+    /// External (`SYSTEM`/`PUBLIC`) and parameter entities are never loaded.
+    fn internal_xml_entities(doctype: &str) -> std::collections::HashMap<String, String> {
+        XML_ENTITY_DECLARATION
+            .captures_iter(doctype)
+            .map(|caps| {
+                let value = caps.get(2).or_else(|| caps.get(3)).map_or("", |m| m.as_str());
+                (caps[1].to_string(), value.to_string())
+            })
+            .collect()
+    }
+
+    /// Expand an internal entity, including the references in its replacement
+    /// text. Nesting depth and the total expanded size are capped so
+    /// "billion laughs" declarations fail instead of exhausting memory.
+    fn expand_xml_entity(
+        name: &str,
+        entities: &std::collections::HashMap<String, String>,
+        depth: usize,
+        budget: &mut usize,
+    ) -> Result<String, Error> {
+        let limit_error = || Self::runtime_error("XML entity expansion limit exceeded", format!("&{};", name));
+        if depth >= MAX_ENTITY_DEPTH {
+            return Err(limit_error());
+        }
+        let Some(value) = entities.get(name) else {
+            return Ok(format!("&{};", name));
+        };
+
+        let mut expanded = String::new();
+        let mut rest = value.as_str();
+        while let Some(start) = rest.find('&') {
+            expanded.push_str(&rest[..start]);
+            let reference = &rest[start + 1..];
+            let Some(end) = reference.find(';') else {
+                expanded.push('&');
+                rest = reference;
+                continue;
+            };
+            let (inner, after) = (&reference[..end], &reference[end + 1..]);
+            let character = match inner.strip_prefix('#') {
+                Some(code) => match code.strip_prefix('x').or_else(|| code.strip_prefix('X')) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => code.parse().ok(),
+                }
+                .and_then(char::from_u32)
+                .map(String::from),
+                None => quick_xml::escape::resolve_predefined_entity(inner).map(String::from),
+            };
+            match character {
+                Some(text) => expanded.push_str(&text),
+                None => expanded.push_str(&Self::expand_xml_entity(inner, entities, depth + 1, budget)?),
+            }
+            rest = after;
+        }
+        expanded.push_str(rest);
+
+        *budget = budget.checked_sub(expanded.len()).ok_or_else(limit_error)?;
+        Ok(expanded)
+    }
+
+    /// Script, style and noscript bodies are code or fallback markup rather
+    /// than document text
+    fn is_non_content_element(name: &[u8]) -> bool {
+        [b"script".as_slice(), b"style", b"noscript"]
+            .iter()
+            .any(|skipped| name.eq_ignore_ascii_case(skipped))
+    }
+
+    /// Keep text from adjacent elements from running together
+    fn separate_words(txt: &mut String) {
+        if !txt.is_empty() && !txt.ends_with(char::is_whitespace) {
+            txt.push(' ');
+        }
+    }
+
+    /// Parse XBRL instance documents into fact rows - exposed to Ruby
+    ///
+    /// Each fact is emitted as `name, value, period, unit` (tab-separated),
+    /// with its contextRef and unitRef resolved against the document's
+    /// contexts and units.
+    fn parse_xbrl(&self, data: Vec<u8>) -> Result<String, Error> {
+        use quick_xml::events::Event;
+        use quick_xml::Reader;
+        use std::collections::HashMap;
+
+        struct Fact {
+            name: String,
+            value: String,
+            context: String,
+            unit: Option<String>,
+        }
+
+        let mut reader = Reader::from_reader(&data[..]);
+        let mut buf = Vec::new();
+
+        let mut periods: HashMap<String, String> = HashMap::new();
+        let mut units: HashMap<String, String> = HashMap::new();
+        let mut facts: Vec<Fact> = Vec::new();
+
+        // Parser state
+        let mut context_id: Option<String> = None;
+        let mut period_parts: Vec<String> = Vec::new();
+        let mut unit_id: Option<String> = None;
+        let mut numerators: Vec<String> = Vec::new();
+        let mut denominators: Vec<String> = Vec::new();
+        let mut in_denominator = false;
+        let mut capture: Option<&'static str> = None;
+        let mut text = String::new();
+        let mut fact_depth = 0;
+
+        loop {
+            let event = reader
+                .read_event_into(&mut buf)
+                .map_err(|e| Self::runtime_error("XML parse error", e))?;
+            match event {
+                Event::Start(ref e) | Event::Empty(ref e) => {
+                    let is_empty = matches!(event, Event::Empty(_));
+                    let local = e.name().local_name().as_ref().to_vec();
+
+                    if fact_depth > 0 {
+                        if !is_empty {
+                            fact_depth += 1;
+                        }
+                    } else if let Some(context_ref) = Self::xml_attribute(e, b"contextRef") {
+                        facts.push(Fact {
+                            name: String::from_utf8_lossy(e.name().as_ref()).to_string(),
+                            value: String::new(),
+                            context: context_ref,
+                            unit: Self::xml_attribute(e, b"unitRef"),
+                        });
+                        if !is_empty {
+                            fact_depth = 1;
+                            text.clear();
+                        }
+                    } else {
+                        match local.as_slice() {
+                            b"context" => {
+                                context_id = Self::xml_attribute(e, b"id");
+                                period_parts.clear();
+                            }
+                            b"forever" => period_parts.push("forever".to_string()),
+                            b"instant" | b"startDate" | b"endDate" => {
+                                capture = Some("period");
+                                text.clear();
+                            }
+                            b"unit" => {
+                                unit_id = Self::xml_attribute(e, b"id");
+                                numerators.clear();
+                                denominators.clear();
+                            }
+                            b"unitDenominator" => in_denominator = true,
+                            b"measure" => {
+                                capture = Some("measure");
+                                text.clear();
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                Event::Text(e) => {
+                    if fact_depth > 0 || capture.is_some() {
+                        text.push_str(&e.decode().unwrap_or_default());
+                    }
+                }
+                Event::CData(e) => {
+                    if fact_depth > 0 {
+                        text.push_str(&String::from_utf8_lossy(&e));
+                    }
+                }
+                Event::GeneralRef(e) => {
+                    if fact_depth > 0 || capture.is_some() {
+                        text.push_str(&Self::resolve_xml_reference(&e));
+                    }
+                }
+                Event::End(e) => {
+                    if fact_depth > 0 {
+                        fact_depth -= 1;
+                        if fact_depth == 0 {
+                            if let Some(fact) = facts.last_mut() {
+                                fact.value = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                            }
+                        }
+                        buf.clear();
+                        continue;
+                    }
+
+                    match e.name().local_name().as_ref() {
+                        b"instant" | b"startDate" | b"endDate" => {
+                            period_parts.push(text.trim().to_string());
+                            capture = None;
+                        }
+                        b"context" => {
+                            if let Some(id) = context_id.take() {
+                                periods.insert(id, period_parts.join("/"));
+                            }
+                        }
+                        b"measure" => {
+                            // Drop the namespace prefix: iso4217:USD -> USD
+                            let measure = text.trim();
+                            let measure = measure.rsplit(':').next().unwrap_or(measure).to_string();
+                            if in_denominator {
+                                denominators.push(measure);
+                            } else {
+                                numerators.push(measure);
+                            }
+                            capture = None;
+                        }
+                        b"unitDenominator" => in_denominator = false,
+                        b"unit" => {
+                            if let Some(id) = unit_id.take() {
+                                let mut unit = numerators.join("*");
+                                if !denominators.is_empty() {
+                                    unit = format!("{}/{}", unit, denominators.join("*"));
+                                }
+                                units.insert(id, unit);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+            buf.clear();
+        }
+
+        let mut rows = vec!["Fact\tValue\tPeriod\tUnit".to_string()];
+        for fact in facts {
+            let period = periods.get(&fact.context).cloned().unwrap_or(fact.context);
+            let unit = fact
+                .unit
+                .map(|id| units.get(&id).cloned().unwrap_or(id))
+                .unwrap_or_default();
+            rows.push(format!("{}\t{}\t{}\t{}", fact.name, fact.value, period, unit));
+        }
+
+        Ok(rows.join("\n"))
+    }
+
+    /// Parse Flat ODF documents (.fodt, .fods, .fodp) - exposed to Ruby.
+    /// Paragraphs and headings become lines, tables become tab-delimited rows
+    /// (one "Sheet:" block per spreadsheet table) and presentation pages are
+    /// separated by blank lines with their speaker notes appended.
+    fn parse_flat_odf(&self, data: Vec<u8>) -> Result<String, Error> {
+        use quick_xml::events::Event;
+        use quick_xml::Reader;
+
+        // Repeat attributes pad sheets out to their full width and height;
+        // cap them so a styled-but-empty region can't blow up the output
+        const MAX_REPEAT: usize = 1024;
+
+        let mut reader = Reader::from_reader(&data[..]);
+        let mut buf = Vec::new();
+        let mut lines: Vec<String> = Vec::new();
+
+        let mut in_body = false;
+        let mut is_spreadsheet = false;
+        let mut skip_depth = 0usize;
+        let mut para_depth = 0usize;
+        let mut text = String::new();
+        let mut table_depth = 0usize;
+        let mut row: Vec<String> = Vec::new();
+        let mut row_repeat = 1usize;
+        let mut cell: Option<String> = None;
+        let mut cell_repeat = 1usize;
+        let mut pages = 0usize;
+        let mut notes: Option<Vec<String>> = None;
+
+        let repeat = |e: &quick_xml::events::BytesStart, name: &[u8]| -> usize {
+            e.attributes()
+                .flatten()
+                .find(|a| a.key.local_name().as_ref() == name)
+                .and_then(|a| a.unescape_value().ok()?.parse().ok())
+                .unwrap_or(1usize)
+                .clamp(1, MAX_REPEAT)
+        };
+
+        loop {
+            let event = reader
+                .read_event_into(&mut buf)
+                .map_err(|e| Self::runtime_error("Flat ODF parse error", e))?;
+            match event {
+                Event::Start(ref e) | Event::Empty(ref e) => {
+                    let is_empty = matches!(event, Event::Empty(_));
+                    if skip_depth > 0 {
+                        if !is_empty {
+                            skip_depth += 1;
+                        }
+                        buf.clear();
+                        continue;
+                    }
+                    match e.name().local_name().as_ref() {
+                        b"body" => in_body = !is_empty,
+                        _ if !in_body => {}
+                        b"spreadsheet" => is_spreadsheet = true,
+                        // Comments and deleted revisions aren't part of the visible text
+                        b"annotation" | b"tracked-changes" if !is_empty => skip_depth = 1,
+                        b"p" | b"h" if !is_empty => para_depth += 1,
+                        b"s" if para_depth > 0 => {
+                            text.push_str(&" ".repeat(repeat(e, b"c")));
+                        }
+                        b"tab" if para_depth > 0 => text.push('\t'),
+                        b"line-break" if para_depth > 0 => text.push('\n'),
+                        b"table" if !is_empty => {
+                            table_depth += 1;
+                            if table_depth == 1 && is_spreadsheet {
+                                let name = Self::xml_attribute(e, b"name").unwrap_or_default();
+                                if !lines.is_empty() {
+                                    lines.push(String::new());
+                                }
+                                lines.push(format!("Sheet: {}", name));
+                            }
+                        }
+                        b"table-row" if table_depth == 1 => {
+                            row.clear();
+                            row_repeat = repeat(e, b"number-rows-repeated");
+                        }
+                        b"table-cell" | b"covered-table-cell" if table_depth == 1 => {
+                            cell_repeat = repeat(e, b"number-columns-repeated");
+                            if is_empty {
+                                row.extend(std::iter::repeat_n(String::new(), cell_repeat));
+                            } else {
+                                cell = Some(String::new());
+                            }
+                        }
+                        b"page" => {
+                            if pages > 0 {
+                                lines.push(String::new());
+                            }
+                            pages += 1;
+                        }
+                        b"notes" if !is_empty => notes = Some(Vec::new()),
+                        _ => {}
+                    }
+                }
+                Event::Text(e) if skip_depth == 0 && para_depth > 0 => {
+                    // ODF collapses whitespace runs; explicit spaces use <text:s/>
+                    let decoded = e.decode().unwrap_or_default();
+                    let mut last_space = text.ends_with(' ');
+                    for ch in decoded.chars() {
+                        if ch.is_whitespace() {
+                            if !last_space {
+                                text.push(' ');
+                            }
+                            last_space = true;
+                        } else {
+                            text.push(ch);
+                            last_space = false;
+                        }
+                    }
+                }
+                Event::GeneralRef(e) if skip_depth == 0 && para_depth > 0 => {
+                    text.push_str(&Self::resolve_xml_reference(&e));
+                }
+                Event::End(e) => {
+                    if skip_depth > 0 {
+                        skip_depth -= 1;
+                        buf.clear();
+                        continue;
+                    }
+                    match e.name().local_name().as_ref() {
+                        b"body" => in_body = false,
+                        _ if !in_body => {}
+                        b"p" | b"h" if para_depth > 0 => {
+                            para_depth -= 1;
+                            if para_depth == 0 {
+                                let paragraph = text.trim().to_string();
+                                text.clear();
+                                if paragraph.is_empty() {
+                                    // Nothing to record
+                                } else if let Some(cell) = cell.as_mut() {
+                                    if !cell.is_empty() {
+                                        cell.push(' ');
+                                    }
+                                    cell.push_str(&paragraph);
+                                } else if let Some(notes) = notes.as_mut() {
+                                    notes.push(paragraph);
+                                } else {
+                                    lines.push(paragraph);
+                                }
+                            } else {
+                                text.push(' ');
+                            }
+                        }
+                        b"table-cell" | b"covered-table-cell" if table_depth == 1 => {
+                            if let Some(value) = cell.take() {
+                                // Tabs and newlines inside a cell would break the row layout
+                                let value = value.replace(['\t', '\n'], " ");
+                                row.extend(std::iter::repeat_n(value, cell_repeat));
+                            }
+                        }
+                        b"table-row" if table_depth == 1 => {
+                            while row.last().is_some_and(|value| value.is_empty()) {
+                                row.pop();
+                            }
+                            if !row.is_empty() {
+                                let joined = row.join("\t");
+                                lines.extend(std::iter::repeat_n(joined, row_repeat));
+                            }
+                            row.clear();
+                        }
+                        b"table" if table_depth > 0 => table_depth -= 1,
+                        b"notes" => {
+                            if let Some(notes) = notes.take() {
+                                if !notes.is_empty() {
+                                    lines.push(format!("[Notes: {}]", notes.join(" ")));
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+            buf.clear();
+        }
+
+        Ok(lines.join("\n"))
+    }
+
+    /// Resolve a character, predefined or HTML named entity reference
+    /// (`&#169;`, `&amp;`, `&nbsp;`) to its text; unknown entities are kept
+    /// verbatim
+    fn resolve_xml_reference(reference: &quick_xml::events::BytesRef) -> String {
+        if let Ok(Some(ch)) = reference.resolve_char_ref() {
+            return ch.to_string();
+        }
+        let name = reference.decode().unwrap_or_default();
+        if let Some(text) = quick_xml::escape::resolve_predefined_entity(&name) {
+            return text.to_string();
+        }
+
+        // Let the HTML parser look the name up in the HTML5 entity table
+        let raw = format!("&{};", name);
+        let fragment = scraper::Html::parse_fragment(&raw);
+        let decoded: String = fragment.root_element().text().collect();
+        if decoded.is_empty() {
+            raw
+        } else {
+            decoded
+        }
+    }
+
+    /// Parse HTML with a tolerant DOM parser - exposed to Ruby
+    fn parse_html(&self, data: Vec<u8>) -> Result<String, Error> {
+        let html = self.parse_text(data)?;
+        let document = scraper::Html::parse_document(&html);
+
+        let mut text = String::new();
+        if self.config.main_content {
+            let (content, boilerplate) = Self::html_main_content(&document);
+            Self::collect_html_text(content, &mut text, &boilerplate);
+        } else {
+            Self::collect_html_text(document.tree.root(), &mut text, &Default::default());
+        }
+
+        // Drop the blank lines left behind by nested block elements
+        let lines: Vec<&str> = text
+            .lines()
+            .map(|line| line.trim_end())
+            .filter(|line| !line.is_empty())
+            .collect();
+
+        Ok(lines.join("\n"))
+    }
+
+    /// Walk the DOM collecting text, emitting newlines at block boundaries.
+    /// Elements in `skipped` are left out with everything inside them. The
+    /// walk uses `traverse`, so deeply nested markup can't exhaust the stack.
+    fn collect_html_text(
+        root: ego_tree::NodeRef<scraper::Node>,
+        out: &mut String,
+        skipped: &std::collections::HashSet<ego_tree::NodeId>,
+    ) {
+        use ego_tree::iter::Edge;
+
+        // Open `pre` elements, and the element being left out, if any
+        let mut preformatted = 0usize;
+        let mut skipping = None;
+        for edge in root.traverse() {
+            match edge {
+                Edge::Open(node) if skipping.is_none() => match node.value() {
+                    scraper::Node::Text(text) => Self::push_html_text(text, out, preformatted > 0),
+                    scraper::Node::Element(element) => {
+                        let name = element.name();
+                        if name == "br" {
+                            out.push('\n');
+                            continue;
+                        }
+                        if Self::is_non_content_element(name.as_bytes()) || skipped.contains(&node.id()) {
+                            skipping = Some(node.id());
+                            continue;
+                        }
+
+                        if Self::is_html_block_element(name) && !out.is_empty() && !out.ends_with('\n') {
+                            out.push('\n');
+                        }
+                        // Keep table rows on one line with tab-separated cells
+                        if matches!(name, "td" | "th") && !out.is_empty() && !out.ends_with('\n') {
+                            out.truncate(out.trim_end_matches(' ').len());
+                            out.push('\t');
+                        }
+                        if name == "pre" {
+                            preformatted += 1;
+                        }
+                    }
+                    _ => {}
+                },
+                Edge::Open(_) => {}
+                Edge::Close(node) => {
+                    if skipping.is_some() {
+                        if skipping == Some(node.id()) {
+                            skipping = None;
+                        }
+                        continue;
+                    }
+                    if let scraper::Node::Element(element) = node.value() {
+                        let name = element.name();
+                        if name == "br" {
+                            continue;
+                        }
+                        if name == "pre" {
+                            preformatted -= 1;
+                        }
+                        if Self::is_html_block_element(name) && !out.ends_with('\n') {
+                            out.push('\n');
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Add a DOM text node to `out`, collapsing runs of whitespace the way a
+    /// browser would outside `pre`
+    fn push_html_text(text: &str, out: &mut String, preformatted: bool) {
+        if preformatted {
+            out.push_str(text);
+            return;
+        }
+        let starts_with_space = text.starts_with(char::is_whitespace);
+        let ends_with_space = text.ends_with(char::is_whitespace);
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let at_line_start = out.is_empty() || out.ends_with('\n') || out.ends_with(' ');
+        if words.is_empty() {
+            if !at_line_start {
+                out.push(' ');
+            }
+            return;
+        }
+        if starts_with_space && !at_line_start {
+            out.push(' ');
+        }
+        out.push_str(&words.join(" "));
+        if ends_with_space {
+            out.push(' ');
+        }
+    }
+
+    /// Find the main content of an HTML page for `main_content`, in the
+    /// manner of Readability. Navigation, asides, footers and elements
+    /// whose class or id reads like ads, sidebars, menus or share widgets
+    /// are boilerplate. Every paragraph of 25 characters or more
+    /// outside them scores for its parent, and half for its grandparent:
+    /// one point, one per comma and one per 100 characters up to three.
+    /// The best-scoring element, after tag and class weights and a cut for
+    /// the share of its text in links, holds the content; link lists within
+    /// it are boilerplate too. Returns that element, or the whole document
+    /// when no paragraph scores, with the boilerplate to skip.
+    fn html_main_content(
+        document: &scraper::Html,
+    ) -> (ego_tree::NodeRef<'_, scraper::Node>, std::collections::HashSet<ego_tree::NodeId>) {
+        let class_and_id = |element: &scraper::node::Element| {
+            format!("{} {}", element.attr("class").unwrap_or_default(), element.id().unwrap_or_default())
+        };
+
+        let root = document.tree.root();
+        let mut boilerplate = std::collections::HashSet::new();
+        for node in root.descendants() {
+            let Some(element) = node.value().as_element() else { continue };
+            let name = element.name();
+            let role = element.attr("role").unwrap_or_default();
+            let names = class_and_id(element);
+            let dropped = matches!(name, "nav" | "aside" | "footer" | "menu" | "dialog" | "iframe" | "button")
+                || matches!(role, "navigation" | "banner" | "contentinfo" | "complementary" | "search" | "dialog")
+                || (!matches!(name, "html" | "body" | "article" | "main")
+                    && HTML_UNLIKELY_CONTENT.is_match(&names)
+                    && !HTML_LIKELY_CONTENT.is_match(&names));
+            if dropped {
+                boilerplate.insert(node.id());
+            }
+        }
+        let kept = |node: ego_tree::NodeRef<scraper::Node>| {
+            !node.ancestors().chain(std::iter::once(node)).any(|n| boilerplate.contains(&n.id()))
+        };
+
+        // Candidates in document order, so ties go to the first
+        let mut candidates: Vec<(ego_tree::NodeId, f64)> = Vec::new();
+        let mut index = std::collections::HashMap::new();
+        for node in root.descendants() {
+            let is_paragraph = node
+                .value()
+                .as_element()
+                .is_some_and(|element| matches!(element.name(), "p" | "pre" | "td" | "blockquote"));
+            if !is_paragraph || !kept(node) {
+                continue;
+            }
+            let text: String = node.descendants().filter_map(|n| n.value().as_text().map(|t| &**t)).collect();
+            let length = Self::html_text_length(node);
+            if length < 25 {
+                continue;
+            }
+            let score = 1.0 + text.matches(',').count() as f64 + (length / 100).min(3) as f64;
+            for (level, ancestor) in node.ancestors().filter(|n| n.value().is_element()).take(2).enumerate() {
+                let slot = *index.entry(ancestor.id()).or_insert_with(|| {
+                    let element = ancestor.value().as_element().unwrap();
+                    let weight = match element.name() {
+                        "article" | "main" => 10.0,
+                        "div" => 5.0,
+                        "pre" | "td" | "blockquote" => 3.0,
+                        "address" | "ol" | "ul" | "dl" | "dd" | "dt" | "li" | "form" => -3.0,
+                        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => -5.0,
+                        _ => 0.0,
+                    };
+                    let names = class_and_id(element);
+                    let weight = weight + if HTML_LIKELY_CONTENT.is_match(&names) { 25.0 } else { 0.0 }
+                        - if HTML_UNLIKELY_CONTENT.is_match(&names) { 25.0 } else { 0.0 };
+                    candidates.push((ancestor.id(), weight));
+                    candidates.len() - 1
+                });
+                candidates[slot].1 += if level == 0 { score } else { score / 2.0 };
+            }
+        }
+
+        let best = candidates
+            .iter()
+            .filter_map(|&(id, score)| document.tree.get(id).map(|node| (node, score)))
+            .map(|(node, score)| (node, score * (1.0 - Self::html_link_density(node))))
+            .fold(None, |best: Option<(ego_tree::NodeRef<scraper::Node>, f64)>, (node, score)| match best {
+                Some((_, top)) if top >= score => best,
+                _ => Some((node, score)),
+            });
+        let content = best.map_or(root, |(node, _)| node);
+        for node in content.descendants().skip(1) {
+            let is_list = node
+                .value()
+                .as_element()
+                .is_some_and(|element| matches!(element.name(), "ul" | "ol" | "div" | "section" | "table"));
+            if is_list && Self::html_link_density(node) > 0.5 {
+                boilerplate.insert(node.id());
+            }
+        }
+        (content, boilerplate)
+    }
+
+    /// Characters of text under an HTML node, whitespace collapsed
+    fn html_text_length(node: ego_tree::NodeRef<scraper::Node>) -> usize {
+        node.descendants()
+            .filter_map(|n| n.value().as_text())
+            .flat_map(|text| text.split_whitespace())
+            .map(|word| word.chars().count() + 1)
+            .sum()
+    }
+
+    /// The share of the text under an HTML node that sits in links
+    fn html_link_density(node: ego_tree::NodeRef<scraper::Node>) -> f64 {
+        let total = Self::html_text_length(node);
+        if total == 0 {
+            return 0.0;
+        }
+        let linked: usize = node
+            .descendants()
+            .filter(|n| n.value().as_element().is_some_and(|element| element.name() == "a"))
+            .map(Self::html_text_length)
+            .sum();
+        linked as f64 / total as f64
+    }
+
+    /// Elements that start a new line of text when rendered
+    fn is_html_block_element(name: &str) -> bool {
+        matches!(
+            name,
+            "address" | "article" | "aside" | "blockquote" | "body" | "caption" | "dd" | "div"
+                | "dl" | "dt" | "fieldset" | "figcaption" | "figure" | "footer" | "form" | "h1"
+                | "h2" | "h3" | "h4" | "h5" | "h6" | "head" | "header" | "hr" | "html" | "li"
+                | "main" | "nav" | "ol" | "p" | "pre" | "section" | "table" | "title" | "tr"
+                | "ul"
+        )
+    }
+
+    /// Parse MHTML web archives (.mht/.mhtml) - exposed to Ruby
+    fn parse_mhtml(&self, data: Vec<u8>) -> Result<String, Error> {
+        use mailparse::MailHeaderMap;
+
+        let archive = mailparse::parse_mail(&data)
+            .map_err(|e| Self::runtime_error("Failed to parse MHTML archive", e))?;
+
+        // Flatten the MIME tree into its leaf parts
+        let mut parts = Vec::new();
+        let mut pending = vec![&archive];
+        while let Some(part) = pending.pop() {
+            if part.subparts.is_empty() {
+                parts.push(part);
+            } else {
+                pending.extend(part.subparts.iter().rev());
+            }
+        }
+
+        // The first HTML part is the page itself; everything else is a resource
+        let main_index = parts
+            .iter()
+            .position(|part| part.ctype.mimetype.eq_ignore_ascii_case("text/html"));
+
+        let mut result = match main_index {
+            Some(index) => {
+                let body = parts[index]
+                    .get_body()
+                    .map_err(|e| Self::runtime_error("Failed to decode MHTML page", e))?;
+                self.parse_html(body.into_bytes())?
+            }
+            None => String::new(),
+        };
+
+        let resources: Vec<String> = parts
+            .iter()
+            .enumerate()
+            .filter(|&(index, _)| Some(index) != main_index)
+            .map(|(_, part)| {
+                let location = part
+                    .headers
+                    .get_first_value("Content-Location")
+                    .or_else(|| part.headers.get_first_value("Content-ID"))
+                    .unwrap_or_default();
+                format!("  {} {}", part.ctype.mimetype, location).trim_end().to_string()
+            })
+            .collect();
+
+        if !resources.is_empty() {
+            result.push_str("\n\nResources:\n");
+            result.push_str(&resources.join("\n"));
+        }
+
+        Ok(result.trim().to_string())
+    }
+
+    /// Parse Outlook PST/OST mail stores - exposed to Ruby
+    ///
+    /// With a block, each message is yielded as soon as it is read and nil is
+    /// returned, so large stores never sit in memory as one string. Without a
+    /// block the messages are returned joined by blank lines. Needs the `pst`
+    /// feature and libpst's `readpst` at runtime.
+    fn parse_pst(ruby: &Ruby, rb_self: &Self, data: Vec<u8>) -> Result<Option<String>, Error> {
+        if ruby.block_given() {
+            rb_self.each_pst_message(&data, |message| ruby.yield_value::<_, Value>(message).map(|_| ()))?;
+            Ok(None)
+        } else {
+            rb_self.pst_text(&data).map(Some)
+        }
+    }
+
+    /// All messages of a PST/OST store, separated by blank lines
+    fn pst_text(&self, data: &[u8]) -> Result<String, Error> {
+        Self::with_temp_file(data, "pst", |path| self.pst_file_text(path))
+    }
+
+    /// `pst_text` for a store already on disk
+    fn pst_file_text(&self, path: &std::path::Path) -> Result<String, Error> {
+        let mut messages = Vec::new();
+        self.each_pst_message_at(path, |message| {
+            messages.push(message);
+            Ok(())
+        })?;
+        Ok(messages.join("\n\n"))
+    }
+
+    /// Export a PST/OST store with `readpst` and hand each message, rendered
+    /// by `format_email`, to `f` in folder order. `readpst` only reads files,
+    /// so the store is written to a private temporary file first.
+    fn each_pst_message(
+        &self,
+        data: &[u8],
+        f: impl FnMut(String) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        Self::with_temp_file(data, "pst", |path| self.each_pst_message_at(path, f))
+    }
+
+    /// `each_pst_message` for a store already on disk, such as one given to
+    /// `parse_file`, which is never read into memory
+    fn each_pst_message_at(
+        &self,
+        path: &std::path::Path,
+        mut f: impl FnMut(String) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        #[cfg(feature = "pst")]
+        {
+            use std::process::Command;
+
+            // Exported messages go to a private directory, removed on drop
+            let out_dir = tempfile::Builder::new()
+                .prefix("parsekit-pst-")
+                .tempdir()
+                .map_err(|e| Self::io_error("Failed to create temporary directory", e))?;
+
+            // -e: one .eml file per message, -t e: e-mail items only,
+            // -b: skip the RTF copy of each body
+            let output = Command::new("readpst")
+                .args(["-e", "-b", "-q", "-t", "e", "-o"])
+                .arg(out_dir.path())
+                .arg(path)
+                .output()
+                .map_err(|e| Self::runtime_error("Failed to run readpst (is libpst installed?)", e))?;
+            if !output.status.success() {
+                return Err(Self::runtime_error(
+                    "Failed to parse PST file",
+                    String::from_utf8_lossy(&output.stderr).trim(),
+                ));
+            }
+
+            for (folder, file) in Self::pst_message_files(out_dir.path())? {
+                let raw = std::fs::read(&file)
+                    .map_err(|e| Self::io_error("Failed to read exported message", e))?;
+                let message = match mailparse::parse_mail(&raw) {
+                    Ok(mail) => self.format_email(&mail)?,
+                    Err(e) => format!("(failed to parse: {})", e),
+                };
+                f(format!("Folder: {}\n{}", folder, message))?;
+            }
+            Ok(())
+        }
+
+        #[cfg(not(feature = "pst"))]
+        {
+            let _ = (path, &mut f);
+            Err(Self::runtime_error(
+                "Failed to parse PST file",
+                "PST support requires building with the `pst` feature",
+            ))
+        }
+    }
+
+    /// The `.eml` files written by `readpst`, paired with their folder path
+    /// and ordered by folder, then message number
+    #[cfg(feature = "pst")]
+    fn pst_message_files(root: &std::path::Path) -> Result<Vec<(String, std::path::PathBuf)>, Error> {
+        let mut files = Vec::new();
+        let mut pending = vec![root.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            let entries = std::fs::read_dir(&dir)
+                .map_err(|e| Self::io_error("Failed to read exported folder", e))?;
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.is_dir() {
+                    pending.push(path);
+                } else if path.extension().is_some_and(|ext| ext == "eml") {
+                    let folder = dir
+                        .strip_prefix(root)
+                        .map(|folder| folder.to_string_lossy().to_string())
+                        .unwrap_or_default();
+                    files.push((folder, path));
+                }
+            }
+        }
+
+        let number = |path: &std::path::Path| {
+            path.file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse::<u64>().ok())
+                .unwrap_or(u64::MAX)
+        };
+        files.sort_by(|(a_folder, a), (b_folder, b)| {
+            a_folder.cmp(b_folder).then_with(|| number(a).cmp(&number(b)))
+        });
+        Ok(files)
+    }
+
+    /// Render an e-mail as its address headers, date and subject followed by
+    /// the body. The plain-text part is preferred; HTML-only bodies are
+    /// converted to text. Attachments are listed by file name and, with
+    /// `parse_attachments: true`, decoded and parsed into their own
+    /// `=== Attachment: name ===` sections.
+    fn format_email(&self, mail: &mailparse::ParsedMail) -> Result<String, Error> {
+        use mailparse::MailHeaderMap;
+
+        let mut lines = Vec::new();
+        for header in ["From", "To", "Cc", "Date", "Subject"] {
+            if let Some(value) = mail.headers.get_first_value(header) {
+                lines.push(format!("{}: {}", header, value.trim()));
+            }
+        }
+
+        // Flatten the MIME tree into its leaf parts
+        let mut parts = Vec::new();
+        let mut pending = vec![mail];
+        while let Some(part) = pending.pop() {
+            if part.subparts.is_empty() {
+                parts.push(part);
+            } else {
+                pending.extend(part.subparts.iter().rev());
+            }
+        }
+
+        let (attachments, inline): (Vec<_>, Vec<_>) = parts.into_iter().partition(|part| {
+            part.get_content_disposition().disposition == mailparse::DispositionType::Attachment
+        });
+
+        let plain = inline.iter().copied().find(|part| part.ctype.mimetype.eq_ignore_ascii_case("text/plain"));
+        let html = inline.iter().copied().find(|part| part.ctype.mimetype.eq_ignore_ascii_case("text/html"));
+        let decode = |part: &mailparse::ParsedMail| {
+            part.get_body()
+                .map_err(|e| Self::runtime_error("Failed to decode message body", e))
+        };
+        let body = if let Some(part) = plain {
+            decode(part)?.replace("\r\n", "\n").trim().to_string()
+        } else if let Some(part) = html {
+            self.parse_html(decode(part)?.into_bytes())?
+        } else {
+            String::new()
+        };
+
+        let names: Vec<String> = attachments
+            .iter()
+            .map(|part| {
+                let disposition = part.get_content_disposition();
+                disposition
+                    .params
+                    .get("filename")
+                    .or_else(|| part.ctype.params.get("name"))
+                    .cloned()
+                    .unwrap_or_else(|| part.ctype.mimetype.clone())
+            })
+            .collect();
+        if !names.is_empty() {
+            lines.push(format!("Attachments: {}", names.join(", ")));
+        }
+
+        if !body.is_empty() {
+            lines.push(String::new());
+            lines.push(body);
+        }
+
+        if self.config.parse_attachments {
+            for (part, name) in attachments.iter().zip(&names) {
+                let text = match part.get_body_raw() {
+                    Ok(contents) => self.parse_embedded_file(name, contents)?,
+                    Err(e) => format!("(failed to decode: {})", e),
+                };
+                lines.push(String::new());
+                lines.push(format!("=== Attachment: {} ===\n{}", name, text));
+            }
+        }
+        Ok(lines.join("\n"))
+    }
+
+    /// Parse e-mail messages (.eml, RFC 5322/MIME) - exposed to Ruby
+    fn parse_email(&self, data: Vec<u8>) -> Result<String, Error> {
+        let mail = mailparse::parse_mail(&data)
+            .map_err(|e| Self::runtime_error("Failed to parse e-mail message", e))?;
+        self.format_email(&mail)
+    }
+
+    /// Parse Compiled HTML Help (.chm) files - exposed to Ruby
+    fn parse_chm(&self, data: Vec<u8>) -> Result<String, Error> {
+        use chmlib::{ChmFile, Filter};
+
+        Self::with_temp_file(&data, "chm", |path| {
+            let mut chm = ChmFile::open(path)
+                .map_err(|e| Self::runtime_error("Failed to open CHM file", e))?;
+
+            // Collect the HTML topics first; reading inside the enumeration
+            // callback would need a second mutable borrow of the file
+            let mut topics = Vec::new();
+            chm.for_each(Filter::NORMAL | Filter::FILES, |_, unit| {
+                let is_html = unit
+                    .path()
+                    .and_then(|p| p.extension())
+                    .and_then(|e| e.to_str())
+                    .map(|e| e.eq_ignore_ascii_case("htm") || e.eq_ignore_ascii_case("html"))
+                    .unwrap_or(false);
+                if is_html {
+                    topics.push(unit);
+                }
+            })
+            .map_err(|e| Self::runtime_error("Failed to list CHM contents", e))?;
+
+            // Topic sizes come from the directory, so they're checked against
+            // the limits before anything is allocated for them
+            let mut sections = Vec::new();
+            let mut budget = self.config.max_size as u64;
+            for topic in topics {
+                let length = topic.length();
+                if length > self.config.max_entry_size as u64 {
+                    return Err(self.entry_size_error("CHM"));
+                }
+                if length > budget {
+                    return Err(self.decompressed_size_error("CHM"));
+                }
+                budget -= length;
+
+                let mut buffer = vec![0u8; length as usize];
+                let read = chm
+                    .read(&topic, 0, &mut buffer)
+                    .map_err(|e| Self::runtime_error("Failed to read CHM topic", e))?;
+                buffer.truncate(read);
+
+                let text = self.parse_html(buffer)?;
+                if !text.is_empty() {
+                    sections.push(text);
+                }
+            }
+
+            Ok(sections.join("\n\n"))
+        })
+    }
+
+    /// Parse MOBI/AZW3 (Kindle) and PalmDOC ebooks - exposed to Ruby
+    fn parse_mobi(&self, data: Vec<u8>) -> Result<String, Error> {
+        let html = Self::extract_mobi_text(&data)?;
+        self.parse_html(html)
+    }
+
+    /// Decompress the text records of a PalmDB ebook into its raw (HTML) text
+    fn extract_mobi_text(data: &[u8]) -> Result<Vec<u8>, Error> {
+        let invalid = |msg: &str| Self::runtime_error("Failed to parse MOBI file", msg);
+        let u16_at = |d: &[u8], i: usize| d.get(i..i + 2).map(|b| u16::from_be_bytes([b[0], b[1]]));
+        let u32_at = |d: &[u8], i: usize| {
+            d.get(i..i + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        };
+
+        // PalmDB header: record count at 76, record offsets from 78
+        let record_count = u16_at(data, 76).ok_or_else(|| invalid("truncated PalmDB header"))? as usize;
+        let offsets = (0..record_count)
+            .map(|i| u32_at(data, 78 + i * 8).map(|o| o as usize))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| invalid("truncated record list"))?;
+        let record = |i: usize| -> Option<&[u8]> {
+            let start = *offsets.get(i)?;
+            let end = offsets.get(i + 1).copied().unwrap_or(data.len());
+            data.get(start..end.max(start))
+        };
+
+        // Record 0 holds the PalmDOC header, optionally followed by the MOBI header
+        let header = record(0).ok_or_else(|| invalid("missing header record"))?;
+        let compression = u16_at(header, 0).ok_or_else(|| invalid("truncated PalmDOC header"))?;
+        let text_records = u16_at(header, 8).unwrap_or(0) as usize;
+        if u16_at(header, 12).unwrap_or(0) != 0 {
+            return Err(invalid("book is DRM-encrypted"));
+        }
+
+        let mut extra_flags = 0u16;
+        if header.get(16..20) == Some(b"MOBI") {
+            let mobi_header_len = u32_at(header, 20).unwrap_or(0);
+            if mobi_header_len >= 0xE4 {
+                extra_flags = u16_at(header, 0xF2).unwrap_or(0);
+            }
+        }
+
+        let mut text = Vec::new();
+        for i in 1..=text_records {
+            let Some(raw) = record(i) else { break };
+            let raw = Self::strip_mobi_trailing_entries(raw, extra_flags);
+            match compression {
+                1 => text.extend_from_slice(raw),
+                2 => Self::palmdoc_decompress(raw, &mut text),
+                17480 => return Err(invalid("HUFF/CDIC compression is not supported")),
+                other => return Err(invalid(&format!("unknown compression type {}", other))),
+            }
+        }
+
+        Ok(text)
+    }
+
+    /// Remove the trailing entries MOBI appends to text records, as described
+    /// by the extra data flags in the MOBI header
+    fn strip_mobi_trailing_entries(record: &[u8], flags: u16) -> &[u8] {
+        let mut end = record.len();
+
+        // Bits 1-15: each entry ends with its own size as a backward varint
+        for bit in 1..16 {
+            if flags & (1 << bit) == 0 {
+                continue;
+            }
+            let mut size = 0usize;
+            let mut shift = 0;
+            let mut pos = end;
+            while pos > 0 && shift < 28 {
+                pos -= 1;
+                let byte = record[pos];
+                size |= ((byte & 0x7F) as usize) << shift;
+                shift += 7;
+                if byte & 0x80 != 0 {
+                    break;
+                }
+            }
+            end = end.saturating_sub(size);
+        }
+
+        // Bit 0: multibyte character overlap, sized by the low bits of the last byte
+        if flags & 1 != 0 && end > 0 {
+            end = end.saturating_sub((record[end - 1] & 0x3) as usize + 1);
+        }
+
+        &record[..end]
+    }
+
+    /// Decompress a PalmDOC (LZ77 variant) record
+    fn palmdoc_decompress(input: &[u8], out: &mut Vec<u8>) {
+        let start = out.len();
+        let mut i = 0;
+
+        while i < input.len() {
+            let byte = input[i];
+            i += 1;
+            match byte {
+                // Copy the next 1-8 bytes verbatim
+                0x01..=0x08 => {
+                    let end = (i + byte as usize).min(input.len());
+                    out.extend_from_slice(&input[i..end]);
+                    i = end;
+                }
+                // Back-reference: 11-bit distance, 3-bit length
+                0x80..=0xBF => {
+                    let Some(&next) = input.get(i) else { break };
+                    i += 1;
+                    let pair = (((byte as usize) << 8) | next as usize) & 0x3FFF;
+                    let distance = pair >> 3;
+                    let length = (pair & 0x7) + 3;
+                    if distance == 0 || distance > out.len() - start {
+                        continue;
+                    }
+                    for _ in 0..length {
+                        out.push(out[out.len() - distance]);
+                    }
+                }
+                // Space followed by an ASCII character
+                0xC0..=0xFF => {
+                    out.push(b' ');
+                    out.push(byte ^ 0x80);
+                }
+                // Literal byte
+                _ => out.push(byte),
+            }
+        }
+    }
+
+    /// Parse DICOM medical images - exposed to Ruby
+    ///
+    /// Emits patient, study and series attributes as `Name: value` lines. With
+    /// `ocr_pixel_data: true`, the first frame is also run through OCR to
+    /// pick up annotations burned into the image.
+    fn parse_dicom(&self, data: Vec<u8>) -> Result<String, Error> {
+        let dataset = Self::read_dicom(&data)?;
+
+        let mut lines: Vec<String> = dataset
+            .fields
+            .iter()
+            .map(|(name, value)| format!("{}: {}", name, value))
+            .collect();
+
+        if self.config.ocr_pixel_data
+            && (dataset.pixel_data.is_some() || !dataset.pixel_fragments.is_empty())
+        {
+            let image = Self::render_dicom_frame(&dataset)
+                .map_err(|e| Self::runtime_error("Failed to decode DICOM pixel data", e))?;
+            let text = self.ocr_text(&image)?;
+            if !text.is_empty() {
+                lines.push(String::new());
+                lines.push("Burned-in text:".to_string());
+                lines.push(text);
+            }
+        }
+
+        Ok(lines.join("\n"))
+    }
+
+    /// Read the text attributes and pixel data of a DICOM Part 10 file
+    fn read_dicom(data: &[u8]) -> Result<DicomDataSet, Error> {
+        if data.get(128..132) != Some(b"DICM") {
+            return Err(Self::runtime_error("Failed to parse DICOM file", "missing DICM prefix"));
+        }
+
+        let mut dataset = DicomDataSet::default();
+        let mut pos = 132;
+        Self::read_dicom_elements(data, &mut pos, data.len(), &mut dataset, 0)?;
+        Ok(dataset)
+    }
+
+    /// Walk data elements until `end` or a sequence/item delimiter
+    ///
+    /// Elements inside sequences (`depth` above 0) are stepped over but not
+    /// recorded. Sequences nested deeper than `MAX_DICOM_NESTING` raise a
+    /// ResourceLimitError.
+    fn read_dicom_elements(
+        data: &[u8],
+        pos: &mut usize,
+        end: usize,
+        dataset: &mut DicomDataSet,
+        depth: usize,
+    ) -> Result<(), Error> {
+        const UNDEFINED_LENGTH: usize = 0xFFFF_FFFF;
+        let truncated = || Self::runtime_error("Failed to parse DICOM file", "truncated data element");
+        if depth > MAX_DICOM_NESTING {
+            return Err(Self::resource_limit_error(
+                "Nesting depth exceeds limit",
+                format!("DICOM sequences are nested more than {} deep", MAX_DICOM_NESTING),
+            ));
+        }
+        let nested = depth > 0;
+
+        while *pos + 8 <= end {
+            // Group 0002 (file meta) is little endian regardless of transfer syntax
+            let le_group = u16::from_le_bytes([data[*pos], data[*pos + 1]]);
+            let (explicit_vr, little_endian) = if le_group == 0x0002 {
+                (true, true)
+            } else {
+                (dataset.explicit_vr, dataset.little_endian)
+            };
+            let read_u16 = |i: usize| {
+                let b = [data[i], data[i + 1]];
+                if little_endian { u16::from_le_bytes(b) } else { u16::from_be_bytes(b) }
+            };
+            let read_u32 = |i: usize| -> Option<usize> {
+                let b: [u8; 4] = data.get(i..i + 4)?.try_into().ok()?;
+                Some(if little_endian { u32::from_le_bytes(b) } else { u32::from_be_bytes(b) } as usize)
+            };
+
+            let tag = (read_u16(*pos), read_u16(*pos + 2));
+
+            // Items and delimiters never carry a VR
+            if tag.0 == 0xFFFE {
+                let length = read_u32(*pos + 4).ok_or_else(truncated)?;
+                *pos += 8;
+                match tag.1 {
+                    0xE00D | 0xE0DD => return Ok(()),
+                    0xE000 if length == UNDEFINED_LENGTH => {
+                        Self::read_dicom_elements(data, pos, end, dataset, depth + 1)?;
+                    }
+                    _ => {
+                        let item_end = pos.checked_add(length).filter(|&e| e <= end).ok_or_else(truncated)?;
+                        Self::read_dicom_elements(data, pos, item_end, dataset, depth + 1)?;
+                        *pos = item_end;
+                    }
+                }
+                continue;
+            }
+
+            let (vr, length) = if explicit_vr {
+                let vr = [data[*pos + 4], data[*pos + 5]];
+                if matches!(&vr, b"OB" | b"OD" | b"OF" | b"OL" | b"OV" | b"OW" | b"SQ" | b"SV" | b"UC" | b"UN" | b"UR" | b"UT" | b"UV") {
+                    let length = read_u32(*pos + 8).ok_or_else(truncated)?;
+                    *pos += 12;
+                    (Some(vr), length)
+                } else {
+                    let length = read_u16(*pos + 6) as usize;
+                    *pos += 8;
+                    (Some(vr), length)
+                }
+            } else {
+                let length = read_u32(*pos + 4).ok_or_else(truncated)?;
+                *pos += 8;
+                (None, length)
+            };
+
+            if length == UNDEFINED_LENGTH {
+                if tag == (0x7FE0, 0x0010) {
+                    // Encapsulated (compressed) pixel data: a list of fragment items
+                    while *pos + 8 <= end {
+                        let item = (read_u16(*pos), read_u16(*pos + 2));
+                        let length = read_u32(*pos + 4).ok_or_else(truncated)?;
+                        *pos += 8;
+                        if item != (0xFFFE, 0xE000) {
+                            break;
+                        }
+                        let fragment = data.get(*pos..*pos + length).ok_or_else(truncated)?;
+                        if !nested {
+                            dataset.pixel_fragments.push(fragment.to_vec());
+                        }
+                        *pos += length;
+                    }
+                } else {
+                    // Sequence of undefined length, ended by a sequence delimiter
+                    Self::read_dicom_elements(data, pos, end, dataset, depth + 1)?;
+                }
+                continue;
+            }
+
+            let value_end = pos.checked_add(length).filter(|&e| e <= end).ok_or_else(truncated)?;
+            if vr == Some(*b"SQ") {
+                Self::read_dicom_elements(data, pos, value_end, dataset, depth + 1)?;
+                *pos = value_end;
+                continue;
+            }
+
+            let start = *pos;
+            let value = &data[start..value_end];
+            *pos = value_end;
+            if nested {
+                continue;
+            }
+
+            let number = || if value.len() >= 2 { read_u16(start) } else { 0 };
+            match tag {
+                (0x0002, 0x0010) => {
+                    let syntax = String::from_utf8_lossy(value);
+                    match syntax.trim_end_matches(['\0', ' ']) {
+                        "1.2.840.10008.1.2" => dataset.explicit_vr = false,
+                        "1.2.840.10008.1.2.2" => dataset.little_endian = false,
+                        "1.2.840.10008.1.2.1.99" => {
+                            return Err(Self::runtime_error("Failed to parse DICOM file", "deflated transfer syntax is not supported"));
+                        }
+                        _ => {}
+                    }
+                }
+                (0x0008, 0x0005) => dataset.specific_character_set = String::from_utf8_lossy(value).trim().to_string(),
+                (0x0028, 0x0002) => dataset.samples_per_pixel = number() as usize,
+                (0x0028, 0x0004) => dataset.photometric_interpretation = String::from_utf8_lossy(value).trim().to_string(),
+                (0x0028, 0x0006) => dataset.planar_configuration = number(),
+                (0x0028, 0x0010) => dataset.rows = number() as usize,
+                (0x0028, 0x0011) => dataset.columns = number() as usize,
+                (0x0028, 0x0100) => dataset.bits_allocated = number() as usize,
+                (0x0028, 0x0103) => dataset.pixel_representation = number(),
+                (0x7FE0, 0x0010) => dataset.pixel_data = Some(value.to_vec()),
+                _ => {
+                    if let Some((_, name, vr)) = DICOM_TEXT_TAGS.iter().find(|(t, _, _)| *t == tag) {
+                        let text = Self::dicom_text_value(value, &dataset.specific_character_set, vr);
+                        if !text.is_empty() {
+                            dataset.fields.push((name, text));
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Decode a DICOM string value, formatting names, dates and times for reading
+    fn dicom_text_value(value: &[u8], character_set: &str, vr: &[u8; 2]) -> String {
+        let decoded = if character_set.contains("ISO_IR 100") {
+            encoding_rs::WINDOWS_1252.decode(value).0
+        } else {
+            String::from_utf8_lossy(value)
+        };
+
+        decoded
+            .trim_end_matches(['\0', ' '])
+            .split('\\')
+            .map(|v| {
+                let v = v.trim();
+                let digits = v.len() >= 6 && v.bytes().take(6).all(|b| b.is_ascii_digit());
+                match vr {
+                    // Person names: Family^Given^Middle^Prefix^Suffix
+                    b"PN" => v.split('^').filter(|p| !p.is_empty()).collect::<Vec<_>>().join(" "),
+                    b"DA" if v.len() == 8 && digits => {
+                        format!("{}-{}-{}", &v[0..4], &v[4..6], &v[6..8])
+                    }
+                    b"TM" if digits => {
+                        format!("{}:{}:{}", &v[0..2], &v[2..4], &v[4..6])
+                    }
+                    _ => v.to_string(),
+                }
+            })
+            .filter(|v| !v.is_empty())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Render the first frame of a DICOM image as PNG bytes for OCR
+    fn render_dicom_frame(dataset: &DicomDataSet) -> Result<Vec<u8>, String> {
+        // Compressed frames are typically JPEG; fragment 0 is the offset table
+        if let Some(fragment) = dataset.pixel_fragments.iter().skip(1).find(|f| !f.is_empty()) {
+            return Ok(fragment.clone());
+        }
+
+        let pixels = dataset.pixel_data.as_deref().ok_or("no pixel data")?;
+        let (width, height) = (dataset.columns, dataset.rows);
+        let samples = dataset.samples_per_pixel.max(1);
+        let bytes_per_sample = dataset.bits_allocated.div_ceil(8);
+        if !matches!(bytes_per_sample, 1 | 2) || !matches!(samples, 1 | 3) {
+            return Err(format!(
+                "unsupported pixel layout ({} bits, {} samples)",
+                dataset.bits_allocated, samples
+            ));
+        }
+
+        let count = width * height * samples;
+        let frame = pixels
+            .get(..count * bytes_per_sample)
+            .ok_or("pixel data is shorter than rows x columns")?;
+
+        // Window 16-bit samples linearly onto 0-255
+        let mut values: Vec<u8> = if bytes_per_sample == 1 {
+            frame.to_vec()
+        } else {
+            let raw: Vec<i32> = frame
+                .chunks_exact(2)
+                .map(|b| {
+                    let v = if dataset.little_endian { u16::from_le_bytes([b[0], b[1]]) } else { u16::from_be_bytes([b[0], b[1]]) };
+                    if dataset.pixel_representation == 1 { v as i16 as i32 } else { v as i32 }
+                })
+                .collect();
+            let min = raw.iter().copied().min().unwrap_or(0);
+            let max = raw.iter().copied().max().unwrap_or(0);
+            let range = (max - min).max(1) as f32;
+            raw.iter().map(|&v| ((v - min) as f32 * 255.0 / range).round() as u8).collect()
+        };
+
+        if dataset.photometric_interpretation == "MONOCHROME1" {
+            values.iter_mut().for_each(|v| *v = 255 - *v);
+        }
+
+        let image = if samples == 1 {
+            image::GrayImage::from_raw(width as u32, height as u32, values).map(image::DynamicImage::ImageLuma8)
+        } else {
+            // Planar configuration 1 stores all reds, then greens, then blues
+            if dataset.planar_configuration == 1 {
+                let plane = width * height;
+                values = (0..count).map(|i| values[(i % 3) * plane + i / 3]).collect();
+            }
+            image::RgbImage::from_raw(width as u32, height as u32, values).map(image::DynamicImage::ImageRgb8)
+        }
+        .ok_or("invalid image dimensions")?;
+
+        let mut png = Vec::new();
+        image
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .map_err(|e| e.to_string())?;
+        Ok(png)
+    }
+
+    /// Parse SubRip, WebVTT and ASS/SSA subtitles - exposed to Ruby
+    ///
+    /// Timing lines, cue settings, markup tags and ASS override blocks are
+    /// dropped, leaving one line of dialogue per cue. With `timestamps: true`
+    /// each line is prefixed with its start time as `[hh:mm:ss]`.
+    fn parse_subtitles(&self, data: Vec<u8>) -> Result<String, Error> {
+        let text = self.parse_text(data)?;
+        let text = text.trim_start_matches('\u{feff}').replace("\r\n", "\n").replace('\r', "\n");
+
+        let trimmed = text.trim_start();
+        let cues = if trimmed.starts_with("[Script Info]") || text.contains("\nDialogue:") {
+            Self::ass_cues(&text)
+        } else {
+            Self::timed_cues(&text, trimmed.starts_with("WEBVTT"))
+        };
+
+        let mut lines: Vec<String> = Vec::new();
+        let mut previous = String::new();
+        for (start, cue) in cues {
+            let cue = SUBTITLE_MARKUP
+                .replace_all(&cue, "")
+                .replace("&nbsp;", " ")
+                .replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&amp;", "&");
+            let cue = cue.split_whitespace().collect::<Vec<_>>().join(" ");
+
+            // Rolling captions repeat the same line across consecutive cues
+            if cue.is_empty() || cue == previous {
+                continue;
+            }
+            match (self.config.timestamps, Self::subtitle_timestamp(&start)) {
+                (true, Some(stamp)) => lines.push(format!("[{}] {}", stamp, cue)),
+                _ => lines.push(cue.clone()),
+            }
+            previous = cue;
+        }
+
+        Ok(lines.join("\n"))
+    }
+
+    /// Split SubRip or WebVTT blocks into (start time, text) cues; for WebVTT
+    /// the header and NOTE, STYLE and REGION blocks are skipped
+    fn timed_cues(text: &str, webvtt: bool) -> Vec<(String, String)> {
+        let mut cues = Vec::new();
+        for block in text.split("\n\n").skip(usize::from(webvtt)) {
+            let first = block.trim_start().lines().next().unwrap_or("");
+            if webvtt && ["NOTE", "STYLE", "REGION"].iter().any(|kw| first.starts_with(kw)) {
+                continue;
+            }
+            let mut lines = block.lines().skip_while(|l| !l.contains("-->"));
+            let Some(timing) = lines.next() else { continue };
+            let start = timing.split("-->").next().unwrap_or("").trim().to_string();
+            cues.push((start, lines.collect::<Vec<_>>().join(" ")));
+        }
+        cues
+    }
+
+    /// Read `Dialogue:` events from an ASS/SSA script, using the `[Events]`
+    /// Format line to locate the Start and Text fields
+    fn ass_cues(text: &str) -> Vec<(String, String)> {
+        let mut fields: Vec<String> = ["Layer", "Start", "End", "Style", "Name", "MarginL", "MarginR", "MarginV", "Effect", "Text"]
+            .iter()
+            .map(|f| f.to_string())
+            .collect();
+        let mut in_events = false;
+        let mut cues = Vec::new();
+
+        for line in text.lines() {
+            let line = line.trim();
+            if line.starts_with('[') {
+                in_events = line.eq_ignore_ascii_case("[Events]");
+                continue;
+            }
+            if !in_events {
+                continue;
+            }
+            if let Some(format) = line.strip_prefix("Format:") {
+                fields = format.split(',').map(|f| f.trim().to_string()).collect();
+            } else if let Some(event) = line.strip_prefix("Dialogue:") {
+                // Text is always the last field and may itself contain commas
+                let values: Vec<&str> = event.trim_start().splitn(fields.len(), ',').collect();
+                let field = |name: &str| {
+                    fields.iter().position(|f| f.eq_ignore_ascii_case(name)).and_then(|i| values.get(i)).copied()
+                };
+                let start = field("Start").unwrap_or("").trim().to_string();
+                let dialogue = field("Text").unwrap_or("").replace("\\N", " ").replace("\\n", " ").replace("\\h", " ");
+                cues.push((start, dialogue));
+            }
+        }
+        cues
+    }
+
+    /// Normalize a cue start time (`00:01:02,500`, `01:02.500`, `0:01:02.50`) to `hh:mm:ss`
+    fn subtitle_timestamp(start: &str) -> Option<String> {
+        let whole = start.split(['.', ',']).next()?;
+        let parts = whole
+            .split(':')
+            .map(|p| p.trim().parse::<u64>())
+            .collect::<Result<Vec<_>, _>>()
+            .ok()?;
+        let (h, m, s) = match parts.as_slice() {
+            [h, m, s] => (*h, *m, *s),
+            [m, s] => (0, *m, *s),
+            _ => return None,
+        };
+        Some(format!("{:02}:{:02}:{:02}", h, m, s))
+    }
+
+    /// Transcribe speech in WAV, MP3, M4A and OGG Vorbis audio - exposed to Ruby
+    ///
+    /// Requires the `audio` feature and a ggml Whisper model, given by the
+    /// `whisper_model` option or the `PARSEKIT_WHISPER_MODEL` environment
+    /// variable. With `timestamps: true` each segment is prefixed with
+    /// `[hh:mm:ss]`.
+    fn parse_audio(&self, data: Vec<u8>) -> Result<String, Error> {
+        #[cfg(feature = "audio")]
+        {
+            let samples = Self::decode_audio(data)
+                .map_err(|e| Self::runtime_error("Failed to decode audio", e))?;
+            self.transcribe(&samples)
+        }
+
+        #[cfg(not(feature = "audio"))]
+        {
+            let _ = data;
+            Err(Self::runtime_error(
+                "Failed to transcribe audio",
+                "audio transcription requires building with the `audio` feature",
+            ))
+        }
+    }
+
+    /// Decode the first audio track of a container to 16 kHz mono samples,
+    /// the input format Whisper expects
+    #[cfg(feature = "audio")]
+    fn decode_audio(data: Vec<u8>) -> Result<Vec<f32>, String> {
+        use symphonia::core::audio::SampleBuffer;
+        use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+        use symphonia::core::errors::Error as SymphoniaError;
+        use symphonia::core::formats::FormatOptions;
+        use symphonia::core::io::MediaSourceStream;
+        use symphonia::core::meta::MetadataOptions;
+        use symphonia::core::probe::Hint;
+
+        const WHISPER_SAMPLE_RATE: u32 = 16_000;
+
+        let source = MediaSourceStream::new(Box::new(std::io::Cursor::new(data)), Default::default());
+        let probed = symphonia::default::get_probe()
+            .format(&Hint::new(), source, &FormatOptions::default(), &MetadataOptions::default())
+            .map_err(|e| e.to_string())?;
+        let mut format = probed.format;
+
+        let track = format
+            .tracks()
+            .iter()
+            .find(|t| t.codec_params.codec != CODEC_TYPE_NULL && t.codec_params.sample_rate.is_some())
+            .ok_or("no audio track found")?;
+        let track_id = track.id;
+        let sample_rate = track.codec_params.sample_rate.unwrap_or(WHISPER_SAMPLE_RATE);
+        let mut decoder = symphonia::default::get_codecs()
+            .make(&track.codec_params, &DecoderOptions::default())
+            .map_err(|e| e.to_string())?;
+
+        // Downmix every packet to mono
+        let mut mono = Vec::new();
+        loop {
+            let packet = match format.next_packet() {
+                Ok(packet) => packet,
+                Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.to_string()),
+            };
+            if packet.track_id() != track_id {
+                continue;
+            }
+            let decoded = match decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                // Skip corrupt packets rather than failing the whole file
+                Err(SymphoniaError::DecodeError(_)) => continue,
+                Err(e) => return Err(e.to_string()),
+            };
+            let spec = *decoded.spec();
+            let channels = spec.channels.count().max(1);
+            let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+            buffer.copy_interleaved_ref(decoded);
+            mono.extend(
+                buffer
+                    .samples()
+                    .chunks(channels)
+                    .map(|frame| frame.iter().sum::<f32>() / channels as f32),
+            );
+        }
+
+        if mono.is_empty() {
+            return Err("audio track contains no samples".to_string());
+        }
+        if sample_rate == WHISPER_SAMPLE_RATE {
+            return Ok(mono);
+        }
+
+        // Linear resampling is plenty for speech recognition
+        let ratio = sample_rate as f64 / WHISPER_SAMPLE_RATE as f64;
+        let length = (mono.len() as f64 / ratio) as usize;
+        Ok((0..length)
+            .map(|i| {
+                let position = i as f64 * ratio;
+                let index = position as usize;
+                let fraction = (position - index as f64) as f32;
+                let next = mono.get(index + 1).copied().unwrap_or(mono[index]);
+                mono[index] + (next - mono[index]) * fraction
+            })
+            .collect())
+    }
+
+    /// Run Whisper over 16 kHz mono samples and join its segments into a transcript
+    #[cfg(feature = "audio")]
+    fn transcribe(&self, samples: &[f32]) -> Result<String, Error> {
+        use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+
+        let model = self
+            .config
+            .whisper_model
+            .clone()
+            .or_else(|| std::env::var("PARSEKIT_WHISPER_MODEL").ok())
+            .ok_or_else(|| {
+                Self::argument_error(
+                    "No Whisper model configured: pass whisper_model: or set PARSEKIT_WHISPER_MODEL",
+                )
+            })?;
+
+        let context = WhisperContext::new_with_params(&model, WhisperContextParameters::default())
+            .map_err(|e| Self::runtime_error("Failed to load Whisper model", e))?;
+        let mut state = context
+            .create_state()
+            .map_err(|e| Self::runtime_error("Failed to initialize Whisper", e))?;
+
+        let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+        params.set_language(Some("auto"));
+        params.set_print_progress(false);
+        params.set_print_realtime(false);
+        params.set_print_special(false);
+        params.set_print_timestamps(false);
+
+        state
+            .full(params, samples)
+            .map_err(|e| Self::runtime_error("Failed to transcribe audio", e))?;
+
+        let segment_count = state
+            .full_n_segments()
+            .map_err(|e| Self::runtime_error("Failed to read transcript", e))?;
+        let mut lines = Vec::new();
+        for i in 0..segment_count {
+            let text = state
+                .full_get_segment_text(i)
+                .map_err(|e| Self::runtime_error("Failed to read transcript", e))?;
+            let text = text.trim();
+            if text.is_empty() {
+                continue;
+            }
+            if self.config.timestamps {
+                // Segment times are in centiseconds
+                let start = state.full_get_segment_t0(i).unwrap_or(0).max(0) as u64 / 100;
+                lines.push(format!("[{}] {}", Self::format_hms(start), text));
+            } else {
+                lines.push(text.to_string());
+            }
+        }
+
+        Ok(lines.join("\n"))
+    }
+
+    /// Parse MP4/MOV and Matroska/WebM video - exposed to Ruby
+    ///
+    /// Reports the container title, duration and chapters. With
+    /// `transcribe: true` (and the `audio` feature) the audio track is also
+    /// transcribed and appended under `Transcript:`.
+    fn parse_video(&self, data: Vec<u8>) -> Result<String, Error> {
+        let metadata = if data.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) {
+            Self::matroska_metadata(&data)
+        } else if data.get(4..8) == Some(b"ftyp") {
+            Self::mp4_metadata(&data)
+        } else {
+            return Err(Self::runtime_error("Failed to parse video", "not an MP4 or Matroska container"));
+        };
+
+        let mut lines = Vec::new();
+        if let Some(title) = metadata.title.filter(|t| !t.trim().is_empty()) {
+            lines.push(format!("Title: {}", title.trim()));
+        }
+        if let Some(duration) = metadata.duration {
+            lines.push(format!("Duration: {}", Self::format_hms(duration as u64)));
+        }
+        if !metadata.chapters.is_empty() {
+            lines.push("Chapters:".to_string());
+            for (start, name) in &metadata.chapters {
+                lines.push(format!("[{}] {}", Self::format_hms(*start as u64), name));
+            }
+        }
+
+        if self.config.transcribe {
+            let transcript = self.parse_audio(data)?;
+            if !lines.is_empty() {
+                lines.push(String::new());
+            }
+            lines.push("Transcript:".to_string());
+            lines.push(transcript);
+        }
+
+        Ok(lines.join("\n"))
+    }
+
+    /// Read title, duration and Nero (`chpl`) chapters from an ISO-BMFF file
+    fn mp4_metadata(data: &[u8]) -> VideoMetadata {
+        let mut metadata = VideoMetadata::default();
+        let Some(moov) = Self::mp4_boxes(data).find(|(kind, _)| kind == b"moov").map(|(_, body)| body) else {
+            return metadata;
+        };
+
+        for (kind, body) in Self::mp4_boxes(moov) {
+            match &kind {
+                b"mvhd" => {
+                    // Version 1 uses 64-bit creation/modification times and duration
+                    let (timescale, duration) = if body.first() == Some(&1) {
+                        (Self::be_uint(body.get(20..24)), Self::be_uint(body.get(24..32)))
+                    } else {
+                        (Self::be_uint(body.get(12..16)), Self::be_uint(body.get(16..20)))
+                    };
+                    if let (Some(timescale), Some(duration)) = (timescale, duration) {
+                        if timescale > 0 {
+                            metadata.duration = Some(duration as f64 / timescale as f64);
+                        }
+                    }
+                }
+                b"udta" => {
+                    for (kind, body) in Self::mp4_boxes(body) {
+                        match &kind {
+                            b"chpl" => metadata.chapters = Self::mp4_nero_chapters(body),
+                            // QuickTime-style title directly under udta
+                            b"\xa9nam" => {
+                                let text = body.get(4..).unwrap_or_default();
+                                metadata.title = Some(String::from_utf8_lossy(text).to_string());
+                            }
+                            b"meta" => {
+                                // ISO `meta` is a full box; QuickTime's omits the version/flags
+                                let children = if body.get(4..8).is_some_and(|b| b != b"hdlr") {
+                                    body.get(4..).unwrap_or_default()
+                                } else {
+                                    body
+                                };
+                                let ilst = Self::mp4_boxes(children).find(|(kind, _)| kind == b"ilst");
+                                let name = ilst.and_then(|(_, ilst)| {
+                                    Self::mp4_boxes(ilst).find(|(kind, _)| kind == b"\xa9nam")
+                                });
+                                // ©nam > data box: 4 bytes type, 4 bytes locale, then UTF-8
+                                if let Some((_, item)) = name {
+                                    if let Some((_, value)) = Self::mp4_boxes(item).find(|(kind, _)| kind == b"data") {
+                                        let text = value.get(8..).unwrap_or_default();
+                                        metadata.title = Some(String::from_utf8_lossy(text).to_string());
+                                    }
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        metadata
+    }
+
+    /// Decode a Nero `chpl` box: 100ns start times with length-prefixed titles
+    fn mp4_nero_chapters(body: &[u8]) -> Vec<(f64, String)> {
+        let mut chapters = Vec::new();
+        // version + flags, then a reserved word in version 1
+        let mut pos = if body.first() == Some(&1) { 8 } else { 4 };
+        let count = body.get(pos).copied().unwrap_or(0);
+        pos += 1;
+
+        for _ in 0..count {
+            let Some(start) = Self::be_uint(body.get(pos..pos + 8)) else { break };
+            let Some(&len) = body.get(pos + 8) else { break };
+            let Some(title) = body.get(pos + 9..pos + 9 + len as usize) else { break };
+            chapters.push((start as f64 / 10_000_000.0, String::from_utf8_lossy(title).to_string()));
+            pos += 9 + len as usize;
+        }
+
+        chapters
+    }
+
+    /// Iterate over the ISO-BMFF boxes in `data` as (type, body) pairs
+    fn mp4_boxes(data: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+        let mut pos = 0;
+        std::iter::from_fn(move || {
+            let size = Self::be_uint(data.get(pos..pos + 4))? as usize;
+            let kind: [u8; 4] = data.get(pos + 4..pos + 8)?.try_into().ok()?;
+            let (header, size) = match size {
+                0 => (8, data.len() - pos),
+                1 => (16, Self::be_uint(data.get(pos + 8..pos + 16))? as usize),
+                _ => (8, size),
+            };
+            let body = data.get(pos + header..pos.checked_add(size)?)?;
+            pos += size.max(header);
+            Some((kind, body))
+        })
+    }
+
+    /// Read title, duration and chapters from a Matroska/WebM file
+    fn matroska_metadata(data: &[u8]) -> VideoMetadata {
+        const SEGMENT: u64 = 0x18538067;
+        const INFO: u64 = 0x1549A966;
+        const CHAPTERS: u64 = 0x1043A770;
+        const CLUSTER: u64 = 0x1F43B675;
+
+        let mut metadata = VideoMetadata::default();
+        let mut timestamp_scale = 1_000_000.0; // nanoseconds per tick
+        let mut raw_duration = None;
+
+        let Some((_, segment)) = Self::ebml_elements(data).find(|(id, _)| *id == SEGMENT) else {
+            return metadata;
+        };
+        for (id, body) in Self::ebml_elements(segment) {
+            match id {
+                INFO => {
+                    for (id, value) in Self::ebml_elements(body) {
+                        match id {
+                            0x2AD7B1 => timestamp_scale = Self::be_uint(Some(value)).unwrap_or(1_000_000) as f64,
+                            0x4489 => {
+                                raw_duration = match value.len() {
+                                    4 => Some(f32::from_be_bytes(value.try_into().unwrap()) as f64),
+                                    8 => Some(f64::from_be_bytes(value.try_into().unwrap())),
+                                    _ => None,
+                                }
+                            }
+                            0x7BA9 => metadata.title = Some(String::from_utf8_lossy(value).trim_end_matches('\0').to_string()),
+                            _ => {}
+                        }
+                    }
+                }
+                CHAPTERS => {
+                    for (id, edition) in Self::ebml_elements(body) {
+                        if id == 0x45B9 {
+                            Self::matroska_chapter_atoms(edition, &mut metadata.chapters, 0);
+                        }
+                    }
+                }
+                // Clusters hold the media itself; metadata normally precedes them
+                CLUSTER => break,
+                _ => {}
+            }
+        }
+
+        metadata.duration = raw_duration.map(|ticks| ticks * timestamp_scale / 1e9);
+        metadata
+    }
+
+    /// Collect ChapterAtoms (including nested ones, down to
+    /// `MAX_CHAPTER_NESTING`) as (start seconds, title)
+    fn matroska_chapter_atoms(parent: &[u8], chapters: &mut Vec<(f64, String)>, depth: usize) {
+        if depth > MAX_CHAPTER_NESTING {
+            return;
+        }
+        for (id, atom) in Self::ebml_elements(parent) {
+            if id != 0xB6 {
+                continue;
+            }
+            let mut start = 0.0;
+            let mut title = String::new();
+            for (id, value) in Self::ebml_elements(atom) {
+                match id {
+                    0x91 => start = Self::be_uint(Some(value)).unwrap_or(0) as f64 / 1e9,
+                    0x80 if title.is_empty() => {
+                        if let Some((_, name)) = Self::ebml_elements(value).find(|(id, _)| *id == 0x85) {
+                            title = String::from_utf8_lossy(name).to_string();
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            chapters.push((start, title));
+            Self::matroska_chapter_atoms(atom, chapters, depth + 1);
+        }
+    }
+
+    /// Iterate over EBML elements in `data` as (id, body) pairs; elements of
+    /// unknown size extend to the end of `data`
+    fn ebml_elements(data: &[u8]) -> impl Iterator<Item = (u64, &[u8])> {
+        // Variable-length integer: the leading zero bits give the width
+        fn vint(data: &[u8], pos: usize, keep_marker: bool) -> Option<(u64, usize, bool)> {
+            let first = *data.get(pos)?;
+            let width = first.leading_zeros() as usize + 1;
+            if width > 8 {
+                return None;
+            }
+            let bytes = data.get(pos..pos + width)?;
+            let mut value = if keep_marker { first as u64 } else { (first as u64) & (0xFF >> width) };
+            for &b in &bytes[1..] {
+                value = (value << 8) | b as u64;
+            }
+            let unknown = !keep_marker && value == (1u64 << (7 * width)) - 1;
+            Some((value, width, unknown))
+        }
+
+        let mut pos = 0;
+        std::iter::from_fn(move || {
+            let (id, id_len, _) = vint(data, pos, true)?;
+            let (size, size_len, unknown) = vint(data, pos + id_len, false)?;
+            let start = pos + id_len + size_len;
+            let end = if unknown { data.len() } else { start.checked_add(size as usize)?.min(data.len()) };
+            let body = data.get(start..end)?;
+            pos = end;
+            Some((id, body))
+        })
+    }
+
+    /// Read a big-endian unsigned integer of up to 8 bytes
+    fn be_uint(bytes: Option<&[u8]>) -> Option<u64> {
+        let bytes = bytes.filter(|b| b.len() <= 8)?;
+        Some(bytes.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64))
+    }
+
+    /// Format a number of seconds as `hh:mm:ss`
+    fn format_hms(seconds: u64) -> String {
+        format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+    }
+
+    /// Parse TAR archives, running each member through its own parser - exposed to Ruby
+    fn parse_tar(&self, data: Vec<u8>) -> Result<String, Error> {
+        let mut archive = tar::Archive::new(std::io::Cursor::new(data));
+        let entries = archive
+            .entries()
+            .map_err(|e| Self::runtime_error("Failed to read TAR archive", e))?;
+
+        let mut members = Vec::new();
+        let mut budget = self.config.max_size as u64;
+        for entry in entries {
+            let mut entry = entry.map_err(|e| Self::runtime_error("Failed to read TAR entry", e))?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let name = entry
+                .path()
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let contents = self.read_decompressed(&mut entry, None, &mut budget, "TAR")?;
+            members.push((name, contents));
+        }
+
+        self.parse_archive_members(members)
+    }
+
+    /// Parse GZIP-compressed data (including .tar.gz) - exposed to Ruby
+    fn parse_gzip(&self, data: Vec<u8>) -> Result<String, Error> {
+        let mut decoder = flate2::read::MultiGzDecoder::new(&data[..]);
+        let mut budget = self.config.max_size as u64;
+        let contents = self.read_decompressed(&mut decoder, Some(data.len() as u64), &mut budget, "GZIP")?;
+
+        // The header may carry the original file name, which helps detection
+        let name = decoder
+            .header()
+            .and_then(|h| h.filename())
+            .map(|n| String::from_utf8_lossy(n).to_string());
+        let format = FormatDetector::detect(name.as_deref(), Some(&contents));
+        self.parse_decompressed(format, contents, budget, "GZIP")
+    }
+
+    /// Parse BZIP2-compressed data (including .tar.bz2) - exposed to Ruby
+    fn parse_bzip2(&self, data: Vec<u8>) -> Result<String, Error> {
+        let decoder = bzip2::read::MultiBzDecoder::new(&data[..]);
+        let mut budget = self.config.max_size as u64;
+        let contents = self.read_decompressed(decoder, Some(data.len() as u64), &mut budget, "BZIP2")?;
+        let format = FormatDetector::detect_from_content(&contents);
+        self.parse_decompressed(format, contents, budget, "BZIP2")
+    }
 
-        // Iterate through pages
-        for page_num in 0..page_count {
-            // Continue on page errors rather than failing entirely
-            if let Ok(page) = doc.load_page(page_num) {
-                // Extract text from the page
-                if let Ok(text) = page.to_text() {
-                    all_text.push_str(&text);
-                    all_text.push('\n');
-                }
-            }
-        }
+    /// Parse XZ-compressed data (including .tar.xz) - exposed to Ruby
+    fn parse_xz(&self, data: Vec<u8>) -> Result<String, Error> {
+        let decoder = xz2::read::XzDecoder::new_multi_decoder(&data[..]);
+        let mut budget = self.config.max_size as u64;
+        let contents = self.read_decompressed(decoder, Some(data.len() as u64), &mut budget, "XZ")?;
+        let format = FormatDetector::detect_from_content(&contents);
+        self.parse_decompressed(format, contents, budget, "XZ")
+    }
 
-        if all_text.is_empty() {
-            Ok("PDF contains no extractable text (might be scanned/image-based)".to_string())
-        } else {
-            Ok(all_text.trim().to_string())
+    /// Parse the output of a compression layer one container deeper, failing
+    /// past `max_depth`. What's left of the layer's `budget` becomes the
+    /// inner parser's `max_size`, so nested layers (a .tar.gz, a gzip inside
+    /// a gzip) share one decompression budget instead of each getting its own.
+    fn parse_decompressed(&self, format: FileFormat, contents: Vec<u8>, budget: u64, container: &str) -> Result<String, Error> {
+        if self.depth >= self.config.max_depth {
+            return Err(Self::resource_limit_error(
+                "Nesting depth exceeds limit",
+                format!("{} data is nested more than {} containers deep", container, self.config.max_depth),
+            ));
         }
+        let mut config = self.config.clone();
+        config.max_size = budget as usize;
+        let nested = Parser {
+            config,
+            depth: self.depth + 1,
+        };
+        nested.dispatch_to_parser(format, contents)
     }
 
-    /// Parse DOCX (Word) files - exposed to Ruby
-    fn parse_docx(&self, data: Vec<u8>) -> Result<String, Error> {
-        use docx_rs::read_docx;
+    /// Read a decompression stream or archive entry under the resource
+    /// limits: at most `max_entry_size` bytes, no more than is left of the
+    /// container's `budget` (which starts at `max_size`), and, when the
+    /// compressed size is known, within `max_compression_ratio`
+    fn read_decompressed(
+        &self,
+        reader: impl std::io::Read,
+        compressed_size: Option<u64>,
+        budget: &mut u64,
+        format: &str,
+    ) -> Result<Vec<u8>, Error> {
+        let mut contents = Vec::new();
+        self.read_decompressed_into(reader, compressed_size, budget, format, &mut contents)?;
+        Ok(contents)
+    }
 
-        match read_docx(&data) {
-            Ok(docx) => {
-                let mut result = String::new();
+    /// `read_decompressed` into a buffer the caller reuses, cleared first
+    fn read_decompressed_into(
+        &self,
+        reader: impl std::io::Read,
+        compressed_size: Option<u64>,
+        budget: &mut u64,
+        format: &str,
+        contents: &mut Vec<u8>,
+    ) -> Result<(), Error> {
+        use std::io::Read;
 
-                // Extract text from all document children
-                // For simplicity, we'll focus on paragraphs only for now
-                // Tables require more complex handling with the current API
-                for child in docx.document.children.iter() {
-                    if let docx_rs::DocumentChild::Paragraph(p) = child {
-                        // Extract text from paragraph
-                        for p_child in &p.children {
-                            if let docx_rs::ParagraphChild::Run(r) = p_child {
-                                for run_child in &r.children {
-                                    if let docx_rs::RunChild::Text(t) = run_child {
-                                        result.push_str(&t.text);
-                                    }
-                                }
-                            }
-                        }
-                        result.push('\n');
-                    }
-                    // Note: Table text extraction would require iterating through
-                    // table.rows -> TableChild::TableRow -> row.cells -> TableRowChild
-                    // which has a more complex structure in docx-rs
-                }
+        let entry_limit = self.config.max_entry_size as u64;
+        contents.clear();
+        reader
+            .take(entry_limit.min(*budget) + 1)
+            .read_to_end(contents)
+            .map_err(|e| Self::runtime_error(&format!("Failed to decompress {} data", format), e))?;
 
-                Ok(result.trim().to_string())
-            }
-            Err(e) => Err(Self::runtime_error("Failed to parse DOCX file", e)),
+        let size = contents.len() as u64;
+        if size > *budget {
+            return Err(self.decompressed_size_error(format));
         }
+        if size > entry_limit {
+            return Err(self.entry_size_error(format));
+        }
+        if let Some(compressed_size) = compressed_size {
+            self.check_compression_ratio(size, compressed_size, format)?;
+        }
+        *budget -= size;
+        Ok(())
     }
 
-    /// Parse PPTX (PowerPoint) files - exposed to Ruby
-    fn parse_pptx(&self, data: Vec<u8>) -> Result<String, Error> {
-        use std::io::{Cursor, Read};
-        use zip::ZipArchive;
-        
-        let cursor = Cursor::new(data);
-        let mut archive = ZipArchive::new(cursor)
-            .map_err(|e| Self::runtime_error("Failed to open PPTX as ZIP", e))?;
-        
-        let mut all_text = Vec::new();
-        let mut slide_numbers = Vec::new();
-        
-        // First, collect slide numbers and sort them
+    /// Check a ZIP container's central directory against the resource limits
+    /// before handing it to a parser that decompresses it internally. Data
+    /// that isn't a readable ZIP is left for the parser to report.
+    fn check_zip_limits(&self, data: &[u8], format: &str) -> Result<(), Error> {
+        let Ok(mut archive) = zip::ZipArchive::new(std::io::Cursor::new(data)) else {
+            return Ok(());
+        };
+
+        let mut total = 0u64;
         for i in 0..archive.len() {
-            let file = match archive.by_index(i) {
-                Ok(file) => file,
-                Err(_) => continue,
-            };
-            
-            let name = file.name();
-            // Match slide XML files (e.g., ppt/slides/slide1.xml)
-            if name.starts_with("ppt/slides/slide") && name.ends_with(".xml") && !name.contains("_rels") {
-                // Extract slide number from filename
-                if let Some(num_str) = name
-                    .strip_prefix("ppt/slides/slide")
-                    .and_then(|s| s.strip_suffix(".xml"))
-                {
-                    if let Ok(num) = num_str.parse::<usize>() {
-                        slide_numbers.push((num, i));
-                    }
-                }
-            }
-        }
-        
-        // Sort by slide number to maintain order
-        slide_numbers.sort_by_key(|&(num, _)| num);
-        
-        // Now process slides in order
-        for (_, index) in slide_numbers {
-            let mut file = match archive.by_index(index) {
-                Ok(file) => file,
-                Err(_) => continue,
+            let Ok(file) = archive.by_index_raw(i) else {
+                continue;
             };
-            
-            let mut contents = String::new();
-            if file.read_to_string(&mut contents).is_ok() {
-                // Extract text from slide XML
-                let text = self.extract_text_from_slide_xml(&contents);
-                if !text.is_empty() {
-                    all_text.push(text);
-                }
+            if file.size() > self.config.max_entry_size as u64 {
+                return Err(self.entry_size_error(format));
             }
-        }
-        
-        // Also extract notes if present
-        for i in 0..archive.len() {
-            let mut file = match archive.by_index(i) {
-                Ok(file) => file,
-                Err(_) => continue,
-            };
-            
-            let name = file.name();
-            // Match notes slide XML files
-            if name.starts_with("ppt/notesSlides/notesSlide") && name.ends_with(".xml") && !name.contains("_rels") {
-                let mut contents = String::new();
-                if file.read_to_string(&mut contents).is_ok() {
-                    let text = self.extract_text_from_slide_xml(&contents);
-                    if !text.is_empty() {
-                        all_text.push(format!("[Notes: {}]", text));
-                    }
-                }
+            total = total.saturating_add(file.size());
+            if total > self.config.max_size as u64 {
+                return Err(self.decompressed_size_error(format));
             }
+            self.check_compression_ratio(file.size(), file.compressed_size(), format)?;
         }
-        
-        if all_text.is_empty() {
-            Ok("".to_string())
-        } else {
-            Ok(all_text.join("\n\n"))
+        Ok(())
+    }
+
+    fn check_compression_ratio(&self, size: u64, compressed_size: u64, format: &str) -> Result<(), Error> {
+        let ratio = self.config.max_compression_ratio as u64;
+        if size > COMPRESSION_RATIO_GRACE && size > compressed_size.max(1).saturating_mul(ratio) {
+            return Err(Self::resource_limit_error(
+                "Compression ratio exceeds limit",
+                format!("{} data expands more than {}x", format, ratio),
+            ));
         }
+        Ok(())
     }
-    
-    /// Helper method to extract text from slide XML
-    fn extract_text_from_slide_xml(&self, xml_content: &str) -> String {
-        use quick_xml::events::Event;
-        use quick_xml::Reader;
-        
-        let mut reader = Reader::from_str(xml_content);
-        
-        let mut text_parts = Vec::new();
-        let mut buf = Vec::new();
-        let mut in_text_element = false;
-        
-        loop {
-            match reader.read_event_into(&mut buf) {
-                Ok(Event::Start(ref e)) => {
-                    // Look for text elements (a:t or t)
-                    let name = e.name();
-                    let local_name_bytes = name.local_name();
-                    let local_name = std::str::from_utf8(local_name_bytes.as_ref()).unwrap_or("");
-                    if local_name == "t" {
-                        in_text_element = true;
-                    }
+
+    fn file_size_error(&self, size: u64) -> Error {
+        Self::resource_limit_error(
+            "File size exceeds limit",
+            format!("{} bytes exceeds maximum allowed size of {} bytes",
+                size, self.config.max_size)
+        )
+    }
+
+    fn decompressed_size_error(&self, format: &str) -> Error {
+        Self::resource_limit_error(
+            "Decompressed size exceeds limit",
+            format!("{} data expands beyond the maximum allowed size of {} bytes",
+                format, self.config.max_size)
+        )
+    }
+
+    fn entry_size_error(&self, format: &str) -> Error {
+        Self::resource_limit_error(
+            "Decompressed entry exceeds limit",
+            format!("{} entry expands beyond the maximum allowed size of {} bytes",
+                format, self.config.max_entry_size)
+        )
+    }
+
+    /// Parse 7z archives, running each member through its own parser - exposed to Ruby
+    fn parse_sevenzip(&self, data: Vec<u8>) -> Result<String, Error> {
+        use sevenz_rust::{Password, SevenZReader};
+
+        let len = data.len() as u64;
+        let mut archive = SevenZReader::new(std::io::Cursor::new(data), len, Password::empty())
+            .map_err(|e| Self::runtime_error("Failed to open 7z archive", e))?;
+
+        // Members share one decompression budget of `max_size` bytes. Solid
+        // blocks have no per-entry compressed size, so the ratio is checked
+        // for the archive as a whole.
+        let mut members = Vec::new();
+        let mut budget = self.config.max_size as u64;
+        let mut read_error = None;
+        archive
+            .for_each_entries(|entry, reader| {
+                if entry.is_directory() {
+                    return Ok(true);
                 }
-                Ok(Event::Text(e)) => {
-                    if in_text_element {
-                        if let Ok(text) = e.decode() {
-                            let text_str = text.trim();
-                            if !text_str.is_empty() {
-                                text_parts.push(text_str.to_string());
-                            }
-                        }
+                match self.read_decompressed(reader, None, &mut budget, "7z") {
+                    Ok(contents) => {
+                        members.push((entry.name().to_string(), contents));
+                        Ok(true)
                     }
-                }
-                Ok(Event::End(ref e)) => {
-                    let name = e.name();
-                    let local_name_bytes = name.local_name();
-                    let local_name = std::str::from_utf8(local_name_bytes.as_ref()).unwrap_or("");
-                    if local_name == "t" {
-                        in_text_element = false;
+                    Err(e) => {
+                        read_error = Some(e);
+                        Ok(false)
                     }
                 }
-                Ok(Event::Eof) => break,
-                _ => {}
+            })
+            .map_err(|e| Self::runtime_error("Failed to extract 7z archive", e))?;
+
+        if let Some(e) = read_error {
+            return Err(e);
+        }
+        self.check_compression_ratio(self.config.max_size as u64 - budget, len, "7z")?;
+        self.parse_archive_members(members)
+    }
+
+    /// Parse RAR (v4 and v5) archives, running each member through its own
+    /// parser - exposed to Ruby
+    fn parse_rar(&self, data: Vec<u8>) -> Result<String, Error> {
+        Self::with_temp_file(&data, "rar", |path| {
+            let mut archive = unrar::Archive::new(path)
+                .open_for_processing()
+                .map_err(|e| Self::runtime_error("Failed to open RAR archive", e))?;
+
+            let mut members = Vec::new();
+            let mut remaining = self.config.max_size as u64;
+            let mut total = 0u64;
+            while let Some(header) = archive
+                .read_header()
+                .map_err(|e| Self::runtime_error("Failed to read RAR entry", e))?
+            {
+                let entry = header.entry();
+                archive = if entry.is_file() {
+                    // Check the declared size before unpacking anything
+                    if entry.unpacked_size > remaining {
+                        return Err(self.decompressed_size_error("RAR"));
+                    }
+                    if entry.unpacked_size > self.config.max_entry_size as u64 {
+                        return Err(self.entry_size_error("RAR"));
+                    }
+                    let name = entry.filename.to_string_lossy().to_string();
+                    let (contents, rest) = header
+                        .read()
+                        .map_err(|e| Self::runtime_error("Failed to extract RAR entry", e))?;
+                    remaining = remaining.saturating_sub(contents.len() as u64);
+                    total += contents.len() as u64;
+                    members.push((name, contents));
+                    rest
+                } else {
+                    header
+                        .skip()
+                        .map_err(|e| Self::runtime_error("Failed to read RAR entry", e))?
+                };
             }
-            buf.clear();
+
+            self.check_compression_ratio(total, data.len() as u64, "RAR")?;
+            self.parse_archive_members(members)
+        })
+    }
+
+    /// Dispatch archive members to their parsers, one `=== name ===` section
+    /// each; members that fail to parse are noted rather than failing the archive
+    fn parse_archive_members(&self, members: Vec<(String, Vec<u8>)>) -> Result<String, Error> {
+        let mut sections = Vec::new();
+
+        for (name, contents) in members {
+            let text = self.parse_embedded_file(&name, contents)?;
+            sections.push(format!("=== {} ===\n{}", name, text));
         }
-        
-        text_parts.join(" ")
+
+        Ok(sections.join("\n\n"))
     }
 
-    /// Parse Excel files - exposed to Ruby
-    fn parse_xlsx(&self, data: Vec<u8>) -> Result<String, Error> {
-        use calamine::{Reader, Xlsx};
-        use std::io::Cursor;
+    /// Parse a file found inside a container (archive member, attachment),
+    /// detecting its format from the name and contents. Errors become a note
+    /// in the text so one bad file doesn't fail the whole container; resource
+    /// limits, including nesting deeper than `max_depth`, fail it outright.
+    fn parse_embedded_file(&self, name: &str, contents: Vec<u8>) -> Result<String, Error> {
+        if self.depth >= self.config.max_depth {
+            return Err(Self::resource_limit_error(
+                "Nesting depth exceeds limit",
+                format!("{} is nested more than {} containers deep", name, self.config.max_depth),
+            ));
+        }
+        let nested = Parser {
+            config: self.config.clone(),
+            depth: self.depth + 1,
+        };
 
-        let cursor = Cursor::new(data);
-        match Xlsx::new(cursor) {
-            Ok(mut workbook) => {
-                let mut result = String::new();
+        let format = FormatDetector::detect(Some(name), Some(&contents));
+        let text = if format == FileFormat::Unknown && std::str::from_utf8(&contents).is_err() {
+            "(skipped: unsupported format)".to_string()
+        } else {
+            match nested.dispatch_to_parser(format, contents) {
+                Ok(text) => text,
+                Err(e) if ParserError::is_resource_limit(&e) => return Err(e),
+                Err(e) => format!("(failed to parse: {})", e),
+            }
+        };
+        Ok(text.trim().to_string())
+    }
+
+    /// Parse log files into normalized `timestamp`, `level`, `message` rows - exposed to Ruby
+    ///
+    /// Lines are matched against the built-in syslog (RFC 3164 and 5424),
+    /// Apache/Nginx combined and JSON-lines layouts. With `pattern:` only that
+    /// regex is used; its named captures `timestamp`, `level` and `message`
+    /// fill the columns (the whole line stands in for a missing `message`).
+    fn parse_log(&self, args: &[Value]) -> Result<String, Error> {
+        let args = scan_args::scan_args::<(Vec<u8>,), (), (), (), RHash, ()>(args)?;
+        let (data,) = args.required;
+        let kwargs = scan_args::get_kwargs::<_, (), (Option<String>,), ()>(
+            args.keywords,
+            &[],
+            &["pattern"],
+        )?;
+        let (pattern,) = kwargs.optional;
 
-                for sheet_name in workbook.sheet_names().to_owned() {
-                    result.push_str(&format!("Sheet: {}\n", sheet_name));
+        let pattern = pattern
+            .map(|pattern| {
+                regex::Regex::new(&pattern)
+                    .map_err(|e| Self::argument_error(&format!("Invalid log pattern: {}", e)))
+            })
+            .transpose()?;
 
-                    if let Ok(range) = workbook.worksheet_range(&sheet_name) {
-                        for row in range.rows() {
-                            for cell in row {
-                                result.push_str(&format!("{}\t", cell));
+        self.normalize_log(data, pattern.as_ref())
+    }
+
+    /// Turn log lines into tab-delimited `timestamp level message` rows.
+    /// Fields a layout doesn't carry are written as `-`, indented lines
+    /// (stack traces) are folded into the previous entry and anything else
+    /// that doesn't match is kept with `-` timestamp and level.
+    fn normalize_log(&self, data: Vec<u8>, pattern: Option<&regex::Regex>) -> Result<String, Error> {
+        let text = self.parse_text(data)?;
+
+        let mut entries: Vec<(String, String, String)> = Vec::new();
+
+        for line in text.lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let entry = match pattern {
+                Some(pattern) => pattern.captures(line).map(|caps| {
+                    let field = |name: &str| caps.name(name).map(|m| m.as_str().trim().to_string());
+                    (
+                        field("timestamp").unwrap_or_default(),
+                        field("level").map(|level| Self::normalize_log_level(&level)).unwrap_or_default(),
+                        field("message").unwrap_or_else(|| line.trim().to_string()),
+                    )
+                }),
+                None => Self::json_log_entry(line)
+                    .or_else(|| {
+                        SYSLOG_RFC5424.captures(line).map(|caps| {
+                            let message = match &caps["app"] {
+                                "-" => caps["message"].to_string(),
+                                app => format!("{}: {}", app, &caps["message"]),
+                            };
+                            (caps["timestamp"].to_string(), Self::syslog_severity(&caps["pri"]), message)
+                        })
+                    })
+                    .or_else(|| {
+                        SYSLOG_RFC3164.captures(line).map(|caps| {
+                            let level = caps.name("pri").map(|pri| Self::syslog_severity(pri.as_str()));
+                            (
+                                caps["timestamp"].split_whitespace().collect::<Vec<_>>().join(" "),
+                                level.unwrap_or_default(),
+                                caps["message"].to_string(),
+                            )
+                        })
+                    })
+                    .or_else(|| {
+                        COMBINED_LOG_FORMAT.captures(line).map(|caps| {
+                            let level = match caps["status"].as_bytes()[0] {
+                                b'5' => "ERROR",
+                                b'4' => "WARNING",
+                                _ => "INFO",
+                            };
+                            let mut message = format!(
+                                "{} \"{}\" {} {}",
+                                &caps["client"], &caps["request"], &caps["status"], &caps["bytes"]
+                            );
+                            if let (Some(referer), Some(agent)) = (caps.name("referer"), caps.name("agent")) {
+                                message.push_str(&format!(" \"{}\" \"{}\"", referer.as_str(), agent.as_str()));
                             }
-                            result.push('\n');
-                        }
-                    }
-                    result.push('\n');
-                }
+                            (Self::clf_timestamp(&caps["timestamp"]), level.to_string(), message)
+                        })
+                    }),
+            };
 
-                Ok(result)
+            match entry {
+                Some(entry) => entries.push(entry),
+                None => match entries.last_mut() {
+                    Some(previous) if line.starts_with(char::is_whitespace) => {
+                        previous.2.push(' ');
+                        previous.2.push_str(line.trim());
+                    }
+                    _ => entries.push((String::new(), String::new(), line.trim().to_string())),
+                },
             }
-            Err(e) => Err(Self::runtime_error("Failed to parse Excel file", e)),
         }
+
+        let or_dash = |value: &str| if value.is_empty() { "-".to_string() } else { value.replace('\t', " ") };
+        Ok(entries
+            .iter()
+            .map(|(timestamp, level, message)| {
+                format!("{}\t{}\t{}", or_dash(timestamp), or_dash(level), or_dash(message))
+            })
+            .collect::<Vec<_>>()
+            .join("\n"))
     }
 
-    /// Parse JSON files - exposed to Ruby
-    fn parse_json(&self, data: Vec<u8>) -> Result<String, Error> {
-        let text = String::from_utf8_lossy(&data);
-        match serde_json::from_str::<serde_json::Value>(&text) {
-            Ok(json) => {
-                Ok(serde_json::to_string_pretty(&json).unwrap_or_else(|_| text.to_string()))
-            }
-            Err(_) => Ok(text.to_string()),
+    /// Read a JSON-lines record, taking the timestamp, level and message
+    /// from their common key spellings. Records without a message key keep
+    /// their remaining fields as compact JSON.
+    fn json_log_entry(line: &str) -> Option<(String, String, String)> {
+        let trimmed = line.trim();
+        if !trimmed.starts_with('{') {
+            return None;
         }
-    }
+        let mut record = match serde_json::from_str::<serde_json::Value>(trimmed).ok()? {
+            serde_json::Value::Object(record) => record,
+            _ => return None,
+        };
 
-    /// Parse XML/HTML files - exposed to Ruby
-    fn parse_xml(&self, data: Vec<u8>) -> Result<String, Error> {
-        use quick_xml::events::Event;
-        use quick_xml::Reader;
+        let mut take = |keys: &[&str]| -> Option<String> {
+            let key = keys.iter().find(|key| record.contains_key(**key))?;
+            match record.remove(*key)? {
+                serde_json::Value::String(value) => Some(value),
+                value => Some(value.to_string()),
+            }
+        };
 
-        let mut reader = Reader::from_reader(&data[..]);
-        let mut txt = String::new();
-        let mut buf = Vec::new();
+        let timestamp = take(&["timestamp", "@timestamp", "time", "ts", "datetime", "date"]);
+        let level = take(&["level", "severity", "lvl", "loglevel", "log.level"]);
+        let message = take(&["message", "msg", "@message", "text", "log"]);
 
-        loop {
-            match reader.read_event_into(&mut buf) {
-                Ok(Event::Text(e)) => {
-                    txt.push_str(&e.decode().unwrap_or_default());
-                    txt.push(' ');
-                }
-                Ok(Event::Eof) => break,
-                Err(e) => {
-                    return Err(Self::runtime_error("XML parse error", e))
-                }
-                _ => {}
-            }
-            buf.clear();
+        let message = message.unwrap_or_else(|| serde_json::Value::Object(record).to_string());
+        Some((
+            timestamp.unwrap_or_default(),
+            level.map(|level| Self::normalize_log_level(&level)).unwrap_or_default(),
+            message,
+        ))
+    }
+
+    /// Map a syslog PRI value to its severity name
+    fn syslog_severity(pri: &str) -> String {
+        const SEVERITIES: [&str; 8] = [
+            "EMERGENCY", "ALERT", "CRITICAL", "ERROR", "WARNING", "NOTICE", "INFO", "DEBUG",
+        ];
+        pri.parse::<usize>()
+            .map(|pri| SEVERITIES[pri % 8].to_string())
+            .unwrap_or_default()
+    }
+
+    /// Upper-case a level name, folding common abbreviations onto the syslog names
+    fn normalize_log_level(level: &str) -> String {
+        let level = level.trim().to_uppercase();
+        match level.as_str() {
+            "WARN" => "WARNING".to_string(),
+            "ERR" => "ERROR".to_string(),
+            "CRIT" | "FATAL" => "CRITICAL".to_string(),
+            "EMERG" | "PANIC" => "EMERGENCY".to_string(),
+            "INFORMATION" | "INFORMATIONAL" => "INFO".to_string(),
+            _ => level,
         }
+    }
 
-        Ok(txt.trim().to_string())
+    /// Convert a Common Log Format timestamp (`10/Oct/2000:13:55:36 -0700`)
+    /// to ISO 8601 (`2000-10-10T13:55:36-07:00`)
+    fn clf_timestamp(timestamp: &str) -> String {
+        const MONTHS: [&str; 12] = [
+            "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+        ];
+        let parsed = (|| {
+            let (date_time, offset) = timestamp.split_once(' ')?;
+            let mut parts = date_time.splitn(3, '/');
+            let (day, month, rest) = (parts.next()?, parts.next()?, parts.next()?);
+            let (year, time) = rest.split_once(':')?;
+            let month = MONTHS.iter().position(|m| *m == month)? + 1;
+            // `[+-]hhmm`, checked before slicing it by byte
+            let (sign, digits) = offset.split_at_checked(1)?;
+            if !matches!(sign, "+" | "-") || digits.len() != 4 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            Some(format!("{}-{:02}-{}T{}{}:{}", year, month, day, time, &offset[..3], &offset[3..]))
+        })();
+        parsed.unwrap_or_else(|| timestamp.to_string())
     }
 
     /// Parse plain text with encoding detection - exposed to Ruby
@@ -522,10 +11338,43 @@ impl Parser {
     /// Parse a file
     fn parse_file(&self, path: String) -> Result<String, Error> {
         use std::fs;
+        use std::io::{Read, Seek};
 
-        let data = fs::read(&path)
+        let mut file = fs::File::open(&path)
             .map_err(|e| Self::io_error("Failed to read file", e))?;
 
+        // PPTX decks are mostly media, so they are read from the file part
+        // by part instead of whole, plain XML is streamed through the
+        // reader and PST stores are handed to readpst where they lie. ZIP
+        // packages are told apart by the names in their first entries, XML
+        // dialects by the root element.
+        let mut data = Vec::new();
+        (&mut file)
+            .take(4096)
+            .read_to_end(&mut data)
+            .map_err(|e| Self::io_error("Failed to read file", e))?;
+        let format = FormatDetector::detect(Some(&path), Some(&data));
+        if matches!(format, FileFormat::Pptx | FileFormat::Xml | FileFormat::Pst) {
+            let size = file.metadata()
+                .map_err(|e| Self::io_error("Failed to read file", e))?
+                .len();
+            if size > self.config.max_size as u64 {
+                return Err(self.file_size_error(size));
+            }
+            if format == FileFormat::Pst {
+                return self.pst_file_text(std::path::Path::new(&path));
+            }
+            file.rewind()
+                .map_err(|e| Self::io_error("Failed to read file", e))?;
+            let file = std::io::BufReader::new(file);
+            return match format {
+                FileFormat::Pptx => self.pptx_text(file, &PageSelection::All),
+                _ => self.xml_text(file),
+            };
+        }
+
+        file.read_to_end(&mut data)
+            .map_err(|e| Self::io_error("Failed to read file", e))?;
         self.parse_bytes_internal(data, Some(&path))
     }
 
@@ -546,6 +11395,53 @@ impl Parser {
         hash.aset(ruby.to_symbol("max_depth"), self.config.max_depth)?;
         hash.aset(ruby.to_symbol("encoding"), self.config.encoding.as_str())?;
         hash.aset(ruby.to_symbol("max_size"), self.config.max_size)?;
+        hash.aset(ruby.to_symbol("max_entry_size"), self.config.max_entry_size)?;
+        hash.aset(ruby.to_symbol("max_compression_ratio"), self.config.max_compression_ratio)?;
+        hash.aset(ruby.to_symbol("max_rows"), self.config.max_rows)?;
+        hash.aset(ruby.to_symbol("ocr_pixel_data"), self.config.ocr_pixel_data)?;
+        hash.aset(ruby.to_symbol("transcribe"), self.config.transcribe)?;
+        hash.aset(ruby.to_symbol("timestamps"), self.config.timestamps)?;
+        hash.aset(ruby.to_symbol("whisper_model"), self.config.whisper_model.clone())?;
+        hash.aset(ruby.to_symbol("parse_attachments"), self.config.parse_attachments)?;
+        hash.aset(ruby.to_symbol("resolve_entities"), self.config.resolve_entities)?;
+        hash.aset(ruby.to_symbol("lenient"), self.config.lenient)?;
+        hash.aset(ruby.to_symbol("normalize_text"), self.config.normalize_text)?;
+        hash.aset(ruby.to_symbol("pdf_structure_order"), self.config.pdf_structure_order)?;
+        hash.aset(ruby.to_symbol("min_confidence"), self.config.min_confidence)?;
+        hash.aset(ruby.to_symbol("ocr_output"), ruby.to_symbol(self.config.ocr_output.name()))?;
+        let stages: Vec<_> = self.config.ocr_preprocess.iter().map(|stage| ruby.to_symbol(stage.name())).collect();
+        hash.aset(ruby.to_symbol("ocr_preprocess"), stages)?;
+        hash.aset(ruby.to_symbol("ocr_dpi"), self.config.ocr_dpi)?;
+        hash.aset(ruby.to_symbol("ocr_language"), self.config.ocr_language.as_str())?;
+        hash.aset(ruby.to_symbol("ocr_psm"), self.config.ocr_psm)?;
+        hash.aset(ruby.to_symbol("ocr_oem"), self.config.ocr_oem)?;
+        hash.aset(ruby.to_symbol("ocr_embedded_images"), self.config.ocr_embedded_images)?;
+        hash.aset(ruby.to_symbol("ocr_backend"), self.config.ocr_backend.to_value(&ruby))?;
+        hash.aset(ruby.to_symbol("tessdata_path"), self.config.tessdata_path.as_deref())?;
+        hash.aset(ruby.to_symbol("ocr_threads"), self.config.ocr_threads)?;
+        hash.aset(ruby.to_symbol("docx_comments"), self.config.docx_comments)?;
+        hash.aset(ruby.to_symbol("revisions"), ruby.to_symbol(self.config.revisions.name()))?;
+        hash.aset(ruby.to_symbol("docx_links"), self.config.docx_links)?;
+        hash.aset(ruby.to_symbol("docx_headings"), self.config.docx_headings)?;
+        hash.aset(ruby.to_symbol("pptx_alt_text"), self.config.pptx_alt_text)?;
+        hash.aset(ruby.to_symbol("pptx_notes"), ruby.to_symbol(self.config.pptx_notes.name()))?;
+        hash.aset(ruby.to_symbol("pptx_links"), self.config.pptx_links)?;
+        hash.aset(ruby.to_symbol("include_formulas"), self.config.include_formulas)?;
+        hash.aset(ruby.to_symbol("include_hidden"), self.config.include_hidden)?;
+        hash.aset(ruby.to_symbol("xlsx_links"), self.config.xlsx_links)?;
+        hash.aset(ruby.to_symbol("xlsx_cell_separator"), self.config.xlsx_cell_separator.as_str())?;
+        hash.aset(ruby.to_symbol("xlsx_trailing_separator"), self.config.xlsx_trailing_separator)?;
+        hash.aset(ruby.to_symbol("xlsx_row_separator"), self.config.xlsx_row_separator.as_str())?;
+        hash.aset(ruby.to_symbol("xlsx_sheet_header"), self.config.xlsx_sheet_header.as_deref())?;
+        hash.aset(ruby.to_symbol("xlsx_skip_empty_rows"), self.config.xlsx_skip_empty_rows)?;
+        hash.aset(ruby.to_symbol("json_output"), ruby.to_symbol(self.config.json_output.name()))?;
+        match &self.config.xml_attributes {
+            XmlAttributes::Named(names) => hash.aset(ruby.to_symbol("xml_attributes"), names.clone())?,
+            other => hash.aset(ruby.to_symbol("xml_attributes"), *other == XmlAttributes::All)?,
+        }
+        hash.aset(ruby.to_symbol("xml_elements"), self.config.xml_elements.clone())?;
+        hash.aset(ruby.to_symbol("xml_namespaces"), self.config.xml_namespaces.clone())?;
+        hash.aset(ruby.to_symbol("main_content"), self.config.main_content)?;
         Ok(hash)
     }
 
@@ -576,10 +11472,174 @@ impl Parser {
     }
 }
 
+/// An XLSX opened once by `Parser#open_xlsx`, whose sheets are read on
+/// demand and kept for later queries
+#[derive(TypedData)]
+#[magnus(class = "ParseKit::Workbook", free_immediately, size)]
+pub struct Workbook {
+    /// Sheet names in workbook order, without hidden sheets unless
+    /// `include_hidden` was on
+    sheets: Vec<String>,
+    /// Hidden rows and columns by sheet name, when `include_hidden` was off
+    hidden: std::collections::HashMap<String, (std::collections::HashSet<u32>, std::collections::HashSet<u32>)>,
+    state: std::cell::RefCell<WorkbookState>,
+}
+
+impl DataTypeFunctions for Workbook {}
+
+struct WorkbookState {
+    workbook: calamine::Xlsx<std::io::Cursor<Vec<u8>>>,
+    /// Sheets read so far. Shared so rows can be yielded without holding
+    /// the state borrowed.
+    ranges: std::collections::HashMap<String, std::sync::Arc<calamine::Range<calamine::Data>>>,
+}
+
+impl Parser {
+    /// Open an XLSX for reading sheet by sheet - exposed to Ruby
+    ///
+    /// The package is checked and indexed once; each sheet is parsed the
+    /// first time it is asked for. Cells come back typed as in
+    /// `parse_xlsx_structured`, and `include_hidden` applies as there.
+    fn open_xlsx(&self, data: Vec<u8>) -> Result<Workbook, Error> {
+        use calamine::{Reader, Xlsx};
+
+        self.check_zip_limits(&data, "XLSX")?;
+        let hidden = self.read_xlsx_hidden_lines(&data)?;
+        let workbook = Xlsx::new(std::io::Cursor::new(data))
+            .map_err(|e| Self::runtime_error("Failed to parse Excel file", e))?;
+        let sheets = self.xlsx_sheet_names(&workbook, &SheetSelection::All);
+
+        Ok(Workbook {
+            sheets,
+            hidden,
+            state: std::cell::RefCell::new(WorkbookState {
+                workbook,
+                ranges: std::collections::HashMap::new(),
+            }),
+        })
+    }
+}
+
+impl Workbook {
+    /// The names of the sheets, in workbook order
+    fn sheet_names(&self) -> Vec<String> {
+        self.sheets.clone()
+    }
+
+    /// The used range of a sheet as "A1:C4", or nil when it is empty
+    fn dimensions(&self, sheet: Value) -> Result<Option<String>, Error> {
+        let range = self.worksheet(&self.sheet_name(sheet)?)?;
+        let cell = |(row, column): (u32, u32)| format!("{}{}", Parser::xlsx_column_name(column), row + 1);
+        Ok(range.start().zip(range.end()).map(|(start, end)| format!("{}:{}", cell(start), cell(end))))
+    }
+
+    /// All rows of a sheet as arrays of typed cells, without hidden rows
+    /// and columns when `include_hidden` was off
+    fn rows(ruby: &Ruby, rb_self: &Self, sheet: Value) -> Result<RArray, Error> {
+        let rows = ruby.ary_new();
+        rb_self.each_visible_row(ruby, sheet, |row| rows.push(row))?;
+        Ok(rows)
+    }
+
+    /// Yield the rows of a sheet one at a time, as `rows` returns them.
+    /// Without a block, returns an Enumerator.
+    fn each_row(ruby: &Ruby, rb_self: magnus::typed_data::Obj<Self>, sheet: Value) -> Result<Value, Error> {
+        if !ruby.block_given() {
+            return Ok(rb_self.enumeratorize("each_row", (sheet,)).as_value());
+        }
+        rb_self.each_visible_row(ruby, sheet, |row| ruby.yield_value::<_, Value>(row).map(|_| ()))?;
+        Ok(rb_self.as_value())
+    }
+
+    /// The cells of an A1-style range such as "B2:D10" as rows of typed
+    /// cells, nil where a cell is empty. Hidden rows and columns are kept,
+    /// since the range names them; rows and columns past the sheet's used
+    /// range are left off.
+    fn range(ruby: &Ruby, rb_self: &Self, sheet: Value, reference: String) -> Result<RArray, Error> {
+        let (first, last) = reference.split_once(':').unwrap_or((&reference, &reference));
+        let (Some(first), Some(last)) = (Parser::xlsx_cell_position(first), Parser::xlsx_cell_position(last)) else {
+            return Err(Parser::argument_error(&format!("Unsupported range: {}", reference)));
+        };
+        let range = rb_self.worksheet(&rb_self.sheet_name(sheet)?)?;
+
+        let rows = ruby.ary_new();
+        let Some((bottom, right)) = range.end() else {
+            return Ok(rows);
+        };
+        for row in first.0.min(last.0)..=first.0.max(last.0).min(bottom) {
+            let cells = ruby.ary_new();
+            for column in first.1.min(last.1)..=first.1.max(last.1).min(right) {
+                match range.get_value((row, column)) {
+                    Some(cell) => cells.push(Parser::xlsx_cell_value(ruby, cell)?)?,
+                    None => cells.push(ruby.qnil())?,
+                }
+            }
+            rows.push(cells)?;
+        }
+        Ok(rows)
+    }
+
+    /// Hand each row of a sheet to `f` as an array of typed cells, hidden
+    /// rows and columns left out
+    fn each_visible_row(
+        &self,
+        ruby: &Ruby,
+        sheet: Value,
+        mut f: impl FnMut(RArray) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let name = self.sheet_name(sheet)?;
+        let range = self.worksheet(&name)?;
+        let no_lines = Default::default();
+        let (hidden_rows, hidden_columns) = self.hidden.get(&name).unwrap_or(&no_lines);
+
+        let (top, left) = range.start().unwrap_or((0, 0));
+        for (r, row) in range.rows().enumerate() {
+            if hidden_rows.contains(&(top + r as u32)) {
+                continue;
+            }
+            let cells = ruby.ary_new();
+            for (c, cell) in row.iter().enumerate() {
+                if !hidden_columns.contains(&(left + c as u32)) {
+                    cells.push(Parser::xlsx_cell_value(ruby, cell)?)?;
+                }
+            }
+            f(cells)?;
+        }
+        Ok(())
+    }
+
+    /// The sheet a name or index from 0 refers to
+    fn sheet_name(&self, sheet: Value) -> Result<String, Error> {
+        Parser::sheet_selection(Some(sheet))?
+            .names(&self.sheets)
+            .into_iter()
+            .next()
+            .ok_or_else(|| Parser::argument_error("No such sheet in the workbook"))
+    }
+
+    /// A sheet's cells, parsed on first use
+    fn worksheet(&self, name: &str) -> Result<std::sync::Arc<calamine::Range<calamine::Data>>, Error> {
+        use calamine::Reader;
+
+        let mut state = self.state.borrow_mut();
+        if let Some(range) = state.ranges.get(name) {
+            return Ok(range.clone());
+        }
+        let range = state
+            .workbook
+            .worksheet_range(name)
+            .map_err(|e| Parser::runtime_error("Failed to read worksheet", e))?;
+        let range = std::sync::Arc::new(range);
+        state.ranges.insert(name.to_string(), range.clone());
+        Ok(range)
+    }
+}
+
 /// Module-level convenience function for parsing files
 fn parse_file_direct(path: String) -> Result<String, Error> {
     let parser = Parser {
         config: ParserConfig::default(),
+        depth: 0,
     };
     parser.parse_file(path)
 }
@@ -588,6 +11648,7 @@ fn parse_file_direct(path: String) -> Result<String, Error> {
 fn parse_bytes_direct(data: Vec<u8>) -> Result<String, Error> {
     let parser = Parser {
         config: ParserConfig::default(),
+        depth: 0,
     };
     parser.parse_bytes_internal(data, None)
 }
@@ -606,14 +11667,71 @@ pub fn init(_ruby: &Ruby, module: RModule) -> Result<(), Error> {
     class.define_method("supports_file?", method!(Parser::supports_file, 1))?;
 
     // Individual parser methods exposed to Ruby
-    class.define_method("parse_pdf", method!(Parser::parse_pdf, 1))?;
+    class.define_method("parse_pdf", method!(Parser::parse_pdf, -1))?;
+    class.define_method("parse_pdf_pages", method!(Parser::parse_pdf_pages, -1))?;
+    class.define_method("parse_pdf_tables", method!(Parser::parse_pdf_tables, -1))?;
+    class.define_method("parse_pdf_spans", method!(Parser::parse_pdf_spans, -1))?;
+    class.define_method("render_pdf_page", method!(Parser::render_pdf_page, -1))?;
+    class.define_method("pdf_outline", method!(Parser::pdf_outline, 1))?;
+    class.define_method("pdf_annotations", method!(Parser::pdf_annotations, -1))?;
+    class.define_method("pdf_javascript", method!(Parser::pdf_javascript, 1))?;
+    class.define_method("pdf_attachments", method!(Parser::pdf_attachments, -1))?;
+    class.define_method("pdf_xfa", method!(Parser::pdf_xfa, 1))?;
+    class.define_method("parse_djvu", method!(Parser::parse_djvu, 1))?;
+    class.define_method("parse_postscript", method!(Parser::parse_postscript, 1))?;
     class.define_method("parse_docx", method!(Parser::parse_docx, 1))?;
-    class.define_method("parse_pptx", method!(Parser::parse_pptx, 1))?;
-    class.define_method("parse_xlsx", method!(Parser::parse_xlsx, 1))?;
+    class.define_method("docx_comments", method!(Parser::docx_comments, 1))?;
+    class.define_method("docx_links", method!(Parser::docx_links, 1))?;
+    class.define_method("docx_images", method!(Parser::docx_images, -1))?;
+    class.define_method("docx_form_fields", method!(Parser::docx_form_fields, 1))?;
+    class.define_method("docx_sections", method!(Parser::docx_sections, 1))?;
+    class.define_method("parse_wordperfect", method!(Parser::parse_wordperfect, 1))?;
+    class.define_method("parse_pptx", method!(Parser::parse_pptx, -1))?;
+    class.define_method("parse_pptx_slides", method!(Parser::parse_pptx_slides, -1))?;
+    class.define_method("parse_vsdx", method!(Parser::parse_vsdx, 1))?;
+    class.define_method("parse_onenote", method!(Parser::parse_onenote, 1))?;
+    class.define_method("parse_xlsx", method!(Parser::parse_xlsx, -1))?;
+    class.define_method("parse_xlsx_structured", method!(Parser::parse_xlsx_structured, -1))?;
+    class.define_method("xlsx_defined_names", method!(Parser::xlsx_defined_names, 1))?;
+    class.define_method("xlsx_to_csv", method!(Parser::xlsx_to_csv, -1))?;
+    class.define_method("open_xlsx", method!(Parser::open_xlsx, 1))?;
+    class.define_method("parse_parquet", method!(Parser::parse_parquet, 1))?;
+    class.define_method("parse_arrow", method!(Parser::parse_arrow, 1))?;
+    class.define_method("parse_dbf", method!(Parser::parse_dbf, 1))?;
     class.define_method("parse_json", method!(Parser::parse_json, 1))?;
+    class.define_method("parse_json_object", method!(Parser::parse_json_object, 1))?;
+    class.define_method("parse_msgpack", method!(Parser::parse_msgpack, 1))?;
+    class.define_method("parse_cbor", method!(Parser::parse_cbor, 1))?;
+    class.define_method("parse_bson", method!(Parser::parse_bson, 1))?;
+    class.define_method("parse_protobuf", method!(Parser::parse_protobuf, -1))?;
+    class.define_method("parse_dicom", method!(Parser::parse_dicom, 1))?;
+    class.define_method("parse_audio", method!(Parser::parse_audio, 1))?;
+    class.define_method("parse_video", method!(Parser::parse_video, 1))?;
     class.define_method("parse_xml", method!(Parser::parse_xml, 1))?;
+    class.define_method("parse_xml_structured", method!(Parser::parse_xml_structured, 1))?;
+    class.define_method("xml_query", method!(Parser::xml_query, -1))?;
+    class.define_method("parse_xbrl", method!(Parser::parse_xbrl, 1))?;
+    class.define_method("parse_flat_odf", method!(Parser::parse_flat_odf, 1))?;
+    class.define_method("parse_html", method!(Parser::parse_html, 1))?;
+    class.define_method("parse_mhtml", method!(Parser::parse_mhtml, 1))?;
+    class.define_method("parse_email", method!(Parser::parse_email, 1))?;
+    class.define_method("parse_chm", method!(Parser::parse_chm, 1))?;
+    class.define_method("parse_mobi", method!(Parser::parse_mobi, 1))?;
+    class.define_method("parse_subtitles", method!(Parser::parse_subtitles, 1))?;
+    class.define_method("parse_log", method!(Parser::parse_log, -1))?;
+    class.define_method("parse_tar", method!(Parser::parse_tar, 1))?;
+    class.define_method("parse_gzip", method!(Parser::parse_gzip, 1))?;
+    class.define_method("parse_bzip2", method!(Parser::parse_bzip2, 1))?;
+    class.define_method("parse_xz", method!(Parser::parse_xz, 1))?;
+    class.define_method("parse_sevenzip", method!(Parser::parse_sevenzip, 1))?;
+    class.define_method("parse_rar", method!(Parser::parse_rar, 1))?;
+    class.define_method("parse_pst", method!(Parser::parse_pst, 1))?;
     class.define_method("parse_text", method!(Parser::parse_text, 1))?;
     class.define_method("ocr_image", method!(Parser::ocr_image, 1))?;
+    class.define_method("ocr_image_result", method!(Parser::ocr_image_result, 1))?;
+    class.define_method("ocr_image_region", method!(Parser::ocr_image_region, -1))?;
+    class.define_method("ocr_to_pdf", method!(Parser::ocr_to_pdf, 1))?;
+    class.define_method("extract_macros", method!(Parser::extract_macros, -1))?;
     
     // Format detection methods
     class.define_method("detect_format_from_bytes", method!(Parser::detect_format_from_bytes, 1))?;
@@ -626,5 +11744,14 @@ pub fn init(_ruby: &Ruby, module: RModule) -> Result<(), Error> {
     module.define_singleton_method("parse_file", function!(parse_file_direct, 1))?;
     module.define_singleton_method("parse_bytes", function!(parse_bytes_direct, 1))?;
 
+    // Workbooks come from Parser#open_xlsx
+    let workbook = module.define_class("Workbook", Ruby::get().unwrap().class_object())?;
+    workbook.undef_default_alloc_func();
+    workbook.define_method("sheet_names", method!(Workbook::sheet_names, 0))?;
+    workbook.define_method("dimensions", method!(Workbook::dimensions, 1))?;
+    workbook.define_method("rows", method!(Workbook::rows, 1))?;
+    workbook.define_method("each_row", method!(Workbook::each_row, 1))?;
+    workbook.define_method("range", method!(Workbook::range, 2))?;
+
     Ok(())
 }
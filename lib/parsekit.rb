@@ -10,23 +10,56 @@ rescue LoadError
 end
 
 require_relative "parsekit/error"
+require_relative "parsekit/cell_error"
 require_relative "parsekit/parser"
+require_relative "parsekit/tessdata"
 
 # ParseKit is a Ruby document parsing toolkit with PDF and OCR support
 module ParseKit
   # Supported file formats and their extensions
   SUPPORTED_FORMATS = {
     pdf: ['.pdf'],
+    djvu: ['.djvu', '.djv'],
+    postscript: ['.ps', '.eps', '.epsf'],
     docx: ['.docx'],
+    wordperfect: ['.wpd', '.wp', '.wp5', '.wp6'],
     xlsx: ['.xlsx'],
     xls: ['.xls'],
     pptx: ['.pptx'],
+    vsdx: ['.vsdx', '.vsdm'],
+    onenote: ['.one'],
+    parquet: ['.parquet'],
+    arrow: ['.arrow', '.arrows', '.feather', '.ipc'],
+    dbf: ['.dbf'],
     png: ['.png'],
     jpeg: ['.jpg', '.jpeg'],
     tiff: ['.tiff', '.tif'],
     bmp: ['.bmp'],
+    gif: ['.gif'],
+    avif: ['.avif'],
+    dicom: ['.dcm', '.dicom'],
+    audio: ['.wav', '.mp3', '.m4a', '.ogg', '.oga'],
+    video: ['.mp4', '.m4v', '.mov', '.mkv', '.webm'],
     json: ['.json'],
+    msgpack: ['.msgpack', '.mpk'],
+    cbor: ['.cbor'],
+    bson: ['.bson'],
+    xbrl: ['.xbrl'],
+    flat_odf: ['.fodt', '.fods', '.fodp'],
     xml: ['.xml', '.html'],
+    mhtml: ['.mht', '.mhtml'],
+    email: ['.eml'],
+    chm: ['.chm'],
+    mobi: ['.mobi', '.azw', '.azw3', '.prc'],
+    subtitles: ['.srt', '.vtt', '.ass', '.ssa'],
+    log: ['.log'],
+    tar: ['.tar'],
+    gzip: ['.gz', '.tgz'],
+    bzip2: ['.bz2', '.tbz2', '.tbz'],
+    xz: ['.xz', '.txz'],
+    sevenzip: ['.7z'],
+    rar: ['.rar'],
+    pst: ['.pst', '.ost'],
     text: ['.txt', '.md', '.csv']
   }.freeze
 
@@ -0,0 +1,11 @@
+# frozen_string_literal: true
+
+module ParseKit
+  # An error value held by a spreadsheet cell, such as "#DIV/0!" or "#N/A",
+  # as returned by Parser#parse_xlsx_structured
+  CellError = Struct.new(:code) do
+    def to_s
+      code
+    end
+  end
+end
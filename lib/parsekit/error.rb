@@ -7,9 +7,14 @@ module ParseKit
   # Base error class for ParseKit (defined in native extension)
   # class Error < StandardError; end
   
-  # Raised when parsing fails (defined in native extension)
-  # class ParseError < Error; end
+  # Raised when parsing fails, e.g. on malformed JSON in strict mode
+  # (defined in native extension)
+  # class ParseError < RuntimeError; end
   
   # Raised when configuration is invalid (defined in native extension)
   # class ConfigError < Error; end
+
+  # Raised when a file or its decompressed contents exceed max_size,
+  # max_entry_size or max_compression_ratio (defined in native extension)
+  # class ResourceLimitError < RuntimeError; end
 end
\ No newline at end of file
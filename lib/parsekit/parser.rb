@@ -17,7 +17,40 @@ module ParseKit
     # - config
     # - supports_file?(path)
     # - strict_mode?
-    # - parse_pdf, parse_docx, parse_xlsx, parse_pptx, parse_json, parse_xml, parse_text, ocr_image
+    # - parse_pdf, parse_docx, parse_xlsx, parse_pptx, parse_json, parse_xml, parse_xbrl, parse_flat_odf, parse_html, parse_text, ocr_image
+    # - parse_mhtml, parse_email, parse_chm, parse_mobi, parse_subtitles, parse_djvu, parse_postscript, parse_wordperfect, parse_dicom, parse_audio, parse_video
+    # - parse_vsdx, parse_onenote, parse_parquet, parse_arrow, parse_dbf, parse_msgpack, parse_cbor, parse_bson
+    # - parse_tar, parse_gzip, parse_bzip2, parse_xz, parse_sevenzip, parse_rar
+    # - parse_pst(data) { |message| ... }
+    # - parse_json_object(data) -> Hash/Array/String/Integer/BigDecimal/true/false/nil
+    # - parse_protobuf(data, descriptor_set: nil, message_type: nil)
+    # - parse_xml_structured(data) -> { name:, attributes:, children: [...], text: } for the root element
+    # - xml_query(data, xpath, namespaces: {}) -> [{ name:, text:, attributes: }] for node-sets, else Float/String/true/false
+    # - parse_pdf(data, pages: nil), parse_pdf_pages(data, pages: nil)
+    # - parse_pdf_tables(data, pages: nil, format: :array), parse_pdf_spans(data, pages: nil)
+    # - render_pdf_page(data, page, dpi: 150)
+    # - pdf_outline(data), pdf_annotations(data, pages: nil), pdf_attachments(data, parse: false)
+    # - parse_log(data, pattern: nil)
+    # - pdf_javascript(data) -> { javascript:, actions: [{ trigger:, type:, script: }] }
+    # - pdf_xfa(data) -> [{ name:, label:, value: }]
+    # - ocr_image_result(data) -> { text:, confidence:, needs_review:, words: [{ text:, confidence:, bbox: }] }
+    # - ocr_image_region(data, x:, y:, w:, h:)
+    # - ocr_to_pdf(image_or_pdf_data) -> PDF bytes with an invisible text layer, a page per TIFF or PDF page
+    # - extract_macros(data) or extract_macros(path: "file.xlsm") -> { module_name => source }
+    # - docx_comments(data) -> [{ id:, author:, date:, text:, anchor: }]
+    # - docx_links(data) -> [{ text:, url: }]
+    # - docx_form_fields(data) -> [{ tag:, title:, type:, value: }]
+    # - docx_images(data, ocr: false) -> [{ name:, content_type:, paragraph:, data:, text: }]
+    # - docx_sections(data) -> [{ heading:, level:, text:, children: [...] }]
+    # - parse_xlsx(data, sheets: nil) with names, indexes from 0 or Regexps
+    # - parse_xlsx_structured(data, sheets: nil) -> [{ name:, rows: [[cell, ...]], merged: [{ row:, column:, rows:, columns: }] }] with typed cells
+    # - parse_xlsx_structured(data, headers: true) -> [{ name:, headers: [...], records: [{ "Header" => cell }] }]
+    # - xlsx_defined_names(data) -> [{ name:, scope:, refers_to:, sheet:, range:, hidden: }]
+    # - xlsx_to_csv(data, sheet: nil, col_sep: ",") -> RFC 4180 CSV of one sheet
+    # - open_xlsx(data) -> ParseKit::Workbook with sheet_names, dimensions(sheet), rows(sheet),
+    #   each_row(sheet) and range(sheet, "A1:C10"); sheets are parsed once, on first use
+    # - parse_pptx(data, slides: nil), parse_pptx_slides(data, slides: nil) -> [{ slide:, title:, body:, notes: }]
+    #   with path: in place of data to read the deck from the file part by part
     # See NATIVE_API.md for detailed documentation
     
     # Ruby convenience methods and helpers
@@ -49,11 +82,40 @@ module ParseKit
       
       case ext.downcase
       when 'docx' then :docx
+      when 'wpd', 'wp', 'wp5', 'wp6' then :wordperfect
       when 'pptx' then :pptx
+      when 'vsdx', 'vsdm' then :vsdx
+      when 'one' then :onenote
       when 'xlsx', 'xls' then :xlsx
+      when 'parquet' then :parquet
+      when 'arrow', 'arrows', 'feather', 'ipc' then :arrow
+      when 'dbf' then :dbf
       when 'pdf' then :pdf
+      when 'djvu', 'djv' then :djvu
+      when 'ps', 'eps', 'epsf' then :postscript
+      when 'dcm', 'dicom' then :dicom
+      when 'wav', 'mp3', 'm4a', 'ogg', 'oga' then :audio
+      when 'mp4', 'm4v', 'mov', 'mkv', 'webm' then :video
       when 'json' then :json
+      when 'msgpack', 'mpk' then :msgpack
+      when 'cbor' then :cbor
+      when 'bson' then :bson
       when 'xml', 'html' then :xml
+      when 'xbrl' then :xbrl
+      when 'fodt', 'fods', 'fodp' then :flat_odf
+      when 'mht', 'mhtml' then :mhtml
+      when 'eml' then :email
+      when 'chm' then :chm
+      when 'mobi', 'azw', 'azw3', 'prc' then :mobi
+      when 'srt', 'vtt', 'ass', 'ssa' then :subtitles
+      when 'log' then :log
+      when 'tar' then :tar
+      when 'gz', 'tgz' then :gzip
+      when 'bz2', 'tbz2', 'tbz' then :bzip2
+      when 'xz', 'txz' then :xz
+      when '7z' then :sevenzip
+      when 'rar' then :rar
+      when 'pst', 'ost' then :pst
       when 'txt', 'text', 'md', 'markdown' then :text
       when 'csv' then :text  # CSV is handled as text for now
       else :text  # Default to text
@@ -86,6 +148,30 @@ module ParseKit
         return :jpeg
       end
       
+      # GIF
+      if bytes.size >= 6 && ["GIF87a", "GIF89a"].include?(bytes[0..5].pack('C*'))
+        return :gif
+      end
+      
+      # AVIF (ISO-BMFF ftyp box with an AVIF brand)
+      if bytes.size >= 12 && bytes[4..7].pack('C*') == 'ftyp' && %w[avif avis].include?(bytes[8..11].pack('C*'))
+        return :avif
+      end
+      
+      # Audio: WAV, Ogg, M4A, MP3 with an ID3 tag or frame sync
+      header = bytes.first(12).pack('C*')
+      if (header.start_with?('RIFF') && header[8, 4] == 'WAVE') ||
+         header.start_with?('OggS', 'ID3') ||
+         (header[4, 4] == 'ftyp' && ['M4A ', 'M4B ', 'M4P '].include?(header[8, 4])) ||
+         mpeg_audio_frame?(bytes)
+        return :audio
+      end
+      
+      # Video: any other ISO-BMFF (MP4/MOV) file, or Matroska/WebM
+      if header[4, 4] == 'ftyp' || bytes.first(4) == [0x1A, 0x45, 0xDF, 0xA3]
+        return :video
+      end
+      
       # BMP
       if bytes.size >= 2 && bytes[0..1] == [0x42, 0x4D]  # BM
         return :bmp
@@ -100,6 +186,79 @@ module ParseKit
         end
       end
       
+      # DjVu
+      if bytes.size >= 8 && bytes[0..7].pack('C*') == 'AT&TFORM'
+        return :djvu
+      end
+      
+      # PostScript, plain or with a DOS EPS binary header
+      if bytes.size >= 4 && (bytes[0..3] == [0x25, 0x21, 0x50, 0x53] || bytes[0..3] == [0xC5, 0xD0, 0xD3, 0xC6])  # %!PS
+        return :postscript
+      end
+      
+      # DICOM (128-byte preamble followed by "DICM")
+      if bytes.size >= 132 && bytes[128..131].pack('C*') == 'DICM'
+        return :dicom
+      end
+      
+      # Compiled HTML Help
+      if bytes.size >= 4 && bytes[0..3].pack('C*') == 'ITSF'
+        return :chm
+      end
+      
+      # MOBI/AZW and PalmDOC ebooks (PalmDB type/creator at offset 60)
+      if bytes.size >= 68 && %w[BOOKMOBI TEXtREAd].include?(bytes[60..67].pack('C*'))
+        return :mobi
+      end
+      
+      # WordPerfect
+      if bytes.size >= 4 && bytes[0..3] == [0xFF, 0x57, 0x50, 0x43]  # \xFFWPC
+        return :wordperfect
+      end
+      
+      # Compressed streams: GZIP, BZIP2, XZ
+      return :gzip if bytes.first(2) == [0x1F, 0x8B]
+      return :bzip2 if bytes.size >= 4 && bytes[0..2].pack('C*') == 'BZh' && bytes[3].between?(0x30, 0x39)
+      return :xz if bytes.first(6) == [0xFD, 0x37, 0x7A, 0x58, 0x5A, 0x00]
+      
+      # 7z and RAR
+      return :sevenzip if bytes.first(6) == [0x37, 0x7A, 0xBC, 0xAF, 0x27, 0x1C]
+      return :rar if bytes.first(6) == [0x52, 0x61, 0x72, 0x21, 0x1A, 0x07]  # Rar!\x1A\x07
+      
+      # Outlook PST/OST
+      return :pst if bytes.first(4) == [0x21, 0x42, 0x44, 0x4E] && ['SM', 'SO'].include?(bytes[8, 2].to_a.pack('C*'))
+      
+      # TAR (ustar magic at offset 257)
+      if bytes.size >= 262 && bytes[257..261].pack('C*') == 'ustar'
+        return :tar
+      end
+      
+      # Parquet
+      if bytes.size >= 4 && bytes[0..3] == [0x50, 0x41, 0x52, 0x31]  # PAR1
+        return :parquet
+      end
+      
+      # Arrow IPC file / Feather v2
+      if bytes.size >= 6 && bytes[0..5] == [0x41, 0x52, 0x52, 0x4F, 0x57, 0x31]  # ARROW1
+        return :arrow
+      end
+      
+      # Self-described CBOR
+      if bytes.size >= 3 && bytes[0..2] == [0xD9, 0xD9, 0xF7]
+        return :cbor
+      end
+      
+      # dBase table: version byte, plausible date, header terminator
+      if bytes.size >= 65
+        header_len = bytes[8] | (bytes[9] << 8)
+        record_len = bytes[10] | (bytes[11] << 8)
+        if [0x02, 0x03, 0x04, 0x05, 0x30, 0x31, 0x32, 0x43, 0x63, 0x83, 0x8B, 0xCB, 0xF5, 0xFB].include?(bytes[0]) &&
+           bytes[2].between?(1, 12) && bytes[3].between?(1, 31) && record_len > 1 && header_len > 32 &&
+           (bytes[header_len - 1] == 0x0D || (header_len > 264 && bytes[header_len - 264] == 0x0D))
+          return :dbf
+        end
+      end
+      
       # OLE Compound Document (old Excel/Word) - return :xlsx for compatibility
       if bytes.size >= 4 && bytes[0..3] == [0xD0, 0xCF, 0x11, 0xE0]
         return :xlsx  # Return :xlsx for compatibility with existing tests
@@ -116,6 +275,9 @@ module ParseKit
       if bytes.size >= 5
         first_chars = bytes[0..4].pack('C*')
         if first_chars == '<?xml' || first_chars.start_with?('<!')
+          root = bytes.first(4096).pack('C*')[/<(?![?!])([^\s>\/]+)/, 1]
+          return :xbrl if root && root.split(':').last == 'xbrl'
+          return :flat_odf if root == 'office:document'
           return :xml
         end
       end
@@ -145,13 +307,21 @@ module ParseKit
         end
       end
       
+      # Subtitles: WebVTT, ASS/SSA, or a SubRip cue (index line, then a timing line)
+      head = bytes.first(256).pack('C*').force_encoding('UTF-8').scrub.delete_prefix("\uFEFF").lstrip
+      first_line, second_line = head.lines
+      if head.start_with?('WEBVTT', '[Script Info]') ||
+         (first_line&.strip&.match?(/\A\d+\z/) && second_line&.include?('-->'))
+        return :subtitles
+      end
+      
       # Default to text if not recognized
       :text
     end
     
     # Detect specific Office format from ZIP data
     # @param bytes [Array<Integer>] ZIP file bytes
-    # @return [Symbol] :docx, :xlsx, :pptx, or :unknown
+    # @return [Symbol] :docx, :xlsx, :pptx, :vsdx, or :unknown
     def detect_office_format_from_zip(bytes)
       # This is a simplified detection - in practice you'd parse the ZIP
       # For the test, we'll check for known patterns in the ZIP structure
@@ -166,12 +336,28 @@ module ParseKit
         :xlsx
       elsif content.include?('ppt/') || content.include?('ppt/_rels')
         :pptx
+      elsif content.include?('visio/')
+        :vsdx
       else
         # Default to xlsx for generic ZIP
         :xlsx
       end
     end
     
+    # Check for an MPEG audio frame header with no reserved version, layer,
+    # bitrate or sample rate; FF FE is the UTF-16LE byte-order mark
+    # @param bytes [Array<Integer>] File bytes
+    # @return [Boolean] True if the bytes start an MPEG audio frame
+    def mpeg_audio_frame?(bytes)
+      return false if bytes.size < 3 || bytes[0] != 0xFF || (bytes[1] & 0xE0) != 0xE0 || bytes[1] == 0xFE
+
+      version = (bytes[1] >> 3) & 0x03
+      layer = (bytes[1] >> 1) & 0x03
+      bitrate = bytes[2] >> 4
+      sample_rate = (bytes[2] >> 2) & 0x03
+      version != 0b01 && layer != 0b00 && ![0b0000, 0b1111].include?(bitrate) && sample_rate != 0b11
+    end
+    
     # Parse file using format-specific parser
     # This method delegates to parse_file which uses centralized dispatch in Rust
     # @param path [String] File path
@@ -0,0 +1,72 @@
+# frozen_string_literal: true
+
+require "fileutils"
+require "net/http"
+require "uri"
+
+module ParseKit
+  # Fetches Tesseract language models (+.traineddata+ files) into a local
+  # cache, so OCR works in containers without a system tessdata package.
+  # The parser searches the cache on its own; the directory can also be
+  # passed as +tessdata_path+.
+  module Tessdata
+    # Where models are downloaded from (the fast integer LSTM models)
+    REPOSITORY = "https://github.com/tesseract-ocr/tessdata_fast/raw/main"
+
+    class << self
+      # The cache directory: $PARSEKIT_TESSDATA_CACHE, or parsekit/tessdata
+      # under $XDG_CACHE_HOME (default ~/.cache)
+      # @return [String]
+      def cache_dir
+        ENV.fetch("PARSEKIT_TESSDATA_CACHE") do
+          base = ENV["XDG_CACHE_HOME"].to_s
+          base = File.join(Dir.home, ".cache") if base.empty?
+          File.join(base, "parsekit", "tessdata")
+        end
+      end
+
+      # Download the models for the given languages unless +dir+ already has them
+      # @param languages [String, Array<String>] e.g. "deu", "eng+deu" or %w[eng deu]
+      # @param dir [String] Directory to store the models in
+      # @param repository [String] Base URL the models are fetched from
+      # @return [String] The directory, ready to pass as +tessdata_path+
+      def download(languages, dir: cache_dir, repository: REPOSITORY)
+        Array(languages).flat_map { |language| language.to_s.split("+") }.uniq.each do |language|
+          unless language.match?(%r{\A\w+(/\w+)?\z})
+            raise ArgumentError, "Invalid Tesseract language: #{language.inspect}"
+          end
+
+          path = File.join(dir, "#{language}.traineddata")
+          next if File.exist?(path)
+
+          FileUtils.mkdir_p(File.dirname(path))
+          fetch(URI("#{repository}/#{language}.traineddata"), path)
+        end
+        dir
+      end
+
+      private
+
+      # Save +uri+ to +path+, following redirects. The body goes to a
+      # temporary file first so an interrupted download isn't taken for a model.
+      def fetch(uri, path, redirects = 5)
+        Net::HTTP.start(uri.host, uri.port, use_ssl: uri.scheme == "https") do |http|
+          http.request(Net::HTTP::Get.new(uri)) do |response|
+            case response
+            when Net::HTTPRedirection
+              raise IOError, "Too many redirects downloading #{uri}" if redirects.zero?
+
+              return fetch(URI.join(uri, response["location"]), path, redirects - 1)
+            when Net::HTTPSuccess
+              partial = "#{path}.part"
+              File.open(partial, "wb") { |file| response.read_body { |chunk| file.write(chunk) } }
+              File.rename(partial, path)
+            else
+              raise IOError, "Failed to download #{uri}: HTTP #{response.code}"
+            end
+          end
+        end
+      end
+    end
+  end
+end
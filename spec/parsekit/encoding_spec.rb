@@ -95,6 +95,17 @@ RSpec.describe "ParseKit Encoding Support" do
         expect(result).to be_a(String)
         expect(result).not_to be_empty
       end
+
+      it "reads a UTF-16LE text file with a byte-order mark as text, not audio" do
+        require "tempfile"
+        Tempfile.create(['bom', '.txt'], binmode: true) do |file|
+          file.write("\xFF\xFE".b + "Hello UTF-16 with a BOM".encode("UTF-16LE").b)
+          file.close
+
+          result = parser.parse_file(file.path)
+          expect(result).to match(/H.*e.*l.*l.*o/)
+        end
+      end
     end
   end
 
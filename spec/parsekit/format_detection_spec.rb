@@ -12,11 +12,26 @@ RSpec.describe "Format Detection" do
       expect(ParseKit.detect_format("FILE.PdF")).to eq(:pdf)
     end
 
+    it "detects DjVu files by extension" do
+      expect(ParseKit.detect_format("scan.djvu")).to eq(:djvu)
+      expect(ParseKit.detect_format("scan.djv")).to eq(:djvu)
+    end
+
+    it "detects PostScript files by extension" do
+      expect(ParseKit.detect_format("print.ps")).to eq(:postscript)
+      expect(ParseKit.detect_format("figure.EPS")).to eq(:postscript)
+    end
+
     it "detects Word documents by extension" do
       expect(ParseKit.detect_format("document.docx")).to eq(:docx)
       expect(ParseKit.detect_format("Document.DOCX")).to eq(:docx)
     end
 
+    it "detects WordPerfect documents by extension" do
+      expect(ParseKit.detect_format("brief.wpd")).to eq(:wordperfect)
+      expect(ParseKit.detect_format("BRIEF.WP5")).to eq(:wordperfect)
+    end
+
     it "detects Excel files by extension" do
       expect(ParseKit.detect_format("data.xlsx")).to eq(:xlsx)
       expect(ParseKit.detect_format("data.XLSX")).to eq(:xlsx)
@@ -29,6 +44,15 @@ RSpec.describe "Format Detection" do
       expect(ParseKit.detect_format("Presentation.PPTX")).to eq(:pptx)
     end
 
+    it "detects Visio diagrams by extension" do
+      expect(ParseKit.detect_format("network.vsdx")).to eq(:vsdx)
+      expect(ParseKit.detect_format("Network.VSDM")).to eq(:vsdx)
+    end
+
+    it "detects OneNote sections by extension" do
+      expect(ParseKit.detect_format("Meeting Notes.one")).to eq(:onenote)
+    end
+
     it "detects image files by extension" do
       expect(ParseKit.detect_format("image.png")).to eq(:png)
       expect(ParseKit.detect_format("image.PNG")).to eq(:png)
@@ -38,6 +62,43 @@ RSpec.describe "Format Detection" do
       expect(ParseKit.detect_format("scan.tiff")).to eq(:tiff)
       expect(ParseKit.detect_format("scan.tif")).to eq(:tiff)
       expect(ParseKit.detect_format("picture.bmp")).to eq(:bmp)
+      expect(ParseKit.detect_format("scan.gif")).to eq(:gif)
+      expect(ParseKit.detect_format("photo.avif")).to eq(:avif)
+    end
+
+    it "detects DICOM files by extension" do
+      expect(ParseKit.detect_format("IM00001.dcm")).to eq(:dicom)
+      expect(ParseKit.detect_format("scan.DICOM")).to eq(:dicom)
+    end
+
+    it "detects audio files by extension" do
+      expect(ParseKit.detect_format("interview.wav")).to eq(:audio)
+      expect(ParseKit.detect_format("podcast.mp3")).to eq(:audio)
+      expect(ParseKit.detect_format("memo.m4a")).to eq(:audio)
+      expect(ParseKit.detect_format("clip.ogg")).to eq(:audio)
+    end
+
+    it "detects video files by extension" do
+      expect(ParseKit.detect_format("meeting.mp4")).to eq(:video)
+      expect(ParseKit.detect_format("Recording.MOV")).to eq(:video)
+      expect(ParseKit.detect_format("talk.mkv")).to eq(:video)
+      expect(ParseKit.detect_format("clip.webm")).to eq(:video)
+    end
+
+    it "detects Parquet files by extension" do
+      expect(ParseKit.detect_format("events.parquet")).to eq(:parquet)
+      expect(ParseKit.detect_format("Events.PARQUET")).to eq(:parquet)
+    end
+
+    it "detects Arrow IPC files by extension" do
+      expect(ParseKit.detect_format("table.arrow")).to eq(:arrow)
+      expect(ParseKit.detect_format("table.feather")).to eq(:arrow)
+      expect(ParseKit.detect_format("batches.arrows")).to eq(:arrow)
+    end
+
+    it "detects dBase tables by extension" do
+      expect(ParseKit.detect_format("parcels.dbf")).to eq(:dbf)
+      expect(ParseKit.detect_format("PARCELS.DBF")).to eq(:dbf)
     end
 
     it "detects text files by extension" do
@@ -51,12 +112,79 @@ RSpec.describe "Format Detection" do
       expect(ParseKit.detect_format("Config.JSON")).to eq(:json)
     end
 
+    it "detects MessagePack files by extension" do
+      expect(ParseKit.detect_format("payload.msgpack")).to eq(:msgpack)
+      expect(ParseKit.detect_format("payload.mpk")).to eq(:msgpack)
+    end
+
+    it "detects CBOR files by extension" do
+      expect(ParseKit.detect_format("reading.cbor")).to eq(:cbor)
+    end
+
+    it "detects BSON files by extension" do
+      expect(ParseKit.detect_format("users.bson")).to eq(:bson)
+    end
+
     it "detects XML/HTML files by extension" do
       expect(ParseKit.detect_format("data.xml")).to eq(:xml)
       expect(ParseKit.detect_format("page.html")).to eq(:xml)
       expect(ParseKit.detect_format("Page.HTML")).to eq(:xml)
     end
 
+    it "detects XBRL instances by extension" do
+      expect(ParseKit.detect_format("10-K.xbrl")).to eq(:xbrl)
+    end
+
+    it "detects Flat ODF documents by extension" do
+      expect(ParseKit.detect_format("report.fodt")).to eq(:flat_odf)
+      expect(ParseKit.detect_format("budget.fods")).to eq(:flat_odf)
+      expect(ParseKit.detect_format("deck.fodp")).to eq(:flat_odf)
+    end
+
+    it "detects MHTML web archives by extension" do
+      expect(ParseKit.detect_format("saved_page.mht")).to eq(:mhtml)
+      expect(ParseKit.detect_format("saved_page.MHTML")).to eq(:mhtml)
+    end
+
+    it "detects e-mail messages by extension" do
+      expect(ParseKit.detect_format("forwarded.eml")).to eq(:email)
+    end
+
+    it "detects CHM help files by extension" do
+      expect(ParseKit.detect_format("manual.chm")).to eq(:chm)
+    end
+
+    it "detects Kindle ebooks by extension" do
+      expect(ParseKit.detect_format("novel.mobi")).to eq(:mobi)
+      expect(ParseKit.detect_format("novel.azw3")).to eq(:mobi)
+    end
+
+    it "detects subtitle files by extension" do
+      expect(ParseKit.detect_format("episode.srt")).to eq(:subtitles)
+      expect(ParseKit.detect_format("episode.vtt")).to eq(:subtitles)
+      expect(ParseKit.detect_format("episode.ass")).to eq(:subtitles)
+      expect(ParseKit.detect_format("episode.ssa")).to eq(:subtitles)
+    end
+
+    it "detects log files by extension" do
+      expect(ParseKit.detect_format("access.log")).to eq(:log)
+    end
+
+    it "detects archives and compressed files by extension" do
+      expect(ParseKit.detect_format("logs.tar")).to eq(:tar)
+      expect(ParseKit.detect_format("backup.tar.gz")).to eq(:gzip)
+      expect(ParseKit.detect_format("backup.tgz")).to eq(:gzip)
+      expect(ParseKit.detect_format("dump.sql.bz2")).to eq(:bzip2)
+      expect(ParseKit.detect_format("release.tar.xz")).to eq(:xz)
+      expect(ParseKit.detect_format("delivery.7z")).to eq(:sevenzip)
+      expect(ParseKit.detect_format("delivery.rar")).to eq(:rar)
+    end
+
+    it "detects Outlook mail stores by extension" do
+      expect(ParseKit.detect_format("archive.pst")).to eq(:pst)
+      expect(ParseKit.detect_format("mailbox.ost")).to eq(:pst)
+    end
+
     it "returns :unknown for unsupported extensions" do
       expect(ParseKit.detect_format("file.xyz")).to eq(:unknown)
       expect(ParseKit.detect_format("file")).to eq(:unknown)
@@ -98,6 +226,12 @@ RSpec.describe "Format Detection" do
       end
     end
 
+    it "detects VSDX by magic bytes (ZIP with visio/ parts)" do
+      vsdx_bytes = [0x50, 0x4B, 0x03, 0x04] + [0] * 26 + "visio/document.xml".bytes
+      format = parser.detect_format_from_bytes(vsdx_bytes)
+      expect(format).to eq(:vsdx)
+    end
+
     it "detects XLS by magic bytes (OLE compound document)" do
       xls_bytes = [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1] + [0] * 100
       format = parser.detect_format_from_bytes(xls_bytes)
@@ -133,12 +267,46 @@ RSpec.describe "Format Detection" do
       expect(format).to eq(:tiff)
     end
 
+    it "detects GIF by magic bytes" do
+      expect(parser.detect_format_from_bytes("GIF87a".bytes + [0] * 10)).to eq(:gif)
+      expect(parser.detect_format_from_bytes("GIF89a".bytes + [0] * 10)).to eq(:gif)
+    end
+
+    it "detects AVIF by its ftyp brand" do
+      avif_bytes = [0x00, 0x00, 0x00, 0x1C] + "ftypavif".bytes + [0] * 10
+      format = parser.detect_format_from_bytes(avif_bytes)
+      expect(format).to eq(:avif)
+    end
+
     it "detects BMP by magic bytes" do
       bmp_bytes = [0x42, 0x4D] + [0] * 10
       format = parser.detect_format_from_bytes(bmp_bytes)
       expect(format).to eq(:bmp)
     end
 
+    it "detects PostScript by magic bytes" do
+      expect(parser.detect_format_from_bytes("%!PS-Adobe-3.0\n".bytes)).to eq(:postscript)
+      expect(parser.detect_format_from_bytes([0xC5, 0xD0, 0xD3, 0xC6] + [0] * 10)).to eq(:postscript)
+    end
+
+    it "detects Parquet by magic bytes" do
+      parquet_bytes = "PAR1".bytes + [0] * 10
+      format = parser.detect_format_from_bytes(parquet_bytes)
+      expect(format).to eq(:parquet)
+    end
+
+    it "detects Arrow IPC files by magic bytes" do
+      arrow_bytes = "ARROW1".bytes + [0] * 10
+      format = parser.detect_format_from_bytes(arrow_bytes)
+      expect(format).to eq(:arrow)
+    end
+
+    it "detects self-described CBOR by magic bytes" do
+      cbor_bytes = [0xD9, 0xD9, 0xF7, 0xA0]
+      format = parser.detect_format_from_bytes(cbor_bytes)
+      expect(format).to eq(:cbor)
+    end
+
     it "detects JSON by content pattern" do
       json_bytes = '{"key": "value"}'.bytes
       format = parser.detect_format_from_bytes(json_bytes)
@@ -159,6 +327,65 @@ RSpec.describe "Format Detection" do
       expect(format).to eq(:xml)
     end
 
+    it "detects DICOM by preamble and DICM prefix" do
+      dicom_bytes = [0] * 128 + "DICM".bytes + [0x02, 0x00, 0x00, 0x00]
+      expect(parser.detect_format_from_bytes(dicom_bytes)).to eq(:dicom)
+    end
+
+    it "detects audio by magic bytes" do
+      expect(parser.detect_format_from_bytes("RIFF\x24\x08\x00\x00WAVEfmt ".bytes)).to eq(:audio)
+      expect(parser.detect_format_from_bytes("OggS\x00\x02".bytes)).to eq(:audio)
+      expect(parser.detect_format_from_bytes("ID3\x04\x00".bytes)).to eq(:audio)
+      expect(parser.detect_format_from_bytes([0x00, 0x00, 0x00, 0x20] + "ftypM4A ".bytes)).to eq(:audio)
+      expect(parser.detect_format_from_bytes([0xFF, 0xFB, 0x90, 0x64])).to eq(:audio)
+    end
+
+    it "does not take a UTF-16LE byte-order mark or a reserved MPEG header for audio" do
+      expect(parser.detect_format_from_bytes([0xFF, 0xFE] + "Hi".encode("UTF-16LE").bytes)).to eq(:text)
+      expect(parser.detect_format_from_bytes([0xFF, 0xFB, 0xF0, 0x64])).to eq(:text)
+    end
+
+    it "detects video containers by magic bytes" do
+      expect(parser.detect_format_from_bytes([0x00, 0x00, 0x00, 0x18] + "ftypisom".bytes)).to eq(:video)
+      expect(parser.detect_format_from_bytes([0x1A, 0x45, 0xDF, 0xA3, 0x9F, 0x42, 0x86])).to eq(:video)
+    end
+
+    it "detects archives and compressed streams by magic bytes" do
+      tar_bytes = [0] * 257 + "ustar\0".bytes + [0] * 249
+      expect(parser.detect_format_from_bytes(tar_bytes)).to eq(:tar)
+      expect(parser.detect_format_from_bytes([0x1F, 0x8B, 0x08, 0x00])).to eq(:gzip)
+      expect(parser.detect_format_from_bytes("BZh91AY&SY".bytes)).to eq(:bzip2)
+      expect(parser.detect_format_from_bytes([0xFD, 0x37, 0x7A, 0x58, 0x5A, 0x00, 0x00])).to eq(:xz)
+      expect(parser.detect_format_from_bytes([0x37, 0x7A, 0xBC, 0xAF, 0x27, 0x1C, 0x00, 0x04])).to eq(:sevenzip)
+      expect(parser.detect_format_from_bytes("Rar!\x1A\x07\x01\x00".bytes)).to eq(:rar)
+    end
+
+    it "detects Outlook mail stores by magic bytes" do
+      expect(parser.detect_format_from_bytes("!BDN\x00\x00\x00\x00SM\x17\x00".bytes)).to eq(:pst)
+    end
+
+    it "detects dBase tables by header" do
+      dbf_bytes = [0x03, 124, 5, 17, 2, 0, 0, 0, 65, 0, 11, 0] + [0] * 20 +
+                  "NAME".bytes + [0] * 7 + [0x43, 0, 0, 0, 0, 10] + [0] * 15 + [0x0D]
+      expect(parser.detect_format_from_bytes(dbf_bytes)).to eq(:dbf)
+    end
+
+    it "detects subtitles by content" do
+      expect(parser.detect_format_from_bytes("WEBVTT\n\n00:01.000 --> 00:02.000\nHi")).to eq(:subtitles)
+      expect(parser.detect_format_from_bytes("1\n00:00:01,000 --> 00:00:02,000\nHi")).to eq(:subtitles)
+      expect(parser.detect_format_from_bytes("[Script Info]\nTitle: Test")).to eq(:subtitles)
+    end
+
+    it "detects XBRL instances by root element" do
+      xbrl_bytes = '<?xml version="1.0"?><xbrli:xbrl xmlns:xbrli="http://www.xbrl.org/2003/instance"/>'.bytes
+      expect(parser.detect_format_from_bytes(xbrl_bytes)).to eq(:xbrl)
+    end
+
+    it "detects Flat ODF documents by root element" do
+      fodt_bytes = '<?xml version="1.0"?><office:document xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0"/>'.bytes
+      expect(parser.detect_format_from_bytes(fodt_bytes)).to eq(:flat_odf)
+    end
+
     it "returns :text for plain text without specific patterns" do
       text_bytes = "Hello, World!\nThis is plain text.".bytes
       format = parser.detect_format_from_bytes(text_bytes)
@@ -323,7 +550,16 @@ RSpec.describe "Format Detection" do
 
     it "all parse methods have corresponding format entries" do
       # List of known parse methods in the Rust implementation
-      parse_methods = [:pdf, :docx, :xlsx, :xls, :pptx, :png, :jpeg, :tiff, :bmp, :json, :xml, :text]
+      parse_methods = [
+        :pdf, :djvu, :postscript,
+        :docx, :wordperfect, :xlsx, :xls, :pptx, :vsdx, :onenote,
+        :parquet, :arrow, :dbf,
+        :png, :jpeg, :tiff, :bmp, :gif, :avif, :dicom, :audio, :video,
+        :json, :msgpack, :cbor, :bson,
+        :xml, :xbrl, :flat_odf, :mhtml, :email, :chm, :mobi, :subtitles, :log,
+        :tar, :gzip, :bzip2, :xz, :sevenzip, :rar, :pst,
+        :text
+      ]
       
       parse_methods.each do |format|
         # Check if format has file extensions defined
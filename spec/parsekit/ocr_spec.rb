@@ -95,6 +95,117 @@ RSpec.describe "OCR with Tesseract" do
       end
     end
 
+    context "with ocr_language" do
+      it "joins an Array of languages with +" do
+        parser = ParseKit::Parser.new(ocr_language: %w[eng deu fra])
+        expect(parser.config[:ocr_language]).to eq("eng+deu+fra")
+        expect(ParseKit::Parser.new.config[:ocr_language]).to eq("eng")
+      end
+
+      it "recognizes text with the languages given" do
+        parser = ParseKit::Parser.new(ocr_language: ["eng"])
+        png_data = File.read("spec/fixtures/ocr_test.png", mode: 'rb')
+        expect(parser.ocr_image(png_data.bytes)).to include("OCR TEST IMAGE")
+      end
+
+      it "rejects invalid language codes" do
+        expect { ParseKit::Parser.new(ocr_language: []) }.to raise_error(ArgumentError, /ocr_language/)
+        expect { ParseKit::Parser.new(ocr_language: ["eng", "../x"]) }.to raise_error(ArgumentError, /ocr_language/)
+      end
+
+      it "names the languages when their data is missing" do
+        parser = ParseKit::Parser.new(ocr_language: "zzz")
+        png_data = File.read("spec/fixtures/ocr_test.png", mode: 'rb')
+        expect { parser.ocr_image(png_data.bytes) }.to raise_error(RuntimeError, /Failed to initialize Tesseract for 'zzz'/)
+      end
+    end
+
+    context "with ocr_psm and ocr_oem" do
+      it "passes the modes through to Tesseract" do
+        # PSM 6 reads the image as a single uniform block of text
+        parser = ParseKit::Parser.new(ocr_psm: 6, ocr_oem: 1)
+        expect(parser.config).to include(ocr_psm: 6, ocr_oem: 1)
+        png_data = File.read("spec/fixtures/ocr_test.png", mode: 'rb')
+        expect(parser.ocr_image(png_data.bytes)).to include("OCR TEST IMAGE")
+      end
+
+      it "reads a single line with PSM 7" do
+        parser = ParseKit::Parser.new(ocr_psm: 7)
+        png_data = File.read("spec/fixtures/static_test.png", mode: 'rb')
+        expect(parser.ocr_image(png_data.bytes)).to include("Static OK")
+      end
+
+      it "rejects modes Tesseract doesn't have" do
+        expect { ParseKit::Parser.new(ocr_psm: 14) }.to raise_error(ArgumentError, /ocr_psm/)
+        expect { ParseKit::Parser.new(ocr_oem: 4) }.to raise_error(ArgumentError, /ocr_oem/)
+      end
+    end
+
+    context "with min_confidence" do
+      let(:png_data) { File.read("spec/fixtures/ocr_test.png", mode: 'rb').bytes }
+
+      it "keeps confident words" do
+        parser = ParseKit::Parser.new(min_confidence: 50)
+        expect(parser.ocr_image(png_data)).to include("OCR TEST IMAGE")
+      end
+
+      it "drops every word below an impossible threshold" do
+        parser = ParseKit::Parser.new(min_confidence: 100)
+        expect(parser.ocr_image(png_data)).to eq("")
+        expect(parser.ocr_image_result(png_data)[:needs_review]).to be true
+      end
+
+      it "rejects thresholds outside 0-100" do
+        expect { ParseKit::Parser.new(min_confidence: 101) }.to raise_error(ArgumentError, /min_confidence/)
+      end
+    end
+
+    context "with ocr_output" do
+      let(:png_data) { File.read("spec/fixtures/ocr_test.png", mode: 'rb').bytes }
+
+      it "emits an hOCR document" do
+        result = ParseKit::Parser.new(ocr_output: :hocr).ocr_image(png_data)
+        expect(result).to start_with("<?xml")
+        expect(result).to include("ocr_page", "ocrx_word", "TEST")
+      end
+
+      it "emits TSV with a header row" do
+        result = ParseKit::Parser.new(ocr_output: :tsv).ocr_image(png_data)
+        rows = result.lines.map { |line| line.chomp.split("\t") }
+        expect(rows.first).to eq(%w[level page_num block_num par_num line_num word_num left top width height conf text])
+        expect(rows.map(&:last)).to include("TEST")
+      end
+
+      it "emits an ALTO document" do
+        result = ParseKit::Parser.new(ocr_output: :alto).ocr_image(png_data)
+        expect(result).to include("<alto", "<Layout>", "CONTENT=\"TEST\"")
+      end
+
+      it "rejects unknown formats" do
+        expect { ParseKit::Parser.new(ocr_output: :pdf) }.to raise_error(ArgumentError, /Unsupported OCR output/)
+      end
+    end
+
+    context "with ocr_preprocess" do
+      let(:png_data) { File.read("spec/fixtures/ocr_test.png", mode: 'rb').bytes }
+
+      it "runs the default pipeline with true" do
+        parser = ParseKit::Parser.new(ocr_preprocess: true)
+        expect(parser.config[:ocr_preprocess]).to eq(%i[grayscale contrast despeckle deskew binarize])
+        expect(parser.ocr_image(png_data)).to include("OCR TEST IMAGE")
+      end
+
+      it "runs the stages given, in order" do
+        parser = ParseKit::Parser.new(ocr_preprocess: %i[contrast binarize])
+        expect(parser.config[:ocr_preprocess]).to eq(%i[contrast binarize])
+        expect(parser.ocr_image(png_data)).to include("OCR TEST IMAGE")
+      end
+
+      it "rejects unknown stages" do
+        expect { ParseKit::Parser.new(ocr_preprocess: [:sharpen]) }.to raise_error(ArgumentError, /Unsupported OCR preprocessing stage/)
+      end
+    end
+
     context "with invalid image data" do
       it "raises error for non-image data" do
         invalid_data = "This is not image data".bytes
@@ -129,6 +240,227 @@ RSpec.describe "OCR with Tesseract" do
     end
   end
 
+  describe "#ocr_image_result" do
+    it "returns the text with overall and per-word confidence" do
+      png_data = File.read("spec/fixtures/ocr_test.png", mode: 'rb').bytes
+      result = parser.ocr_image_result(png_data)
+
+      expect(result[:text]).to include("OCR TEST IMAGE")
+      expect(result[:confidence]).to be_between(50, 100)
+      expect(result[:needs_review]).to be false
+      expect(result[:words].map { |word| word[:text] }).to include("OCR", "TEST", "IMAGE")
+      result[:words].each do |word|
+        expect(word[:confidence]).to be_between(0, 100)
+        expect(word[:bbox].size).to eq(4)
+      end
+    end
+  end
+
+  describe "#ocr_image_region" do
+    # ocr_test.png is 400x100 pixels
+    let(:png_data) { File.read("spec/fixtures/ocr_test.png", mode: 'rb').bytes }
+
+    it "recognizes text inside the region" do
+      expect(parser.ocr_image_region(png_data, x: 0, y: 0, w: 400, h: 100)).to include("OCR TEST IMAGE")
+    end
+
+    it "ignores text outside the region" do
+      expect(parser.ocr_image_region(png_data, x: 0, y: 0, w: 4, h: 4)).not_to include("TEST")
+    end
+
+    it "rejects regions outside the image" do
+      expect { parser.ocr_image_region(png_data, x: 300, y: 0, w: 200, h: 50) }
+        .to raise_error(ArgumentError, /outside the 400x100 image/)
+      expect { parser.ocr_image_region(png_data, x: 0, y: 0, w: 0, h: 50) }.to raise_error(ArgumentError)
+    end
+
+    it "requires every coordinate" do
+      expect { parser.ocr_image_region(png_data, x: 0, y: 0) }.to raise_error(ArgumentError)
+    end
+  end
+
+  describe "resolution handling" do
+    let(:bmp_data) { File.read("spec/fixtures/ocr_test.bmp", mode: 'rb').bytes }
+
+    it "enlarges images recorded below ocr_dpi and reports their resolution" do
+      # ocr_test.bmp records 96 DPI
+      result = parser.ocr_image_result(bmp_data)
+      expect(result[:text]).to include("BMP Format")
+      expect(result[:source_dpi]).to eq(96)
+      expect(result[:scale]).to be_within(0.01).of(300.0 / 96)
+      expect(result[:low_resolution]).to be true
+    end
+
+    it "leaves images at or above ocr_dpi alone" do
+      result = ParseKit::Parser.new(ocr_dpi: 96).ocr_image_result(bmp_data)
+      expect(result[:scale]).to eq(1.0)
+      expect(result[:low_resolution]).to be false
+    end
+
+    it "enlarges small images without a recorded resolution" do
+      # 400x100 pixels, no pHYs chunk
+      png_data = File.read("spec/fixtures/ocr_test.png", mode: 'rb').bytes
+      result = parser.ocr_image_result(png_data)
+      expect(result[:source_dpi]).to be_nil
+      expect(result[:scale]).to eq(2.5)
+      expect(result[:low_resolution]).to be true
+      result[:words].each do |word|
+        left, top, width, height = word[:bbox]
+        expect(left + width).to be <= 400
+        expect(top + height).to be <= 100
+      end
+    end
+
+    it "rejects implausible ocr_dpi values" do
+      expect { ParseKit::Parser.new(ocr_dpi: 10) }.to raise_error(ArgumentError, /ocr_dpi/)
+    end
+  end
+
+  describe "Tesseract instance reuse" do
+    let(:png_data) { File.read("spec/fixtures/ocr_test.png", mode: 'rb').bytes }
+
+    it "gives the same text on repeated calls" do
+      first = parser.ocr_image(png_data)
+      3.times { expect(parser.ocr_image(png_data)).to eq(first) }
+    end
+
+    it "keeps instances with different settings apart" do
+      default_text = parser.ocr_image(png_data)
+      ParseKit::Parser.new(ocr_psm: 7).ocr_image(png_data)
+      expect { ParseKit::Parser.new(ocr_language: "zzz").ocr_image(png_data) }.to raise_error(RuntimeError, /zzz/)
+      expect(parser.ocr_image(png_data)).to eq(default_text)
+    end
+  end
+
+  describe "#ocr_to_pdf" do
+    let(:png_data) { File.read("spec/fixtures/ocr_test.png", mode: 'rb').bytes }
+
+    it "puts the recognized words in an invisible text layer over the image" do
+      pdf = parser.ocr_to_pdf(png_data)
+      expect(pdf).to start_with("%PDF-")
+      expect(parser.parse_pdf(pdf.bytes)).to include("OCR TEST IMAGE")
+    end
+
+    it "sizes the page from ocr_dpi when the image records no resolution" do
+      # 400x100 pixels at 300 DPI is 96x24 points
+      pages = parser.parse_pdf_pages(parser.ocr_to_pdf(png_data).bytes)
+      expect(pages.size).to eq(1)
+      expect(pages.first[:width]).to be_within(0.5).of(96)
+      expect(pages.first[:height]).to be_within(0.5).of(24)
+    end
+
+    it "makes a scanned PDF searchable page by page" do
+      scanned = parser.ocr_to_pdf(png_data)
+      pages = parser.parse_pdf_pages(parser.ocr_to_pdf(scanned.bytes).bytes)
+      expect(pages.size).to eq(1)
+      expect(pages.first[:text]).to include("OCR TEST IMAGE")
+    end
+  end
+
+  describe "multi-page input" do
+    # multipage.tiff holds ocr_test.png and multiline.png as two pages
+    let(:tiff_data) { File.read("spec/fixtures/multipage.tiff", mode: 'rb').bytes }
+
+    it "recognizes every page of a TIFF, in order" do
+      result = parser.ocr_image(tiff_data)
+      expect(result).to include("OCR TEST IMAGE", "Line One", "Line Three")
+      expect(result.index("OCR TEST IMAGE")).to be < result.index("Line One")
+    end
+
+    it "gives the same text with one thread or several" do
+      sequential = ParseKit::Parser.new(ocr_threads: 1).ocr_image(tiff_data)
+      expect(ParseKit::Parser.new(ocr_threads: 4).ocr_image(tiff_data)).to eq(sequential)
+    end
+
+    it "makes a page of searchable PDF per TIFF page" do
+      pages = parser.parse_pdf_pages(parser.ocr_to_pdf(tiff_data).bytes)
+      expect(pages.size).to eq(2)
+      expect(pages[0][:text]).to include("OCR TEST IMAGE")
+      expect(pages[1][:text]).to include("Line Two")
+    end
+
+    it "reports and validates ocr_threads" do
+      expect(parser.config[:ocr_threads]).to be >= 1
+      expect(ParseKit::Parser.new(ocr_threads: 2).config[:ocr_threads]).to eq(2)
+      expect { ParseKit::Parser.new(ocr_threads: 0) }.to raise_error(ArgumentError, /ocr_threads/)
+    end
+  end
+
+  describe "tessdata_path" do
+    let(:png_data) { File.read("spec/fixtures/ocr_test.png", mode: 'rb').bytes }
+
+    it "only loads models from the given directory" do
+      Dir.mktmpdir do |dir|
+        isolated = ParseKit::Parser.new(tessdata_path: dir)
+        expect(isolated.config[:tessdata_path]).to eq(dir)
+        expect { isolated.ocr_image(png_data) }
+          .to raise_error(RuntimeError, /Failed to initialize Tesseract for 'eng' from #{Regexp.escape(dir)}/)
+      end
+    end
+
+    it "rejects a path that isn't a directory" do
+      expect { ParseKit::Parser.new(tessdata_path: "/nonexistent/tessdata") }
+        .to raise_error(ArgumentError, /tessdata_path is not a directory/)
+    end
+  end
+
+  describe "ocr_backend" do
+    let(:png_data) { File.read("spec/fixtures/ocr_test.png", mode: 'rb').bytes }
+
+    it "uses Tesseract by default" do
+      expect(parser.config[:ocr_backend]).to eq(:tesseract)
+    end
+
+    it "hands images to a callable backend" do
+      received = nil
+      backend = ->(image) { received = image; "  remote text\n" }
+      callback_parser = ParseKit::Parser.new(ocr_backend: backend)
+      expect(callback_parser.ocr_image(png_data)).to eq("remote text")
+      expect(received.bytes).to eq(png_data)
+      expect(callback_parser.config[:ocr_backend]).to be(backend)
+    end
+
+    it "hands only the region to a callable backend" do
+      received = nil
+      callback_parser = ParseKit::Parser.new(ocr_backend: ->(image) { received = image; "" })
+      callback_parser.ocr_image_region(png_data, x: 0, y: 0, w: 40, h: 20)
+      expect(received.byteslice(0, 8).bytes).to eq([0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A])
+      expect(received.byteslice(16, 8).unpack("NN")).to eq([40, 20])
+    end
+
+    it "needs Tesseract for word confidences" do
+      callback_parser = ParseKit::Parser.new(ocr_backend: ->(_image) { "text" })
+      expect { callback_parser.ocr_image_result(png_data) }.to raise_error(RuntimeError, /ocr_backend/)
+    end
+
+    it "rejects unknown backends" do
+      expect { ParseKit::Parser.new(ocr_backend: :onnx) }.to raise_error(ArgumentError, /Unsupported OCR backend/)
+      expect { ParseKit::Parser.new(ocr_backend: 42) }.to raise_error(ArgumentError, /ocr_backend/)
+    end
+  end
+
+  describe "embedded images" do
+    # screenshot.pptx has a picture of ocr_test.png on its first slide
+    let(:pptx_data) { File.read("spec/fixtures/screenshot.pptx", mode: 'rb').bytes }
+
+    it "appends the text of pictures to their slide with ocr_embedded_images" do
+      result = ParseKit::Parser.new(ocr_embedded_images: true).parse_pptx(pptx_data)
+      first_slide = result.split("\n\n").first
+      expect(first_slide).to include("Hello")
+      expect(first_slide).to match(/\[Image: .*OCR TEST IMAGE.*\]/)
+    end
+
+    it "leaves pictures alone by default" do
+      expect(parser.parse_pptx(pptx_data)).not_to include("[Image:")
+      expect(parser.config[:ocr_embedded_images]).to be false
+    end
+
+    it "recognizes DOCX pictures listed with docx_images(ocr: true)" do
+      images = parser.docx_images(File.binread("spec/fixtures/figure.docx").bytes, ocr: true)
+      expect(images.first[:text]).to include("OCR TEST IMAGE")
+    end
+  end
+
   describe "#parse_file" do
     context "with images" do
       it "automatically detects and processes PNG files" do
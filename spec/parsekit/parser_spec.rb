@@ -1,5 +1,8 @@
 # frozen_string_literal: true
 
+require "json"
+require "zlib"
+
 RSpec.describe ParseKit::Parser do
   describe "#initialize" do
     it "creates a parser with default options" do
@@ -71,6 +74,32 @@ let(:test_file) { File.join(temp_dir, "parser_test.txt") }
     it "raises error for non-existent file" do
       expect { parser.parse_file("missing.txt") }.to raise_error(IOError)
     end
+
+    context "with an XML file" do
+      let(:xml_file) { File.join(temp_dir, "export.xml") }
+      let(:xml) do
+        +'<?xml version="1.0"?><export>' << (1..2000).map { |i| "<row id=\"#{i}\">Item #{i} &amp; co</row>" }.join << "</export>"
+      end
+
+      before { File.write(xml_file, xml) }
+
+      it "streams it to the same text as parse_xml" do
+        text = parser.parse_file(xml_file)
+        expect(text).to eq(parser.parse_xml(xml.bytes))
+        expect(text).to start_with("Item 1 & co")
+        expect(text).to end_with("Item 2000 & co")
+      end
+
+      it "still applies max_size" do
+        expect { described_class.new(max_size: 1024).parse_file(xml_file) }.to raise_error(ParseKit::ResourceLimitError, /File size exceeds limit/)
+      end
+
+      it "still hands XBRL instances to parse_xbrl" do
+        xbrl_file = File.join(temp_dir, "filing.xml")
+        File.write(xbrl_file, '<?xml version="1.0"?><xbrli:xbrl xmlns:xbrli="http://www.xbrl.org/2003/instance"></xbrli:xbrl>')
+        expect(parser.parse_file(xbrl_file)).to eq(parser.parse_xbrl(File.binread(xbrl_file).bytes))
+      end
+    end
   end
 
   describe "#config" do
@@ -384,6 +413,195 @@ let(:test_file) { File.join(temp_dir, "test.xyz") }
       end
     end
 
+    describe "#parse_json with relaxed JSON" do
+      let(:config_json) { "{\n  // Server settings\n  host: \"localhost\", /* default */\n  ports: [80, 443,],\n}\n".bytes }
+
+      it "accepts comments, trailing commas and unquoted keys" do
+        expect(JSON.parse(parser.parse_json(config_json))).to eq("host" => "localhost", "ports" => [80, 443])
+        expect(parser.parse_json_object(config_json)).to eq("host" => "localhost", "ports" => [80, 443])
+      end
+
+      it "leaves comment markers inside strings alone" do
+        expect(parser.parse_json_object('{"url": "http://example.com/*x*/"}'.bytes)).to eq("url" => "http://example.com/*x*/")
+      end
+
+      it "accepts only standard JSON in strict mode" do
+        strict = ParseKit::Parser.strict
+        expect { strict.parse_json(config_json) }.to raise_error(RuntimeError, /Failed to parse JSON/)
+        expect { strict.parse_json_object(config_json) }.to raise_error(RuntimeError, /Failed to parse JSON/)
+        expect(JSON.parse(strict.parse_json('{"a": 1}'.bytes))).to eq("a" => 1)
+      end
+    end
+
+    describe "#parse_json in strict mode" do
+      let(:strict) { ParseKit::Parser.strict }
+
+      it "reports duplicate keys with their location" do
+        json = "{\n  \"id\": 1,\n  \"tags\": {\"a\": 1, \"a\": 2}\n}".bytes
+        expect { strict.parse_json(json) }.to raise_error(ParseKit::ParseError, /duplicate key `a` at line 3 column/)
+        expect { strict.parse_json_object(json) }.to raise_error(ParseKit::ParseError, /duplicate key `a`/)
+        expect(parser.parse_json_object(json)["tags"]).to eq("a" => 2)
+      end
+
+      it "allows the same key in different objects" do
+        expect(strict.parse_json_object('[{"a": 1}, {"a": 2, "b": {"a": 3}}]'.bytes)).to eq([{ "a" => 1 }, { "a" => 2, "b" => { "a" => 3 } }])
+      end
+
+      it "reports invalid UTF-8 with its location" do
+        json = "{\n  \"name\": \"caf".bytes + [0xE9] + "\"\n}".bytes
+        expect { strict.parse_json(json) }.to raise_error(ParseKit::ParseError, /invalid UTF-8 at line 2 column 15/)
+        expect(parser.parse_json(json)).to include("caf\uFFFD")
+      end
+
+      it "raises ParseKit::ParseError, a RuntimeError, for malformed JSON" do
+        expect(ParseKit::ParseError.ancestors).to include(RuntimeError)
+        expect { strict.parse_json("{nope".bytes) }.to raise_error(ParseKit::ParseError, /at line 1 column/)
+      end
+    end
+
+    describe "#parse_json number precision" do
+      let(:json) { '{"id": 123456789012345678901234567890, "amount": 0.10000000000000000001, "price": 19.90}'.bytes }
+
+      it "keeps numbers as written" do
+        result = parser.parse_json(json)
+        expect(result).to include('"id": 123456789012345678901234567890')
+        expect(result).to include('"amount": 0.10000000000000000001')
+        expect(result).to include('"price": 19.90')
+        expect(ParseKit::Parser.new(json_output: :flat).parse_json(json)).to include("amount: 0.10000000000000000001")
+      end
+
+      it "returns big integers and decimals exactly from parse_json_object" do
+        result = parser.parse_json_object(json)
+        expect(result["id"]).to eq(123456789012345678901234567890)
+        expect(result["price"]).to eq(19.9)
+        expect(result["amount"]).to eq(BigDecimal("0.10000000000000000001"))
+        expect(parser.parse_json_object("-9223372036854775809".bytes)).to eq(-9223372036854775809)
+      end
+    end
+
+    describe "#parse_json with json_output: :flat" do
+      let(:flat_parser) { ParseKit::Parser.new(json_output: :flat) }
+
+      it "emits a dotted.key: value line per scalar" do
+        json = '{"title": "Report", "author": {"name": "Ada", "active": true}, "tags": ["a", "b"], "items": [{"sku": "A-1", "qty": 2.5}]}'
+        expect(flat_parser.parse_json(json.bytes)).to eq(
+          "title: Report\nauthor.name: Ada\nauthor.active: true\ntags[0]: a\ntags[1]: b\nitems[0].sku: A-1\nitems[0].qty: 2.5"
+        )
+      end
+
+      it "skips nulls, empty strings and empty containers" do
+        expect(flat_parser.parse_json('{"a": null, "b": "", "c": [], "d": {}, "e": 0}'.bytes)).to eq("e: 0")
+      end
+
+      it "shows a top-level scalar alone" do
+        expect(flat_parser.parse_json('"just text"'.bytes)).to eq("just text")
+        expect(flat_parser.parse_json("[1, 2]".bytes)).to eq("[0]: 1\n[1]: 2")
+      end
+
+      it "is pretty by default and rejects unknown modes" do
+        expect(parser.config[:json_output]).to eq(:pretty)
+        expect { ParseKit::Parser.new(json_output: :yaml) }.to raise_error(ArgumentError, /json_output/)
+      end
+    end
+
+    describe "#parse_json_object" do
+      it "returns Ruby objects, as JSON.parse would" do
+        json = '{"name": "app", "ports": [80, 443], "ratio": 0.5, "debug": false, "owner": null, "big": 18446744073709551615}'
+        expect(parser.parse_json_object(json.bytes)).to eq(JSON.parse(json))
+      end
+
+      it "keeps object keys in document order" do
+        result = parser.parse_json_object('{"b": 1, "a": {"d": 2, "c": 3}}'.bytes)
+        expect(result.keys).to eq(["b", "a"])
+        expect(result["a"].keys).to eq(["d", "c"])
+      end
+
+      it "takes top-level scalars" do
+        expect(parser.parse_json_object("42".bytes)).to eq(42)
+        expect(parser.parse_json_object('"text"'.bytes)).to eq("text")
+      end
+
+      it "raises on invalid JSON" do
+        expect { parser.parse_json_object("{nope".bytes) }.to raise_error(RuntimeError, /Failed to parse JSON/)
+      end
+    end
+
+    describe "#parse_msgpack" do
+      it "renders MessagePack data as pretty JSON" do
+        msgpack_data = [0x82, 0xA3, 0x6B, 0x65, 0x79, 0xA5, 0x76, 0x61, 0x6C, 0x75, 0x65, 0xA1, 0x6E, 0x2A]  # {"key"=>"value", "n"=>42}
+        result = parser.parse_msgpack(msgpack_data)
+        expect(result).to eq(parser.parse_json('{"key": "value", "n": 42}'.bytes))
+      end
+
+      it "renders binary values as base64" do
+        msgpack_data = [0xC4, 0x03, 0x01, 0x02, 0x03]  # bin8 [1, 2, 3]
+        result = parser.parse_msgpack(msgpack_data)
+        expect(result).to include('"$binary": "AQID"')
+      end
+
+      it "raises an error for truncated data" do
+        expect { parser.parse_msgpack([0x92, 0x01]) }.to raise_error(RuntimeError, /MessagePack/)
+      end
+    end
+
+    describe "#parse_cbor" do
+      it "renders CBOR data as pretty JSON" do
+        cbor_data = [0xA2, 0x63, 0x6B, 0x65, 0x79, 0x65, 0x76, 0x61, 0x6C, 0x75, 0x65, 0x61, 0x6E, 0x18, 0x2A]  # {"key"=>"value", "n"=>42}
+        result = parser.parse_cbor(cbor_data)
+        expect(result).to eq(parser.parse_json('{"key": "value", "n": 42}'.bytes))
+      end
+
+      it "raises an error for truncated data" do
+        expect { parser.parse_cbor([0x82, 0x01]) }.to raise_error(RuntimeError, /CBOR/)
+      end
+    end
+
+    describe "#parse_bson" do
+      # {"_id" => ObjectId("5f1d7f6e8f1b2c3d4e5f6a7b"), "name" => "Ada"}
+      let(:bson_data) do
+        oid = [0x5F, 0x1D, 0x7F, 0x6E, 0x8F, 0x1B, 0x2C, 0x3D, 0x4E, 0x5F, 0x6A, 0x7B]
+        body = [0x07] + "_id".bytes + [0x00] + oid +
+               [0x02] + "name".bytes + [0x00] + [4, 0, 0, 0] + "Ada".bytes + [0x00] +
+               [0x00]
+        [body.size + 4].pack("l<").bytes + body
+      end
+
+      it "renders BSON documents as pretty JSON" do
+        result = parser.parse_bson(bson_data)
+        expect(result).to include('"name": "Ada"')
+        expect(result).to include('"$oid": "5f1d7f6e8f1b2c3d4e5f6a7b"')
+      end
+
+      it "renders concatenated documents as an array" do
+        result = parser.parse_bson(bson_data + bson_data)
+        expect(result).to start_with("[")
+      end
+
+      it "raises an error for truncated data" do
+        expect { parser.parse_bson(bson_data[0..-3]) }.to raise_error(RuntimeError, /BSON/)
+      end
+    end
+
+    describe "#parse_protobuf" do
+      # field 1 = 150, field 2 = "hi", field 3 = { field 1 = 1 }
+      let(:protobuf_data) { [0x08, 0x96, 0x01, 0x12, 0x02, 0x68, 0x69, 0x1A, 0x02, 0x08, 0x01] }
+
+      it "decodes raw fields by number without a descriptor" do
+        result = JSON.parse(parser.parse_protobuf(protobuf_data))
+        expect(result).to eq("1" => 150, "2" => "hi", "3" => { "1" => 1 })
+      end
+
+      it "requires message_type when a descriptor set is given" do
+        expect {
+          parser.parse_protobuf(protobuf_data, descriptor_set: [])
+        }.to raise_error(ArgumentError, /message_type/)
+      end
+
+      it "raises an error for malformed wire data" do
+        expect { parser.parse_protobuf([0x08]) }.to raise_error(RuntimeError, /protobuf/)
+      end
+    end
+
     describe "#parse_text" do
       it "parses text data" do
         text_data = "Hello, World!".bytes
@@ -398,18 +616,1012 @@ let(:test_file) { File.join(temp_dir, "test.xyz") }
         result = parser.parse_xml(xml_data)
         expect(result).to include("test")
       end
+
+      it "skips script, style and noscript content" do
+        xhtml = "<html><head><style>body { color: red }</style><script>var a = 1;</script></head>" \
+                "<body><p>Visible</p><noscript><p>Enable JavaScript</p></noscript></body></html>"
+        expect(parser.parse_xml(xhtml.bytes)).to eq("Visible")
+      end
+
+      it "decodes character, predefined and HTML entities" do
+        xml_data = '<?xml version="1.0"?><p>Tom &amp; Jerry &#169; 2024&nbsp;Ltd</p>'.bytes
+        expect(parser.parse_xml(xml_data)).to eq("Tom & Jerry \u00A9 2024\u00A0Ltd")
+      end
+
+      context "with entities declared in the DOCTYPE" do
+        let(:declared) do
+          '<?xml version="1.0"?><!DOCTYPE note [<!ENTITY company "Acme &amp; Co">' \
+            '<!ENTITY secret SYSTEM "file:///etc/passwd">]><note>&company; &secret;</note>'
+        end
+
+        it "leaves them unexpanded by default" do
+          expect(parser.parse_xml(declared.bytes)).to eq("&company; &secret;")
+        end
+
+        it "expands internal entities but never external ones with resolve_entities" do
+          resolving = described_class.new(resolve_entities: true)
+          expect(resolving.parse_xml(declared.bytes)).to eq("Acme & Co &secret;")
+        end
+
+        it "stops billion-laughs style expansion" do
+          levels = (1..7).map do |i|
+            %(<!ENTITY lol#{i} "#{"&lol#{i - 1};" * 10}">)
+          end
+          bomb = %(<?xml version="1.0"?><!DOCTYPE lolz [<!ENTITY lol0 "lol">#{levels.join}]><lolz>&lol7;</lolz>)
+          resolving = described_class.new(resolve_entities: true)
+          expect { resolving.parse_xml(bomb.bytes) }.to raise_error(RuntimeError, /entity expansion limit/)
+        end
+      end
+    end
+
+    describe "#parse_xml with attribute and element options" do
+      let(:feed) do
+        ('<feed xmlns="urn:atom" xmlns:m="urn:media"><title>News</title>' \
+          '<entry id="1"><title>First &amp; best</title><link href="http://a/1"/>' \
+          '<m:thumb url="t.jpg">Thumb</m:thumb></entry></feed>').bytes
+      end
+
+      it "leaves attributes out by default" do
+        expect(parser.parse_xml(feed)).to eq("News First & best Thumb")
+      end
+
+      it "adds attribute values with xml_attributes" do
+        expect(ParseKit::Parser.new(xml_attributes: true).parse_xml(feed)).to eq("News 1 First & best http://a/1 t.jpg Thumb")
+        expect(ParseKit::Parser.new(xml_attributes: ["href", "url"]).parse_xml(feed)).to eq("News First & best http://a/1 t.jpg Thumb")
+      end
+
+      it "keeps only text within the elements named in xml_elements" do
+        expect(ParseKit::Parser.new(xml_elements: ["entry"]).parse_xml(feed)).to eq("First & best Thumb")
+        expect(ParseKit::Parser.new(xml_elements: ["title"]).parse_xml(feed)).to eq("News First & best")
+        expect(ParseKit::Parser.new(xml_elements: ["link"], xml_attributes: ["href"]).parse_xml(feed)).to eq("http://a/1")
+      end
+
+      it "keeps only text within elements in xml_namespaces" do
+        parser = ParseKit::Parser.new(xml_namespaces: ["urn:media"], xml_attributes: true)
+        expect(parser.parse_xml(feed)).to eq("t.jpg Thumb")
+      end
+
+      it "echoes the options in config" do
+        config = ParseKit::Parser.new(xml_attributes: ["href"], xml_elements: ["entry"]).config
+        expect(config).to include(xml_attributes: ["href"], xml_elements: ["entry"], xml_namespaces: [])
+        expect(parser.config[:xml_attributes]).to be(false)
+      end
+    end
+
+    describe "#parse_xml_structured" do
+      it "returns the element tree as nested hashes" do
+        xml = '<?xml version="1.0"?><config env="prod"><db host="localhost" port="5432"/>' \
+              "<features>\n  <feature>search</feature>\n  <feature><![CDATA[a < b]]></feature>\n</features></config>"
+        expect(parser.parse_xml_structured(xml.bytes)).to eq(
+          name: "config", attributes: { "env" => "prod" }, text: "",
+          children: [
+            { name: "db", attributes: { "host" => "localhost", "port" => "5432" }, children: [], text: "" },
+            { name: "features", attributes: {}, text: "", children: [
+              { name: "feature", attributes: {}, children: [], text: "search" },
+              { name: "feature", attributes: {}, children: [], text: "a < b" }
+            ] }
+          ]
+        )
+      end
+
+      it "keeps prefixes and decodes entities" do
+        xml = '<r:root xmlns:r="urn:x"><r:item r:id="a&amp;b">Tom &amp; Jerry</r:item></r:root>'
+        item = parser.parse_xml_structured(xml.bytes)[:children].first
+        expect(item).to eq(name: "r:item", attributes: { "r:id" => "a&b" }, children: [], text: "Tom & Jerry")
+      end
+
+      it "raises on malformed XML" do
+        expect { parser.parse_xml_structured("<root><open></root>".bytes) }.to raise_error(RuntimeError, /XML parse error/)
+        expect { parser.parse_xml_structured("<root><open>".bytes) }.to raise_error(RuntimeError, /XML parse error/)
+      end
+    end
+
+    describe "#xml_query" do
+      let(:feed) do
+        ('<?xml version="1.0"?><feed version="2" xmlns:media="http://search.yahoo.com/mrss/">' \
+          '<item id="1"><title>First</title><media:content url="a.jpg"/></item>' \
+          '<item id="2"><title>Second</title><media:content url="b.jpg"/></item></feed>').bytes
+      end
+
+      it "returns matched elements with their text and attributes" do
+        expect(parser.xml_query(feed, "//item")).to eq([
+          { name: "item", text: "First", attributes: { "id" => "1" } },
+          { name: "item", text: "Second", attributes: { "id" => "2" } }
+        ])
+      end
+
+      it "returns attribute and text nodes" do
+        expect(parser.xml_query(feed, "//item/@id").map { |node| node[:text] }).to eq(["1", "2"])
+        expect(parser.xml_query(feed, "//item[@id='2']/title/text()")).to eq([{ name: nil, text: "Second", attributes: {} }])
+      end
+
+      it "returns numbers, strings and booleans for other expressions" do
+        expect(parser.xml_query(feed, "count(//item)")).to eq(2.0)
+        expect(parser.xml_query(feed, "string(/feed/@version)")).to eq("2")
+        expect(parser.xml_query(feed, "boolean(//missing)")).to be(false)
+      end
+
+      it "resolves prefixes given in namespaces:" do
+        urls = parser.xml_query(feed, "//m:content/@url", namespaces: { "m" => "http://search.yahoo.com/mrss/" })
+        expect(urls.map { |node| node[:text] }).to eq(["a.jpg", "b.jpg"])
+      end
+
+      it "raises on invalid XPath and malformed XML" do
+        expect { parser.xml_query(feed, "//item[") }.to raise_error(ArgumentError, /Invalid XPath/)
+        expect { parser.xml_query("<root><open></root>".bytes, "//open") }.to raise_error(RuntimeError, /Failed to parse XML/)
+      end
+    end
+
+    describe "#parse_html" do
+      it "puts block elements on separate lines" do
+        html_data = "<html><body><h1>Title</h1><p>First <b>bold</b> paragraph</p><div>Second</div></body></html>".bytes
+        result = parser.parse_html(html_data)
+        expect(result).to eq("Title\nFirst bold paragraph\nSecond")
+      end
+
+      it "tolerates malformed markup" do
+        html_data = "<p>Unclosed <i>tags<p>Next paragraph<br>line".bytes
+        result = parser.parse_html(html_data)
+        expect(result).to eq("Unclosed tags\nNext paragraph\nline")
+      end
+
+      it "keeps table cells on one line" do
+        html_data = "<table><tr><td>a</td><td>b</td></tr><tr><td>c</td><td>d</td></tr></table>".bytes
+        expect(parser.parse_html(html_data)).to eq("a\tb\nc\td")
+      end
+
+      it "skips script and style content" do
+        html_data = "<html><head><style>p { margin: 0 }</style></head><body><p>Text</p><script>track();</script></body></html>".bytes
+        expect(parser.parse_html(html_data)).to eq("Text")
+      end
+
+      context "with main_content: true" do
+        # article.html: a blog post between a site header, cookie banner,
+        # sponsored sidebar, related-posts list and footer
+        let(:html_data) { File.binread("spec/fixtures/article.html").bytes }
+        let(:reader) { ParseKit::Parser.new(main_content: true) }
+
+        it "returns just the article body" do
+          expect(reader.parse_html(html_data)).to eq(
+            "Reading a tide pool\n" \
+            "Tide pools fill with water twice a day, and the animals living in them, from anemones to hermit crabs, have adapted to the change.\n" \
+            "Look under rocks first, since most of the life hides there, away from gulls, sun and the pull of the waves.\n" \
+            "Put every rock back the way you found it, with the same side up, or the animals sheltering beneath it will dry out."
+          )
+        end
+
+        it "keeps the whole page by default" do
+          text = parser.parse_html(html_data)
+          expect(text).to include("Related posts", "Sponsored", "Copyright 2024")
+        end
+
+        it "drops navigation from pages without an article" do
+          html = "<body><nav><a href='/'>Home</a></nav><h1>Contact</h1><p>Call us</p><footer>Legal</footer></body>"
+          expect(reader.parse_html(html.bytes)).to eq("Contact\nCall us")
+        end
+      end
+    end
+
+    describe "#parse_mhtml" do
+      let(:mhtml_data) do
+        <<~MHT.gsub("\n", "\r\n").bytes
+          From: <Saved by Blink>
+          MIME-Version: 1.0
+          Content-Type: multipart/related; boundary="----boundary"; type="text/html"
+
+          ------boundary
+          Content-Type: text/html; charset="utf-8"
+          Content-Transfer-Encoding: quoted-printable
+          Content-Location: https://example.com/
+
+          <html><body><h1>Saved page</h1><p>Archived =3D text</p></body></html>
+          ------boundary
+          Content-Type: image/png
+          Content-Transfer-Encoding: base64
+          Content-Location: https://example.com/logo.png
+
+          iVBORw0KGgo=
+          ------boundary--
+        MHT
+      end
+
+      it "extracts the main HTML part and lists resources" do
+        result = parser.parse_mhtml(mhtml_data)
+        expect(result).to start_with("Saved page\nArchived = text")
+        expect(result).to include("Resources:\n  image/png https://example.com/logo.png")
+      end
+    end
+
+    describe "#parse_email" do
+      let(:eml_data) do
+        <<~EML.gsub("\n", "\r\n").bytes
+          From: Alice <alice@example.com>
+          To: bob@example.com
+          Subject: Q3 numbers
+          MIME-Version: 1.0
+          Content-Type: multipart/mixed; boundary="mixed"
+
+          --mixed
+          Content-Type: text/plain; charset="utf-8"
+
+          Hi Bob,
+          see attached.
+          --mixed
+          Content-Type: text/csv; name="totals.csv"
+          Content-Disposition: attachment; filename="totals.csv"
+          Content-Transfer-Encoding: base64
+
+          #{["region,total\nEMEA,42\n"].pack("m0")}
+          --mixed--
+        EML
+      end
+
+      it "renders headers, body and attachment names" do
+        expect(parser.parse_email(eml_data)).to eq(
+          "From: Alice <alice@example.com>\nTo: bob@example.com\nSubject: Q3 numbers\n" \
+          "Attachments: totals.csv\n\nHi Bob,\nsee attached."
+        )
+      end
+
+      it "parses attachments into labeled sections when enabled" do
+        result = described_class.new(parse_attachments: true).parse_email(eml_data)
+        expect(result).to end_with("see attached.\n\n=== Attachment: totals.csv ===\nregion,total\nEMEA,42")
+      end
+    end
+
+    describe "#parse_djvu" do
+      def iff_chunk(id, body)
+        chunk = id.bytes + [body.size].pack("N").bytes + body
+        body.size.odd? ? chunk + [0] : chunk
+      end
+
+      def djvu_page(text)
+        iff_chunk("FORM", "DJVU".bytes + iff_chunk("TXTa", [0, 0, text.bytesize] + text.bytes))
+      end
+
+      it "reads the uncompressed text layer of each page" do
+        document = "AT&T".bytes + iff_chunk("FORM", "DJVM".bytes + djvu_page("First page") + djvu_page("Second page"))
+        expect(parser.parse_djvu(document)).to eq("First page\n\nSecond page")
+      end
+
+      it "skips DJVM bundles nested inside the top-level one" do
+        # 100,000 nested FORM:DJVM headers around one page, built from the inside out
+        deep = djvu_page("Too deep")
+        size = deep.size
+        headers = Array.new(100_000) { size += 12; ["FORM", size - 8, "DJVM"].pack("a4Na4").bytes }
+        nested = headers.reverse.flatten + deep
+        document = "AT&T".bytes + iff_chunk("FORM", "DJVM".bytes + djvu_page("Top page") + nested)
+        expect(parser.parse_djvu(document)).to eq("Top page")
+      end
+
+      it "raises an error for data without a DjVu header" do
+        expect { parser.parse_djvu("not djvu".bytes) }.to raise_error(RuntimeError, /DjVu/)
+      end
+    end
+
+    describe "#parse_mobi" do
+      # Minimal PalmDB with a PalmDOC header and one text record
+      def palm_book(text_record, compression:)
+        header = [compression, 0, 0, 1, 0, 0, 0, 0].pack("n2Nn2n2").bytes
+        records_start = 78 + 2 * 8 + 2
+        pdb = ("book".ljust(32, "\0") + "\0" * 28 + "BOOKMOBI" + "\0" * 8).bytes
+        pdb += [2].pack("n").bytes
+        pdb += [records_start, 0, records_start + header.size, 1].pack("N4").bytes
+        pdb + [0, 0] + header + text_record
+      end
+
+      it "extracts text from uncompressed records" do
+        book = palm_book("<html><body><p>Chapter One</p></body></html>".bytes, compression: 1)
+        expect(parser.parse_mobi(book)).to eq("Chapter One")
+      end
+
+      it "decompresses PalmDOC records" do
+        # "<p>ab" + back-reference (distance 2, length 3) + "</p>"
+        record = "<p>ab".bytes + [0x80, 0x10] + "</p>".bytes
+        book = palm_book(record, compression: 2)
+        expect(parser.parse_mobi(book)).to eq("ababa")
+      end
     end
 
     describe "#parse_docx" do
       it "exists as a method" do
         expect(parser).to respond_to(:parse_docx)
       end
+
+      it "keeps list numbers and bullets, indented by level" do
+        text = parser.parse_docx(File.binread("spec/fixtures/lists.docx").bytes)
+        expect(text).to eq(
+          "Steps\n1. Open the valve\n  1.a) Check the pressure\n  1.b) Log the reading\n" \
+          "2. Close the valve\n  2.a) Sign off\nNotes\n- Wear gloves\n  - Nitrile"
+        )
+      end
+
+      context "with docx_comments" do
+        # comments.docx has one comment on "twelve percent" in its second paragraph
+        let(:docx_data) { File.binread("spec/fixtures/comments.docx").bytes }
+
+        it "leaves comments out by default" do
+          expect(parser.parse_docx(docx_data)).not_to include("Comment")
+        end
+
+        it "puts each comment after the paragraph it is anchored in" do
+          text = ParseKit::Parser.new(docx_comments: true).parse_docx(docx_data)
+          expect(text).to eq(
+            "Quarterly report\nRevenue grew by twelve percent this year.\n" \
+            "[Comment by Alice Reviewer, 2024-01-05: Please cite the source.]\nCosts were flat."
+          )
+        end
+      end
+
+      it "writes table rows as tab-separated cells" do
+        text = parser.parse_docx(File.binread("spec/fixtures/sample.docx").bytes)
+        expect(text).to include("Table example:\nColumn 1\tColumn 2\nData A\tData B\n")
+      end
+
+      it "puts text boxes after the paragraph they are anchored in" do
+        # textboxes.docx has a DrawingML text box with a VML fallback copy, and a VML-only one
+        text = parser.parse_docx(File.binread("spec/fixtures/textboxes.docx").bytes)
+        expect(text).to eq(
+          "Annual Report\nConfidential\nDraft & final\nRevenue grew.\nCall-out: see page 4\nCosts were flat."
+        )
+      end
+
+      it "keeps the text of block-level content controls" do
+        text = parser.parse_docx(File.binread("spec/fixtures/form.docx").bytes)
+        expect(text).to include("Name: Ada Lovelace\n")
+        expect(text).to include("\nPrefers mornings.\n")
+      end
+
+      it "writes equations as LaTeX-ish lines after their paragraph" do
+        text = parser.parse_docx(File.binread("spec/fixtures/equations.docx").bytes)
+        expect(text).to start_with("The area is .\n$A=πr^{2}$\n")
+        expect(text).to include("\n$$x=\\frac{-b±\\sqrt{b^{2}-4ac}}{2a}$$\n")
+        expect(text).to end_with("\n$\\sum_{i=1}^{n} x_{i}$\n$[a|b]$")
+      end
+
+      context "with docx_headings" do
+        # headings.docx has Heading 1 and 2 paragraphs, a style based on Heading 1 and an outline level 3 paragraph
+        let(:docx_data) { File.binread("spec/fixtures/headings.docx").bytes }
+
+        it "leaves headings unmarked by default" do
+          expect(parser.parse_docx(docx_data)).not_to include("#")
+        end
+
+        it "marks headings with a # per level" do
+          text = ParseKit::Parser.new(docx_headings: true).parse_docx(docx_data)
+          expect(text).to eq(
+            "# Introduction\nParsing documents is hard.\n## Scope\nOnly DOCX is covered.\n" \
+            "# Appendix\nSources are listed below.\n### Details"
+          )
+        end
+      end
+
+      context "with docx_links" do
+        # links.docx has two external links, one showing its own URL, and an internal bookmark link
+        let(:docx_data) { File.binread("spec/fixtures/links.docx").bytes }
+
+        it "keeps the link text by default" do
+          expect(parser.parse_docx(docx_data)).to eq("See the docs or https://example.com/faq.\nBack to top")
+        end
+
+        it "follows link text with its target" do
+          text = ParseKit::Parser.new(docx_links: true).parse_docx(docx_data)
+          expect(text).to eq("See the docs (https://example.com/docs) or https://example.com/faq.\nBack to top")
+        end
+      end
+    end
+
+    describe "revisions" do
+      # revisions.docx has "ten" deleted and "twelve" inserted as tracked changes
+      let(:docx_data) { File.binread("spec/fixtures/revisions.docx").bytes }
+
+      it "reads the document with every change accepted by default" do
+        expect(parser.config[:revisions]).to eq(:accept)
+        expect(parser.parse_docx(docx_data)).to eq("Service agreement\nThe fee is twelve dollars.")
+      end
+
+      it "reads the original text when rejecting changes" do
+        text = ParseKit::Parser.new(revisions: :reject).parse_docx(docx_data)
+        expect(text).to eq("Service agreement\nThe fee is ten dollars.")
+      end
+
+      it "marks both sides up with :show_markup" do
+        text = ParseKit::Parser.new(revisions: :show_markup).parse_docx(docx_data)
+        expect(text).to eq("Service agreement\nThe fee is [-ten-]{+twelve+} dollars.")
+      end
+
+      it "rejects unknown modes" do
+        expect { ParseKit::Parser.new(revisions: :merge) }.to raise_error(ArgumentError, /Unsupported revisions mode/)
+      end
+    end
+
+    describe "#docx_comments" do
+      it "lists comments with their author, date and commented text" do
+        comments = parser.docx_comments(File.binread("spec/fixtures/comments.docx").bytes)
+        expect(comments).to eq([
+          { id: "1", author: "Alice Reviewer", date: "2024-01-05T10:00:00Z", text: "Please cite the source.", anchor: "twelve percent" }
+        ])
+      end
+
+      it "returns an empty list for a document without comments" do
+        expect(parser.docx_comments(File.binread("spec/fixtures/sample.docx").bytes)).to eq([])
+      end
+    end
+
+    describe "#docx_images" do
+      it "returns the stored pictures with their content type and paragraph" do
+        # figure.docx has ocr_test.png in its second paragraph
+        images = parser.docx_images(File.binread("spec/fixtures/figure.docx").bytes)
+        expect(images.map { |image| image.except(:data) }).to eq([
+          { name: "word/media/rIdImage1.png", content_type: "image/png", paragraph: 1 }
+        ])
+        expect(images.first[:data]).to eq(File.binread("spec/fixtures/ocr_test.png"))
+        expect(images.first[:data].encoding).to eq(Encoding::BINARY)
+      end
+
+      it "returns an empty list for a document without pictures" do
+        expect(parser.docx_images(File.binread("spec/fixtures/comments.docx").bytes)).to eq([])
+      end
+    end
+
+    describe "#docx_form_fields" do
+      it "lists content controls and legacy form fields with their values" do
+        # form.docx also has a cover page building block, which isn't a field
+        fields = parser.docx_form_fields(File.binread("spec/fixtures/form.docx").bytes)
+        expect(fields).to eq([
+          { tag: "applicant_name", title: "Applicant name", type: "text", value: "Ada Lovelace" },
+          { tag: "start_date", title: "Start date", type: "date", value: "2024-03-01" },
+          { tag: "department", title: nil, type: "drop_down", value: "Research" },
+          { tag: "remote", title: nil, type: "checkbox", value: "true" },
+          { tag: "notes", title: "Notes", type: "rich_text", value: "Prefers mornings." },
+          { tag: "phone", title: nil, type: "text", value: nil },
+          { tag: "Manager", title: nil, type: "text", value: "Grace Hopper" },
+          { tag: "Approved", title: nil, type: "checkbox", value: "true" },
+          { tag: "Level", title: nil, type: "drop_down", value: "Senior" }
+        ])
+      end
+
+      it "returns an empty list for a document without fields" do
+        expect(parser.docx_form_fields(File.binread("spec/fixtures/links.docx").bytes)).to eq([])
+      end
+    end
+
+    describe "#docx_sections" do
+      it "nests the text under each heading by level" do
+        sections = parser.docx_sections(File.binread("spec/fixtures/headings.docx").bytes)
+        expect(sections).to eq([
+          { heading: "Introduction", level: 1, text: "Parsing documents is hard.", children: [
+            { heading: "Scope", level: 2, text: "Only DOCX is covered.", children: [] }
+          ] },
+          { heading: "Appendix", level: 1, text: "Sources are listed below.", children: [
+            { heading: "Details", level: 3, text: "", children: [] }
+          ] }
+        ])
+      end
+
+      it "puts text before the first heading in a section without one" do
+        sections = parser.docx_sections(File.binread("spec/fixtures/links.docx").bytes)
+        expect(sections).to eq([
+          { heading: nil, level: 0, text: "See the docs or https://example.com/faq.\nBack to top", children: [] }
+        ])
+      end
+    end
+
+    describe "#docx_links" do
+      it "lists external links with their text and target" do
+        links = parser.docx_links(File.binread("spec/fixtures/links.docx").bytes)
+        expect(links).to eq([
+          { text: "the docs", url: "https://example.com/docs" },
+          { text: "https://example.com/faq", url: "https://example.com/faq" }
+        ])
+      end
+    end
+
+    describe "#parse_wordperfect" do
+      it "extracts text runs from a WordPerfect 5 document" do
+        header = [0xFF, 0x57, 0x50, 0x43, 16, 0, 0, 0, 1, 10, 0, 0, 0, 0, 0, 0]
+        body = "Dear Sir,".bytes + [0x0A] + "Bold".bytes + [0xC3, 0x0C, 0xC3] + " text".bytes
+        expect(parser.parse_wordperfect(header + body)).to eq("Dear Sir,\nBold text")
+      end
+
+      it "keeps extended characters from WordPerfect 5 and 6 documents" do
+        wp5 = [0xFF, 0x57, 0x50, 0x43, 16, 0, 0, 0, 1, 10, 0, 0, 0, 0, 0, 0]
+        body = "Caf".bytes + [0xC0, 0x29, 1, 0xC0] + " ".bytes + [0xC0, 0x22, 4, 0xC0]
+        expect(parser.parse_wordperfect(wp5 + body)).to eq("Café —")
+
+        wp6 = [0xFF, 0x57, 0x50, 0x43, 16, 0, 0, 0, 1, 10, 2, 0, 0, 0, 0, 0]
+        body = "Gar".bytes + [0x0C] + "on ".bytes + [0xF0, 0x47, 1, 0xF0] + "ber".bytes
+        expect(parser.parse_wordperfect(wp6 + body)).to eq("Garçon über")
+      end
+
+      it "raises an error without a WPC header" do
+        expect { parser.parse_wordperfect("plain".bytes) }.to raise_error(RuntimeError, /WordPerfect/)
+      end
+    end
+
+    describe "#parse_pptx" do
+      it "writes slide table rows as tab-separated cells" do
+        # The second slide of sample.pptx has a two-column table
+        text = parser.parse_pptx(File.binread("spec/fixtures/sample.pptx").bytes)
+        expect(text).to end_with("Table example:\nColumn 1\tColumn 2\nData A\tData B")
+      end
+
+      context "with pptx_alt_text" do
+        # The picture on the first slide of screenshot.pptx has alt text
+        let(:pptx_data) { File.binread("spec/fixtures/screenshot.pptx").bytes }
+
+        it "leaves alt text out by default" do
+          expect(parser.parse_pptx(pptx_data)).not_to include("Screenshot of")
+        end
+
+        it "adds the alt text of each picture after the slide text" do
+          text = ParseKit::Parser.new(pptx_alt_text: true).parse_pptx(pptx_data)
+          expect(text).to include("Hello 世界 🌍\n[Image: Screenshot of the OCR test page]\n\n")
+        end
+      end
+
+      context "with pptx_notes" do
+        # notes.pptx is sample.pptx with speaker notes on both slides
+        let(:pptx_data) { File.binread("spec/fixtures/notes.pptx").bytes }
+
+        it "appends the notes after the slides by default" do
+          text = parser.parse_pptx(pptx_data)
+          expect(text).to end_with("Data A\tData B\n\n[Notes: Greet the audience.]\n\n[Notes: Walk through the table.]")
+        end
+
+        it "leaves the notes out with :exclude" do
+          text = ParseKit::Parser.new(pptx_notes: :exclude).parse_pptx(pptx_data)
+          expect(text).to eq(parser.parse_pptx(File.binread("spec/fixtures/sample.pptx").bytes))
+        end
+
+        it "returns only the notes with :only" do
+          text = ParseKit::Parser.new(pptx_notes: :only).parse_pptx(pptx_data)
+          expect(text).to eq("Greet the audience.\n\nWalk through the table.")
+        end
+
+        it "rejects unknown modes" do
+          expect { ParseKit::Parser.new(pptx_notes: :inline) }.to raise_error(ArgumentError, /Unsupported pptx_notes mode/)
+        end
+      end
+
+      context "with slides:" do
+        let(:pptx_data) { File.binread("spec/fixtures/notes.pptx").bytes }
+
+        it "parses only the selected slides and their notes" do
+          text = parser.parse_pptx(pptx_data, slides: [2])
+          expect(text).to start_with("Table example:")
+          expect(text).to end_with("Data A\tData B\n\n[Notes: Walk through the table.]")
+          expect(text).not_to include("Greet the audience.")
+        end
+
+        it "takes ranges and {first: n} like pages: of parse_pdf" do
+          first = parser.parse_pptx(pptx_data, slides: { first: 1 })
+          expect(first).to end_with("[Notes: Greet the audience.]")
+          expect(first).not_to include("Table example:")
+          expect(parser.parse_pptx(pptx_data, slides: 1..)).to eq(parser.parse_pptx(pptx_data))
+          expect(parser.parse_pptx(pptx_data, slides: 3..)).to eq("")
+        end
+
+        it "rejects slide numbers below 1" do
+          expect { parser.parse_pptx(pptx_data, slides: [0]) }.to raise_error(ArgumentError, /slides are numbered from 1/)
+        end
+      end
+
+      context "with pptx_links" do
+        # links.pptx is sample.pptx with a link over two runs on the first
+        # slide, a link to the second slide and a link in a table cell
+        let(:pptx_data) { File.binread("spec/fixtures/links.pptx").bytes }
+
+        it "leaves link targets out by default" do
+          expect(parser.parse_pptx(pptx_data)).to eq(parser.parse_pptx(File.binread("spec/fixtures/sample.pptx").bytes))
+        end
+
+        it "follows link text with its target" do
+          text = ParseKit::Parser.new(pptx_links: true).parse_pptx(pptx_data)
+          expect(text).to include("This is a Microsoft Powerpoint (https://example.com/powerpoint) document for testing.")
+          expect(text).to include("Italic text Unicode:")
+          expect(text).to end_with("Data A\tData B (https://example.com/data)")
+        end
+      end
+    end
+
+    describe "#parse_file with a PPTX" do
+      # Decks are read from the file part by part rather than loaded whole
+      it "reads the same text as parse_pptx" do
+        expect(parser.parse_file("spec/fixtures/notes.pptx"))
+          .to eq(parser.parse_pptx(File.binread("spec/fixtures/notes.pptx").bytes))
+      end
+
+      it "still checks the file size limit" do
+        small = ParseKit::Parser.new(max_size: 1024)
+        expect { small.parse_file("spec/fixtures/sample.pptx") }.to raise_error(RuntimeError, /File size exceeds limit/)
+      end
+    end
+
+    describe "#parse_pptx with path:" do
+      let(:pptx_data) { File.binread("spec/fixtures/notes.pptx").bytes }
+
+      it "reads the deck from the file" do
+        expect(parser.parse_pptx(path: "spec/fixtures/notes.pptx", slides: [2]))
+          .to eq(parser.parse_pptx(pptx_data, slides: [2]))
+        expect(parser.parse_pptx_slides(path: "spec/fixtures/notes.pptx")).to eq(parser.parse_pptx_slides(pptx_data))
+      end
+
+      it "takes either data or path:" do
+        expect { parser.parse_pptx }.to raise_error(ArgumentError, /data or path:/)
+        expect { parser.parse_pptx(pptx_data, path: "spec/fixtures/notes.pptx") }.to raise_error(ArgumentError, /not both/)
+      end
+
+      it "checks the file size limit" do
+        small = ParseKit::Parser.new(max_size: 1024)
+        expect { small.parse_pptx(path: "spec/fixtures/sample.pptx") }.to raise_error(RuntimeError, /File size exceeds limit/)
+      end
+    end
+
+    describe "#parse_pptx_slides" do
+      let(:pptx_data) { File.binread("spec/fixtures/notes.pptx").bytes }
+
+      it "returns the title, body and notes of each slide" do
+        slides = parser.parse_pptx_slides(pptx_data)
+        expect(slides.map { |slide| slide[:slide] }).to eq([1, 2])
+        expect(slides[0]).to include(title: "Title", notes: "Greet the audience.")
+        expect(slides[0][:body]).to start_with("This is a Microsoft Powerpoint document")
+        expect(slides[1]).to eq(
+          slide: 2,
+          title: "Table example:",
+          body: "Column 1\tColumn 2\nData A\tData B",
+          notes: "Walk through the table."
+        )
+      end
+
+      it "has nil notes for slides without them" do
+        slides = parser.parse_pptx_slides(File.binread("spec/fixtures/sample.pptx").bytes)
+        expect(slides.map { |slide| slide[:notes] }).to eq([nil, nil])
+      end
+
+      it "takes slides: like parse_pptx" do
+        slides = parser.parse_pptx_slides(pptx_data, slides: [2])
+        expect(slides.map { |slide| slide[:title] }).to eq(["Table example:"])
+      end
     end
 
     describe "#parse_xlsx" do
       it "exists as a method" do
         expect(parser).to respond_to(:parse_xlsx)
       end
+
+      context "with sheets:" do
+        # sample.xlsx has Sheet1 (a small table) and Sheet2 (Unicode text)
+        let(:xlsx_data) { File.binread("spec/fixtures/sample.xlsx").bytes }
+
+        it "extracts only the named sheets" do
+          text = parser.parse_xlsx(xlsx_data, sheets: "Sheet2")
+          expect(text).to start_with("Sheet: Sheet2\nUnicode Test")
+          expect(text).not_to include("Sheet1")
+        end
+
+        it "takes indexes from 0 and Regexps" do
+          expect(parser.parse_xlsx(xlsx_data, sheets: 0)).to start_with("Sheet: Sheet1\n")
+          expect(parser.parse_xlsx(xlsx_data, sheets: 0)).not_to include("Sheet2")
+          expect(parser.parse_xlsx(xlsx_data, sheets: [/sheet2/i])).to eq(parser.parse_xlsx(xlsx_data, sheets: "Sheet2"))
+        end
+
+        it "keeps workbook order and ignores sheets that don't exist" do
+          expect(parser.parse_xlsx(xlsx_data, sheets: ["Sheet2", "Missing", 0, 7])).to eq(parser.parse_xlsx(xlsx_data))
+        end
+
+        it "rejects negative indexes" do
+          expect { parser.parse_xlsx(xlsx_data, sheets: -1) }.to raise_error(ArgumentError, /start at 0/)
+        end
+      end
+
+      context "with include_formulas" do
+        # B4 of Sheet1 in sample.xlsx is =SUM(B2:B3)
+        let(:xlsx_data) { File.binread("spec/fixtures/sample.xlsx").bytes }
+
+        it "shows cached values only by default" do
+          expect(parser.parse_xlsx(xlsx_data)).to include("Formula\t579\tTotal\t\n")
+        end
+
+        it "follows formula results with their formula" do
+          text = ParseKit::Parser.new(include_formulas: true).parse_xlsx(xlsx_data)
+          expect(text).to include("Formula\t579 (=SUM(B2:B3))\tTotal\t\n")
+          expect(text).to include("Data 1\t123\t45.67\t\n")
+        end
+      end
+    end
+
+    describe "#open_xlsx" do
+      let(:workbook) { parser.open_xlsx(File.binread("spec/fixtures/sample.xlsx").bytes) }
+
+      it "returns a ParseKit::Workbook listing the sheets" do
+        expect(workbook).to be_a(ParseKit::Workbook)
+        expect(workbook.sheet_names).to eq(["Sheet1", "Sheet2"])
+        expect(workbook.dimensions("Sheet1")).to eq("A1:C4")
+      end
+
+      it "reads rows as parse_xlsx_structured does" do
+        structured = parser.parse_xlsx_structured(File.binread("spec/fixtures/sample.xlsx").bytes)
+        expect(workbook.rows("Sheet1")).to eq(structured.first[:rows])
+        expect(workbook.rows(1)).to eq(structured.last[:rows])
+      end
+
+      it "iterates rows with or without a block" do
+        rows = []
+        workbook.each_row("Sheet1") { |row| rows << row }
+        expect(rows.first).to eq(["Header 1", "Header 2", "Header 3"])
+        expect(workbook.each_row("Sheet1").first(2)).to eq(rows.first(2))
+      end
+
+      it "reads A1-style ranges" do
+        expect(workbook.range("Sheet1", "B2:C3")).to eq([[123.0, 45.67], [456.0, 89.1]])
+        expect(workbook.range("Sheet1", "C4:D5")).to eq([["Total", nil]])
+        expect { workbook.range("Sheet1", "nope") }.to raise_error(ArgumentError, /Unsupported range/)
+      end
+
+      it "rejects unknown sheets" do
+        expect { workbook.rows("Missing") }.to raise_error(ArgumentError, /No such sheet/)
+      end
+
+      it "leaves hidden sheets, rows and columns out when include_hidden is false" do
+        workbook = ParseKit::Parser.new(include_hidden: false).open_xlsx(File.binread("spec/fixtures/hidden.xlsx").bytes)
+        expect(workbook.sheet_names).to eq(["Data", "Summary"])
+        expect(workbook.rows("Data")).to eq([["Name", "Total"], ["Visible", 1.0], ["Shown", 3.0]])
+      end
+    end
+
+    describe "#xlsx_to_csv" do
+      # csv.xlsx: Export holds a comma, a line break, quotes and a tab in
+      # its cells; Other is a second sheet
+      let(:xlsx_data) { File.binread("spec/fixtures/csv.xlsx").bytes }
+
+      it "quotes fields as RFC 4180 CSV" do
+        expect(parser.xlsx_to_csv(xlsx_data)).to eq(
+          "Name,Note\r\n\"Smith, Jane\",\"Line 1\nLine 2\"\r\n\"Say \"\"hi\"\"\",tab\there\r\n"
+        )
+      end
+
+      it "round-trips through Ruby's CSV" do
+        require "csv"
+        rows = CSV.parse(parser.xlsx_to_csv(xlsx_data))
+        expect(rows).to eq([["Name", "Note"], ["Smith, Jane", "Line 1\nLine 2"], ['Say "hi"', "tab\there"]])
+      end
+
+      it "writes TSV with col_sep:" do
+        expect(parser.xlsx_to_csv(xlsx_data, col_sep: "\t")).to eq(
+          "Name\tNote\r\nSmith, Jane\t\"Line 1\nLine 2\"\r\n\"Say \"\"hi\"\"\"\t\"tab\there\"\r\n"
+        )
+      end
+
+      it "picks the sheet by name or index" do
+        expect(parser.xlsx_to_csv(xlsx_data, sheet: "Other")).to eq("x\r\n")
+        expect(parser.xlsx_to_csv(xlsx_data, sheet: 1)).to eq("x\r\n")
+        expect { parser.xlsx_to_csv(xlsx_data, sheet: "Missing") }.to raise_error(ArgumentError, /No such sheet/)
+      end
+
+      it "rejects separators that can't be quoted around" do
+        expect { parser.xlsx_to_csv(xlsx_data, col_sep: '"') }.to raise_error(ArgumentError, /col_sep/)
+        expect { parser.xlsx_to_csv(xlsx_data, col_sep: ";;") }.to raise_error(ArgumentError, /col_sep/)
+      end
+    end
+
+    describe "#xlsx_defined_names" do
+      # names.xlsx has sheets Inputs and "Q1 Data"; Years is local to Inputs
+      # and the print area to "Q1 Data"
+      let(:names) { parser.xlsx_defined_names(File.binread("spec/fixtures/names.xlsx").bytes) }
+
+      it "lists names in workbook order with their scope" do
+        expect(names.map { |name| [name[:name], name[:scope]] }).to eq([
+          ["Rate", nil], ["Sales", nil], ["_xlnm.Print_Area", "Q1 Data"],
+          ["Years", "Inputs"], ["Scratch", nil], ["Positive", nil]
+        ])
+      end
+
+      it "splits single ranges into sheet and range" do
+        expect(names[0]).to include(refers_to: "Inputs!$B$2", sheet: "Inputs", range: "B2", hidden: false)
+        expect(names[1]).to include(refers_to: "'Q1 Data'!$A$1:$B$2", sheet: "Q1 Data", range: "A1:B2")
+        expect(names[2]).to include(sheet: "Q1 Data", range: "A:B")
+      end
+
+      it "keeps constants and formulas whole" do
+        expect(names[4]).to include(refers_to: "0.05", sheet: nil, range: nil, hidden: true)
+        expect(names[5]).to include(refers_to: "Inputs!$B$2>0", sheet: nil, range: nil)
+      end
+
+      it "returns an empty array for workbooks without names" do
+        expect(parser.xlsx_defined_names(File.binread("spec/fixtures/sample.xlsx").bytes)).to eq([])
+      end
+    end
+
+    describe "#parse_xlsx with include_hidden" do
+      # hidden.xlsx: Data hides row 3 and column B, Notes is a hidden sheet
+      let(:xlsx_data) { File.binread("spec/fixtures/hidden.xlsx").bytes }
+
+      it "keeps hidden sheets, rows and columns by default" do
+        text = parser.parse_xlsx(xlsx_data)
+        expect(text).to include("Sheet: Notes\nInternal only")
+        expect(text).to include("Scratch\ty\t2\t\n")
+        expect(text).to include("Name\tSecret\tTotal\t\n")
+      end
+
+      it "leaves them out when false" do
+        text = ParseKit::Parser.new(include_hidden: false).parse_xlsx(xlsx_data)
+        expect(text).to eq("Sheet: Data\nName\tTotal\t\nVisible\t1\t\nShown\t3\t\n\nSheet: Summary\nDone\t\n\n")
+      end
+
+      it "applies to parse_xlsx_structured" do
+        sheets = ParseKit::Parser.new(include_hidden: false).parse_xlsx_structured(xlsx_data)
+        expect(sheets.map { |sheet| sheet[:name] }).to eq(["Data", "Summary"])
+        expect(sheets.first[:rows]).to eq([["Name", "Total"], ["Visible", 1.0], ["Shown", 3.0]])
+      end
+    end
+
+    describe "#parse_xlsx with xlsx_links" do
+      # links.xlsx links A1 and B1 (with a #setup location) to web pages,
+      # A2 to a cell of the workbook, A3 through a HYPERLINK formula and
+      # A4 to the URL it shows
+      let(:xlsx_data) { File.binread("spec/fixtures/links.xlsx").bytes }
+
+      it "shows only the display text by default" do
+        expect(parser.parse_xlsx(xlsx_data)).to include("Docs\tGuide\t\n")
+      end
+
+      it "follows linked cells with their target" do
+        text = ParseKit::Parser.new(xlsx_links: true).parse_xlsx(xlsx_data)
+        expect(text).to include("Docs (https://example.com/docs)\tGuide (https://example.com/guide#setup)\t\n")
+        expect(text).to include(%(Quote (https://example.com/q?a="b")\t))
+      end
+
+      it "skips links within the workbook and targets matching the text" do
+        text = ParseKit::Parser.new(xlsx_links: true).parse_xlsx(xlsx_data)
+        expect(text).to include("Jump\t\t\n")
+        expect(text).to include("https://example.com/\t\t\n")
+      end
+    end
+
+    describe "#parse_xlsx layout options" do
+      # layout.xlsx: sheet Layout with Item/Qty in row 1, nothing in row 2
+      # and Pens/4 in row 3
+      let(:xlsx_data) { File.binread("spec/fixtures/layout.xlsx").bytes }
+
+      it "keeps the tab-separated layout by default" do
+        expect(parser.parse_xlsx(xlsx_data)).to eq("Sheet: Layout\nItem\tQty\t\n\t\t\nPens\t4\t\n\n")
+      end
+
+      it "takes the cell and row separators" do
+        text = ParseKit::Parser.new(xlsx_cell_separator: " | ", xlsx_trailing_separator: false, xlsx_row_separator: "\r\n").parse_xlsx(xlsx_data)
+        expect(text).to eq("Sheet: Layout\r\nItem | Qty\r\n | \r\nPens | 4\r\n\r\n")
+      end
+
+      it "formats or omits the sheet header" do
+        expect(ParseKit::Parser.new(xlsx_sheet_header: "## %{name}").parse_xlsx(xlsx_data)).to start_with("## Layout\nItem")
+        expect(ParseKit::Parser.new(xlsx_sheet_header: nil).parse_xlsx(xlsx_data)).to start_with("Item\tQty")
+      end
+
+      it "skips empty rows" do
+        text = ParseKit::Parser.new(xlsx_skip_empty_rows: true).parse_xlsx(xlsx_data)
+        expect(text).to eq("Sheet: Layout\nItem\tQty\t\nPens\t4\t\n\n")
+      end
+
+      it "echoes the options in config" do
+        config = ParseKit::Parser.new(xlsx_sheet_header: nil).config
+        expect(config).to include(xlsx_cell_separator: "\t", xlsx_trailing_separator: true, xlsx_row_separator: "\n",
+                                  xlsx_sheet_header: nil, xlsx_skip_empty_rows: false)
+      end
+    end
+
+    describe "#parse_xlsx with merged cells" do
+      # merged.xlsx merges A1:A2 ("Region") and B1:C1 ("Sales")
+      let(:xlsx_data) { File.binread("spec/fixtures/merged.xlsx").bytes }
+
+      it "repeats the top-left value across the merged range" do
+        expect(parser.parse_xlsx(xlsx_data)).to eq("Sheet: Report\nRegion\tSales\tSales\t\nRegion\tQ1\tQ2\t\nNorth\t10\t20\t\n\n")
+      end
+
+      it "lists merged ranges in structured output" do
+        sheet = parser.parse_xlsx_structured(xlsx_data).first
+        expect(sheet[:rows]).to eq([["Region", "Sales", nil], [nil, "Q1", "Q2"], ["North", 10.0, 20.0]])
+        expect(sheet[:merged]).to eq([
+          { row: 0, column: 0, rows: 2, columns: 1 },
+          { row: 0, column: 1, rows: 1, columns: 2 }
+        ])
+      end
+    end
+
+    describe "#parse_xlsx with date cells" do
+      # dates.xlsx holds 2023-03-15 as a date, the same day at noon, 18:00
+      # as a time and 36 hours as a [h]:mm:ss duration; dates_1904.xlsx
+      # holds the same values counted from the 1904 epoch
+      it "renders dates, times and durations as ISO-8601" do
+        text = parser.parse_xlsx(File.binread("spec/fixtures/dates.xlsx").bytes)
+        expect(text).to include("Date\t2023-03-15\t\n")
+        expect(text).to include("Timestamp\t2023-03-15T12:00:00\t\n")
+        expect(text).to include("Time\t18:00:00\t\n")
+        expect(text).to include("Duration\tPT129600S\t\n")
+        expect(text).to include("Number\t42\t\n")
+      end
+
+      it "applies the workbook's 1904 epoch" do
+        text = parser.parse_xlsx(File.binread("spec/fixtures/dates_1904.xlsx").bytes)
+        expect(text).to eq(parser.parse_xlsx(File.binread("spec/fixtures/dates.xlsx").bytes))
+      end
+    end
+
+    describe "#parse_xlsx_structured" do
+      let(:xlsx_data) { File.binread("spec/fixtures/sample.xlsx").bytes }
+
+      it "returns each sheet's rows with typed cells" do
+        sheets = parser.parse_xlsx_structured(xlsx_data)
+        expect(sheets.map { |sheet| sheet[:name] }).to eq(["Sheet1", "Sheet2"])
+
+        rows = sheets.first[:rows]
+        expect(rows[0]).to eq(["Header 1", "Header 2", "Header 3"])
+        expect(rows[1]).to eq(["Data 1", 123.0, 45.67])
+        expect(rows[3]).to eq(["Formula", 579.0, "Total"])
+        expect(rows[1][1]).to be_a(Float)
+      end
+
+      it "keeps Unicode strings" do
+        sheet = parser.parse_xlsx_structured(xlsx_data, sheets: "Sheet2").first
+        expect(sheet[:rows].flatten).to eq(["Unicode Test", "世界", "Здравствуй мир"])
+      end
+
+      it "takes sheets: like parse_xlsx" do
+        expect(parser.parse_xlsx_structured(xlsx_data, sheets: [/2$/]).map { |sheet| sheet[:name] }).to eq(["Sheet2"])
+      end
+
+      context "with headers: true" do
+        it "returns the rows after the header row as hashes" do
+          sheet = parser.parse_xlsx_structured(xlsx_data, headers: true).first
+          expect(sheet[:headers]).to eq(["Header 1", "Header 2", "Header 3"])
+          expect(sheet[:records].first).to eq({ "Header 1" => "Data 1", "Header 2" => 123.0, "Header 3" => 45.67 })
+          expect(sheet[:records].length).to eq(3)
+          expect(sheet).not_to have_key(:rows)
+        end
+
+        it "names blank headers after their column" do
+          # merged.xlsx: C1 is covered by the B1:C1 merge, so it is blank
+          sheet = parser.parse_xlsx_structured(File.binread("spec/fixtures/merged.xlsx").bytes, headers: true).first
+          expect(sheet[:headers]).to eq(["Region", "Sales", "C"])
+          expect(sheet[:records]).to eq([
+            { "Region" => nil, "Sales" => "Q1", "C" => "Q2" },
+            { "Region" => "North", "Sales" => 10.0, "C" => 20.0 }
+          ])
+        end
+      end
+
+      it "returns date cells as UTC Times and durations as seconds" do
+        rows = parser.parse_xlsx_structured(File.binread("spec/fixtures/dates.xlsx").bytes).first[:rows]
+        expect(rows[0][1]).to eq(Time.utc(2023, 3, 15))
+        expect(rows[1][1]).to eq(Time.utc(2023, 3, 15, 12))
+        expect(rows[3][1]).to eq(129_600.0)
+      end
+    end
+
+    describe ParseKit::CellError do
+      it "reads as its error code" do
+        expect(ParseKit::CellError.new("#DIV/0!").to_s).to eq("#DIV/0!")
+      end
+    end
+
+    describe "#extract_macros" do
+      it "returns an empty hash for documents without a VBA project" do
+        expect(parser.extract_macros(path: "spec/fixtures/sample.docx")).to eq({})
+        expect(parser.extract_macros(File.binread("spec/fixtures/sample.xlsx").bytes)).to eq({})
+      end
+
+      it "reads a String as data, never as a path" do
+        expect { parser.extract_macros("spec/fixtures/sample.docx") }.to raise_error(ArgumentError, /OOXML or OLE/)
+      end
+
+      it "takes either data or path:" do
+        expect { parser.extract_macros }.to raise_error(ArgumentError, /data or path:/)
+        expect { parser.extract_macros("PK", path: "spec/fixtures/sample.docx") }.to raise_error(ArgumentError, /not both/)
+      end
+
+      it "raises an error for data that is not an Office document" do
+        expect { parser.extract_macros("plain text".bytes) }.to raise_error(ArgumentError, /OOXML or OLE/)
+      end
     end
 
     describe "#parse_pdf" do
@@ -417,6 +1629,357 @@ let(:test_file) { File.join(temp_dir, "test.xyz") }
         expect(parser).to respond_to(:parse_pdf)
       end
     end
+
+    describe "#parse_audio" do
+      it "exists as a method" do
+        expect(parser).to respond_to(:parse_audio)
+      end
+
+      it "raises an error for data that is not audio" do
+        expect { parser.parse_audio("not audio".bytes) }.to raise_error(RuntimeError, /audio/)
+      end
+    end
+
+    describe "#parse_video" do
+      def box(type, body)
+        [body.bytesize + 8].pack("N") + type + body
+      end
+
+      it "reports title, duration and chapters from an MP4" do
+        mvhd = [0, 0, 0, 600, 600 * 95].pack("NNNNN") + "\0" * 80
+        chpl = [0x01000000, 0, 2].pack("NNC") +  # version 1, reserved word, count
+               [0].pack("Q>") + [5].pack("C") + "Intro" +
+               [65 * 10_000_000].pack("Q>") + [4].pack("C") + "Demo"
+        data = [0, 1].pack("NN") + "All hands"
+        meta = [0].pack("N") + box("hdlr", "\0" * 25) + box("ilst", box("\xA9nam".b, box("data", data)))
+        mp4 = box("ftyp", "isom\0\0\0\0isom") +
+              box("moov", box("mvhd", mvhd) + box("udta", box("chpl", chpl) + box("meta", meta)))
+
+        expect(parser.parse_video(mp4.bytes)).to eq(
+          "Title: All hands\nDuration: 00:01:35\nChapters:\n[00:00:00] Intro\n[00:01:05] Demo"
+        )
+      end
+
+      it "raises an error for data that is not a video container" do
+        expect { parser.parse_video("not video".bytes) }.to raise_error(RuntimeError, /video/)
+      end
+    end
+
+    describe "#parse_gzip" do
+      it "decompresses and parses the contents" do
+        gz = Zlib.gzip('{"status": "ok"}')
+        expect(JSON.parse(parser.parse_gzip(gz.bytes))).to eq("status" => "ok")
+      end
+
+      it "enforces max_size on the decompressed output" do
+        small = described_class.new(max_size: 1024)
+        gz = Zlib.gzip("a" * 4096)
+        expect { small.parse_gzip(gz.bytes) }.to raise_error(RuntimeError, /Decompressed size exceeds limit/)
+      end
+
+      it "rejects data that expands beyond max_compression_ratio" do
+        gz = Zlib.gzip("\0" * 2_000_000)
+        expect { parser.parse_gzip(gz.bytes) }
+          .to raise_error(ParseKit::ResourceLimitError, /Compression ratio exceeds limit/)
+      end
+    end
+
+    describe "#parse_tar" do
+      def tar_entry(name, body)
+        header = [name, "0000644", "0000000", "0000000", format("%011o", body.bytesize), "00000000000"]
+                   .pack("a100a8a8a8a12a12") + " " * 8 + "0" + "\0" * 100 + "ustar\x0000" + "\0" * 247
+        checksum = header.bytes.sum
+        header[148, 8] = format("%06o\0 ", checksum)
+        header + body + "\0" * ((512 - body.bytesize % 512) % 512)
+      end
+
+      it "parses each member under its own heading" do
+        tar = tar_entry("notes.txt", "Meeting notes") + tar_entry("data.json", '{"a":1}') + "\0" * 1024
+        result = parser.parse_tar(tar.bytes)
+        expect(result).to start_with("=== notes.txt ===\nMeeting notes\n\n=== data.json ===")
+        expect(result).to include('"a": 1')
+      end
+
+      it "is reached through gzip for .tar.gz" do
+        tar = tar_entry("readme.txt", "Hello from a tarball") + "\0" * 1024
+        expect(parser.parse_gzip(Zlib.gzip(tar).bytes)).to eq("=== readme.txt ===\nHello from a tarball")
+      end
+
+      it "counts compression layers against max_depth and max_size" do
+        nested = Zlib.gzip(Zlib.gzip("x" * 600))
+        expect(parser.parse_gzip(nested.bytes)).to eq("x" * 600)
+
+        expect { described_class.new(max_depth: 1).parse_gzip(nested.bytes) }
+          .to raise_error(ParseKit::ResourceLimitError, /Nesting depth exceeds limit/)
+
+        tarball = Zlib.gzip(tar_entry("big.txt", "x" * 600) + "\0" * 1024)
+        expect(parser.parse_gzip(tarball.bytes)).to include("=== big.txt ===")
+        expect { described_class.new(max_size: 3000).parse_gzip(tarball.bytes) }
+          .to raise_error(ParseKit::ResourceLimitError, /Decompressed size exceeds limit/)
+      end
+
+      it "enforces max_depth on archives nested inside archives" do
+        inner = tar_entry("inner.txt", "deep") + "\0" * 1024
+        outer = tar_entry("inner.tar", inner) + "\0" * 1024
+        expect(parser.parse_tar(outer.bytes)).to include("=== inner.txt ===\ndeep")
+
+        shallow = described_class.new(max_depth: 1)
+        expect { shallow.parse_tar(outer.bytes) }
+          .to raise_error(ParseKit::ResourceLimitError, /Nesting depth exceeds limit/)
+      end
+
+      it "enforces max_entry_size on each member" do
+        small = described_class.new(max_entry_size: 10)
+        tar = tar_entry("big.txt", "x" * 100) + "\0" * 1024
+        expect { small.parse_tar(tar.bytes) }
+          .to raise_error(ParseKit::ResourceLimitError, /Decompressed entry exceeds limit/)
+      end
+    end
+
+    describe "#parse_sevenzip" do
+      it "raises an error for data that is not a 7z archive" do
+        expect { parser.parse_sevenzip("not 7z".bytes) }.to raise_error(RuntimeError, /7z/)
+      end
+    end
+
+    describe "#parse_rar" do
+      it "raises an error for data that is not a RAR archive" do
+        expect { parser.parse_rar("not rar".bytes) }.to raise_error(RuntimeError, /RAR/)
+      end
+    end
+
+    describe "#parse_pst" do
+      it "exists as a method" do
+        expect(parser).to respond_to(:parse_pst)
+      end
+
+      it "raises an error for data that is not a mail store" do
+        expect { parser.parse_pst("not a pst".bytes) }.to raise_error(RuntimeError, /PST|readpst/)
+      end
+    end
+
+    describe "#parse_subtitles" do
+      let(:srt) { "1\n00:00:01,000 --> 00:00:04,000\n<i>Hello</i> there\n\n2\n00:01:05,500 --> 00:01:07,000\nGeneral Kenobi\n" }
+
+      it "strips cue numbers, timings and tags from SubRip" do
+        expect(parser.parse_subtitles(srt.bytes)).to eq("Hello there\nGeneral Kenobi")
+      end
+
+      it "prefixes cues with their start time when timestamps are enabled" do
+        timed = described_class.new(timestamps: true)
+        expect(timed.parse_subtitles(srt.bytes)).to eq("[00:00:01] Hello there\n[00:01:05] General Kenobi")
+      end
+
+      it "skips WebVTT notes and styles" do
+        vtt = "WEBVTT\n\nNOTE draft\n\nSTYLE\n::cue { color: red }\n\n00:01.000 --> 00:04.000 align:start\n<v Roger>Hi <c.loud>there</c>\n"
+        expect(parser.parse_subtitles(vtt.bytes)).to eq("Hi there")
+      end
+
+      it "drops ASS override blocks and line breaks" do
+        ass = "[Script Info]\nTitle: Test\n\n[Events]\n" \
+              "Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n" \
+              "Dialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,{\\i1}Well,{\\i0} hello\\Nworld\n"
+        expect(parser.parse_subtitles(ass.bytes)).to eq("Well, hello world")
+      end
+    end
+
+    describe "#parse_log" do
+      it "normalizes syslog, combined and JSON-lines entries" do
+        log = <<~LOG
+          <34>Oct 11 22:14:15 mymachine su: 'su root' failed for lonvick
+          127.0.0.1 - frank [10/Oct/2000:13:55:36 -0700] "GET /apache_pb.gif HTTP/1.0" 404 2326 "-" "curl/8.0"
+          {"ts":"2024-01-02T03:04:05Z","level":"warn","msg":"disk low"}
+        LOG
+        expect(parser.parse_log(log.bytes)).to eq([
+          "Oct 11 22:14:15\tCRITICAL\tsu: 'su root' failed for lonvick",
+          "2000-10-10T13:55:36-07:00\tWARNING\t127.0.0.1 \"GET /apache_pb.gif HTTP/1.0\" 404 2326 \"-\" \"curl/8.0\"",
+          "2024-01-02T03:04:05Z\tWARNING\tdisk low"
+        ].join("\n"))
+      end
+
+      it "keeps a combined log timestamp whose offset isn't [+-]hhmm as it is" do
+        log = %(1.2.3.4 - - [10/Oct/2000:13:55:36 +0\u00E90] "GET /" 200 5\n)
+        expect(parser.parse_log(log.bytes)).to eq(%(10/Oct/2000:13:55:36 +0\u00E90\tINFO\t1.2.3.4 "GET /" 200 5))
+      end
+
+      it "folds indented continuation lines into the previous entry" do
+        log = %({"level":"error","message":"boom"}\n\tat Foo.bar(Foo.java:1)\n)
+        expect(parser.parse_log(log.bytes)).to eq("-\tERROR\tboom at Foo.bar(Foo.java:1)")
+      end
+
+      it "uses named captures from a custom pattern" do
+        log = "2024-01-01 10:00:00 [err] broken\n"
+        pattern = '^(?P<timestamp>\S+ \S+) \[(?P<level>\w+)\] (?P<message>.*)$'
+        expect(parser.parse_log(log.bytes, pattern: pattern)).to eq("2024-01-01 10:00:00\tERROR\tbroken")
+      end
+
+      it "rejects invalid patterns" do
+        expect { parser.parse_log("x".bytes, pattern: "(") }.to raise_error(ArgumentError, /Invalid log pattern/)
+      end
+    end
+
+    describe "#parse_dicom" do
+      def element(group, elem, vr, value)
+        header = [group, elem].pack("v2") + vr
+        header += vr == "OB" ? [0, value.bytesize].pack("vV") : [value.bytesize].pack("v")
+        (header + value).bytes
+      end
+
+      let(:dicom) do
+        [0] * 128 + "DICM".bytes +
+          element(0x0002, 0x0010, "UI", "1.2.840.10008.1.2.1\0") +
+          element(0x0008, 0x0020, "DA", "20240115") +
+          element(0x0008, 0x0060, "CS", "CT") +
+          element(0x0010, 0x0010, "PN", "Doe^Jane") +
+          element(0x0010, 0x0020, "LO", "MRN-0042")
+      end
+
+      it "extracts patient and study attributes" do
+        expect(parser.parse_dicom(dicom)).to eq(
+          "Study Date: 2024-01-15\nModality: CT\nPatient's Name: Doe Jane\nPatient ID: MRN-0042"
+        )
+      end
+
+      it "raises an error without the DICM prefix" do
+        expect { parser.parse_dicom("not dicom".bytes) }.to raise_error(RuntimeError, /DICOM/)
+      end
+
+      it "raises ResourceLimitError for sequences nested too deeply" do
+        sequence = [0x0040, 0xA730].pack("v2") + "SQ" + [0, 0xFFFFFFFF].pack("vV")
+        item = [0xFFFE, 0xE000, 0xFFFFFFFF].pack("v2V")
+        nested = [0] * 128 + "DICM".bytes + element(0x0002, 0x0010, "UI", "1.2.840.10008.1.2.1\0") +
+                 ((sequence + item) * 100).bytes
+        expect { parser.parse_dicom(nested) }
+          .to raise_error(ParseKit::ResourceLimitError, /Nesting depth exceeds limit/)
+      end
+    end
+
+    describe "#parse_xbrl" do
+      let(:instance) do
+        <<~XML
+          <?xml version="1.0"?>
+          <xbrli:xbrl xmlns:xbrli="http://www.xbrl.org/2003/instance" xmlns:iso4217="http://www.xbrl.org/2003/iso4217" xmlns:us-gaap="http://fasb.org/us-gaap/2023">
+            <xbrli:context id="FY2023">
+              <xbrli:entity><xbrli:identifier scheme="http://www.sec.gov/CIK">0000320193</xbrli:identifier></xbrli:entity>
+              <xbrli:period><xbrli:startDate>2022-09-25</xbrli:startDate><xbrli:endDate>2023-09-30</xbrli:endDate></xbrli:period>
+            </xbrli:context>
+            <xbrli:unit id="USD"><xbrli:measure>iso4217:USD</xbrli:measure></xbrli:unit>
+            <us-gaap:Revenues contextRef="FY2023" unitRef="USD" decimals="-6">383285000000</us-gaap:Revenues>
+          </xbrli:xbrl>
+        XML
+      end
+
+      it "emits facts with resolved periods and units" do
+        result = parser.parse_xbrl(instance.bytes)
+        expect(result.lines.first.chomp).to eq("Fact\tValue\tPeriod\tUnit")
+        expect(result).to include("us-gaap:Revenues\t383285000000\t2022-09-25/2023-09-30\tUSD")
+      end
+
+      it "is used by parse_xml for XBRL documents" do
+        expect(parser.parse_xml(instance.bytes)).to eq(parser.parse_xbrl(instance.bytes))
+      end
+    end
+
+    describe "#parse_flat_odf" do
+      def flat_odf(body)
+        <<~XML
+          <?xml version="1.0"?>
+          <office:document xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0" xmlns:text="urn:oasis:names:tc:opendocument:xmlns:text:1.0" xmlns:table="urn:oasis:names:tc:opendocument:xmlns:table:1.0" xmlns:draw="urn:oasis:names:tc:opendocument:xmlns:drawing:1.0" xmlns:presentation="urn:oasis:names:tc:opendocument:xmlns:presentation:1.0">
+            <office:body>#{body}</office:body>
+          </office:document>
+        XML
+      end
+
+      it "keeps paragraphs and table rows" do
+        doc = flat_odf(<<~XML)
+          <office:text>
+            <text:h>Quarterly Report</text:h>
+            <text:p>Revenue<text:s text:c="2"/>grew &amp; costs<text:tab/>fell</text:p>
+            <table:table table:name="Table1">
+              <table:table-row><table:table-cell><text:p>Region</text:p></table:table-cell><table:table-cell><text:p>Total</text:p></table:table-cell></table:table-row>
+              <table:table-row><table:table-cell><text:p>EMEA</text:p></table:table-cell><table:table-cell><text:p>42</text:p></table:table-cell></table:table-row>
+            </table:table>
+          </office:text>
+        XML
+        expect(parser.parse_flat_odf(doc.bytes)).to eq("Quarterly Report\nRevenue  grew & costs\tfell\nRegion\tTotal\nEMEA\t42")
+      end
+
+      it "labels spreadsheet tables and ignores repeated empty cells" do
+        doc = flat_odf(<<~XML)
+          <office:spreadsheet>
+            <table:table table:name="Budget">
+              <table:table-row><table:table-cell><text:p>Item</text:p></table:table-cell><table:table-cell table:number-columns-repeated="16383"/></table:table-row>
+              <table:table-row table:number-rows-repeated="1048575"><table:table-cell table:number-columns-repeated="16384"/></table:table-row>
+            </table:table>
+          </office:spreadsheet>
+        XML
+        expect(parser.parse_flat_odf(doc.bytes)).to eq("Sheet: Budget\nItem")
+      end
+
+      it "separates presentation pages and appends speaker notes" do
+        doc = flat_odf(<<~XML)
+          <office:presentation>
+            <draw:page><draw:frame><draw:text-box><text:p>Slide one</text:p></draw:text-box></draw:frame>
+              <presentation:notes><draw:frame><draw:text-box><text:p>Say hello</text:p></draw:text-box></draw:frame></presentation:notes>
+            </draw:page>
+            <draw:page><draw:frame><draw:text-box><text:p>Slide two</text:p></draw:text-box></draw:frame></draw:page>
+          </office:presentation>
+        XML
+        expect(parser.parse_flat_odf(doc.bytes)).to eq("Slide one\n[Notes: Say hello]\n\nSlide two")
+      end
+
+      it "is used by parse_xml for Flat ODF documents" do
+        doc = flat_odf("<office:text><text:p>Hello</text:p></office:text>")
+        expect(parser.parse_xml(doc.bytes)).to eq("Hello")
+      end
+    end
+
+    describe "#parse_parquet" do
+      it "exists as a method" do
+        expect(parser).to respond_to(:parse_parquet)
+      end
+
+      it "raises an error for data that is not Parquet" do
+        expect { parser.parse_parquet("not parquet".bytes) }.to raise_error(RuntimeError, /Parquet/)
+      end
+    end
+
+    describe "#parse_dbf" do
+      let(:dbf) do
+        fields = [["NAME", "C", 10, 0], ["BORN", "D", 8, 0], ["SALARY", "N", 8, 2]]
+        header = [0x03, 124, 5, 17, 2, 32 + 32 * fields.size + 1, 1 + 10 + 8 + 8].pack("C4Vvv") + "\0" * 20
+        fields.each { |name, type, len, dec| header += [name, type, 0, len, dec].pack("a11aVCC") + "\0" * 14 }
+        header + "\r" + " Ada       18151210 1234.50" + "*Deleted   19000101    0.00" + " Grace     19061209   99.00" + "\x1A"
+      end
+
+      it "emits the field list and records as delimited rows" do
+        expect(parser.parse_dbf(dbf.bytes)).to eq(
+          "Schema:\n  NAME: C(10)\n  BORN: D(8)\n  SALARY: N(8,2)\n\n" \
+          "NAME\tBORN\tSALARY\nAda\t1815-12-10\t1234.50\nGrace\t1906-12-09\t99.00"
+        )
+      end
+
+      it "caps output at max_rows" do
+        capped = described_class.new(max_rows: 1)
+        expect(capped.parse_dbf(dbf.bytes)).to end_with("[Truncated: showing 1 of 2 rows]")
+      end
+
+      it "raises ParseKit::ParseError for a zero record length" do
+        bytes = dbf.bytes
+        bytes[10] = bytes[11] = 0
+        expect { parser.parse_dbf(bytes) }.to raise_error(ParseKit::ParseError, /zero record length/)
+      end
+    end
+
+    describe "#parse_arrow" do
+      it "exists as a method" do
+        expect(parser).to respond_to(:parse_arrow)
+      end
+
+      it "raises an error for data that is not Arrow IPC" do
+        expect { parser.parse_arrow("not arrow".bytes) }.to raise_error(RuntimeError, /Arrow IPC/)
+      end
+    end
   end
 
   describe "#parse_file_routed" do
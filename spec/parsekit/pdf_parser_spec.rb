@@ -3,6 +3,40 @@
 RSpec.describe "PDF Parsing with MuPDF" do
   let(:parser) { ParseKit::Parser.new }
 
+  # Builds a PDF from object bodies numbered from 1, with a valid xref table.
+  # Object 1 must be the catalog.
+  def pdf_file(*objects)
+    out = +"%PDF-1.7\n"
+    offsets = objects.each_with_index.map do |body, i|
+      offset = out.bytesize
+      out << "#{i + 1} 0 obj\n#{body}\nendobj\n"
+      offset
+    end
+    xref = out.bytesize
+    out << "xref\n0 #{objects.size + 1}\n0000000000 65535 f \n"
+    offsets.each { |offset| out << format("%010d 00000 n \n", offset) }
+    out << "trailer\n<< /Size #{objects.size + 1} /Root 1 0 R >>\nstartxref\n#{xref}\n%%EOF\n"
+  end
+
+  # A PDF with one page per string, each showing that string in Helvetica
+  def text_pdf(*texts)
+    kids = texts.each_index.map { |i| "#{4 + 2 * i} 0 R" }.join(" ")
+    pages = texts.each_with_index.flat_map do |text, i|
+      content = "BT /F1 12 Tf 20 100 Td (#{text}) Tj ET"
+      [
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Resources << /Font << /F1 3 0 R >> >> " \
+        "/Contents #{5 + 2 * i} 0 R >>",
+        "<< /Length #{content.bytesize} >>\nstream\n#{content}\nendstream"
+      ]
+    end
+    pdf_file(
+      "<< /Type /Catalog /Pages 2 0 R >>",
+      "<< /Type /Pages /Kids [#{kids}] /Count #{texts.size} >>",
+      "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>",
+      *pages
+    )
+  end
+
   describe "#parse_pdf" do
     context "with valid PDF data" do
       let(:simple_pdf) do
@@ -142,6 +176,361 @@ RSpec.describe "PDF Parsing with MuPDF" do
         end
       end
     end
+
+    context "with damaged files" do
+      # Page 2 points at an object that doesn't exist
+      let(:missing_page_pdf) do
+        text_pdf("Alpha").sub("/Kids [4 0 R] /Count 1", "/Kids [4 0 R 99 0 R] /Count 2").bytes
+      end
+
+      it "recovers a PDF with data before its header" do
+        expect(parser.parse_pdf(("Content-Type: application/pdf\r\n\r\n" * 100).bytes + text_pdf("Alpha").bytes))
+          .to eq("Alpha")
+      end
+
+      it "skips unreadable pages" do
+        expect(parser.parse_pdf(missing_page_pdf)).to eq("Alpha")
+      end
+
+      it "notes unreadable pages when lenient" do
+        lenient = ParseKit::Parser.new(lenient: true)
+        expect(lenient.parse_pdf(missing_page_pdf)).to match(/\AAlpha\n\[Warning: page 2 could not be read: .+\]\z/)
+        expect(lenient.parse_pdf_pages(missing_page_pdf).last).to include(page: 2, text: "", warning: /could not be read/)
+      end
+    end
+
+    context "with normalize_text" do
+      let(:normalizing) { ParseKit::Parser.new(normalize_text: true) }
+
+      it "expands ligatures and rejoins hyphenated words" do
+        # \256 is the fi ligature in StandardEncoding
+        content = "BT /F1 12 Tf 20 100 Td (a \\256le with hyphen-) Tj 0 -14 Td (ated words) Tj ET"
+        pdf = pdf_file(
+          "<< /Type /Catalog /Pages 2 0 R >>",
+          "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+          "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Resources << /Font << /F1 5 0 R >> >> /Contents 4 0 R >>",
+          "<< /Length #{content.bytesize} >>\nstream\n#{content}\nendstream",
+          "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>"
+        ).bytes
+        expect(parser.parse_pdf(pdf)).to eq("a \uFB01le with hyphen-\nated words")
+        expect(normalizing.parse_pdf(pdf)).to eq("a file with hyphenated words")
+      end
+
+      it "keeps the hyphen unless both sides are lowercase" do
+        content = "BT /F1 12 Tf 20 100 Td (UTF-) Tj 0 -14 Td (encoded) Tj ET"
+        pdf = pdf_file(
+          "<< /Type /Catalog /Pages 2 0 R >>",
+          "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+          "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Resources << /Font << /F1 5 0 R >> >> /Contents 4 0 R >>",
+          "<< /Length #{content.bytesize} >>\nstream\n#{content}\nendstream",
+          "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>"
+        ).bytes
+        expect(normalizing.parse_pdf(pdf)).to eq("UTF-\nencoded")
+      end
+    end
+
+    context "with a tagged PDF" do
+      let(:structured) { ParseKit::Parser.new(pdf_structure_order: true) }
+
+      it "follows the structure tree and drops artifacts with pdf_structure_order" do
+        content = "/Artifact BMC BT /F1 10 Tf 90 20 Td (Page 1) Tj ET EMC " \
+                  "/P <</MCID 0>> BDC BT /F1 12 Tf 20 150 Td (Left one) Tj 0 -20 Td (Left two) Tj ET EMC " \
+                  "/P <</MCID 1>> BDC BT /F1 12 Tf 110 150 Td (Right one) Tj 0 -20 Td (Right two) Tj ET EMC"
+        pdf = pdf_file(
+          "<< /Type /Catalog /Pages 2 0 R /MarkInfo << /Marked true >> /StructTreeRoot 6 0 R >>",
+          "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+          "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Resources << /Font << /F1 5 0 R >> >> " \
+          "/Contents 4 0 R /StructParents 0 >>",
+          "<< /Length #{content.bytesize} >>\nstream\n#{content}\nendstream",
+          "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>",
+          "<< /Type /StructTreeRoot /K 7 0 R >>",
+          "<< /Type /StructElem /S /Document /P 6 0 R /K [8 0 R 9 0 R] >>",
+          "<< /Type /StructElem /S /P /P 7 0 R /Pg 3 0 R /K 0 >>",
+          "<< /Type /StructElem /S /P /P 7 0 R /Pg 3 0 R /K 1 >>"
+        ).bytes
+
+        expect(structured.parse_pdf(pdf)).to eq("Left one Left two\nRight one Right two")
+        expect(structured.parse_pdf_pages(pdf).first[:text]).to eq("Left one Left two\nRight one Right two")
+        expect(parser.parse_pdf(pdf)).to include("Page 1")
+      end
+
+      it "decodes fonts with an /Encoding /Differences array" do
+        content = "/P <</MCID 0>> BDC BT /F1 12 Tf 20 150 Td <0102> Tj ET EMC"
+        pdf = pdf_file(
+          "<< /Type /Catalog /Pages 2 0 R /MarkInfo << /Marked true >> /StructTreeRoot 6 0 R >>",
+          "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+          "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Resources << /Font << /F1 5 0 R >> >> " \
+          "/Contents 4 0 R /StructParents 0 >>",
+          "<< /Length #{content.bytesize} >>\nstream\n#{content}\nendstream",
+          "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding << /Differences [1 /H /i] >> >>",
+          "<< /Type /StructTreeRoot /K 7 0 R >>",
+          "<< /Type /StructElem /S /P /P 6 0 R /Pg 3 0 R /K 0 >>"
+        ).bytes
+
+        expect(structured.parse_pdf(pdf)).to eq("Hi")
+      end
+
+      it "falls back to layout order for pages the structure tree yields no text for" do
+        content = "BT /F1 12 Tf 20 150 Td (Untagged text) Tj ET"
+        pdf = pdf_file(
+          "<< /Type /Catalog /Pages 2 0 R /MarkInfo << /Marked true >> /StructTreeRoot 6 0 R >>",
+          "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+          "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Resources << /Font << /F1 5 0 R >> >> " \
+          "/Contents 4 0 R /StructParents 0 >>",
+          "<< /Length #{content.bytesize} >>\nstream\n#{content}\nendstream",
+          "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>",
+          "<< /Type /StructTreeRoot /K 7 0 R >>",
+          "<< /Type /StructElem /S /P /P 6 0 R /Pg 3 0 R /K 0 >>"
+        ).bytes
+
+        expect(structured.parse_pdf(pdf)).to eq("Untagged text")
+      end
+    end
+
+    context "with pages:" do
+      it "extracts only the selected pages" do
+        pdf = text_pdf("Alpha", "Bravo", "Charlie").bytes
+        expect(parser.parse_pdf(pdf)).to include("Alpha", "Bravo", "Charlie")
+
+        expect(parser.parse_pdf(pdf, pages: 2..3)).to include("Bravo", "Charlie")
+        expect(parser.parse_pdf(pdf, pages: 2..3)).not_to include("Alpha")
+        expect(parser.parse_pdf(pdf, pages: [1, 3, 10])).not_to include("Bravo")
+        expect(parser.parse_pdf(pdf, pages: { first: 1 })).to eq("Alpha")
+        expect(parser.parse_pdf(pdf, pages: 2)).to eq("Bravo")
+      end
+
+      it "counts negative range bounds back from the last page like Array#[]" do
+        pdf = text_pdf("Alpha", "Bravo", "Charlie").bytes
+        expect(parser.parse_pdf(pdf, pages: 2..-1)).to eq(parser.parse_pdf(pdf, pages: 2..3))
+        expect(parser.parse_pdf(pdf, pages: -2..)).to eq(parser.parse_pdf(pdf, pages: 2..3))
+        expect(parser.parse_pdf(pdf, pages: 1...-1)).to eq(parser.parse_pdf(pdf, pages: 1..2))
+        expect(parser.parse_pdf(pdf, pages: -5..-1)).to eq("")
+      end
+
+      it "rejects page numbers below 1" do
+        pdf = text_pdf("Alpha").bytes
+        expect { parser.parse_pdf(pdf, pages: [0]) }.to raise_error(ArgumentError, /numbered from 1/)
+        expect { parser.parse_pdf(pdf, pages: 0..1) }.to raise_error(ArgumentError, /numbered from 1/)
+      end
+    end
+  end
+
+  describe "#parse_pdf_pages" do
+    it "returns each page's number, text and size" do
+      pages = parser.parse_pdf_pages(text_pdf("Alpha", "Bravo").bytes)
+      expect(pages).to eq([
+        { page: 1, text: "Alpha", width: 200.0, height: 200.0 },
+        { page: 2, text: "Bravo", width: 200.0, height: 200.0 }
+      ])
+    end
+
+    it "honours pages:" do
+      pages = parser.parse_pdf_pages(text_pdf("Alpha", "Bravo", "Charlie").bytes, pages: [3])
+      expect(pages.map { |page| page[:page] }).to eq([3])
+    end
+  end
+
+  describe "#parse_pdf_tables" do
+    let(:table_pdf) do
+      cells = [["Item", 20, 180], ["Qty", 100, 180], ["Widget", 20, 165], ["2", 100, 165], ["Gadget", 20, 150], ["10", 100, 150]]
+      content = (["BT /F1 10 Tf 20 195 Td (Order summary) Tj ET"] +
+                 cells.map { |text, x, y| "BT /F1 10 Tf #{x} #{y} Td (#{text}) Tj ET" }).join("\n")
+      pdf_file(
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [4 0 R] /Count 1 >>",
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Resources << /Font << /F1 3 0 R >> >> /Contents 5 0 R >>",
+        "<< /Length #{content.bytesize} >>\nstream\n#{content}\nendstream"
+      ).bytes
+    end
+
+    it "rebuilds rows and columns from positioned text" do
+      expect(parser.parse_pdf_tables(table_pdf)).to eq([
+        { page: 1, rows: [%w[Item Qty], %w[Widget 2], %w[Gadget 10]] }
+      ])
+    end
+
+    it "renders Markdown with format: :markdown" do
+      tables = parser.parse_pdf_tables(table_pdf, format: :markdown)
+      expect(tables.first[:markdown]).to eq("| Item | Qty |\n| --- | --- |\n| Widget | 2 |\n| Gadget | 10 |")
+    end
+
+    it "finds no tables in running text" do
+      expect(parser.parse_pdf_tables(text_pdf("Just a sentence").bytes)).to eq([])
+    end
+  end
+
+  describe "#pdf_outline" do
+    it "returns bookmarks as a nested tree with page numbers" do
+      pdf = pdf_file(
+        "<< /Type /Catalog /Pages 2 0 R /Outlines 5 0 R >>",
+        "<< /Type /Pages /Kids [3 0 R 4 0 R] /Count 2 >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] >>",
+        "<< /Type /Outlines /First 6 0 R /Last 6 0 R /Count 2 >>",
+        "<< /Title (Chapter 1) /Parent 5 0 R /Dest [3 0 R /Fit] /First 7 0 R /Last 7 0 R /Count 1 >>",
+        "<< /Title (Section 1.1) /Parent 6 0 R /Dest [4 0 R /Fit] >>"
+      )
+      expect(parser.pdf_outline(pdf.bytes)).to eq([
+        { title: "Chapter 1", page: 1, children: [{ title: "Section 1.1", page: 2, children: [] }] }
+      ])
+    end
+
+    it "returns an empty array without an outline" do
+      expect(parser.pdf_outline(text_pdf("Alpha").bytes)).to eq([])
+    end
+  end
+
+  describe "#parse_pdf_spans" do
+    it "returns font runs with size, style and position" do
+      content = "BT /F1 12 Tf 20 100 Td (Title) Tj /F2 10 Tf 60 0 Td (body) Tj ET"
+      pdf = pdf_file(
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Contents 4 0 R " \
+        "/Resources << /Font << /F1 5 0 R /F2 6 0 R >> >> >>",
+        "<< /Length #{content.bytesize} >>\nstream\n#{content}\nendstream",
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold >>",
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Times-Italic >>"
+      )
+      spans = parser.parse_pdf_spans(pdf.bytes)
+      expect(spans.map { |span| span.slice(:page, :text, :size, :bold, :italic) }).to eq([
+        { page: 1, text: "Title", size: 12.0, bold: true, italic: false },
+        { page: 1, text: "body", size: 10.0, bold: false, italic: true }
+      ])
+      x0, y0, x1, y1 = spans.first[:bbox]
+      expect(x0).to be_within(1).of(20)
+      expect(y1).to be_within(4).of(100)
+      expect(x1).to be > x0
+      expect(y1).to be > y0
+    end
+  end
+
+  describe "#render_pdf_page" do
+    it "returns a PNG sized by dpi" do
+      png = parser.render_pdf_page(text_pdf("Alpha", "Bravo").bytes, 2, dpi: 36)
+      expect(png.encoding).to eq(Encoding::BINARY)
+      expect(png.byteslice(0, 8)).to eq("\x89PNG\r\n\x1A\n".b)
+      expect(png.byteslice(16, 8).unpack("NN")).to eq([100, 100])
+    end
+
+    it "rejects pages outside the document" do
+      expect { parser.render_pdf_page(text_pdf("Alpha").bytes, 2) }.to raise_error(ArgumentError, /out of range/)
+    end
+  end
+
+  describe "#pdf_annotations" do
+    it "returns notes, free text and highlights with the text under them" do
+      content = "BT /F1 12 Tf 20 100 Td (Alpha Bravo) Tj ET"
+      pdf = pdf_file(
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [4 0 R] /Count 1 /MediaBox [0 0 200 200] >>",
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>",
+        "<< /Type /Page /Parent 2 0 R /Resources << /Font << /F1 3 0 R >> >> /Contents 5 0 R /Annots [6 0 R 7 0 R 8 0 R] >>",
+        "<< /Length #{content.bytesize} >>\nstream\n#{content}\nendstream",
+        "<< /Type /Annot /Subtype /Highlight /Rect [18 96 56 112] /QuadPoints [18 112 56 112 18 96 56 96] /T (Ann) /Contents (Check) >>",
+        "<< /Type /Annot /Subtype /Text /Rect [150 150 170 170] /Contents (Looks good) >>",
+        "<< /Type /Annot /Subtype /Link /Rect [0 0 10 10] >>"
+      )
+      expect(parser.pdf_annotations(pdf.bytes)).to eq([
+        { page: 1, type: "highlight", contents: "Check", author: "Ann", text: "Alpha" },
+        { page: 1, type: "note", contents: "Looks good", author: nil }
+      ])
+    end
+  end
+
+  describe "#pdf_attachments" do
+    let(:invoice_pdf) do
+      xml = "<Invoice><Total>42.00</Total></Invoice>"
+      pdf_file(
+        "<< /Type /Catalog /Pages 2 0 R /Names << /EmbeddedFiles << /Names [(factur-x.xml) 3 0 R] >> >> >>",
+        "<< /Type /Pages /Kids [] /Count 0 >>",
+        "<< /Type /Filespec /F (factur-x.xml) /UF (factur-x.xml) /Desc (Invoice data) /EF << /F 4 0 R >> >>",
+        "<< /Type /EmbeddedFile /Subtype /text#2Fxml /Length #{xml.bytesize} >>\nstream\n#{xml}\nendstream"
+      ).bytes
+    end
+
+    it "returns the name, description, MIME type and bytes of each file" do
+      expect(parser.pdf_attachments(invoice_pdf)).to eq([
+        { name: "factur-x.xml", description: "Invoice data", mime_type: "text/xml",
+          data: "<Invoice><Total>42.00</Total></Invoice>".b }
+      ])
+    end
+
+    it "parses each file with parse: true" do
+      attachment = parser.pdf_attachments(invoice_pdf, parse: true).first
+      expect(attachment[:text]).to include("42.00")
+    end
+
+    it "returns an empty array when nothing is embedded" do
+      expect(parser.pdf_attachments(text_pdf("Alpha").bytes)).to eq([])
+    end
+  end
+
+  describe "#pdf_javascript" do
+    it "reports open actions and document-level scripts" do
+      pdf = pdf_file(
+        "<< /Type /Catalog /Pages 2 0 R /OpenAction 4 0 R /Names << /JavaScript 5 0 R >> >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] >>",
+        "<< /S /JavaScript /JS (app.alert\\(1\\)) >>",
+        "<< /Names [(init) << /S /JavaScript /JS (this.print\\(\\)) >>] >>"
+      )
+      result = parser.pdf_javascript(pdf.bytes)
+      expect(result[:javascript]).to be true
+      expect(result[:actions]).to eq([
+        { trigger: "OpenAction", type: "JavaScript", script: "app.alert(1)" },
+        { trigger: "Names /JavaScript init", type: "JavaScript", script: "this.print()" }
+      ])
+    end
+
+    it "lists non-script open actions without flagging JavaScript" do
+      pdf = pdf_file(
+        "<< /Type /Catalog /Pages 2 0 R /OpenAction << /S /URI /URI (https://example.com) >> >>",
+        "<< /Type /Pages /Kids [] /Count 0 >>"
+      )
+      result = parser.pdf_javascript(pdf.bytes)
+      expect(result[:javascript]).to be false
+      expect(result[:actions]).to eq([{ trigger: "OpenAction", type: "URI", script: nil }])
+    end
+  end
+
+  describe "#pdf_xfa" do
+    let(:xfa_pdf) do
+      template = '<template xmlns="http://www.xfa.org/schema/xfa-template/3.3/"><subform name="form1">' \
+                 '<field name="Name"><caption><value><text>Full name</text></value></caption></field>' \
+                 '<field name="City"/></subform></template>'
+      datasets = '<xfa:datasets xmlns:xfa="http://www.xfa.org/schema/xfa-data/1.0/"><xfa:data>' \
+                 '<form1><Name>Jane Doe</Name><City>Oslo</City><Phone/></form1></xfa:data></xfa:datasets>'
+      content = "BT /F1 12 Tf 20 100 Td (Please wait...) Tj ET"
+      pdf_file(
+        "<< /Type /Catalog /Pages 2 0 R /AcroForm << /Fields [] /XFA [(template) 6 0 R (datasets) 7 0 R] >> >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Resources << /Font << /F1 5 0 R >> >> /Contents 4 0 R >>",
+        "<< /Length #{content.bytesize} >>\nstream\n#{content}\nendstream",
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>",
+        "<< /Length #{template.bytesize} >>\nstream\n#{template}\nendstream",
+        "<< /Length #{datasets.bytesize} >>\nstream\n#{datasets}\nendstream"
+      ).bytes
+    end
+
+    it "pairs XFA data values with their template captions" do
+      expect(parser.pdf_xfa(xfa_pdf)).to eq([
+        { name: "form1.Name", label: "Full name", value: "Jane Doe" },
+        { name: "form1.City", label: nil, value: "Oslo" }
+      ])
+    end
+
+    it "adds the form data to parse_pdf" do
+      text = parser.parse_pdf(xfa_pdf)
+      expect(text).to include("Please wait...")
+      expect(text).to end_with("=== XFA form data ===\nFull name: Jane Doe\nform1.City: Oslo")
+      expect(parser.parse_pdf(xfa_pdf, pages: 1)).not_to include("XFA")
+    end
+
+    it "returns an empty Array for PDFs without a form" do
+      expect(parser.pdf_xfa(text_pdf("Alpha").bytes)).to eq([])
+    end
   end
 
   describe "#parse_file with PDF" do
@@ -0,0 +1,41 @@
+require 'spec_helper'
+
+RSpec.describe ParseKit::Tessdata do
+  describe ".cache_dir" do
+    around do |example|
+      saved = ENV.to_h.slice("PARSEKIT_TESSDATA_CACHE", "XDG_CACHE_HOME")
+      example.run
+    ensure
+      %w[PARSEKIT_TESSDATA_CACHE XDG_CACHE_HOME].each { |name| ENV[name] = saved[name] }
+    end
+
+    it "honours PARSEKIT_TESSDATA_CACHE" do
+      ENV["PARSEKIT_TESSDATA_CACHE"] = "/srv/models"
+      expect(described_class.cache_dir).to eq("/srv/models")
+    end
+
+    it "lives under XDG_CACHE_HOME otherwise" do
+      ENV.delete("PARSEKIT_TESSDATA_CACHE")
+      ENV["XDG_CACHE_HOME"] = "/tmp/cache"
+      expect(described_class.cache_dir).to eq("/tmp/cache/parsekit/tessdata")
+    end
+  end
+
+  describe ".download" do
+    it "skips models that are already present" do
+      Dir.mktmpdir do |dir|
+        File.write(File.join(dir, "eng.traineddata"), "model")
+        File.write(File.join(dir, "deu.traineddata"), "model")
+        expect(Net::HTTP).not_to receive(:start)
+        expect(described_class.download("eng+deu", dir: dir)).to eq(dir)
+        expect(described_class.download(%w[eng deu], dir: dir)).to eq(dir)
+      end
+    end
+
+    it "rejects language codes that aren't plain names" do
+      Dir.mktmpdir do |dir|
+        expect { described_class.download("../eng", dir: dir) }.to raise_error(ArgumentError, /Invalid Tesseract language/)
+      end
+    end
+  end
+end
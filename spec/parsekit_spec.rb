@@ -235,11 +235,13 @@ let(:test_file) { File.join(temp_dir, "test.txt") }
 
   describe "error classes" do
     it "defines custom error classes" do
-      # Error classes are defined in the native extension
-      # They don't inherit from StandardError in the current implementation
+      # Error classes are defined in the native extension. Error and
+      # ConfigError are plain classes; ParseError and ResourceLimitError
+      # are RuntimeErrors
       expect(ParseKit::Error).to be_a(Class)
       expect(ParseKit::ParseError).to be_a(Class)
       expect(ParseKit::ConfigError).to be_a(Class)
+      expect(ParseKit::ResourceLimitError.ancestors).to include(RuntimeError)
     end
   end
 end